@@ -0,0 +1,108 @@
+//! The `regex!` macro, which checks a pattern when the code using it is
+//! compiled rather than when it runs.
+
+extern crate proc_macro;
+extern crate regexmatcher;
+
+use proc_macro::{TokenStream, TokenTree};
+
+use regexmatcher::Regex;
+
+/// A `&'static Regex` for a string literal pattern, compiled the first time
+/// the expression is evaluated and shared after that. A pattern that does
+/// not compile is a compile error at the macro call instead of a panic.
+///
+/// ```
+/// #[macro_use] extern crate regexmatcher_macros;
+/// extern crate regexmatcher;
+///
+/// # fn main() {
+/// assert!(regex!("a+b").is_match("aab"));
+/// # }
+/// ```
+///
+/// ```compile_fail
+/// #[macro_use] extern crate regexmatcher_macros;
+/// extern crate regexmatcher;
+///
+/// # fn main() {
+/// regex!("a(b");
+/// # }
+/// ```
+#[proc_macro]
+pub fn regex(input: TokenStream) -> TokenStream {
+    let pattern = match pattern(input) {
+        Ok(pattern) => pattern,
+        Err(message) => return compile_error(&message)
+    };
+    if let Err(error) = Regex::from(&pattern) {
+        return compile_error(&format!("invalid pattern {:?}: {}", pattern, error));
+    }
+
+    format!("{{
+        static REGEX: ::std::sync::OnceLock<::regexmatcher::Regex> = ::std::sync::OnceLock::new();
+        REGEX.get_or_init(|| ::regexmatcher::Regex::from({:?}).unwrap())
+    }}", pattern).parse().unwrap()
+}
+
+fn compile_error(message: &str) -> TokenStream {
+    format!("compile_error!({:?})", message).parse().unwrap()
+}
+
+// the value of the string literal that is the whole input
+fn pattern(input: TokenStream) -> Result<String, String> {
+    let mut tokens = input.into_iter();
+    let literal = match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(literal)), None) => literal.to_string(),
+        _ => return Err("regex! takes one string literal".to_owned())
+    };
+
+    if literal.starts_with('"') {
+        unescape(&literal[1..literal.len() - 1])
+    } else if let Some(raw) = literal.strip_prefix('r') {
+        let hashes = raw.find('"').unwrap_or(0);
+        Ok(raw[1 + hashes..raw.len() - 1 - hashes].to_owned())
+    } else {
+        Err("regex! takes one string literal".to_owned())
+    }
+}
+
+// the chars of a string literal between its quotes, with escapes as Rust
+// reads them
+fn unescape(literal: &str) -> Result<String, String> {
+    let mut value = String::new();
+    let mut chars = literal.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('r') => value.push('\r'),
+            Some('t') => value.push('\t'),
+            Some('0') => value.push('\0'),
+            Some('\\') => value.push('\\'),
+            Some('\'') => value.push('\''),
+            Some('"') => value.push('"'),
+            Some('x') => {
+                let digits = chars.by_ref().take(2).collect::<String>();
+                let byte = u8::from_str_radix(&digits, 16).map_err(|_| format!("bad escape \\x{}", digits))?;
+                value.push(byte as char);
+            },
+            Some('u') => {
+                let digits = chars.by_ref().skip(1).take_while(|&c| c != '}').collect::<String>();
+                let c = u32::from_str_radix(&digits, 16).ok().and_then(::std::char::from_u32);
+                value.push(c.ok_or_else(|| format!("bad escape \\u{{{}}}", digits))?);
+            },
+            Some('\n') => {
+                // a line continuation also skips the next line's indentation
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+            },
+            other => return Err(format!("bad escape \\{}", other.map_or(String::new(), |c| c.to_string())))
+        }
+    }
+    Ok(value)
+}
@@ -0,0 +1,24 @@
+#[macro_use] extern crate regexmatcher_macros;
+extern crate regexmatcher;
+
+use regexmatcher::Match;
+
+#[test]
+fn compiles_patterns() {
+    assert!(regex!("a+b").is_match("aab"));
+    assert_eq!(Some(Match::new(1, 3)), regex!("b[cd]").find("abd"));
+}
+
+#[test]
+fn reads_escapes_and_raw_strings() {
+    assert_eq!("a\\.b\n", regex!("a\\.b\n").as_str());
+    assert_eq!("a\\.b", regex!(r"a\.b").as_str());
+    assert_eq!("\"a\"", regex!(r#""a""#).as_str());
+    assert_eq!("é", regex!("\u{e9}").as_str());
+}
+
+#[test]
+fn shares_one_regex_per_call() {
+    let regexes = (0..2).map(|_| regex!("ab*") as *const _).collect::<Vec<_>>();
+    assert_eq!(regexes[0], regexes[1]);
+}
@@ -0,0 +1,191 @@
+use ::nfa::{State, Transition, NFA, Condition};
+use ::prefilter::Prefilter;
+
+#[cfg(test)] mod spec;
+
+// the most (state, offset) pairs remembered per search, as bits
+const MAX_VISITED: usize = 256 * 1024 * 8;
+
+enum Job {
+    Step(Transition, usize), // follow an edge at an offset
+    Restore(usize, Option<usize>) // a slot's value before a save was followed
+}
+
+// The buffers a search works in, which can be kept for later searches
+// instead of allocating them again.
+pub struct Cache {
+    visited: Vec<u64>,
+    jobs: Vec<Job>
+}
+
+impl Default for Cache {
+    fn default() -> Cache {
+        Cache::new()
+    }
+}
+
+impl Cache {
+    pub fn new() -> Cache {
+        Cache {
+            visited: Vec::new(),
+            jobs: Vec::new()
+        }
+    }
+}
+
+// Tries the alternatives of the NFA depth first, in order of priority, so the
+// first match found is the leftmost-first one. Every (state, offset) pair is
+// explored at most once per search, which keeps the worst case linear in
+// `num_states * text.len()` but needs a bit per pair; it is only used when
+// those fit in `MAX_VISITED` bits. For short texts it beats the Pike VM, which
+// has to copy slots for every thread it keeps.
+pub struct Backtracker<'n> {
+    nfa: &'n NFA,
+    num_slots: usize,
+    prefilter: Option<&'n dyn Prefilter>,
+    longest: bool
+}
+
+impl<'n> Backtracker<'n> {
+    pub fn new(nfa: &'n NFA) -> Backtracker<'n> {
+        Backtracker {
+            nfa,
+            num_slots: nfa.num_groups() * 2,
+            prefilter: None,
+            longest: false
+        }
+    }
+
+    // only tries the starts the prefilter finds
+    pub fn with_prefilter(mut self, prefilter: Option<&'n dyn Prefilter>) -> Backtracker<'n> {
+        self.prefilter = prefilter;
+        self
+    }
+
+    // finds the leftmost-longest match instead of the leftmost-first: every
+    // path from a start is explored, keeping the first to reach the longest
+    // match
+    pub fn with_longest(mut self, longest: bool) -> Backtracker<'n> {
+        self.longest = longest;
+        self
+    }
+
+    // whether a text of `len` bytes is short enough to search
+    pub fn fits(&self, len: usize) -> bool {
+        self.nfa.num_states() * (len + 1) <= MAX_VISITED
+    }
+
+    // the slots of the leftmost-first match starting at or after `start`,
+    // or only at `start` if anchored
+    pub fn captures(&self, text: &[u8], start: usize, anchored: bool) -> Option<Vec<Option<usize>>> {
+        self.captures_with(&mut Cache::new(), text, start, anchored)
+    }
+
+    // like `captures`, but working in the buffers of `cache`
+    pub fn captures_with(&self, cache: &mut Cache, text: &[u8], start: usize, anchored: bool) -> Option<Vec<Option<usize>>> {
+        assert!(self.fits(text.len()), "text too long to backtrack over");
+
+        cache.visited.clear();
+        cache.visited.resize((self.nfa.num_states() * (text.len() + 1)).div_ceil(64), 0);
+        let mut slots = vec![None; self.num_slots];
+
+        let mut at = start;
+        while at <= text.len() {
+            if !anchored {
+                match self.prefilter.map(|prefilter| prefilter.find(text, at)) {
+                    Some(Some(candidate)) => at = candidate,
+                    Some(None) => break,
+                    None => ()
+                }
+            }
+
+            for slot in slots.iter_mut() {
+                *slot = None;
+            }
+            slots[0] = Some(at);
+
+            if self.backtrack(text, at, cache, &mut slots) {
+                return Some(slots);
+            }
+            if anchored {
+                break;
+            }
+            at += 1;
+        }
+
+        None
+    }
+
+    fn backtrack(&self, text: &[u8], at: usize, cache: &mut Cache, slots: &mut Vec<Option<usize>>) -> bool {
+        if self.nfa.num_states() == 0 { // an empty NFA matches immediately
+            slots[1] = Some(at);
+            return true;
+        }
+
+        let Cache { ref mut visited, ref mut jobs } = *cache;
+        jobs.clear();
+        let mut longest: Option<Vec<Option<usize>>> = None;
+        jobs.push(Job::Step(Transition::id(self.nfa.get_start_id()), at));
+
+        while let Some(job) = jobs.pop() {
+            let (edge, at) = match job {
+                Job::Restore(slot, value) => {
+                    slots[slot] = value;
+                    continue;
+                },
+                Job::Step(edge, at) => (edge, at)
+            };
+
+            match edge {
+                Transition::End => {
+                    slots[1] = Some(at);
+                    if !self.longest {
+                        return true;
+                    }
+                    if longest.as_ref().is_none_or(|longest| longest[1] < slots[1]) {
+                        longest = Some(slots.clone());
+                    }
+                },
+                Transition::Id(id) => {
+                    let id = id.index();
+                    // a pair that failed once fails again, whatever the start
+                    let bit = id * (text.len() + 1) + at;
+                    if visited[bit / 64] & (1 << (bit % 64)) != 0 {
+                        continue;
+                    }
+                    visited[bit / 64] |= 1 << (bit % 64);
+
+                    match self.nfa.get_state(id) {
+                        Some(&State::State{condition: Condition::None, out}) => {
+                            jobs.push(Job::Step(out, at));
+                        },
+                        Some(&State::State{ref condition, out}) => {
+                            if at < text.len() && condition.accepts(text[at]) {
+                                jobs.push(Job::Step(out, at + 1));
+                            }
+                        },
+                        Some(&State::Split{out1, out2}) => {
+                            jobs.push(Job::Step(out2, at));
+                            jobs.push(Job::Step(out1, at)); // out1 is preferred
+                        },
+                        Some(&State::Save{slot, out}) => {
+                            jobs.push(Job::Restore(slot, slots[slot]));
+                            slots[slot] = Some(at);
+                            jobs.push(Job::Step(out, at));
+                        },
+                        None => panic!("transition to missing state {}", id)
+                    }
+                },
+                Transition::Detached => panic!("cannot evaluate incomplete NFA")
+            }
+        }
+
+        match longest {
+            Some(longest) => {
+                *slots = longest;
+                true
+            },
+            None => false
+        }
+    }
+}
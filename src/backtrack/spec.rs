@@ -0,0 +1,69 @@
+use ::expr::Expr;
+use ::nfa::NFA;
+use ::pikevm::PikeVM;
+use super::Backtracker;
+
+fn nfa(pattern: &str) -> NFA {
+    NFA::from_expr(&pattern.parse::<Expr>().unwrap())
+}
+
+#[test]
+fn finds_same_captures_as_pike_vm() {
+    let patterns = ["ab", "a|ab", "ab|a", "(a)(bc)", "(ab)+", "([ab])*", "(a)|(b)", "b(a)?", "(a*)(a)", "x*"];
+    let texts = ["", "ab", "abc", "xxabab", "abb", "b", "aaa", "ba"];
+
+    for pattern in patterns.iter() {
+        let nfa = nfa(pattern);
+        for text in texts.iter() {
+            for &anchored in [false, true].iter() {
+                assert_eq!(PikeVM::new(&nfa).captures(text.as_bytes(), 0, anchored),
+                           Backtracker::new(&nfa).captures(text.as_bytes(), 0, anchored),
+                           "{} on {:?}", pattern, text);
+            }
+        }
+    }
+}
+
+#[test]
+fn searches_from_offset() {
+    let nfa = nfa("a(b)");
+    let backtracker = Backtracker::new(&nfa);
+
+    assert_eq!(None, backtracker.captures(b"xab", 0, true));
+    assert_eq!(Some(vec![Some(1), Some(3), Some(2), Some(3)]), backtracker.captures(b"xab", 1, true));
+    assert_eq!(None, backtracker.captures(b"abx", 1, false));
+}
+
+#[test]
+fn does_not_explore_states_twice() {
+    let pattern = "a?".repeat(30) + &"a".repeat(30);
+    let text = "a".repeat(29);
+
+    assert_eq!(None, Backtracker::new(&nfa(&pattern)).captures(text.as_bytes(), 0, false));
+}
+
+#[test]
+fn only_fits_short_texts() {
+    let nfa = nfa("(a|b)*c");
+    let backtracker = Backtracker::new(&nfa);
+
+    assert!(backtracker.fits(1000));
+    assert!(!backtracker.fits(10_000_000));
+}
+
+#[test]
+fn finds_same_longest_captures_as_pike_vm() {
+    let patterns = ["a|ab", "(a|ab)(c|bcd)", "(a*?)(a*)", "abcd|bc", "(a|b)*?b", "x*"];
+    let texts = ["", "ab", "abcd", "xabcd", "aaa", "abab"];
+
+    for pattern in patterns.iter() {
+        let nfa = nfa(pattern);
+        for text in texts.iter() {
+            for &anchored in [false, true].iter() {
+                assert_eq!(PikeVM::new(&nfa).with_longest(true).captures(text.as_bytes(), 0, anchored),
+                           Backtracker::new(&nfa).with_longest(true).captures(text.as_bytes(), 0, anchored),
+                           "{} on {:?}", pattern, text);
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+// A small grep: prints the lines of the files given, or of stdin, that match
+// a pattern.
+//
+//     rmgrep [-i] [-v] [-n] [-o] PATTERN [FILE...]
+//
+//   -i  match letters in either case
+//   -v  print the lines that do not match instead
+//   -n  print the line number before each line
+//   -o  print only the matches, each on a line of its own
+//
+// Exits with 0 if a line was printed, 1 if none was and 2 on an error.
+
+extern crate regexmatcher;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process;
+
+use regexmatcher::{Regex, RegexBuilder};
+
+struct Options {
+    invert: bool,
+    line_numbers: bool,
+    only_matching: bool
+}
+
+fn main() {
+    match run() {
+        Ok(true) => process::exit(0),
+        Ok(false) => process::exit(1),
+        Err(message) => {
+            eprintln!("rmgrep: {}", message);
+            process::exit(2);
+        }
+    }
+}
+
+// whether any line was printed
+fn run() -> Result<bool, String> {
+    let mut case_insensitive = false;
+    let mut options = Options { invert: false, line_numbers: false, only_matching: false };
+    let mut operands = Vec::new();
+
+    for arg in env::args().skip(1) {
+        if operands.is_empty() && arg.len() > 1 && arg.starts_with('-') {
+            for flag in arg[1..].chars() {
+                match flag {
+                    'i' => case_insensitive = true,
+                    'v' => options.invert = true,
+                    'n' => options.line_numbers = true,
+                    'o' => options.only_matching = true,
+                    other => return Err(format!("unknown flag -{}\nusage: rmgrep [-i] [-v] [-n] [-o] PATTERN [FILE...]", other))
+                }
+            }
+        } else {
+            operands.push(arg);
+        }
+    }
+    if operands.is_empty() {
+        return Err("usage: rmgrep [-i] [-v] [-n] [-o] PATTERN [FILE...]".to_owned());
+    }
+
+    let pattern = operands.remove(0);
+    let regex = RegexBuilder::new(&pattern).case_insensitive(case_insensitive).build()
+        .map_err(|e| format!("invalid pattern '{}': {}", pattern, e))?;
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut printed = false;
+
+    if operands.is_empty() {
+        let stdin = io::stdin();
+        printed = grep(&regex, &options, None, stdin.lock(), &mut out)?;
+    } else {
+        let name_lines = operands.len() > 1;
+        for path in &operands {
+            let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+            let name = if name_lines { Some(&path[..]) } else { None };
+            printed |= grep(&regex, &options, name, BufReader::new(file), &mut out)
+                .map_err(|e| format!("{}: {}", path, e))?;
+        }
+    }
+
+    Ok(printed)
+}
+
+fn grep<R: BufRead, W: Write>(regex: &Regex, options: &Options, name: Option<&str>,
+                              reader: R, out: &mut W) -> Result<bool, String> {
+    let mut cache = regex.create_cache();
+    let mut printed = false;
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let matches = regex.find_all(&mut cache, &line);
+        if matches.is_empty() != options.invert {
+            continue;
+        }
+
+        let mut prefix = String::new();
+        if let Some(name) = name {
+            prefix.push_str(name);
+            prefix.push(':');
+        }
+        if options.line_numbers {
+            prefix.push_str(&(i + 1).to_string());
+            prefix.push(':');
+        }
+
+        let written = if options.only_matching && !options.invert {
+            matches.iter()
+                .filter(|m| !m.is_empty()).try_for_each(|m| writeln!(out, "{}{}", prefix, &line[m.start()..m.end()]))
+        } else {
+            writeln!(out, "{}{}", prefix, line)
+        };
+        written.map_err(|e| e.to_string())?;
+        printed = true;
+    }
+
+    Ok(printed)
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use ::error::Error;
+use ::regex::{Regex, RegexBuilder};
+
+#[cfg(test)] mod spec;
+
+const DEFAULT_CAPACITY: usize = 64;
+
+// The compiled regexes by the builder that made them, each with when it was
+// last asked for, so the least recently used one goes first when it is full.
+struct Lru {
+    capacity: usize,
+    clock: u64,
+    regexes: HashMap<RegexBuilder, (Arc<Regex>, u64)>
+}
+
+impl Lru {
+    fn get(&mut self, builder: &RegexBuilder) -> Option<Arc<Regex>> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.regexes.get_mut(builder).map(|entry| {
+            entry.1 = clock;
+            entry.0.clone()
+        })
+    }
+
+    fn insert(&mut self, builder: RegexBuilder, regex: Arc<Regex>) {
+        self.clock += 1;
+        self.regexes.insert(builder, (regex, self.clock));
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.regexes.len() > self.capacity {
+            let oldest = self.regexes.iter()
+                .min_by_key(|&(_, &(_, used))| used)
+                .map(|(builder, _)| builder.clone())
+                .unwrap();
+            self.regexes.remove(&oldest);
+        }
+    }
+}
+
+fn lru() -> &'static Mutex<Lru> {
+    static LRU: OnceLock<Mutex<Lru>> = OnceLock::new();
+    LRU.get_or_init(|| Mutex::new(Lru {
+        capacity: DEFAULT_CAPACITY,
+        clock: 0,
+        regexes: HashMap::new()
+    }))
+}
+
+/// The regex for `pattern`, compiled once and shared by every thread that
+/// asks for it again while it is among the most recently used.
+pub fn regex(pattern: &str) -> Result<Arc<Regex>, Error> {
+    regex_with(&RegexBuilder::new(pattern))
+}
+
+/// Like `regex` with the builder's options, which are part of the key, so
+/// the same pattern with other flags is compiled and kept apart.
+pub fn regex_with(builder: &RegexBuilder) -> Result<Arc<Regex>, Error> {
+    if let Some(regex) = lru().lock().unwrap().get(builder) {
+        return Ok(regex);
+    }
+
+    // compiled without the lock, so threads compiling other patterns do not
+    // wait; two threads compiling the same one both keep the first
+    let regex = Arc::new(builder.build()?);
+    let mut lru = lru().lock().unwrap();
+    if let Some(regex) = lru.get(builder) {
+        return Ok(regex);
+    }
+    lru.insert(builder.clone(), regex.clone());
+    Ok(regex)
+}
+
+/// How many regexes are kept, 64 by default. Shrinking it drops the least
+/// recently used ones.
+pub fn set_capacity(capacity: usize) {
+    let mut lru = lru().lock().unwrap();
+    lru.capacity = capacity;
+    lru.evict();
+}
+
+/// How many regexes are kept now.
+pub fn len() -> usize {
+    lru().lock().unwrap().regexes.len()
+}
+
+/// Drops every regex kept.
+pub fn clear() {
+    lru().lock().unwrap().regexes.clear();
+}
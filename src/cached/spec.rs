@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+
+use ::regex::{Regex, RegexBuilder};
+use super::{Lru, regex, regex_with};
+
+#[test]
+fn shares_compiled_regexes() {
+    let first = regex("cached+").unwrap();
+    let second = regex("cached+").unwrap();
+    assert!(Arc::ptr_eq(&first, &second));
+    assert!(first.is_match("cachedd"));
+}
+
+#[test]
+fn keeps_flags_apart() {
+    let plain = regex("flagged").unwrap();
+    let folded = regex_with(&RegexBuilder::new("flagged").case_insensitive(true)).unwrap();
+    assert!(!Arc::ptr_eq(&plain, &folded));
+    assert!(!plain.is_match("FLAGGED"));
+    assert!(folded.is_match("FLAGGED"));
+}
+
+#[test]
+fn does_not_keep_errors() {
+    assert!(regex("a(b").is_err());
+    assert!(regex("a(b").is_err());
+}
+
+#[test]
+fn shares_between_threads() {
+    let threads = (0..4).map(|_| thread::spawn(|| regex("th(re)+ad").unwrap())).collect::<Vec<_>>();
+    let regexes = threads.into_iter().map(|thread| thread.join().unwrap()).collect::<Vec<_>>();
+    assert!(regexes.iter().all(|regex| Arc::ptr_eq(regex, &regexes[0])));
+}
+
+#[test]
+fn evicts_the_least_recently_used() {
+    let mut lru = Lru { capacity: 2, clock: 0, regexes: HashMap::new() };
+    for pattern in &["a", "b"] {
+        lru.insert(RegexBuilder::new(pattern), Arc::new(Regex::from(pattern).unwrap()));
+    }
+    assert!(lru.get(&RegexBuilder::new("a")).is_some());
+    lru.insert(RegexBuilder::new("c"), Arc::new(Regex::from("c").unwrap()));
+
+    assert!(lru.get(&RegexBuilder::new("a")).is_some());
+    assert!(lru.get(&RegexBuilder::new("b")).is_none());
+    assert!(lru.get(&RegexBuilder::new("c")).is_some());
+}
@@ -0,0 +1,131 @@
+use ::encode::{Encoder, Decoder, DecodeError};
+use ::nfa::{State, Condition, NFA};
+
+// Groups the bytes that no condition of an NFA tells apart, so that DFA
+// tables need a column per group instead of one per byte.
+#[derive(PartialEq,Debug,Clone)]
+pub struct ByteClasses {
+    classes: Vec<u8> // class of each byte
+}
+
+impl ByteClasses {
+    pub fn new(nfa: &NFA) -> ByteClasses {
+        let mut ends = vec![false; 256]; // whether a class ends at each byte
+        // a run of bytes from `first` to `last` that a condition accepts
+        let mut split = |first: u8, last: u8| {
+            if first > 0 {
+                ends[first as usize - 1] = true;
+            }
+            ends[last as usize] = true;
+        };
+
+        for id in 0..nfa.num_states() {
+            if let Some(State::State{condition, ..}) = nfa.get_state(id) {
+                match condition {
+                    Condition::One(byte) => split(*byte, *byte),
+                    Condition::Literal(literal) => {
+                        for &byte in literal.as_bytes() {
+                            split(byte, byte);
+                        }
+                    },
+                    Condition::Class(bytes) => {
+                        let mut run: Option<u8> = None; // where the current run started
+                        for byte in 0..256usize {
+                            let member = bytes.contains(byte as u8);
+                            match run {
+                                None if member => run = Some(byte as u8),
+                                Some(first) if !member => {
+                                    split(first, byte as u8 - 1);
+                                    run = None;
+                                },
+                                _ => ()
+                            }
+                        }
+                        if let Some(first) = run {
+                            split(first, 255);
+                        }
+                    },
+                    Condition::Any => split(b'\n', b'\n'),
+                    Condition::None => ()
+                }
+            }
+        }
+
+        let mut classes = Vec::with_capacity(256);
+        let mut class = 0;
+        for (byte, &end) in ends.iter().enumerate() {
+            classes.push(class);
+            if end && byte < 255 {
+                class += 1;
+            }
+        }
+
+        ByteClasses { classes }
+    }
+
+    // the classes telling apart every two bytes either of these does
+    pub fn combine(&self, other: &ByteClasses) -> ByteClasses {
+        let mut classes = Vec::with_capacity(256);
+        let mut class = 0;
+        for byte in 0..256 {
+            let changes = byte > 0 &&
+                (self.classes[byte] != self.classes[byte - 1] || other.classes[byte] != other.classes[byte - 1]);
+            if changes {
+                class += 1;
+            }
+            classes.push(class);
+        }
+
+        ByteClasses { classes }
+    }
+
+    pub fn get(&self, byte: u8) -> usize {
+        self.classes[byte as usize] as usize
+    }
+
+    pub fn len(&self) -> usize {
+        self.classes[255] as usize + 1
+    }
+
+    // there is always at least one class
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    // the first byte of each class, which stands in for all of them
+    pub fn representatives(&self) -> Vec<u8> {
+        let mut representatives = vec![0];
+        for byte in 1..256 {
+            if self.classes[byte] != self.classes[byte - 1] {
+                representatives.push(byte as u8);
+            }
+        }
+        representatives
+    }
+
+    pub fn encode(&self, encoder: &mut Encoder) {
+        for &class in &self.classes {
+            encoder.u8(class);
+        }
+    }
+
+    // classes have to be numbered in order, without gaps
+    pub fn decode(decoder: &mut Decoder) -> Result<ByteClasses, DecodeError> {
+        let mut classes = Vec::with_capacity(256);
+        for _ in 0..256 {
+            let offset = decoder.offset();
+            let class = decoder.u8()?;
+
+            let in_order = match classes.last() {
+                Some(&previous) => class == previous || class as usize == previous as usize + 1,
+                None => class == 0
+            };
+            if !in_order {
+                return Err(DecodeError::InvalidTag(offset));
+            }
+            classes.push(class);
+        }
+
+        Ok(ByteClasses { classes })
+    }
+}
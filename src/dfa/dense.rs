@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+
+use ::encode::{Encoder, Decoder, DecodeError};
+use ::nfa::NFA;
+use super::{LazyDFA, ByteClasses, DEAD};
+
+const MAGIC: &[u8; 4] = b"RMDF";
+const VERSION: u8 = 2;
+
+// A fully determinized DFA, with every transition of every state in one
+// table indexed by `state * classes.len() + class`. Bytes in the same class
+// always lead to the same state, so they share a column.
+#[derive(PartialEq,Debug,Clone)]
+pub struct DenseDFA {
+    start: usize,
+    classes: ByteClasses,
+    transitions: Vec<u32>,
+    is_match: Vec<bool>
+}
+
+impl DenseDFA {
+    // builds every state reachable from the start, giving up once the
+    // tables would take more than `size_limit` bytes
+    pub fn build(nfa: &NFA, size_limit: usize) -> Option<DenseDFA> {
+        Self::determinize(LazyDFA::with_cache_limit(nfa, usize::MAX), size_limit)
+    }
+
+    // like `build`, but `match_end` finds the longest match
+    pub fn build_longest(nfa: &NFA, size_limit: usize) -> Option<DenseDFA> {
+        Self::determinize(LazyDFA::with_cache_limit(nfa, usize::MAX).longest(), size_limit)
+    }
+
+    fn determinize(mut lazy: LazyDFA, size_limit: usize) -> Option<DenseDFA> {
+        let start = lazy.start();
+        let representatives = lazy.cache.classes.representatives();
+
+        let mut id = 0;
+        while id < lazy.cache.states.len() {
+            for &byte in &representatives {
+                lazy.next(id, byte);
+            }
+            id += 1;
+
+            if Self::size_of(lazy.cache.states.len(), lazy.cache.classes.len()) > size_limit {
+                return None;
+            }
+        }
+
+        Some(DenseDFA {
+            start,
+            classes: lazy.cache.classes.clone(),
+            transitions: lazy.cache.states.iter()
+                .flat_map(|state| state.transitions.iter().map(|&next| next as u32))
+                .collect(),
+            is_match: lazy.cache.states.iter().map(|state| state.is_match).collect()
+        })
+    }
+
+    // merges equivalent states using Hopcroft's algorithm: states start out
+    // split by whether they match, and blocks are split further whenever
+    // some byte leads part of a block into another block
+    pub fn minimize(&self) -> DenseDFA {
+        let num_states = self.num_states();
+        let num_classes = self.classes.len();
+
+        let mut predecessors = vec![Vec::new(); num_states * num_classes];
+        for state in 0..num_states {
+            for class in 0..num_classes {
+                let next = self.transitions[state * num_classes + class] as usize;
+                predecessors[next * num_classes + class].push(state);
+            }
+        }
+
+        let (matching, other): (Vec<usize>, Vec<usize>) = (0..num_states).partition(|&s| self.is_match[s]);
+        let mut blocks = vec![other, matching];
+        blocks.retain(|block| !block.is_empty());
+
+        let mut block_of = vec![0; num_states];
+        for (i, block) in blocks.iter().enumerate() {
+            for &state in block {
+                block_of[state] = i;
+            }
+        }
+
+        let mut worklist = (0..blocks.len()).collect::<Vec<_>>();
+        let mut in_worklist = vec![true; blocks.len()];
+        let mut marked = vec![false; num_states];
+
+        while let Some(splitter) = worklist.pop() {
+            in_worklist[splitter] = false;
+            let splitter = blocks[splitter].clone();
+
+            for class in 0..num_classes {
+                // states leading into the splitter on this class, by block
+                let mut touched = HashMap::<usize, Vec<usize>>::new();
+                for &target in &splitter {
+                    for &state in &predecessors[target * num_classes + class] {
+                        if !marked[state] {
+                            marked[state] = true;
+                            touched.entry(block_of[state]).or_default().push(state);
+                        }
+                    }
+                }
+
+                for (block, members) in touched {
+                    for &state in &members {
+                        marked[state] = false;
+                    }
+                    if members.len() == blocks[block].len() {
+                        continue; // the whole block agrees
+                    }
+
+                    let new_block = blocks.len();
+                    for &state in &members {
+                        block_of[state] = new_block;
+                    }
+                    blocks[block].retain(|&state| block_of[state] == block);
+
+                    // a block that is queued already is split in the queue
+                    // too, otherwise only the smaller half needs to be
+                    let smaller = if in_worklist[block] || members.len() <= blocks[block].len() {
+                        new_block
+                    } else {
+                        block
+                    };
+                    blocks.push(members);
+                    in_worklist.push(false);
+
+                    if !in_worklist[smaller] {
+                        worklist.push(smaller);
+                        in_worklist[smaller] = true;
+                    }
+                }
+            }
+        }
+
+        self.merge(&blocks, &block_of)
+    }
+
+    // builds a DFA with one state per block, numbered in order of their
+    // lowest state so that the dead state stays first
+    fn merge(&self, blocks: &[Vec<usize>], block_of: &[usize]) -> DenseDFA {
+        let mut order = (0..blocks.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&block| blocks[block].iter().min().cloned());
+
+        let mut new_id = vec![0; blocks.len()];
+        for (id, &block) in order.iter().enumerate() {
+            new_id[block] = id;
+        }
+
+        let num_classes = self.classes.len();
+        let mut transitions = Vec::with_capacity(blocks.len() * num_classes);
+        let mut is_match = Vec::with_capacity(blocks.len());
+        for &block in &order {
+            let representative = blocks[block][0];
+
+            is_match.push(self.is_match[representative]);
+            for class in 0..num_classes {
+                let next = self.transitions[representative * num_classes + class] as usize;
+                transitions.push(new_id[block_of[next]] as u32);
+            }
+        }
+
+        DenseDFA {
+            start: new_id[block_of[self.start]],
+            classes: self.classes.clone(),
+            transitions,
+            is_match
+        }
+    }
+
+    // The texts a DFA accepts are those leading from the start to a
+    // matching state. For a DFA from `build_longest` these are exactly the
+    // texts its NFA matches, while `build` leaves out the longer matches
+    // that would lose to shorter ones.
+
+    // whether all of `text` is accepted, rather than some prefix of it
+    pub fn accepts(&self, text: &[u8]) -> bool {
+        let mut current = self.start;
+        for &byte in text {
+            current = self.next(current, byte);
+            if current == DEAD {
+                return false;
+            }
+        }
+        self.is_match[current]
+    }
+
+    // whether no text at all is accepted
+    pub fn is_empty(&self) -> bool {
+        self.minimize().start == DEAD
+    }
+
+    // a DFA accepting the texts both DFAs accept
+    pub fn intersect(&self, other: &DenseDFA) -> DenseDFA {
+        self.product(other, |a, b| a && b)
+    }
+
+    // a DFA accepting the texts either DFA accepts
+    pub fn union(&self, other: &DenseDFA) -> DenseDFA {
+        self.product(other, |a, b| a || b)
+    }
+
+    // a DFA accepting the texts this one does not. The dead state accepts
+    // everything now, so a new dead state is put in front of the ones the
+    // start can reach.
+    pub fn complement(&self) -> DenseDFA {
+        let num_classes = self.classes.len();
+
+        let mut reachable = vec![self.start];
+        let mut new_id = vec![None; self.num_states()];
+        new_id[self.start] = Some(1);
+        let mut i = 0;
+        while i < reachable.len() {
+            let state = reachable[i];
+            for &next in &self.transitions[state * num_classes..(state + 1) * num_classes] {
+                if new_id[next as usize].is_none() {
+                    new_id[next as usize] = Some(reachable.len() + 1);
+                    reachable.push(next as usize);
+                }
+            }
+            i += 1;
+        }
+
+        let mut transitions = vec![DEAD as u32; num_classes];
+        let mut is_match = vec![false];
+        for &state in &reachable {
+            is_match.push(!self.is_match[state]);
+            for &next in &self.transitions[state * num_classes..(state + 1) * num_classes] {
+                transitions.push(new_id[next as usize].unwrap() as u32);
+            }
+        }
+
+        DenseDFA {
+            start: 1,
+            classes: self.classes.clone(),
+            transitions,
+            is_match
+        }.minimize()
+    }
+
+    // runs both DFAs side by side, with a state for each pair of their states
+    // the input can reach, matching where `combine` says the pair does
+    fn product<F>(&self, other: &DenseDFA, combine: F) -> DenseDFA where F: Fn(bool, bool) -> bool {
+        let classes = self.classes.combine(&other.classes);
+        let representatives = classes.representatives();
+
+        let mut pairs = Vec::new();
+        let mut ids = HashMap::new();
+        let mut intern = |pair: (usize, usize), pairs: &mut Vec<(usize, usize)>| {
+            *ids.entry(pair).or_insert_with(|| {
+                pairs.push(pair);
+                pairs.len() - 1
+            })
+        };
+        intern((DEAD, DEAD), &mut pairs);
+        let start = intern((self.start, other.start), &mut pairs);
+
+        let mut transitions = Vec::new();
+        let mut id = 0;
+        while id < pairs.len() {
+            let (a, b) = pairs[id];
+            for &byte in &representatives {
+                let next = intern((self.next(a, byte), other.next(b, byte)), &mut pairs);
+                transitions.push(next as u32);
+            }
+            id += 1;
+        }
+
+        DenseDFA {
+            start,
+            classes,
+            transitions,
+            is_match: pairs.iter().map(|&(a, b)| combine(self.is_match[a], other.is_match[b])).collect()
+        }.minimize()
+    }
+
+    pub fn num_states(&self) -> usize {
+        self.is_match.len()
+    }
+
+    // the heap memory used by the tables
+    pub fn memory_usage(&self) -> usize {
+        Self::size_of(self.num_states(), self.classes.len())
+    }
+
+    fn size_of(num_states: usize, num_classes: usize) -> usize {
+        256 + num_states * (num_classes * 4 + 1)
+    }
+
+    pub fn num_classes(&self) -> usize {
+        self.classes.len()
+    }
+
+    fn next(&self, current: usize, byte: u8) -> usize {
+        self.transitions[current * self.classes.len() + self.classes.get(byte)] as usize
+    }
+
+    pub fn is_match(&self, text: &[u8]) -> bool {
+        self.earliest_end(text).is_some()
+    }
+
+    // the end of the shortest match starting at the beginning of `text`,
+    // found without reading past it
+    pub fn earliest_end(&self, text: &[u8]) -> Option<usize> {
+        let mut current = self.start;
+        if self.is_match[current] {
+            return Some(0);
+        }
+
+        for (i, &byte) in text.iter().enumerate() {
+            current = self.next(current, byte);
+
+            if current == DEAD {
+                return None;
+            }
+            if self.is_match[current] {
+                return Some(i + 1);
+            }
+        }
+
+        None
+    }
+
+    pub fn match_end(&self, text: &[u8]) -> Option<usize> {
+        let mut current = self.start;
+        let mut end = if self.is_match[current] { Some(0) } else { None };
+
+        for (i, &byte) in text.iter().enumerate() {
+            current = self.next(current, byte);
+
+            if current == DEAD {
+                break;
+            }
+            if self.is_match[current] {
+                end = Some(i + 1);
+            }
+        }
+
+        end
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new(MAGIC, VERSION);
+        self.encode(&mut encoder);
+        encoder.finish()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<DenseDFA, DecodeError> {
+        let mut decoder = Decoder::new(bytes, MAGIC, VERSION)?;
+        let dfa = Self::decode(&mut decoder)?;
+        decoder.finish()?;
+        Ok(dfa)
+    }
+
+    pub fn encode(&self, encoder: &mut Encoder) {
+        encoder.u32(self.start);
+        encoder.u32(self.num_states());
+        self.classes.encode(encoder);
+
+        let num_classes = self.classes.len();
+        for (id, &is_match) in self.is_match.iter().enumerate() {
+            encoder.u8(is_match as u8);
+            for &next in &self.transitions[id * num_classes..(id + 1) * num_classes] {
+                encoder.u32(next as usize);
+            }
+        }
+    }
+
+    pub fn decode(decoder: &mut Decoder) -> Result<DenseDFA, DecodeError> {
+        let start = decoder.u32()?;
+        let num_states = decoder.u32()?;
+        let classes = ByteClasses::decode(decoder)?;
+
+        let mut transitions = Vec::new();
+        let mut is_match = Vec::new();
+        for id in 0..num_states {
+            let offset = decoder.offset();
+            match decoder.u8()? {
+                flag if flag <= 1 => is_match.push(flag == 1),
+                _ => return Err(DecodeError::InvalidTag(offset))
+            }
+
+            for _ in 0..classes.len() {
+                match decoder.u32()? {
+                    next if next < num_states => transitions.push(next as u32),
+                    _ => return Err(DecodeError::InvalidTransition(id))
+                }
+            }
+        }
+
+        // the dead state has to be first, and has to stay dead
+        let dead_is_dead = num_states > 0 && !is_match[DEAD] &&
+            transitions[..classes.len()].iter().all(|&next| next as usize == DEAD);
+        if !dead_is_dead {
+            return Err(DecodeError::InvalidTransition(DEAD));
+        }
+        if start >= num_states {
+            return Err(DecodeError::InvalidTransition(start));
+        }
+
+        Ok(DenseDFA {
+            start,
+            classes,
+            transitions,
+            is_match
+        })
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+
+use ::nfa::{State, Transition, NFA, Condition};
+
+mod classes;
+mod dense;
+#[cfg(test)] mod spec;
+
+pub use self::dense::DenseDFA;
+pub use self::classes::ByteClasses;
+
+const DEFAULT_CACHE_LIMIT: usize = 1000;
+
+const UNKNOWN: usize = usize::MAX; // transition not computed yet
+const DEAD: usize = 0; // no match is possible any more
+
+// A DFA state: the NFA states that can consume the next byte, in order of
+// priority. States of lower priority than an accepting path are left out,
+// so the first match found along a path is the leftmost-first one.
+struct DState {
+    nfa_states: Vec<usize>,
+    is_match: bool,
+    transitions: Vec<usize> // by byte class
+}
+
+// Builds DFA states from the NFA as the search reaches them, so only the
+// states the input actually needs are ever constructed. Once more than
+// `cache_limit` states are built, all of them are dropped and rebuilt as
+// needed, which bounds memory use on pathological patterns.
+pub struct LazyDFA<'n> {
+    nfa: &'n NFA,
+    cache: Cache,
+    cache_limit: usize
+}
+
+// The DFA states built so far. Searches with the same NFA can take it over
+// from one another, so that each does not have to build them again. The
+// states depend on whether the longest match is looked for, so that is kept
+// along with them.
+pub struct Cache {
+    longest: bool, // keep going after a match instead of preferring it
+    classes: ByteClasses,
+    states: Vec<DState>,
+    ids: HashMap<(Vec<usize>, bool), usize>
+}
+
+impl Cache {
+    pub fn new(nfa: &NFA) -> Cache {
+        let mut cache = Cache {
+            longest: false,
+            classes: ByteClasses::new(nfa),
+            states: Vec::new(),
+            ids: HashMap::new()
+        };
+        cache.clear();
+        cache
+    }
+
+    fn clear(&mut self) {
+        self.states.clear();
+        self.ids.clear();
+
+        self.states.push(DState {
+            nfa_states: Vec::new(),
+            is_match: false,
+            transitions: vec![DEAD; self.classes.len()]
+        });
+    }
+}
+
+impl<'n> LazyDFA<'n> {
+    pub fn new(nfa: &'n NFA) -> LazyDFA<'n> {
+        Self::with_cache_limit(nfa, DEFAULT_CACHE_LIMIT)
+    }
+
+    pub fn with_cache_limit(nfa: &'n NFA, cache_limit: usize) -> LazyDFA<'n> {
+        LazyDFA {
+            nfa,
+            cache: Cache::new(nfa),
+            cache_limit
+        }
+    }
+
+    // starts from the states an earlier search with `nfa` built
+    pub fn with_cache(nfa: &'n NFA, cache: Cache) -> LazyDFA<'n> {
+        LazyDFA {
+            nfa,
+            cache,
+            cache_limit: DEFAULT_CACHE_LIMIT
+        }
+    }
+
+    // the states built so far, for a later search to start from
+    pub fn into_cache(self) -> Cache {
+        self.cache
+    }
+
+    // makes `match_end` find the longest match rather than the leftmost-first
+    pub fn longest(mut self) -> LazyDFA<'n> {
+        if !self.cache.longest {
+            self.cache.longest = true;
+            self.cache.clear();
+        }
+        self
+    }
+
+    pub fn num_cached_states(&self) -> usize {
+        self.cache.states.len()
+    }
+
+    // whether a match starts at the beginning of `text`
+    pub fn is_match(&mut self, text: &[u8]) -> bool {
+        self.earliest_end(text).is_some()
+    }
+
+    // the end of the shortest match starting at the beginning of `text`,
+    // found without reading past it
+    pub fn earliest_end(&mut self, text: &[u8]) -> Option<usize> {
+        let mut current = self.start();
+        if self.cache.states[current].is_match {
+            return Some(0);
+        }
+
+        for (i, &byte) in text.iter().enumerate() {
+            current = self.next(current, byte);
+
+            if current == DEAD {
+                return None;
+            }
+            if self.cache.states[current].is_match {
+                return Some(i + 1);
+            }
+        }
+
+        None
+    }
+
+    // the end of the leftmost-first match starting at the beginning of `text`
+    pub fn match_end(&mut self, text: &[u8]) -> Option<usize> {
+        self.match_len(text.iter().cloned())
+    }
+
+    // the start of the leftmost-first match ending at the end of `text`,
+    // running over it from the end. The NFA has to have been reversed for
+    // that to be a match of the pattern.
+    pub fn rmatch_start(&mut self, text: &[u8]) -> Option<usize> {
+        self.match_len(text.iter().rev().cloned()).map(|len| text.len() - len)
+    }
+
+    // the length of the leftmost-first match at the start of `bytes`
+    fn match_len<I: Iterator<Item=u8>>(&mut self, bytes: I) -> Option<usize> {
+        let mut current = self.start();
+        let mut end = if self.cache.states[current].is_match { Some(0) } else { None };
+
+        for (i, byte) in bytes.enumerate() {
+            current = self.next(current, byte);
+
+            if current == DEAD {
+                break;
+            }
+            if self.cache.states[current].is_match {
+                end = Some(i + 1);
+            }
+        }
+
+        end
+    }
+
+    fn start(&mut self) -> usize {
+        if self.nfa.num_states() == 0 { // an empty NFA matches immediately
+            return self.intern(Vec::new(), true);
+        }
+
+        let mut nfa_states = Vec::new();
+        let is_match = self.closure(Transition::id(self.nfa.get_start_id()), &mut nfa_states);
+        self.intern(nfa_states, is_match)
+    }
+
+    fn next(&mut self, current: usize, byte: u8) -> usize {
+        let class = self.cache.classes.get(byte);
+        let cached = self.cache.states[current].transitions[class];
+        if cached != UNKNOWN {
+            return cached;
+        }
+
+        let mut nfa_states = Vec::new();
+        let mut is_match = false;
+        for i in 0..self.cache.states[current].nfa_states.len() {
+            let id = self.cache.states[current].nfa_states[i];
+
+            if let Some(&State::State{ref condition, out}) = self.nfa.get_state(id) {
+                if condition.accepts(byte) && self.closure(out, &mut nfa_states) {
+                    is_match = true;
+                    if !self.cache.longest {
+                        break; // lower priority states lose to this match
+                    }
+                }
+            }
+        }
+
+        if self.cache.states.len() >= self.cache_limit {
+            // `current` is dropped along with the rest, so the transition is
+            // not remembered
+            self.cache.clear();
+            return self.intern(nfa_states, is_match);
+        }
+
+        let next = self.intern(nfa_states, is_match);
+        self.cache.states[current].transitions[class] = next;
+        next
+    }
+
+    // adds the states reachable from `edge` without consuming input, in order
+    // of priority, stopping at the end of the NFA unless looking for the
+    // longest match. Returns whether the end was reached.
+    fn closure(&self, edge: Transition, nfa_states: &mut Vec<usize>) -> bool {
+        let mut seen = vec![false; self.nfa.num_states()];
+        for &id in nfa_states.iter() {
+            seen[id] = true;
+        }
+
+        if let Transition::Id(id) = edge {
+            let id = id.index();
+            if let Some(closure) = self.nfa.closure(id) {
+                let states = if self.cache.longest { closure.states() } else { closure.before_end() };
+                for &id in states {
+                    if !seen[id] {
+                        seen[id] = true;
+                        nfa_states.push(id);
+                    }
+                }
+                return closure.matches();
+            }
+        }
+
+        let mut edges = vec![edge];
+        let mut is_match = false;
+
+        while let Some(edge) = edges.pop() {
+            match edge {
+                Transition::End if self.cache.longest => is_match = true,
+                Transition::End => return true,
+                Transition::Id(id) => {
+                    let id = id.index();
+                    if seen[id] {
+                        continue;
+                    }
+                    seen[id] = true;
+
+                    match self.nfa.get_state(id) {
+                        Some(&State::State{condition: Condition::None, out}) => {
+                            edges.push(out);
+                        },
+                        Some(&State::State{..}) => nfa_states.push(id),
+                        Some(&State::Split{out1, out2}) => {
+                            edges.push(out2);
+                            edges.push(out1); // out1 is preferred
+                        },
+                        Some(&State::Save{out, ..}) => {
+                            edges.push(out); // capture slots do not change what matches
+                        },
+                        None => panic!("transition to missing state {}", id)
+                    }
+                },
+                Transition::Detached => panic!("cannot evaluate incomplete NFA")
+            }
+        }
+
+        is_match
+    }
+
+    fn intern(&mut self, nfa_states: Vec<usize>, is_match: bool) -> usize {
+        if nfa_states.is_empty() && !is_match {
+            return DEAD;
+        }
+
+        let key = (nfa_states, is_match);
+        if let Some(&id) = self.cache.ids.get(&key) {
+            return id;
+        }
+
+        let id = self.cache.states.len();
+        self.cache.states.push(DState {
+            nfa_states: key.0.clone(),
+            is_match,
+            transitions: vec![UNKNOWN; self.cache.classes.len()]
+        });
+        self.cache.ids.insert(key, id);
+        id
+    }
+}
@@ -0,0 +1,292 @@
+use ::expr::Expr;
+use ::nfa::{NFA, State, Transition, Condition};
+use ::encode::DecodeError;
+use super::{LazyDFA, DenseDFA, ByteClasses};
+
+fn nfa(pattern: &str) -> NFA {
+    NFA::from_expr(&pattern.parse::<Expr>().unwrap())
+}
+
+#[test]
+fn matches_at_start() {
+    let nfa = nfa("ab*c");
+    let mut dfa = LazyDFA::new(&nfa);
+
+    assert!(dfa.is_match(b"ac"));
+    assert!(dfa.is_match(b"abbbcd"));
+    assert!(!dfa.is_match(b"xac"));
+    assert!(!dfa.is_match(b"abb"));
+}
+
+#[test]
+fn stops_at_earliest_match() {
+    let nfa = nfa("a+");
+    let mut dfa = LazyDFA::new(&nfa);
+
+    assert!(dfa.is_match(b"aaaa"));
+    assert_eq!(3, dfa.num_cached_states()); // dead, start and the first matching state
+}
+
+#[test]
+fn finds_greedy_match_end() {
+    let nfa = nfa("a(bc)*");
+    let mut dfa = LazyDFA::new(&nfa);
+
+    assert_eq!(Some(5), dfa.match_end(b"abcbcb"));
+    assert_eq!(Some(1), dfa.match_end(b"ab"));
+    assert_eq!(None, dfa.match_end(b"bc"));
+}
+
+#[test]
+fn prefers_left_alternative() {
+    assert_eq!(Some(1), LazyDFA::new(&nfa("a|ab")).match_end(b"ab"));
+    assert_eq!(Some(2), LazyDFA::new(&nfa("ab|a")).match_end(b"ab"));
+    assert_eq!(Some(2), LazyDFA::new(&nfa("(ab|.)")).match_end(b"ab"));
+}
+
+#[test]
+fn matches_empty() {
+    assert_eq!(Some(0), LazyDFA::new(&nfa("a*")).match_end(b""));
+    assert_eq!(Some(0), LazyDFA::new(&nfa("a*")).match_end(b"b"));
+    assert_eq!(Some(0), LazyDFA::new(&NFA::new()).match_end(b"b"));
+}
+
+#[test]
+fn follows_empty_conditions() {
+    let nfa = NFA::from_states(vec![
+        State::state(Condition::None, Transition::id(1)),
+        State::state(Condition::one(b'a'), Transition::End)
+    ]);
+
+    assert_eq!(Some(1), LazyDFA::new(&nfa).match_end(b"ab"));
+}
+
+#[test]
+fn reuses_cached_states() {
+    let nfa = nfa("(ab)+");
+    let mut dfa = LazyDFA::new(&nfa);
+
+    assert_eq!(Some(8), dfa.match_end(b"abababab"));
+    assert_eq!(4, dfa.num_cached_states());
+}
+
+#[test]
+fn clears_full_cache() {
+    let nfa = nfa("(a|b)*abb");
+    let mut dfa = LazyDFA::with_cache_limit(&nfa, 3);
+
+    assert_eq!(Some(10), dfa.match_end(b"abababbabb"));
+    assert!(dfa.num_cached_states() <= 3);
+}
+
+#[test]
+fn groups_bytes_conditions_do_not_tell_apart() {
+    let classes = ByteClasses::new(&nfa("a[cd].|e"));
+
+    assert_eq!(13, classes.len());
+    assert!(!classes.is_empty());
+    assert_eq!(classes.get(b'\0'), classes.get(b'\t'));
+    assert_eq!(classes.get(b'f'), classes.get(0x7f));
+    assert_eq!(classes.get(0xc2), classes.get(0xdf)); // `.` tells lead bytes apart by length
+    assert!(classes.get(b'a') != classes.get(b'b'));
+    assert_eq!(classes.get(b'c'), classes.get(b'd')); // a class is only split at its ends
+    assert!(classes.get(b'\n') != classes.get(b'\t'));
+    assert_eq!(vec![0, b'\n', 11, b'a', b'b', b'c', b'e', b'f', 0x80, 0xc0, 0xe0, 0xf0, 0xf8], classes.representatives());
+}
+
+#[test]
+fn dense_dfa_indexes_by_byte_class() {
+    let dfa = DenseDFA::build(&nfa("[abc]+x"), 1 << 20).unwrap();
+
+    assert_eq!(5, dfa.num_classes());
+    assert!(dfa.memory_usage() < dfa.num_states() * 256 * 4);
+}
+
+#[test]
+fn dense_dfa_matches_like_lazy_dfa() {
+    let patterns = ["ab*c", "a(bc)*", "a|ab", "(ab|.)", "a*", "(a|b)*abb", "[ab]+é"];
+    let texts: [&[u8]; 7] = [b"", b"ac", b"abbbc", b"abcbcb", b"ab", b"abababbabb", "baé".as_bytes()];
+
+    for pattern in patterns.iter() {
+        let nfa = nfa(pattern);
+        let dense = DenseDFA::build(&nfa, 1 << 20).unwrap();
+
+        for text in texts.iter() {
+            assert_eq!(LazyDFA::new(&nfa).match_end(text), dense.match_end(text), "{} on {:?}", pattern, text);
+            assert_eq!(LazyDFA::new(&nfa).is_match(text), dense.is_match(text), "{} on {:?}", pattern, text);
+        }
+    }
+}
+
+#[test]
+fn dense_dfa_finds_longest_match_like_lazy_dfa() {
+    let patterns = ["a|ab", "a(bc)*?", "(ab|a)(bc)?", "a*?"];
+    let texts: [&[u8]; 4] = [b"", b"ab", b"abcbc", b"aaa"];
+
+    for pattern in patterns.iter() {
+        let nfa = nfa(pattern);
+        let dense = DenseDFA::build_longest(&nfa, 1 << 20).unwrap();
+
+        for text in texts.iter() {
+            assert_eq!(LazyDFA::new(&nfa).longest().match_end(text), dense.match_end(text), "{} on {:?}", pattern, text);
+        }
+    }
+    assert_eq!(Some(2), DenseDFA::build_longest(&nfa("a|ab"), 1 << 20).unwrap().match_end(b"ab"));
+}
+
+#[test]
+fn dense_dfa_respects_size_limit() {
+    let nfa = nfa("(a|b)*a(a|b)(a|b)(a|b)");
+
+    let dfa = DenseDFA::build(&nfa, 1 << 20).unwrap();
+    assert_eq!(17, dfa.num_states()); // 16 states and the dead state
+    assert_eq!(None, DenseDFA::build(&nfa, dfa.memory_usage() - 1));
+}
+
+#[test]
+fn dense_dfa_round_trips_through_bytes() {
+    let dfa = DenseDFA::build(&nfa("a(b|c)*"), 1 << 20).unwrap();
+    assert_eq!(Ok(dfa.clone()), DenseDFA::from_bytes(&dfa.to_bytes()));
+}
+
+#[test]
+fn dense_dfa_rejects_invalid_bytes() {
+    let bytes = DenseDFA::build(&nfa("a"), 1 << 20).unwrap().to_bytes();
+
+    let mut dangling = bytes.clone();
+    dangling[270 + 4] = 9; // state 0 on the class of 'a'
+    assert_eq!(Err(DecodeError::InvalidTransition(0)), DenseDFA::from_bytes(&dangling));
+
+    let mut live_dead_state = bytes.clone();
+    live_dead_state[270 + 4] = 1;
+    assert_eq!(Err(DecodeError::InvalidTransition(0)), DenseDFA::from_bytes(&live_dead_state));
+
+    let mut bad_flag = bytes.clone();
+    bad_flag[269] = 2;
+    assert_eq!(Err(DecodeError::InvalidTag(269)), DenseDFA::from_bytes(&bad_flag));
+
+    let mut skipped_class = bytes.clone();
+    skipped_class[13 + 97] = 2;
+    assert_eq!(Err(DecodeError::InvalidTag(13 + 97)), DenseDFA::from_bytes(&skipped_class));
+}
+
+#[test]
+fn minimizing_merges_equivalent_states() {
+    // the group keeps the two `b`s apart in the NFA
+    let nfa = nfa("(ab|c(b))d");
+    let dfa = DenseDFA::build(&nfa, 1 << 20).unwrap();
+    let minimal = dfa.minimize();
+
+    assert_eq!(6, dfa.num_states());
+    assert_eq!(5, minimal.num_states()); // after `a` and after `c` are the same
+    for text in [&b"abd"[..], b"cbd", b"ab", b"bd", b"abdd"].iter() {
+        assert_eq!(dfa.match_end(text), minimal.match_end(text));
+    }
+}
+
+#[test]
+fn minimizing_keeps_matches() {
+    let patterns = ["ab*c", "a|ab", "a*", "(a|b)*abb", "(a|b)*a(a|b)", "[ab]+é"];
+    let texts: [&[u8]; 6] = [b"", b"ac", b"abbbc", b"ab", b"abababbabb", "baé".as_bytes()];
+
+    for pattern in patterns.iter() {
+        let dfa = DenseDFA::build(&nfa(pattern), 1 << 20).unwrap();
+        let minimal = dfa.minimize();
+
+        assert!(minimal.num_states() <= dfa.num_states());
+        assert_eq!(minimal, minimal.minimize());
+        for text in texts.iter() {
+            assert_eq!(dfa.match_end(text), minimal.match_end(text), "{} on {:?}", pattern, text);
+        }
+    }
+}
+
+#[test]
+fn finds_longest_match() {
+    let nfa = nfa("a|ab|abcd");
+
+    assert_eq!(Some(1), LazyDFA::new(&nfa).match_end(b"abcd"));
+    assert_eq!(Some(4), LazyDFA::new(&nfa).longest().match_end(b"abcd"));
+    assert_eq!(Some(2), LazyDFA::new(&nfa).longest().match_end(b"abc"));
+}
+
+#[test]
+fn keeps_states_in_cache() {
+    let nfa = nfa("a(b|c)*d");
+    let mut dfa = LazyDFA::new(&nfa);
+    assert_eq!(Some(4), dfa.match_end(b"abcd"));
+    let built = dfa.num_cached_states();
+
+    let mut dfa = LazyDFA::with_cache(&nfa, dfa.into_cache());
+    assert_eq!(built, dfa.num_cached_states());
+    assert_eq!(Some(4), dfa.match_end(b"acbd"));
+    assert_eq!(built, dfa.num_cached_states());
+}
+
+fn longest(pattern: &str) -> DenseDFA {
+    DenseDFA::build_longest(&nfa(pattern), 1 << 20).unwrap()
+}
+
+#[test]
+fn accepts_whole_text() {
+    let dfa = longest("ab*");
+    assert!(dfa.accepts(b"abb"));
+    assert!(!dfa.accepts(b"abba"));
+    assert!(!dfa.accepts(b""));
+    assert!(dfa.is_match(b"abba"));
+}
+
+#[test]
+fn intersects_dfas() {
+    let dfa = longest("(a|b)*a").intersect(&longest("a(a|b)*"));
+    for text in [&b"a"[..], b"aba", b"abba"].iter() {
+        assert!(dfa.accepts(text), "{:?}", text);
+    }
+    for text in [&b""[..], b"ab", b"ba", b"bab"].iter() {
+        assert!(!dfa.accepts(text), "{:?}", text);
+    }
+}
+
+#[test]
+fn unites_dfas() {
+    let dfa = longest("ab").union(&longest("cd*"));
+    for text in [&b"ab"[..], b"c", b"cddd"].iter() {
+        assert!(dfa.accepts(text), "{:?}", text);
+    }
+    for text in [&b""[..], b"a", b"abd", b"d"].iter() {
+        assert!(!dfa.accepts(text), "{:?}", text);
+    }
+}
+
+#[test]
+fn complements_dfas() {
+    let dfa = longest("a+").complement();
+    for text in [&b""[..], b"b", b"ab", b"aab", "é".as_bytes()].iter() {
+        assert!(dfa.accepts(text), "{:?}", text);
+    }
+    for text in [&b"a"[..], b"aaa"].iter() {
+        assert!(!dfa.accepts(text), "{:?}", text);
+    }
+    assert_eq!(dfa.complement(), longest("a+").minimize());
+}
+
+#[test]
+fn matches_one_but_not_the_other() {
+    let words = longest("[abc]+");
+    let dfa = words.intersect(&longest("(a|b|c)*bb(a|b|c)*").complement());
+
+    assert!(dfa.accepts(b"abcab"));
+    assert!(!dfa.accepts(b"abba"));
+    assert!(!dfa.accepts(b""));
+    assert_eq!(dfa, DenseDFA::from_bytes(&dfa.to_bytes()).unwrap());
+}
+
+#[test]
+fn products_of_dfas_that_match_nothing_are_dead() {
+    let nothing = longest("a").intersect(&longest("b"));
+    assert_eq!(1, nothing.num_states());
+    assert!(nothing.is_empty());
+    assert!(!longest("a").is_empty() && !nothing.complement().is_empty());
+    assert!(!nothing.accepts(b"a") && !nothing.accepts(b""));
+    assert!(nothing.complement().accepts(b"a"));
+}
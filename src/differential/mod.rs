@@ -0,0 +1,231 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::str;
+
+use ::expr::{Expr, ALPHABET};
+use ::generate::Generator;
+use ::regex::{RegexBuilder, Match};
+use ::rng::Rng;
+
+#[cfg(test)] mod spec;
+
+// large enough for the patterns the harness makes up
+const DFA_SIZE_LIMIT: usize = 1 << 20;
+
+/// A pattern and text on which the engine found another match than the
+/// reference did.
+#[derive(PartialEq,Eq,Debug,Clone)]
+pub struct Disagreement {
+    pattern: String,
+    text: String,
+    expected: Option<Match>,
+    found: Option<Match>
+}
+
+impl Disagreement {
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// What the reference found.
+    pub fn expected(&self) -> Option<Match> {
+        self.expected
+    }
+
+    /// What the engine found.
+    pub fn found(&self) -> Option<Match> {
+        self.found
+    }
+}
+
+impl fmt::Display for Disagreement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} on {:?}: expected {:?}, found {:?}", self.pattern, self.text, self.expected, self.found)
+    }
+}
+
+/// The leftmost-first match of `expr` in `text`, found by trying every
+/// start and backtracking through the expression itself, without compiling
+/// it. Slow, but simple enough to trust. Offsets are in bytes and `.`
+/// matches one char, as in the engine.
+pub fn reference_find(expr: &Expr, text: &str) -> Option<Match> {
+    for start in 0..text.len() + 1 {
+        let reference = Reference { text: text.as_bytes(), visited: RefCell::new(HashSet::new()) };
+        let mut end = None;
+        reference.matches(expr, start, &mut |at| {
+            end = Some(at);
+            true
+        });
+        if let Some(end) = end {
+            return Some(Match::new(start, end));
+        }
+    }
+    None
+}
+
+type Then<'t> = &'t mut dyn FnMut(usize) -> bool;
+
+// A search that comes to each point of the expression at most once per
+// offset, as the engine's automaton enters each state at most once per
+// offset. Coming back to a point tried before gives up: either it failed
+// then, or the path goes around a loop without consuming anything. So an
+// iteration that matched empty on the way around is given up for the next
+// way the item could match, while one that matched empty on the way in
+// leaves the loop.
+struct Reference<'t> {
+    text: &'t [u8],
+    visited: RefCell<HashSet<(*const Expr, bool, usize)>> // a point, whether after the item there, and an offset
+}
+
+impl<'t> Reference<'t> {
+    fn visit(&self, expr: &Expr, after: bool, at: usize) -> bool {
+        self.visited.borrow_mut().insert((expr as *const Expr, after, at))
+    }
+
+    // Whether `expr` matches at `at` with some end that `then` accepts,
+    // trying the ends in order of preference.
+    fn matches(&self, expr: &Expr, at: usize, then: Then) -> bool {
+        if !self.visit(expr, false, at) {
+            return false;
+        }
+        let text = self.text;
+        match expr {
+            Expr::Empty => then(at),
+            Expr::Single(c) => single(*c, text, at).is_some_and(&mut *then),
+            Expr::Class(chars) => chars.iter().any(|&c| single(c, text, at).is_some_and(&mut *then)),
+            Expr::NegatedClass(chars) => other(chars, text, at).is_some_and(&mut *then),
+            Expr::Any => any(text, at).is_some_and(&mut *then),
+            Expr::Concat(items) => self.concat(items, at, then),
+            Expr::Alternation(alternatives) => {
+                alternatives.iter().any(|alternative| self.matches(alternative, at, then))
+            },
+            Expr::Optional(item) => self.matches(item, at, then) || then(at),
+            // built as `(x+)?`, so the way in is not the way around
+            Expr::ZeroOrMore(item) if item.can_match_empty() => {
+                self.matches(item, at, &mut |end| self.repeat(expr, item, end, then)) || then(at)
+            },
+            Expr::ZeroOrMore(item) => self.repeat(expr, item, at, then),
+            Expr::OneOrMore(item) => self.matches(item, at, &mut |end| self.repeat(expr, item, end, then)),
+            Expr::Group(_, item) => self.matches(item, at, &mut |end| self.visit(expr, true, end) && then(end))
+        }
+    }
+
+    fn concat(&self, items: &[Expr], at: usize, then: Then) -> bool {
+        match items.split_first() {
+            Some((first, rest)) => self.matches(first, at, &mut |end| self.concat(rest, end, then)),
+            None => then(at)
+        }
+    }
+
+    // the choice after each time around `repetition`: going around again,
+    // then leaving
+    fn repeat(&self, repetition: &Expr, item: &Expr, at: usize, then: Then) -> bool {
+        if !self.visit(repetition, true, at) {
+            return false;
+        }
+        self.matches(item, at, &mut |end| self.repeat(repetition, item, end, then)) || then(at)
+    }
+}
+
+// the end of the char but a newline starting at `at`, if one does
+fn any(text: &[u8], at: usize) -> Option<usize> {
+    let len = match *text.get(at)? {
+        b'\n' | 0x80..=0xbf => return None,
+        0..=0x7f => 1,
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        _ => 4
+    };
+    Some(at + len).filter(|&end| end <= text.len())
+}
+
+// the end of the char at `at`, if it is none of `chars`
+fn other(chars: &[char], text: &[u8], at: usize) -> Option<usize> {
+    let len = match *text.get(at)? {
+        0x80..=0xbf => return None,
+        0..=0x7f => 1,
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        _ => 4
+    };
+    let c = str::from_utf8(text.get(at..at + len)?).ok()?.chars().next()?;
+    (!chars.contains(&c)).then_some(at + len)
+}
+
+fn single(c: char, text: &[u8], at: usize) -> Option<usize> {
+    let mut buffer = [0; 4];
+    let encoded = c.encode_utf8(&mut buffer).as_bytes();
+    if text[at..].starts_with(encoded) {
+        Some(at + encoded.len())
+    } else {
+        None
+    }
+}
+
+/// Searches `text` for `expr` with the reference and with the engine, both
+/// as compiled and with a dense DFA, and reports where they differ.
+pub fn check(expr: &Expr, text: &str) -> Vec<Disagreement> {
+    let expected = reference_find(expr, text);
+    let regex = RegexBuilder::from_expr(expr.clone()).build().unwrap();
+    let with_dfa = regex.clone().compile_dfa(DFA_SIZE_LIMIT).ok();
+
+    let mut disagreements = Vec::new();
+    for regex in Some(&regex).into_iter().chain(with_dfa.as_ref()) {
+        let found = regex.find(text);
+        if found != expected {
+            disagreements.push(Disagreement {
+                pattern: expr.to_pattern(),
+                text: text.to_owned(),
+                expected,
+                found
+            });
+        }
+    }
+    disagreements
+}
+
+/// Checks `cases` random expressions of about `size` items, each on texts
+/// made up at random and on texts generated to match it.
+pub fn fuzz(seed: u64, cases: usize, size: usize) -> Vec<Disagreement> {
+    let mut rng = Rng::new(seed);
+    let mut disagreements = Vec::new();
+
+    for _ in 0..cases {
+        let expr = Expr::arbitrary(rng.next(), size);
+        let regex = RegexBuilder::from_expr(expr.clone()).build().unwrap();
+        let mut generator = Generator::new(&regex).with_seed(rng.next()).with_max_repeat(3);
+
+        let mut texts = Vec::new();
+        for _ in 0..4 {
+            let len = rng.below(8);
+            texts.push((0..len).map(|_| ALPHABET[rng.below(ALPHABET.len())]).collect::<String>());
+        }
+        for _ in 0..4 {
+            // surrounded by other text, so the match is not all of it
+            if let Some(text) = generator.generate() {
+                let before = ALPHABET[rng.below(ALPHABET.len())];
+                texts.push(format!("{}{}{}", before, text, before));
+                texts.push(text);
+            }
+        }
+
+        for text in &texts {
+            disagreements.extend(check(&expr, text));
+        }
+    }
+
+    disagreements
+}
+
+/// Like `fuzz` for known pairs.
+pub fn check_all<'a, I>(cases: I) -> Vec<Disagreement> where I: IntoIterator<Item=(&'a str, &'a str)> {
+    cases.into_iter()
+        .flat_map(|(pattern, text)| check(&pattern.parse::<Expr>().unwrap(), text))
+        .collect()
+}
+
@@ -0,0 +1,50 @@
+use ::expr::Expr;
+use ::regex::Match;
+use super::{reference_find, check_all, fuzz};
+
+fn find(pattern: &str, text: &str) -> Option<Match> {
+    reference_find(&pattern.parse::<Expr>().unwrap(), text)
+}
+
+#[test]
+fn reference_finds_leftmost_first_matches() {
+    assert_eq!(Some(Match::new(1, 3)), find("bc|b", "abcd"));
+    assert_eq!(Some(Match::new(1, 2)), find("b|bc", "abcd"));
+    assert_eq!(Some(Match::new(0, 3)), find("a*", "aaab"));
+    assert_eq!(Some(Match::new(0, 0)), find("b*", "aaab"));
+    assert_eq!(Some(Match::new(0, 4)), find("(a|ab)(c|bcd)", "abcd"));
+    assert_eq!(Some(Match::new(0, 2)), find("(a*)*b", "ab"));
+    assert_eq!(Some(Match::new(0, 3)), find(".b", "éb"));
+    assert_eq!(None, find("a.b", "a\nb"));
+}
+
+#[test]
+fn reference_gives_up_iterations_that_come_back_around_empty() {
+    // going around again after `b` matches empty, so `é.` is tried instead
+    assert_eq!(Some(Match::new(0, 7)), find("(c|b||\\*|é.)+", "bé詰c"));
+    // the empty way through `(?:|c)` was already taken leaving the second
+    // time around, so the third gives up before trying `c`
+    assert_eq!(Some(Match::new(0, 2)), find("(?:a?(?:|c))*", "aacb"));
+    // matching empty on the way in leaves the loop
+    assert_eq!(Some(Match::new(0, 0)), find("(?:|a)+", "aa"));
+}
+
+#[test]
+fn engine_agrees_on_known_cases() {
+    let cases = [
+        ("a|ab|abc", "abc"), ("(a|ab)(c|bcd)", "abcd"), ("a*?", "aaa"), ("(a*)+b", "aab"),
+        ("x*", ""), ("[abc]+", "zzcab"), ("é+", "aééb"), (".", "\n\né"), ("(|a)+", "aa"),
+        ("a(b|c)*d", "abcbcd abd"), ("(ab|a)(bc|c)?", "abc"),
+        ("[é*]c", "éc"), ("([.]é)+\n", ".é.é\n"), ("(?:a?(?:|c))*", "aacb"),
+        ("(?:(\\*)?(?:|[\nb.]|é))*", "*éabb"), ("[éc]b(a|.|.)", "\n*ébac")
+    ];
+
+    let disagreements = check_all(cases.iter().cloned());
+    assert!(disagreements.is_empty(), "{}", disagreements[0]);
+}
+
+#[test]
+fn engine_agrees_on_random_cases() {
+    let disagreements = (0..32).flat_map(|seed| fuzz(seed, 50, 10)).collect::<Vec<_>>();
+    assert!(disagreements.is_empty(), "{} disagreements, first {}", disagreements.len(), disagreements[0]);
+}
@@ -0,0 +1,139 @@
+use std::error;
+use std::fmt;
+
+#[cfg(test)] mod spec;
+
+// Compiled automata are encoded as a four byte magic number and a format
+// version, followed by their contents. Integers are little endian u32s.
+
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub enum DecodeError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnexpectedEnd,
+    InvalidTag(usize), // offset of the unknown tag byte
+    InvalidTransition(usize), // id of the state with the dangling transition
+    InvalidSlot(usize), // id of the state saving to a slot no group could have
+    InvalidPattern,
+    TrailingBytes(usize) // offset of the first unread byte
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::BadMagic => write!(f, "not an encoded automaton"),
+            DecodeError::UnsupportedVersion(version) => write!(f, "unsupported format version {}", version),
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            DecodeError::InvalidTag(offset) => write!(f, "invalid tag at offset {}", offset),
+            DecodeError::InvalidTransition(state) => write!(f, "state {} has an invalid transition", state),
+            DecodeError::InvalidSlot(state) => write!(f, "state {} saves to an invalid slot", state),
+            DecodeError::InvalidPattern => write!(f, "pattern is not valid utf-8"),
+            DecodeError::TrailingBytes(offset) => write!(f, "unexpected data at offset {}", offset)
+        }
+    }
+}
+
+impl error::Error for DecodeError {
+    fn description(&self) -> &str {
+        "could not decode automaton"
+    }
+}
+
+
+pub struct Encoder {
+    bytes: Vec<u8>
+}
+
+impl Encoder {
+    pub fn new(magic: &[u8; 4], version: u8) -> Encoder {
+        let mut bytes = magic.to_vec();
+        bytes.push(version);
+
+        Encoder {
+            bytes
+        }
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn u32(&mut self, value: usize) {
+        assert!(value <= u32::MAX as usize, "value too large to encode");
+
+        for i in 0..4 {
+            self.bytes.push((value >> (8 * i)) as u8);
+        }
+    }
+
+    // a length prefixed byte string
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len());
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+
+pub struct Decoder<'a> {
+    bytes: &'a [u8],
+    offset: usize
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(bytes: &'a [u8], magic: &[u8; 4], version: u8) -> Result<Decoder<'a>, DecodeError> {
+        if bytes.len() < 4 || &bytes[..4] != magic {
+            return Err(DecodeError::BadMagic);
+        }
+
+        let mut decoder = Decoder {
+            bytes,
+            offset: 4
+        };
+
+        match decoder.u8()? {
+            v if v == version => Ok(decoder),
+            v => Err(DecodeError::UnsupportedVersion(v))
+        }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<usize, DecodeError> {
+        let bytes = self.take(4)?;
+
+        Ok(bytes.iter().enumerate().fold(0, |value, (i, &byte)| value | (byte as usize) << (8 * i)))
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8], DecodeError> {
+        let len = self.u32()?;
+        self.take(len)
+    }
+
+    pub fn finish(self) -> Result<(), DecodeError> {
+        if self.offset == self.bytes.len() {
+            Ok(())
+        } else {
+            Err(DecodeError::TrailingBytes(self.offset))
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if self.bytes.len() - self.offset < len {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+
+        let taken = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(taken)
+    }
+}
@@ -0,0 +1,41 @@
+use super::{Encoder, Decoder, DecodeError};
+
+#[test]
+fn round_trips_values() {
+    let mut encoder = Encoder::new(b"TEST", 3);
+    encoder.u8(7);
+    encoder.u32(70000);
+    encoder.bytes(b"abc");
+    let bytes = encoder.finish();
+
+    assert_eq!(&b"TEST\x03\x07\x70\x11\x01\x00\x03\x00\x00\x00abc"[..], &bytes[..]);
+
+    let mut decoder = Decoder::new(&bytes, b"TEST", 3).unwrap();
+    assert_eq!(Ok(7), decoder.u8());
+    assert_eq!(Ok(70000), decoder.u32());
+    assert_eq!(Ok(&b"abc"[..]), decoder.bytes());
+    assert_eq!(Ok(()), decoder.finish());
+}
+
+#[test]
+fn checks_header() {
+    assert_eq!(Some(DecodeError::BadMagic), Decoder::new(b"TES", b"TEST", 1).err());
+    assert_eq!(Some(DecodeError::BadMagic), Decoder::new(b"BEST\x01", b"TEST", 1).err());
+    assert_eq!(Some(DecodeError::UnexpectedEnd), Decoder::new(b"TEST", b"TEST", 1).err());
+    assert_eq!(Some(DecodeError::UnsupportedVersion(2)), Decoder::new(b"TEST\x02", b"TEST", 1).err());
+}
+
+#[test]
+fn rejects_truncated_input() {
+    let mut decoder = Decoder::new(b"TEST\x01\x05\x00\x00\x00ab", b"TEST", 1).unwrap();
+    assert_eq!(Err(DecodeError::UnexpectedEnd), decoder.bytes());
+
+    let mut decoder = Decoder::new(b"TEST\x01\x05\x00", b"TEST", 1).unwrap();
+    assert_eq!(Err(DecodeError::UnexpectedEnd), decoder.u32());
+}
+
+#[test]
+fn rejects_trailing_bytes() {
+    let decoder = Decoder::new(b"TEST\x01\x00", b"TEST", 1).unwrap();
+    assert_eq!(Err(DecodeError::TrailingBytes(5)), decoder.finish());
+}
@@ -0,0 +1,138 @@
+use std::cmp;
+use std::error;
+use std::fmt;
+
+use ::span::Span;
+
+#[cfg(test)] mod spec;
+
+// A problem with a pattern, along with the span of the pattern it was found
+// in, as start and end byte offsets. Problems that have nothing to point at,
+// like a pattern over the size limit, have an empty span.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub enum Error {
+    SizeLimitExceeded,
+    NestLimitExceeded,
+    InvalidLineTerminator,
+    UnclosedClass(usize, usize),
+    UnmatchedClassClose(usize, usize),
+    UnclosedGroup(usize, usize),
+    UnmatchedGroupClose(usize, usize),
+    DanglingQuantifier(usize, usize),
+    InvalidEscape(usize, usize),
+    UnsupportedNegation(usize, usize),
+    UnsupportedAnchor(usize, usize),
+    InvalidInterval(usize, usize),
+    InvalidBracket(usize, usize),
+    InvalidGroupName(usize, usize),
+    UnsupportedBackreference(usize, usize)
+}
+
+impl Error {
+    pub fn offset(&self) -> usize {
+        self.span().start()
+    }
+
+    pub fn span(&self) -> Span {
+        match *self {
+            Error::SizeLimitExceeded | Error::NestLimitExceeded | Error::InvalidLineTerminator => Span::new(0, 0),
+            Error::UnclosedClass(start, end) |
+            Error::UnmatchedClassClose(start, end) |
+            Error::UnclosedGroup(start, end) |
+            Error::UnmatchedGroupClose(start, end) |
+            Error::DanglingQuantifier(start, end) |
+            Error::InvalidEscape(start, end) |
+            Error::UnsupportedNegation(start, end) |
+            Error::UnsupportedAnchor(start, end) |
+            Error::InvalidInterval(start, end) |
+            Error::InvalidBracket(start, end) |
+            Error::InvalidGroupName(start, end) |
+            Error::UnsupportedBackreference(start, end) => Span::new(start, end)
+        }
+    }
+
+    /// Renders `pattern` with carets under the span of the problem, e.g.
+    ///
+    /// ```text
+    /// abc[def
+    ///    ^^^^ unclosed character class
+    /// ```
+    pub fn render(&self, pattern: &str) -> String {
+        let span = self.span();
+        let indent = pattern[..span.start()].chars().count();
+        let width = cmp::max(1, pattern[span.range()].chars().count());
+
+        format!("{}\n{}{} {}",
+                pattern,
+                std::iter::repeat_n(' ', indent).collect::<String>(),
+                std::iter::repeat_n('^', width).collect::<String>(),
+                self.description())
+    }
+
+    fn description(&self) -> &'static str {
+        match *self {
+            Error::SizeLimitExceeded => "compiled pattern exceeds the size limit",
+            Error::NestLimitExceeded => "pattern nests deeper than the nest limit",
+            Error::InvalidLineTerminator => "line terminator is not an ascii byte",
+            Error::UnclosedClass(..) => "unclosed character class",
+            Error::UnmatchedClassClose(..) => "unmatched `]`",
+            Error::UnclosedGroup(..) => "unclosed group",
+            Error::UnmatchedGroupClose(..) => "unmatched `)`",
+            Error::DanglingQuantifier(..) => "quantifier has nothing to repeat",
+            Error::InvalidEscape(..) => "unfinished or unknown escape",
+            Error::UnsupportedNegation(..) => "negated character classes are not supported",
+            Error::UnsupportedAnchor(..) => "only a `^` starting the pattern is supported as an anchor",
+            Error::InvalidInterval(..) => "invalid interval",
+            Error::InvalidBracket(..) => "invalid bracket expression",
+            Error::InvalidGroupName(..) => "invalid group name",
+            Error::UnsupportedBackreference(..) => "backreferences are not supported, as they cannot be matched in linear time"
+        }
+    }
+}
+
+
+// A problem in a pattern, as reported when checking the whole pattern at
+// once. The message leaves out the location, which is given by the span.
+#[derive(PartialEq,Eq,Debug,Clone)]
+pub struct Diagnostic {
+    error: Error,
+    message: String
+}
+
+impl Diagnostic {
+    pub fn new(error: Error) -> Diagnostic {
+        Diagnostic {
+            error,
+            message: error.description().to_owned()
+        }
+    }
+
+    pub fn error(&self) -> Error {
+        self.error
+    }
+
+    pub fn span(&self) -> Span {
+        self.error.span()
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::SizeLimitExceeded | Error::NestLimitExceeded | Error::InvalidLineTerminator => {
+                write!(f, "{}", self.description())
+            },
+            _ => write!(f, "{} at offset {}", self.description(), self.offset())
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        Error::description(self)
+    }
+}
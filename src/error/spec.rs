@@ -0,0 +1,45 @@
+use ::span::Span;
+use super::Error;
+
+#[test]
+fn reports_offset() {
+    assert_eq!(0, Error::SizeLimitExceeded.offset());
+    assert_eq!(3, Error::UnclosedClass(3, 7).offset());
+    assert_eq!(5, Error::InvalidEscape(5, 6).offset());
+}
+
+#[test]
+fn reports_span() {
+    assert_eq!(Span::new(0, 0), Error::NestLimitExceeded.span());
+    assert_eq!(Span::new(3, 7), Error::UnclosedClass(3, 7).span());
+}
+
+#[test]
+fn displays_message() {
+    assert_eq!("compiled pattern exceeds the size limit", Error::SizeLimitExceeded.to_string());
+    assert_eq!("pattern nests deeper than the nest limit", Error::NestLimitExceeded.to_string());
+    assert_eq!("line terminator is not an ascii byte", Error::InvalidLineTerminator.to_string());
+    assert_eq!("unclosed character class at offset 3", Error::UnclosedClass(3, 7).to_string());
+    assert_eq!("quantifier has nothing to repeat at offset 0", Error::DanglingQuantifier(0, 1).to_string());
+    assert_eq!("backreferences are not supported, as they cannot be matched in linear time at offset 2",
+               Error::UnsupportedBackreference(2, 4).to_string());
+}
+
+#[test]
+fn renders_caret_under_problem() {
+    let pattern = "abc[def";
+    let error = pattern.parse::<::expr::Expr>().unwrap_err();
+
+    assert_eq!("abc[def\n   ^^^^ unclosed character class", error.render(pattern));
+}
+
+#[test]
+fn renders_caret_for_empty_span() {
+    assert_eq!("a|\n^ compiled pattern exceeds the size limit", Error::SizeLimitExceeded.render("a|"));
+    assert_eq!("\n^ pattern nests deeper than the nest limit", Error::NestLimitExceeded.render(""));
+}
+
+#[test]
+fn renders_caret_after_wide_chars() {
+    assert_eq!("é)\n ^ unmatched `)`", Error::UnmatchedGroupClose(2, 3).render("é)"));
+}
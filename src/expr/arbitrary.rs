@@ -0,0 +1,169 @@
+use ::arbitrary::{Arbitrary, Unstructured, Result};
+use ::rng::Rng;
+use super::Expr;
+
+// Few chars, so that random patterns and texts share many of them, and
+// some that need escaping or take more than one byte.
+pub static ALPHABET: &[char] = &['a', 'b', 'c', '.', '*', '\n', 'é'];
+// the largest expression a fuzzer's input picks
+const MAX_SIZE: usize = 32;
+
+impl Expr {
+    /// A random well-formed expression of about `size` items, the same for
+    /// the same seed. Groups are numbered in order of opening, as `parse`
+    /// numbers them, so the expression can be compiled as is.
+    pub fn arbitrary(seed: u64, size: usize) -> Expr {
+        let expr = arbitrary(&mut Rng::new(seed), size);
+        renumber(&expr, &mut 0)
+    }
+
+    /// Expressions smaller than this one, simplest first, for a failing
+    /// test to try in turn. Each has fewer items or chars than this one,
+    /// so repeatedly shrinking always comes to an end.
+    pub fn shrink(&self) -> Vec<Expr> {
+        if self == &Expr::Empty {
+            return Vec::new();
+        }
+
+        let mut smaller = vec![Expr::Empty];
+        match self {
+            Expr::Empty | Expr::Single(_) | Expr::Any => (),
+            Expr::Class(chars) => {
+                smaller.extend(chars.iter().map(|&c| Expr::Single(c)));
+                for i in 0..chars.len() {
+                    let mut fewer = chars.clone();
+                    fewer.remove(i);
+                    smaller.push(Expr::Class(fewer));
+                }
+            },
+            Expr::NegatedClass(chars) => {
+                for i in 0..chars.len() {
+                    let mut fewer = chars.clone();
+                    fewer.remove(i);
+                    smaller.push(Expr::NegatedClass(fewer));
+                }
+            },
+            Expr::Concat(items) | Expr::Alternation(items) => {
+                let rebuild = |items: Vec<Expr>| match self {
+                    Expr::Concat(_) => Expr::concat(items),
+                    _ => Expr::alternation(items)
+                };
+
+                smaller.extend(items.iter().cloned());
+                for i in 0..items.len() {
+                    let mut fewer = items.clone();
+                    fewer.remove(i);
+                    smaller.push(rebuild(fewer));
+                }
+                for i in 0..items.len() {
+                    for item in items[i].shrink() {
+                        let mut shrunk = items.clone();
+                        shrunk[i] = item;
+                        smaller.push(rebuild(shrunk));
+                    }
+                }
+            },
+            Expr::Optional(item) | Expr::ZeroOrMore(item) |
+            Expr::OneOrMore(item) | Expr::Group(_, item) => {
+                smaller.push((**item).clone());
+                smaller.extend(item.shrink().into_iter().map(|item| match *self {
+                    Expr::Optional(_) => Expr::optional(item),
+                    Expr::ZeroOrMore(_) => Expr::zero_or_more(item),
+                    Expr::OneOrMore(_) => Expr::one_or_more(item),
+                    _ => Expr::group(0, item)
+                }));
+            }
+        }
+
+        // leaving out an `Empty` does not always make the expression smaller
+        let size = self.size();
+        smaller.iter().filter(|expr| expr.size() < size).map(|expr| renumber(expr, &mut 0)).collect()
+    }
+
+    /// How many items and chars there are in the expression, not counting
+    /// `Empty`.
+    pub fn size(&self) -> usize {
+        match self {
+            Expr::Empty => 0,
+            Expr::Single(_) | Expr::Any => 1,
+            Expr::Class(chars) | Expr::NegatedClass(chars) => 1 + chars.len(),
+            Expr::Concat(items) | Expr::Alternation(items) => {
+                1 + items.iter().map(|item| item.size()).sum::<usize>()
+            },
+            Expr::Optional(item) | Expr::ZeroOrMore(item) |
+            Expr::OneOrMore(item) | Expr::Group(_, item) => 1 + item.size()
+        }
+    }
+}
+
+// For fuzzers such as `cargo fuzz`: the input picks the seed and size to
+// build an expression from, so it is as well-formed as any other.
+impl<'a> Arbitrary<'a> for Expr {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Expr> {
+        let seed = u64::arbitrary(u)?;
+        let size = u.int_in_range(0..=MAX_SIZE)?;
+        Ok(Expr::arbitrary(seed, size))
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (9, Some(9))
+    }
+}
+
+fn arbitrary(rng: &mut Rng, size: usize) -> Expr {
+    if size <= 1 {
+        return match rng.below(8) {
+            0 => Expr::Empty,
+            1 => Expr::Any,
+            2 => {
+                let len = 1 + rng.below(3);
+                Expr::class((0..len).map(|_| ALPHABET[rng.below(ALPHABET.len())]))
+            },
+            3 => {
+                let len = rng.below(3);
+                Expr::NegatedClass((0..len).map(|_| ALPHABET[rng.below(ALPHABET.len())]).collect())
+            },
+            _ => Expr::Single(ALPHABET[rng.below(ALPHABET.len())])
+        };
+    }
+
+    match rng.below(6) {
+        0..=2 => {
+            // splits what is left among two or three items
+            let parts = 2 + rng.below(2);
+            let items = (0..parts).map(|_| arbitrary(rng, (size - 1) / parts)).collect::<Vec<_>>();
+            if rng.below(2) == 0 {
+                Expr::concat(items.into_iter().filter(|item| item != &Expr::Empty))
+            } else {
+                Expr::alternation(items)
+            }
+        },
+        3 => Expr::group(0, arbitrary(rng, size - 1)),
+        _ => {
+            let item = arbitrary(rng, size - 1);
+            match rng.below(3) {
+                0 => Expr::optional(item),
+                1 => Expr::zero_or_more(item),
+                _ => Expr::one_or_more(item)
+            }
+        }
+    }
+}
+
+// `expr` with its groups numbered from 1 in order of opening, counting on
+// from `count` groups before it
+fn renumber(expr: &Expr, count: &mut usize) -> Expr {
+    match expr {
+        Expr::Group(_, item) => {
+            *count += 1;
+            let index = *count;
+            Expr::group(index, renumber(item, count))
+        },
+        Expr::Concat(items) => Expr::Concat(items.iter().map(|item| renumber(item, count)).collect()),
+        Expr::Alternation(items) => Expr::Alternation(items.iter().map(|item| renumber(item, count)).collect()),
+        Expr::Optional(item) => Expr::optional(renumber(item, count)),
+        Expr::ZeroOrMore(item) => Expr::zero_or_more(renumber(item, count)),
+        Expr::OneOrMore(item) => Expr::one_or_more(renumber(item, count)),
+        other => other.clone()
+    }
+}
@@ -1,31 +1,65 @@
-use std::str::FromStr;
+use std::cmp;
+use std::fmt;
+use std::mem;
+use std::str::{FromStr, CharIndices};
 use std::collections::vec_deque::VecDeque;
 
+use ::error::{Error, Diagnostic};
+
+mod visitor;
+mod posix;
+mod tokens;
+#[cfg(feature = "arbitrary")] mod arbitrary;
 #[cfg(test)] mod spec;
 
-static UNARY_POSTFIX_OPERATORS: &'static [char] = &['?', '*', '+'];
-static BINARY_OPERATORS: &'static [char] = &['|'];
-static SPECIAL_CHARS: &'static [char] = &['.'];
+pub use self::visitor::{Visitor, visit};
+pub use self::posix::Syntax;
+pub use self::tokens::{tokenize, PatternToken, TokenKind};
+#[cfg(feature = "differential")] pub use self::arbitrary::ALPHABET;
+
+static UNARY_POSTFIX_OPERATORS: &[char] = &['?', '*', '+'];
+static BINARY_OPERATORS: &[char] = &['|'];
+static SPECIAL_CHARS: &[char] = &['.'];
+// everything with a meaning of its own, including characters reserved for future syntax
+static META_CHARS: &[char] = &['\\', '.', '+', '*', '?', '(', ')', '|', '[', ']', '{', '}', '^', '$'];
 
-#[derive(PartialEq, Debug)]
+/// The syntax tree of a pattern, as parsed from a string with `parse`. Walk
+/// it with `visit` to inspect a pattern before compiling it.
+#[derive(PartialEq, Debug, Clone)]
 pub enum Expr {
+    /// Matches the empty string, as `()` and the sides of `a|` do.
+    Empty,
+    /// A literal character.
     Single(char),
+    /// Any one of the characters, as in `[abc]`.
     Class(Vec<char>),
+    /// Any character but those listed, as in `[^abc]`. Unlike `.`, it
+    /// matches a newline unless the newline is listed.
+    NegatedClass(Vec<char>),
+    /// Any character but a newline, `.`.
     Any,
-    Sequence(Box<Expr>, Box<Expr>),
-    Or(Box<Expr>, Box<Expr>),
+    /// Each item in turn. Has at least two items, none of them a `Concat`.
+    Concat(Vec<Expr>),
+    /// Any of the alternatives, preferring earlier ones, `|`. Has at least
+    /// two alternatives, none of them an `Alternation`.
+    Alternation(Vec<Expr>),
+    /// `?`
     Optional(Box<Expr>),
+    /// `*`
     ZeroOrMore(Box<Expr>),
-    OneOrMore(Box<Expr>)
+    /// `+`
+    OneOrMore(Box<Expr>),
+    /// A capturing group, numbered from 1 in order of opening.
+    Group(usize, Box<Expr>)
 }
 
 impl Expr {
     pub fn sequence(left: Expr, right: Expr) -> Expr {
-        Expr::Sequence(Box::new(left), Box::new(right))
+        Self::concat(vec![left, right])
     }
 
     pub fn or(left: Expr, right: Expr) -> Expr {
-        Expr::Or(Box::new(left), Box::new(right))
+        Self::alternation(vec![left, right])
     }
 
     pub fn optional(item: Expr) -> Expr {
@@ -39,116 +73,720 @@ impl Expr {
     pub fn zero_or_more(item: Expr) -> Expr {
         Expr::ZeroOrMore(Box::new(item))
     }
+
+    pub fn group(index: usize, item: Expr) -> Expr {
+        Expr::Group(index, Box::new(item))
+    }
+
+    /// Matches `text` literally.
+    pub fn literal(text: &str) -> Expr {
+        Self::concat(text.chars().map(Expr::Single))
+    }
+
+    /// Matches any one of `chars`, or nothing at all if there are none.
+    pub fn class<I: IntoIterator<Item=char>>(chars: I) -> Expr {
+        Expr::Class(chars.into_iter().collect())
+    }
+
+    /// Matches each of `items` in turn. Items that are themselves a
+    /// `Concat` have their items spliced in.
+    pub fn concat<I: IntoIterator<Item=Expr>>(items: I) -> Expr {
+        let mut flat = Vec::new();
+        for mut item in items {
+            match item {
+                Expr::Concat(ref mut nested) => flat.append(nested),
+                _ => flat.push(item)
+            }
+        }
+
+        match flat.len() {
+            0 => Expr::Empty,
+            1 => flat.pop().unwrap(),
+            _ => Expr::Concat(flat)
+        }
+    }
+
+    /// Matches any of `alternatives`, preferring earlier ones, or nothing at
+    /// all if there are none. Alternatives that are themselves an
+    /// `Alternation` have theirs spliced in.
+    pub fn alternation<I: IntoIterator<Item=Expr>>(alternatives: I) -> Expr {
+        let mut flat = Vec::new();
+        for mut alternative in alternatives {
+            match alternative {
+                Expr::Alternation(ref mut nested) => flat.append(nested),
+                _ => flat.push(alternative)
+            }
+        }
+
+        match flat.len() {
+            0 => Expr::Class(Vec::new()),
+            1 => flat.pop().unwrap(),
+            _ => Expr::Alternation(flat)
+        }
+    }
+
+    // the items of a sequence in order, or just this expression if it is
+    // not one
+    pub fn sequence_items(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Concat(items) => items.iter().collect(),
+            item => vec![item]
+        }
+    }
+
+    // the alternatives of an alternation in order of priority, or just this
+    // expression if it is not one
+    pub fn alternatives(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Alternation(alternatives) => alternatives.iter().collect(),
+            alternative => vec![alternative]
+        }
+    }
+
+    // How deeply groups, repetitions and alternations nest in the expression.
+    // Sequences and alternations of any length count as one level, so this
+    // bounds the recursion needed to compile or search with it.
+    pub fn nest_depth(&self) -> usize {
+        let mut deepest = 0;
+        let mut rest = vec![(self, 0)];
+        while let Some((expr, depth)) = rest.pop() {
+            deepest = cmp::max(deepest, depth);
+            match expr {
+                Expr::Concat(items) => {
+                    rest.extend(items.iter().map(|item| (item, depth)));
+                },
+                Expr::Alternation(alternatives) => {
+                    rest.extend(alternatives.iter().map(|item| (item, depth + 1)));
+                },
+                Expr::Optional(item) | Expr::ZeroOrMore(item) |
+                Expr::OneOrMore(item) | Expr::Group(_, item) => rest.push((item, depth + 1)),
+                Expr::Empty | Expr::Single(_) | Expr::Class(_) | Expr::NegatedClass(_) | Expr::Any => ()
+            }
+        }
+        deepest
+    }
+
+    // whether the empty string is among the strings the expression matches
+    pub fn can_match_empty(&self) -> bool {
+        match self {
+            Expr::Concat(items) => items.iter().all(|item| item.can_match_empty()),
+            Expr::Alternation(alternatives) => alternatives.iter().any(|item| item.can_match_empty()),
+            Expr::Empty | Expr::Optional(_) | Expr::ZeroOrMore(_) => true,
+            Expr::OneOrMore(item) | Expr::Group(_, item) => item.can_match_empty(),
+            Expr::Single(_) | Expr::Class(_) | Expr::NegatedClass(_) | Expr::Any => false
+        }
+    }
+
+    /// An expression matching the same strings in the same order of
+    /// preference, with the same groups, in fewer parts: a quantifier
+    /// applied to another becomes one, a repeated alternative is dropped,
+    /// alternatives next to each other that are one char each are merged
+    /// into a class, and empty items of sequences go. Groups that capture
+    /// are kept, as dropping one would lose what it captures.
+    pub fn simplify(&self) -> Expr {
+        match self {
+            Expr::Concat(items) => {
+                Expr::concat(items.iter().map(|item| item.simplify()).filter(|item| *item != Expr::Empty))
+            },
+            Expr::Alternation(alternatives) => {
+                let mut simplified: Vec<Expr> = Vec::new();
+                for alternative in alternatives {
+                    let alternative = alternative.simplify();
+                    if simplified.contains(&alternative) {
+                        continue; // never preferred to the earlier one
+                    }
+                    // one char alternatives match as long a string, so
+                    // which of them is preferred makes no difference
+                    match (simplified.last_mut(), one_char(&alternative)) {
+                        (Some(last), Some(chars)) if one_char(last).is_some() => {
+                            let mut merged = one_char(last).unwrap();
+                            merged.extend(chars.into_iter().filter(|c| !merged.contains(c)).collect::<Vec<_>>());
+                            *last = Expr::Class(merged);
+                        },
+                        _ => simplified.push(alternative)
+                    }
+                }
+                Expr::alternation(simplified)
+            },
+            Expr::Optional(item) => repeat('?', item.simplify()),
+            Expr::ZeroOrMore(item) => repeat('*', item.simplify()),
+            Expr::OneOrMore(item) => repeat('+', item.simplify()),
+            Expr::Group(index, item) => Expr::group(*index, item.simplify()),
+            Expr::Empty | Expr::Single(_) | Expr::Class(_) | Expr::NegatedClass(_) | Expr::Any => self.clone()
+        }
+    }
+
+    /// A pattern that parses back to this expression, as long as its groups
+    /// are numbered in order of opening. Parts that need grouping without
+    /// capturing are put in `(?:...)`.
+    pub fn to_pattern(&self) -> String {
+        let mut pattern = String::new();
+        write_pattern(self, Precedence::Alternation, &mut pattern);
+        pattern
+    }
+
+    // an expression matching the reversal of every string this one matches
+    pub fn reverse(&self) -> Expr {
+        match self {
+            Expr::Concat(items) => Expr::Concat(items.iter().rev().map(|item| item.reverse()).collect()),
+            Expr::Alternation(alternatives) => {
+                Expr::Alternation(alternatives.iter().map(|item| item.reverse()).collect())
+            },
+            Expr::Optional(item) => Expr::optional(item.reverse()),
+            Expr::ZeroOrMore(item) => Expr::zero_or_more(item.reverse()),
+            Expr::OneOrMore(item) => Expr::one_or_more(item.reverse()),
+            Expr::Group(index, item) => Expr::group(*index, item.reverse()),
+            Expr::Empty => Expr::Empty,
+            Expr::Single(c) => Expr::Single(*c),
+            Expr::Class(chars) => Expr::Class(chars.clone()),
+            Expr::NegatedClass(chars) => Expr::NegatedClass(chars.clone()),
+            Expr::Any => Expr::Any
+        }
+    }
+}
+
+// the chars of an expression matching one of them, and nothing else
+fn one_char(expr: &Expr) -> Option<Vec<char>> {
+    match expr {
+        Expr::Single(c) => Some(vec![*c]),
+        Expr::Class(chars) => Some(chars.clone()),
+        _ => None
+    }
+}
+
+// `item` with the quantifier `op` applied, as one quantifier if `item` has
+// one already: repeating what may be left out or repeated gives `*`, unless
+// both repeat at least once, or both are optional
+fn repeat(op: char, mut item: Expr) -> Expr {
+    let inner = match item {
+        Expr::Empty => return Expr::Empty,
+        Expr::Optional(_) => '?',
+        Expr::ZeroOrMore(_) => '*',
+        Expr::OneOrMore(_) => '+',
+        item => return match op {
+            '?' => Expr::optional(item),
+            '*' => Expr::zero_or_more(item),
+            _ => Expr::one_or_more(item)
+        }
+    };
+    let repeated = match item {
+        Expr::Optional(ref mut repeated) | Expr::ZeroOrMore(ref mut repeated) | Expr::OneOrMore(ref mut repeated) => {
+            mem::replace(&mut **repeated, Expr::Empty)
+        },
+        _ => unreachable!()
+    };
+    match (op, inner) {
+        ('?', '?') => Expr::optional(repeated),
+        ('+', '+') => Expr::one_or_more(repeated),
+        _ => Expr::zero_or_more(repeated)
+    }
+}
+
+// Dropping deeply nested expressions recursively would overflow the stack,
+// so nested expressions are taken out and dropped one at a time.
+impl Drop for Expr {
+    fn drop(&mut self) {
+        let mut nested = Vec::new();
+        take_nested(self, &mut nested);
+
+        while let Some(mut expr) = nested.pop() {
+            take_nested(&mut expr, &mut nested);
+        }
+    }
+}
+
+fn take_nested(expr: &mut Expr, nested: &mut Vec<Expr>) {
+    match expr {
+        Expr::Concat(items) | Expr::Alternation(items) => {
+            nested.append(items);
+        },
+        Expr::Optional(item) | Expr::ZeroOrMore(item) |
+        Expr::OneOrMore(item) | Expr::Group(_, item) => {
+            nested.push(mem::replace(&mut **item, Expr::Any));
+        },
+        _ => ()
+    }
+}
+
+// Writes the pattern from `to_pattern`, so that expressions built in code or
+// transformed can be turned back into patterns.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_pattern())
+    }
+}
+
+impl Expr {
+    /// Parses `pattern` as written in `syntax`. Groups may nest at most
+    /// `nest_limit` deep in POSIX syntax, which is parsed recursively, and
+    /// intervals may not repeat the pattern past roughly `size_limit` bytes
+    /// of compiled states.
+    pub fn parse_with_syntax(pattern: &str, syntax: Syntax, nest_limit: usize, size_limit: usize) -> Result<Expr, Error> {
+        match syntax {
+            Syntax::Default => pattern.parse(),
+            Syntax::PosixExtended => posix::parse(pattern, false, nest_limit, size_limit),
+            Syntax::PosixBasic => posix::parse(pattern, true, nest_limit, size_limit)
+        }
+    }
 }
 
 impl FromStr for Expr {
-    type Err = String;
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Expr,Error> {
+        parse(s, &mut Problems::new(false))
+    }
+}
+
+/// Parses `pattern`, recovering from each problem found so that all of them
+/// are reported at once. Returns an empty list for a valid pattern.
+pub fn diagnose(pattern: &str) -> Vec<Diagnostic> {
+    let mut problems = Problems::new(true);
+    let _ = parse(pattern, &mut problems);
+
+    problems.found.into_iter().map(Diagnostic::new).collect()
+}
+
+/// A pattern parsed while it is still being typed, telling one that only
+/// lacks an ending apart from one that is wrong as far as it goes.
+#[derive(PartialEq,Debug,Clone)]
+pub enum Partial {
+    Complete(Expr),
+    /// More could make it valid: the first class, group or escape it
+    /// leaves unfinished.
+    Incomplete(Error),
+    Invalid(Error)
+}
+
+/// Parses `pattern`, a prefix of one still being typed, so that an open
+/// `[` or `(`, or a trailing `\`, is reported as incomplete rather than
+/// invalid. Problems elsewhere make it invalid.
+pub fn parse_partial(pattern: &str) -> Partial {
+    let mut problems = Problems::new(true);
+    let parsed = parse(pattern, &mut problems);
+
+    // only the end of a pattern leaves a class, group or escape unfinished
+    let unfinished = |error: &Error| match error {
+        Error::UnclosedClass(..) | Error::UnclosedGroup(..) => true,
+        // a `\` with nothing after it, rather than one escaping a char it cannot
+        Error::InvalidEscape(start, end) => end - start == 1,
+        // a name without its `>`
+        Error::InvalidGroupName(_, end) => *end == pattern.len() && !pattern.ends_with('>'),
+        _ => false
+    };
+    match problems.found.iter().find(|error| !unfinished(error)) {
+        Some(&error) => Partial::Invalid(error),
+        None => match parsed {
+            Ok(expr) => Partial::Complete(expr),
+            Err(error) => Partial::Incomplete(error)
+        }
+    }
+}
 
-    fn from_str(s: &str) -> Result<Expr,String> {
+// The problems found while parsing. Unless recovering, parsing stops at the
+// first one.
+struct Problems {
+    recover: bool,
+    found: Vec<Error>
+}
 
-        let mut output_queue = VecDeque::<Expr>::new();
-        let mut operator_stack = Vec::<char>::new();
-        let mut last_was_char = false;
-        let mut in_char_class = false;
+impl Problems {
+    fn new(recover: bool) -> Problems {
+        Problems {
+            recover,
+            found: Vec::new()
+        }
+    }
 
-        let mut current_class = Vec::new();
+    fn report(&mut self, error: Error) -> Result<(), Error> {
+        self.found.push(error);
 
-        let mut chars = s.chars();
-        while chars.clone().count() > 0 {
-            let mut c = chars.next().unwrap();
+        if self.recover { Ok(()) } else { Err(error) }
+    }
+}
 
-            if in_char_class {
-                if c == ']' {
-                    output_queue.push_back(Expr::Class(current_class));
-                    current_class = Vec::new();
-                    in_char_class = false;
-                } else {
-                    current_class.push(c);
+fn parse(s: &str, problems: &mut Problems) -> Result<Expr,Error> {
+
+    let mut output_queue = VecDeque::<Expr>::new();
+    let mut operator_stack = Vec::<char>::new();
+    let mut group_offsets = Vec::<usize>::new(); // offsets of the unclosed `(`s
+    let mut group_indexes = Vec::<Option<usize>>::new(); // and the groups they open, if capturing
+    let mut num_groups = 0;
+    let mut names = Vec::<&str>::new(); // of the named groups so far
+    let mut expect_operand = true; // nothing yet for an operator to apply to
+    let mut class_offset = None; // offset of the `[` while in a character class
+    let mut negated = false; // whether the class starts with `^`
+
+    let mut current_class = Vec::new();
+
+    let mut chars = s.char_indices();
+    while let Some((offset, mut c)) = chars.next() {
+        let escaped = c == '\\';
+        if escaped {
+            match chars.next() {
+                Some((_, next)) => c = next,
+                None => {
+                    problems.report(Error::InvalidEscape(offset, offset + 1))?;
+                    break;
                 }
+            }
+        }
 
-                continue;
+        if class_offset.is_some() {
+            if c == ']' && !escaped {
+                output_queue.push_back(class(current_class, negated));
+                current_class = Vec::new();
+                class_offset = None;
+                negated = false;
+            } else if c == '^' && !escaped && class_offset == Some(offset - 1) {
+                negated = true;
+            } else if escaped {
+                // a `-` may be escaped too, though it makes no range here
+                match escaped_chars(c) {
+                    Some(chars) => current_class.extend(chars),
+                    None if c == '-' => current_class.push(c),
+                    None => {
+                        problems.report(Error::InvalidEscape(offset, offset + 1 + c.len_utf8()))?;
+                        current_class.push(c);
+                    }
+                }
+            } else {
+                current_class.push(c);
             }
 
-            if c == '(' {
+            continue;
+        }
 
-                if !output_queue.is_empty() && last_was_char {
-                    operator_stack.push('@'); // "sequence" operator
-                }
-                operator_stack.push(c);
-                last_was_char = false;
+        // only a `^` starting the pattern anchors it, which the builder
+        // takes off; anywhere else it would have to be an assertion
+        if c == '^' && !escaped && offset > 0 {
+            problems.report(Error::UnsupportedAnchor(offset, offset + 1))?;
+        }
+
+        if escaped {
+
+            let chars = escaped_chars(c);
+            // `\1` to `\9` would refer back to a group elsewhere
+            if c.is_ascii_digit() && c != '0' {
+                problems.report(Error::UnsupportedBackreference(offset, offset + 2))?;
+            } else if chars.is_none() {
+                problems.report(Error::InvalidEscape(offset, offset + 1 + c.len_utf8()))?;
+            }
+            if !expect_operand {
+                operator_stack.push('@'); // "sequence" operator
+            }
+            output_queue.push_back(match chars {
+                Some(ref chars) if chars.len() > 1 => Expr::class(chars.iter().cloned()),
+                Some(chars) => Expr::Single(chars[0]),
+                None => Expr::Single(c)
+            });
+            expect_operand = false;
 
-            } else if c == ')' {
+        } else if c == '(' {
 
-                let mut top = operator_stack.pop().expect("mismatched parens");
-                while top != '(' {
-                    pop_infix_operator(top, &mut output_queue);
-                    top = operator_stack.pop().unwrap();
+            if !expect_operand {
+                operator_stack.push('@'); // "sequence" operator
+            }
+            operator_stack.push(c);
+            group_offsets.push(offset);
+            if s[offset + 1..].starts_with("?:") { // only groups its contents
+                chars.next();
+                chars.next();
+                group_indexes.push(None);
+            } else if s[offset + 1..].starts_with("?P<") { // names the group too
+                let start = offset + 4;
+                match s[start..].find('>') {
+                    Some(length) => {
+                        let name = &s[start..start + length];
+                        if !is_group_name(name) || names.contains(&name) {
+                            problems.report(Error::InvalidGroupName(offset, start + length + 1))?;
+                        }
+                        names.push(name);
+                        skip_to(&mut chars, start + length + 1);
+                    },
+                    None => {
+                        problems.report(Error::InvalidGroupName(offset, s.len()))?;
+                        skip_to(&mut chars, s.len());
+                    }
                 }
+                num_groups += 1;
+                group_indexes.push(Some(num_groups));
+            } else {
+                num_groups += 1;
+                group_indexes.push(Some(num_groups));
+            }
+            expect_operand = true;
 
-            } else if c == '[' {
-                last_was_char = false;
-                in_char_class = true;
-            } else if c == ']' {
-                panic!();
-            } else if BINARY_OPERATORS.contains(&c) {
+        } else if c == ')' {
 
-                while !operator_stack.is_empty() {
-                    if operator_stack.last().unwrap() == &'(' { break; } // parens have higher prescedence than any other operator
-                    pop_infix_operator(operator_stack.pop().unwrap(), &mut output_queue);
+            let index = match group_indexes.pop() {
+                Some(index) => index,
+                None => {
+                    problems.report(Error::UnmatchedGroupClose(offset, offset + 1))?;
+                    continue; // ignore it
                 }
-                operator_stack.push(c);
-                last_was_char = false;
+            };
+            group_offsets.pop();
 
-            } else if UNARY_POSTFIX_OPERATORS.contains(&c) {
+            if expect_operand { // `()`, or `(a|)`
+                output_queue.push_back(Expr::Empty);
+                expect_operand = false;
+            }
 
-                apply_postfix_operator(c, &mut output_queue);
-                last_was_char = false;
+            pop_infix_operators(&mut operator_stack, &mut output_queue);
+            operator_stack.pop(); // the `(`
+            if let Some(index) = index {
+                apply_unary_operator(&mut output_queue, &|expr| Expr::Group(index, expr));
+            }
 
-            } else if SPECIAL_CHARS.contains(&c) {
+        } else if c == '[' {
 
-                if !output_queue.is_empty() && last_was_char {
-                    operator_stack.push('@'); // "sequence" operator
-                }
-                output_queue.push_back(Expr::Any);
-                last_was_char = true;
+            if !expect_operand {
+                operator_stack.push('@'); // "sequence" operator
+            }
+            class_offset = Some(offset);
+            expect_operand = false;
 
-            } else { // literal char
+        } else if c == ']' {
+            problems.report(Error::UnmatchedClassClose(offset, offset + 1))?;
+        } else if BINARY_OPERATORS.contains(&c) {
 
-                if !output_queue.is_empty() && last_was_char {
-                    operator_stack.push('@'); // "sequence" operator
-                }
-                output_queue.push_back(Expr::Single(c));
-                last_was_char = true;
+            if expect_operand { // `|a`, or `a||b`
+                output_queue.push_back(Expr::Empty);
+            }
+
+            pop_infix_operators(&mut operator_stack, &mut output_queue);
+            operator_stack.push(c);
+            expect_operand = true;
+
+        } else if UNARY_POSTFIX_OPERATORS.contains(&c) {
+
+            if expect_operand {
+                problems.report(Error::DanglingQuantifier(offset, offset + 1))?;
+                continue; // ignore the quantifier
+            }
+            if offset == 1 && s.starts_with('^') { // `^*`
+                problems.report(Error::UnsupportedAnchor(0, 1))?;
+            }
+            apply_postfix_operator(c, &mut output_queue);
+
+        } else if SPECIAL_CHARS.contains(&c) {
+
+            if !expect_operand {
+                operator_stack.push('@'); // "sequence" operator
+            }
+            output_queue.push_back(Expr::Any);
+            expect_operand = false;
+
+        } else { // literal char
 
+            if !expect_operand {
+                operator_stack.push('@'); // "sequence" operator
             }
+            output_queue.push_back(Expr::Single(c));
+            expect_operand = false;
+
         }
+    }
+
+    if let Some(offset) = class_offset {
+        problems.report(Error::UnclosedClass(offset, s.len()))?;
+        output_queue.push_back(class(current_class, negated));
+    }
+    for &offset in &group_offsets {
+        problems.report(Error::UnclosedGroup(offset, s.len()))?;
+    }
+
+    // the empty pattern, a trailing `|`, or a group left open when recovering
+    if expect_operand {
+        output_queue.push_back(Expr::Empty);
+    }
+
+    // groups left open are closed at the end
+    while !operator_stack.is_empty() {
+        pop_infix_operators(&mut operator_stack, &mut output_queue);
+        operator_stack.pop();
+    }
 
-        while !operator_stack.is_empty() {
-            pop_infix_operator(operator_stack.pop().unwrap(), &mut output_queue);
+    // a `^` anchors the whole pattern, not only its first alternative
+    let expr = Expr::concat(output_queue);
+    if s.starts_with('^') {
+        if let Expr::Alternation(_) = expr {
+            problems.report(Error::UnsupportedAnchor(0, 1))?;
         }
+    }
+
+    match problems.found.first() {
+        Some(&error) => Err(error),
+        None => Ok(expr)
+    }
+}
 
-        // build sequence tree from queue
-        while output_queue.len() > 1 {
-            let right = output_queue.pop_back().unwrap();
-            let left = output_queue.pop_back().unwrap();
+// Whether `(?P<name>` names a group: a letter or `_`, then any number of
+// letters, digits and `_`s, all ASCII.
+fn is_group_name(name: &str) -> bool {
+    match name.chars().next() {
+        Some(first) if first == '_' || first.is_ascii_alphabetic() => {
+            name.chars().all(|c| c == '_' || c.is_ascii_alphanumeric())
+        },
+        _ => false
+    }
+}
+
+// Moves `chars` on to the char at `end`, or to the end of the pattern.
+fn skip_to(chars: &mut CharIndices, end: usize) {
+    while chars.clone().next().is_some_and(|(offset, _)| offset < end) {
+        chars.next();
+    }
+}
 
-            output_queue.push_back(Expr::sequence(left,
-                                                  right));
+/// The name of each group of `pattern`, a valid pattern in this crate's
+/// own syntax, by number: `None` for the whole match, group 0, and for a
+/// group without a `(?P<name>`.
+pub fn group_names(pattern: &str) -> Vec<Option<String>> {
+    let mut names = vec![None];
+    for token in tokenize(pattern) {
+        let delimiter = &pattern[token.span().range()];
+        if token.kind() != TokenKind::GroupDelimiter || delimiter == ")" || delimiter == "(?:" {
+            continue;
         }
+        names.push(if delimiter.starts_with("(?P<") {
+            Some(delimiter[4..delimiter.len() - 1].to_owned())
+        } else {
+            None
+        });
+    }
+    names
+}
 
-        output_queue.pop_front().ok_or("output queue empty".to_owned())
+// The chars `\c` matches one of: a metachar matches itself, `\n`, `\r` and
+// `\t` the control chars, and `\d`, `\w` and `\s` the ascii digits, word
+// chars and spaces. Any other char has no escape.
+fn escaped_chars(c: char) -> Option<Vec<char>> {
+    let ascii = (0..128u8).map(|b| b as char);
+    match c {
+        'n' => Some(vec!['\n']),
+        'r' => Some(vec!['\r']),
+        't' => Some(vec!['\t']),
+        'd' => Some(ascii.filter(|c| c.is_ascii_digit()).collect()),
+        'w' => Some(ascii.filter(|c| c.is_ascii_alphanumeric() || *c == '_').collect()),
+        's' => Some(vec![' ', '\t', '\n', '\r', '\x0b', '\x0c']),
+        _ if META_CHARS.contains(&c) => Some(vec![c]),
+        _ => None
     }
 }
 
-fn pop_infix_operator(operator: char, output_queue: &mut VecDeque<Expr>) {
-    match operator {
-        '|' => { 
-            apply_binary_operator(output_queue, &|l,r| Expr::Or(l,r));
+fn class(chars: Vec<char>, negated: bool) -> Expr {
+    if negated { Expr::NegatedClass(chars) } else { Expr::Class(chars) }
+}
+
+// How tightly the context of an expression binds: alternatives can be written
+// as they are only where the context does not bind more tightly than `|`.
+#[derive(PartialEq,Eq,PartialOrd,Ord,Clone,Copy)]
+enum Precedence {
+    Alternation,
+    Sequence,
+    Repetition
+}
+
+fn write_pattern(expr: &Expr, context: Precedence, pattern: &mut String) {
+    let own = match expr {
+        Expr::Alternation(_) => Precedence::Alternation,
+        Expr::Concat(_) | Expr::Empty => Precedence::Sequence,
+        _ => Precedence::Repetition
+    };
+    if own < context {
+        pattern.push_str("(?:");
+        write_pattern(expr, Precedence::Alternation, pattern);
+        pattern.push(')');
+        return;
+    }
+
+    match expr {
+        Expr::Empty => (),
+        Expr::Single(c) => pattern.push_str(&escape(&c.to_string())),
+        Expr::Class(chars) | Expr::NegatedClass(chars) => {
+            pattern.push('[');
+            if let &Expr::NegatedClass(_) = expr {
+                pattern.push('^');
+            }
+            for (i, &c) in chars.iter().enumerate() {
+                // a `^` first in the brackets would negate them
+                if c == ']' || c == '\\' || (c == '^' && i == 0) {
+                    pattern.push('\\');
+                }
+                pattern.push(c);
+            }
+            pattern.push(']');
+        },
+        Expr::Any => pattern.push('.'),
+        Expr::Concat(items) => {
+            for item in items {
+                write_pattern(item, Precedence::Sequence, pattern);
+            }
         },
-        '@' => { // sequence operator (inserted between consecutive single chars)
-            apply_binary_operator(output_queue, &|l,r| Expr::Sequence(l,r));
+        Expr::Alternation(alternatives) => {
+            for (i, alternative) in alternatives.iter().enumerate() {
+                if i > 0 {
+                    pattern.push('|');
+                }
+                write_pattern(alternative, Precedence::Sequence, pattern);
+            }
+        },
+        Expr::Optional(item) | Expr::ZeroOrMore(item) | Expr::OneOrMore(item) => {
+            write_pattern(item, Precedence::Repetition, pattern);
+            pattern.push(match *expr {
+                Expr::Optional(_) => '?',
+                Expr::ZeroOrMore(_) => '*',
+                _ => '+'
+            });
         },
-        op => panic!("unknown infix operator {}", op)
+        Expr::Group(_, item) => {
+            pattern.push('(');
+            write_pattern(item, Precedence::Alternation, pattern);
+            pattern.push(')');
+        }
+    }
+}
+
+/// Escapes every metacharacter in `text`, so that it can be matched literally
+/// as part of a pattern.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if META_CHARS.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+// Pops operators down to the innermost `(`, which parens have higher
+// precedence than. A run of sequence operators becomes a single `Concat`, so
+// that long sequences take linear time to build.
+fn pop_infix_operators(operator_stack: &mut Vec<char>, output_queue: &mut VecDeque<Expr>) {
+    while let Some(&operator) = operator_stack.last() {
+        match operator {
+            '(' => break,
+            '|' => {
+                operator_stack.pop();
+                apply_binary_operator(output_queue, &Expr::or);
+            },
+            '@' => { // sequence operator (inserted between consecutive single chars)
+                let mut count = 0;
+                while operator_stack.last() == Some(&'@') {
+                    operator_stack.pop();
+                    count += 1;
+                }
+                let at = output_queue.len() - count - 1;
+                let items = output_queue.split_off(at);
+                output_queue.push_back(Expr::concat(items));
+            },
+            op => panic!("unknown infix operator {}", op)
+        }
     }
 }
 
@@ -168,16 +806,16 @@ fn apply_postfix_operator(operator: char, output_queue: &mut VecDeque<Expr>) {
 }
 
 fn apply_binary_operator(output_queue: &mut VecDeque<Expr>, 
-                         constructor: &Fn(Box<Expr>, Box<Expr>) -> Expr) {
+                         constructor: &dyn Fn(Expr, Expr) -> Expr) {
 
     let right = output_queue.pop_back().expect("not enough elements in queue for binary operator");
     let left = output_queue.pop_back().expect("not enough elements in queue for binary operator");
 
-    output_queue.push_back(constructor(Box::new(left),Box::new(right)));
+    output_queue.push_back(constructor(left, right));
 }
 
 fn apply_unary_operator(output_queue: &mut VecDeque<Expr>,
-                        constructor: &Fn(Box<Expr>) -> Expr) {
+                        constructor: &dyn Fn(Box<Expr>) -> Expr) {
 
     let item = output_queue.pop_back().unwrap();
     output_queue.push_back(constructor(Box::new(item)));
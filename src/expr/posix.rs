@@ -0,0 +1,390 @@
+use std::mem;
+
+use ::error::Error;
+use ::nfa::State;
+use super::Expr;
+
+// the largest count an interval may have, POSIX's RE_DUP_MAX
+const DUP_MAX: usize = 255;
+
+/// The syntax a pattern is written in. In the POSIX syntaxes, a `^`
+/// starting the pattern anchors it, and other anchors are reported as
+/// unsupported rather than taken for chars.
+#[derive(PartialEq,Eq,Debug,Clone,Copy,Hash)]
+pub enum Syntax {
+    /// This crate's own syntax.
+    Default,
+    /// POSIX extended regular expressions, as used by `grep -E` and `awk`:
+    /// `(`, `|`, `+`, `?` and intervals like `{2,3}` are operators, and
+    /// bracket expressions take ranges, classes like `[[:digit:]]` and
+    /// negation like `[^a-z]`.
+    PosixExtended,
+    /// POSIX basic regular expressions, as used by `grep` and `sed`: `*` is
+    /// an operator, and the others are escaped, as in `\(a\|b\)\{2,3\}`.
+    /// `\+`, `\?` and `\|` are GNU extensions.
+    PosixBasic
+}
+
+// Parses a POSIX pattern by recursive descent. Only groups recurse, and each
+// level of them is checked against `nest_limit` before going deeper.
+// Intervals are expanded into copies of what they repeat, so the states the
+// copies add up to are counted against `size_limit` before they are made.
+pub fn parse(pattern: &str, basic: bool, nest_limit: usize, size_limit: usize) -> Result<Expr, Error> {
+    let chars = pattern.char_indices().collect::<Vec<(usize, char)>>();
+    let mut parser = Parser {
+        len: pattern.len(),
+        states: chars.len(),
+        chars,
+        at: 0,
+        basic,
+        num_groups: 0,
+        depth: 0,
+        nest_limit,
+        size_limit
+    };
+
+    // A `^` starting the pattern anchors it, and is kept as the char for the
+    // builder to take off. It anchors only the first alternative, which
+    // cannot be told apart afterwards.
+    if parser.peek() == Some('^') {
+        parser.at += 1;
+        return match parser.alternation()? {
+            Expr::Alternation(_) => Err(Error::UnsupportedAnchor(0, 1)),
+            Expr::Empty => Ok(Expr::Single('^')),
+            rest => Ok(Expr::concat(vec![Expr::Single('^'), rest]))
+        };
+    }
+
+    // everything up to the end is read unless a problem stops it
+    parser.alternation()
+}
+
+struct Parser {
+    len: usize, // of the pattern in bytes
+    chars: Vec<(usize, char)>,
+    at: usize, // index into `chars`
+    basic: bool,
+    num_groups: usize,
+    depth: usize, // of the groups open
+    nest_limit: usize,
+    states: usize, // roughly how many the pattern compiles to, with the copies intervals make
+    size_limit: usize
+}
+
+impl Parser {
+    fn peek_at(&self, ahead: usize) -> Option<char> {
+        self.chars.get(self.at + ahead).map(|&(_, c)| c)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.peek_at(0)
+    }
+
+    // the byte offset of the char `ahead` of the current one
+    fn offset_at(&self, ahead: usize) -> usize {
+        self.chars.get(self.at + ahead).map_or(self.len, |&(offset, _)| offset)
+    }
+
+    fn offset(&self) -> usize {
+        self.offset_at(0)
+    }
+
+    // how many chars the operator `op` takes at the current position, if it
+    // is there. Basic syntax escapes the operators that extended syntax
+    // does not.
+    fn operator(&self, op: char) -> Option<usize> {
+        let escaped = self.basic && op != '*';
+        match (self.peek(), self.peek_at(1)) {
+            (Some('\\'), Some(c)) if escaped && c == op => Some(2),
+            (Some(c), _) if !escaped && c == op => Some(1),
+            _ => None
+        }
+    }
+
+    fn alternation(&mut self) -> Result<Expr, Error> {
+        let mut alternatives = vec![self.concat()?];
+        while let Some(len) = self.operator('|') {
+            self.at += len;
+            alternatives.push(self.concat()?);
+        }
+        Ok(Expr::alternation(alternatives))
+    }
+
+    fn concat(&mut self) -> Result<Expr, Error> {
+        let mut items = Vec::new();
+        loop {
+            if self.peek().is_none() || self.operator('|').is_some() {
+                break;
+            }
+            if let Some(len) = self.operator(')') {
+                if self.depth > 0 {
+                    break;
+                }
+                return Err(Error::UnmatchedGroupClose(self.offset(), self.offset_at(len)));
+            }
+
+            let item = self.atom(items.is_empty())?;
+            items.push(self.repetitions(item)?);
+        }
+        Ok(Expr::concat(items))
+    }
+
+    fn atom(&mut self, first: bool) -> Result<Expr, Error> {
+        let offset = self.offset();
+
+        if let Some(len) = self.operator('(') {
+            self.at += len;
+            return self.group(offset);
+        }
+        // a `*` with nothing to repeat is a literal in basic syntax
+        if self.basic && first && self.peek() == Some('*') {
+            self.at += 1;
+            return Ok(Expr::Single('*'));
+        }
+        let dangling = ['*', '+', '?'].iter().any(|&op| self.operator(op).is_some()) ||
+                       self.interval().is_some();
+        if dangling && (!self.basic || !first) {
+            return Err(Error::DanglingQuantifier(offset, self.offset_at(1)));
+        }
+
+        let c = self.peek().unwrap();
+        self.at += 1;
+        match c {
+            '[' => self.bracket(offset),
+            '.' => Ok(Expr::Any),
+            // anchors anywhere else are not supported, rather than taken for
+            // chars. Basic syntax only has them first and last in the pattern,
+            // or in a group or alternative as GNU does.
+            '^' if !self.basic || first => Err(Error::UnsupportedAnchor(offset, offset + 1)),
+            '$' if !self.basic || self.peek().is_none() || self.operator(')').is_some() || self.operator('|').is_some() => {
+                Err(Error::UnsupportedAnchor(offset, offset + 1))
+            },
+            '\\' => match self.peek() {
+                Some(escaped) if escaped.is_ascii_digit() && escaped != '0' => {
+                    Err(Error::UnsupportedBackreference(offset, self.offset_at(1)))
+                },
+                Some(escaped) => {
+                    self.at += 1;
+                    Ok(Expr::Single(escaped))
+                },
+                None => Err(Error::InvalidEscape(offset, self.len))
+            },
+            c => Ok(Expr::Single(c))
+        }
+    }
+
+    fn group(&mut self, offset: usize) -> Result<Expr, Error> {
+        if self.depth == self.nest_limit {
+            return Err(Error::NestLimitExceeded);
+        }
+        self.depth += 1;
+        self.num_groups += 1;
+        let index = self.num_groups;
+
+        let item = self.alternation()?;
+        match self.operator(')') {
+            Some(len) => self.at += len,
+            None => return Err(Error::UnclosedGroup(offset, self.len))
+        }
+        self.depth -= 1;
+
+        Ok(Expr::group(index, item))
+    }
+
+    // `item` with the repetition operators after it applied, in order
+    fn repetitions(&mut self, mut item: Expr) -> Result<Expr, Error> {
+        let mut size = None; // of `item` in states, counted at the first interval
+        loop {
+            if let Some(len) = self.operator('*') {
+                self.at += len;
+                item = Expr::zero_or_more(item);
+                size = size.map(|size| size + 1);
+            } else if let Some(len) = self.operator('+') {
+                self.at += len;
+                item = Expr::one_or_more(item);
+                size = size.map(|size| size + 1);
+            } else if let Some(len) = self.operator('?') {
+                self.at += len;
+                item = Expr::optional(item);
+                size = size.map(|size| size + 1);
+            } else if let Some((min, max, len)) = self.interval() {
+                let offset = self.offset();
+                if min > DUP_MAX || max.is_some_and(|max| max < min || max > DUP_MAX) {
+                    return Err(Error::InvalidInterval(offset, self.offset_at(len)));
+                }
+                self.at += len;
+
+                // the copies after the first, each optional one with a split
+                let one = size.unwrap_or_else(|| states(&item));
+                let copies = max.unwrap_or(min + 1);
+                let added = (one + 1) * copies.saturating_sub(1) + 1;
+                self.states += added;
+                if self.states.saturating_mul(mem::size_of::<State>()) > self.size_limit {
+                    return Err(Error::SizeLimitExceeded);
+                }
+                size = Some(one + added);
+                item = repeat(item, min, max);
+            } else if self.operator('{').is_some() && self.basic {
+                // `\{` always starts an interval in basic syntax
+                let offset = self.offset();
+                return Err(Error::InvalidInterval(offset, self.offset_at(2)));
+            } else {
+                return Ok(item);
+            }
+        }
+    }
+
+    // the interval at the current position, as its minimum, its maximum if
+    // it has one, and how many chars it takes. In extended syntax a `{` that
+    // does not start one is a literal.
+    fn interval(&self) -> Option<(usize, Option<usize>, usize)> {
+        let mut len = self.operator('{')?;
+        let number = |len: &mut usize| {
+            let mut n: Option<usize> = None;
+            while let Some(digit) = self.peek_at(*len).and_then(|c| c.to_digit(10)) {
+                n = Some(n.unwrap_or(0).saturating_mul(10).saturating_add(digit as usize));
+                *len += 1;
+            }
+            n
+        };
+
+        let min = number(&mut len)?;
+        let max = if self.peek_at(len) == Some(',') {
+            len += 1;
+            number(&mut len)
+        } else {
+            Some(min)
+        };
+
+        let close = if self.basic { "\\}" } else { "}" };
+        for (i, c) in close.chars().enumerate() {
+            if self.peek_at(len + i) != Some(c) {
+                return None;
+            }
+        }
+        Some((min, max, len + close.len()))
+    }
+
+    // a bracket expression, after its `[`. One starting with `^` matches any
+    // char but those listed, a newline included.
+    fn bracket(&mut self, offset: usize) -> Result<Expr, Error> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.at += 1;
+        }
+
+        let mut chars = Vec::new();
+        let mut first = true;
+        loop {
+            let start = match self.peek() {
+                Some(']') if !first => {
+                    self.at += 1;
+                    return Ok(if negated { Expr::NegatedClass(chars) } else { Expr::class(chars) });
+                },
+                Some('[') if self.peek_at(1) == Some(':') => {
+                    let class_offset = self.offset();
+                    chars.extend(self.named_class(class_offset)?);
+                    first = false;
+                    continue;
+                },
+                Some('[') if self.peek_at(1) == Some('.') || self.peek_at(1) == Some('=') => {
+                    self.collating_element()?
+                },
+                Some(c) => {
+                    self.at += 1;
+                    c
+                },
+                None => return Err(Error::UnclosedClass(offset, self.len))
+            };
+            first = false;
+
+            // a `-` first or last in the brackets is one of the chars
+            match (self.peek(), self.peek_at(1)) {
+                (Some('-'), Some(end)) if end != ']' => {
+                    let range_offset = self.offset_at(0);
+                    self.at += 2;
+                    if end < start {
+                        return Err(Error::InvalidBracket(range_offset, self.offset()));
+                    }
+                    chars.extend(start..=end);
+                },
+                _ => chars.push(start)
+            }
+        }
+    }
+
+    // `[:name:]`, at its `[`
+    fn named_class(&mut self, offset: usize) -> Result<Vec<char>, Error> {
+        self.at += 2;
+        let mut name = String::new();
+        while !(self.peek() == Some(':') && self.peek_at(1) == Some(']')) {
+            match self.peek() {
+                Some(c) => name.push(c),
+                None => return Err(Error::UnclosedClass(offset, self.len))
+            }
+            self.at += 1;
+        }
+        self.at += 2;
+
+        let ascii = (0..128u8).map(|b| b as char);
+        let chars: Vec<char> = match &name[..] {
+            "alpha" => ascii.filter(|c| c.is_ascii_alphabetic()).collect(),
+            "digit" => ascii.filter(|c| c.is_ascii_digit()).collect(),
+            "alnum" => ascii.filter(|c| c.is_ascii_alphanumeric()).collect(),
+            "upper" => ascii.filter(|c| c.is_ascii_uppercase()).collect(),
+            "lower" => ascii.filter(|c| c.is_ascii_lowercase()).collect(),
+            "space" => vec![' ', '\t', '\n', '\r', '\x0b', '\x0c'],
+            "blank" => vec![' ', '\t'],
+            "punct" => ascii.filter(|c| c.is_ascii_punctuation()).collect(),
+            "xdigit" => ascii.filter(|c| c.is_ascii_hexdigit()).collect(),
+            "cntrl" => ascii.filter(|c| c.is_ascii_control()).collect(),
+            "print" => ascii.filter(|c| *c >= ' ' && *c <= '~').collect(),
+            "graph" => ascii.filter(|c| c.is_ascii_graphic()).collect(),
+            _ => return Err(Error::InvalidBracket(offset, self.offset()))
+        };
+        Ok(chars)
+    }
+
+    // `[.c.]` or `[=c=]`, at its `[`. Only single chars are supported, which
+    // stand for themselves.
+    fn collating_element(&mut self) -> Result<char, Error> {
+        let offset = self.offset();
+        let delimiter = self.peek_at(1).unwrap();
+        match (self.peek_at(2), self.peek_at(3), self.peek_at(4)) {
+            (Some(c), Some(d), Some(']')) if d == delimiter => {
+                self.at += 5;
+                Ok(c)
+            },
+            _ => Err(Error::InvalidBracket(offset, self.offset_at(2)))
+        }
+    }
+}
+
+// a lower bound on the states `expr` compiles to
+fn states(expr: &Expr) -> usize {
+    match expr {
+        Expr::Empty => 0,
+        Expr::Single(c) => c.len_utf8(),
+        Expr::Class(_) | Expr::NegatedClass(_) | Expr::Any => 1,
+        Expr::Concat(items) => items.iter().map(states).sum(),
+        Expr::Alternation(alternatives) => alternatives.iter().map(states).sum::<usize>() + alternatives.len() - 1,
+        Expr::Optional(item) | Expr::ZeroOrMore(item) | Expr::OneOrMore(item) => states(item) + 1,
+        Expr::Group(_, item) => states(item) + 2
+    }
+}
+
+// `item` repeated at least `min` and at most `max` times, preferring more.
+// The optional repeats follow each other flat, `xx?x?`, rather than nesting
+// as `x(x(x)?)?`, so that an interval as wide as `DUP_MAX` does not nest past
+// the nest limit. They match the same: a later repeat is only taken without
+// an earlier one where the earlier one could have taken the same text.
+fn repeat(item: Expr, min: usize, max: Option<usize>) -> Expr {
+    let mut items = (0..min).map(|_| item.clone()).collect::<Vec<Expr>>();
+
+    match max {
+        None => items.push(Expr::zero_or_more(item)),
+        Some(max) => items.extend((min..max).map(|_| Expr::optional(item.clone())))
+    }
+
+    Expr::concat(items.into_iter().filter(|item| item != &Expr::Empty))
+}
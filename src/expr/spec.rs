@@ -1,4 +1,6 @@
-use super::Expr;
+use super::{Expr, Syntax, Visitor, visit, escape, diagnose, parse_partial, Partial, tokenize, TokenKind, group_names};
+use ::error::{Error, Diagnostic};
+use ::span::Span;
 
 #[test]
 fn parse_single() {
@@ -8,7 +10,7 @@ fn parse_single() {
 
 #[test]
 fn parse_empty() {
-    assert!("".parse::<Expr>().is_err());
+    assert_eq!(Expr::Empty, "".parse::<Expr>().unwrap());
 }
 
 #[test]
@@ -17,16 +19,16 @@ fn parse_sequence() {
                               Expr::Single('b')),
                "ab".parse::<Expr>().unwrap());
 
-    assert_eq!(Expr::sequence(Expr::Single('a'),
+    assert_eq!(Expr::sequence(Expr::group(1, Expr::Single('a')),
                               Expr::Single('b')),
                "(a)b".parse::<Expr>().unwrap());
 
-    assert_eq!(Expr::sequence(Expr::or(Expr::Single('a'),Expr::Single('b')),
+    assert_eq!(Expr::sequence(Expr::group(1, Expr::or(Expr::Single('a'),Expr::Single('b'))),
                               Expr::Single('b')),
                 "(a|b)b".parse::<Expr>().unwrap());
 
-    assert_eq!(Expr::sequence(Expr::Single('a'),
-                              Expr::Single('b')),
+    assert_eq!(Expr::group(1, Expr::sequence(Expr::group(2, Expr::Single('a')),
+                                             Expr::Single('b'))),
                "((a)b)".parse::<Expr>().unwrap());
 }
 
@@ -83,33 +85,44 @@ fn parse_combination() {
 
 #[test]
 fn parse_parens() {
-    assert_eq!(Expr::OneOrMore(Box::new(Expr::sequence(Expr::Single('a'),
-                                             Expr::Single('b')))),
+    assert_eq!(Expr::OneOrMore(Box::new(Expr::group(1, Expr::sequence(Expr::Single('a'),
+                                                                       Expr::Single('b'))))),
                "(ab)+".parse::<Expr>().unwrap());
 
     assert_eq!(
         Expr::sequence(
-            Expr::OneOrMore(Box::new(Expr::sequence(Expr::Single('a'),
-                                          Expr::Single('b')))),
+            Expr::OneOrMore(Box::new(Expr::group(1, Expr::sequence(Expr::Single('a'),
+                                                                   Expr::Single('b'))))),
             Expr::Single('c')),
                "(ab)+c".parse::<Expr>().unwrap());
     
-    assert_eq!(Expr::Single('a'),
+    assert_eq!(Expr::group(1, Expr::Single('a')),
         "(a)".parse::<Expr>().unwrap());
 
-    assert_eq!(Expr::Single('a'),
+    assert_eq!(Expr::sequence(Expr::group(1, Expr::Empty), Expr::Single('a')),
         "()a".parse::<Expr>().unwrap());
 
-    assert_eq!(Expr::Single('a'),
+    assert_eq!(Expr::group(1, Expr::group(2, Expr::Single('a'))),
         "((a))".parse::<Expr>().unwrap());
 }
 
 #[test]
 fn parse_parens_with_or() {
-    assert_eq!(Expr::Or(Box::new(Expr::Single('a')), Box::new(Expr::Single('b'))),
+    assert_eq!(Expr::group(1, Expr::Alternation(vec![Expr::Single('a'), Expr::Single('b')])),
                "(a|b)".parse::<Expr>().unwrap());
 }
 
+#[test]
+fn numbers_groups_in_order_of_opening() {
+    assert_eq!(Expr::sequence(Expr::group(1, Expr::group(2, Expr::Single('a'))),
+                              Expr::group(3, Expr::Single('b'))),
+               "((a))(b)".parse::<Expr>().unwrap());
+
+    assert_eq!(Expr::sequence(Expr::Single('a'),
+                              Expr::sequence(Expr::group(1, Expr::Empty), Expr::group(2, Expr::Single('b')))),
+               "a()(b)".parse::<Expr>().unwrap());
+}
+
 #[test]
 fn parse_dot() {
     assert_eq!(Expr::Any,
@@ -124,18 +137,18 @@ fn parse_parens_with_kleene_star() {
 #[test]
 fn parse_complex() {
     assert_eq!(Expr::one_or_more(
-            Expr::sequence(
-            Expr::Single('a'),
-            Expr::Single('b'))),
+            Expr::group(1, Expr::sequence(
+            Expr::group(2, Expr::Single('a')),
+            Expr::Single('b')))),
             "((a)b)+".parse::<Expr>().unwrap());
 
     assert_eq!(
         Expr::one_or_more(
-            Expr::sequence(
-                Expr::or(
+            Expr::group(1, Expr::sequence(
+                Expr::group(2, Expr::or(
                     Expr::Single('a'),
-                    Expr::Single('b')),
-                Expr::Single('b'))),
+                    Expr::Single('b'))),
+                Expr::Single('b')))),
             "((a|b)b)+".parse::<Expr>().unwrap());
 }
 
@@ -154,3 +167,535 @@ fn parse_char_class() {
         "[ab]+".parse::<Expr>().unwrap());
 }
 
+#[test]
+fn parse_negated_char_class() {
+    assert_eq!(Expr::NegatedClass(vec!['a', 'b']), "[^ab]".parse::<Expr>().unwrap());
+    assert_eq!(Expr::NegatedClass(vec!['^']), "[^^]".parse::<Expr>().unwrap());
+    assert_eq!(Expr::Class(vec!['^', 'a']), "[\\^a]".parse::<Expr>().unwrap());
+    assert_eq!(Expr::Class(vec!['a', '^']), "[a^]".parse::<Expr>().unwrap());
+
+    for pattern in &["[^ab]", "[\\^a]", "[^]", "[^\\]]"] {
+        assert_eq!(*pattern, pattern.parse::<Expr>().unwrap().to_pattern());
+    }
+}
+
+
+#[test]
+fn reverses() {
+    assert_eq!(Expr::sequence(Expr::sequence(Expr::Single('c'),
+                                             Expr::Single('b')),
+                              Expr::Single('a')),
+               "abc".parse::<Expr>().unwrap().reverse());
+
+    assert_eq!(Expr::sequence(Expr::one_or_more(Expr::group(1, Expr::or(Expr::sequence(Expr::Single('d'),
+                                                                                        Expr::Single('c')),
+                                                                         Expr::Single('b')))),
+                              Expr::Single('a')),
+               "a(cd|b)+".parse::<Expr>().unwrap().reverse());
+
+    assert_eq!(Expr::sequence(Expr::optional(Expr::Class(vec!['a','b'])),
+                              Expr::zero_or_more(Expr::Any)),
+               ".*[ab]?".parse::<Expr>().unwrap().reverse());
+}
+
+#[test]
+fn parse_escaped() {
+    assert_eq!(Expr::sequence(Expr::Single('a'),
+                              Expr::Single('*')),
+               "a\\*".parse::<Expr>().unwrap());
+
+    assert_eq!(Expr::one_or_more(Expr::Single('(')),
+               "\\(+".parse::<Expr>().unwrap());
+
+    assert_eq!(Expr::Class(vec![']', '\\']),
+               "[\\]\\\\]".parse::<Expr>().unwrap());
+
+    assert!("a\\".parse::<Expr>().is_err());
+}
+
+#[test]
+fn escapes_metachars() {
+    assert_eq!("abc", escape("abc"));
+    assert_eq!("a\\.b\\*\\(c\\|d\\)\\+\\?", escape("a.b*(c|d)+?"));
+    assert_eq!("\\[a\\]\\\\", escape("[a]\\"));
+    assert_eq!("\\{1\\}\\^\\$", escape("{1}^$"));
+}
+
+#[test]
+fn parse_errors() {
+    assert_eq!(Err(Error::UnclosedClass(3, 6)), "abc[de".parse::<Expr>());
+    assert_eq!(Err(Error::UnmatchedClassClose(1, 2)), "a]".parse::<Expr>());
+    assert_eq!(Err(Error::UnclosedGroup(0, 5)), "((a)b".parse::<Expr>());
+    assert_eq!(Err(Error::UnmatchedGroupClose(3, 4)), "(a))".parse::<Expr>());
+    assert_eq!(Err(Error::DanglingQuantifier(0, 1)), "*a".parse::<Expr>());
+    assert_eq!(Err(Error::DanglingQuantifier(2, 3)), "a|+".parse::<Expr>());
+    assert_eq!(Err(Error::DanglingQuantifier(1, 2)), "(?a)".parse::<Expr>());
+    assert_eq!(Err(Error::InvalidEscape(1, 2)), "a\\".parse::<Expr>());
+    assert_eq!(Err(Error::UnsupportedBackreference(3, 5)), "(a)\\1".parse::<Expr>());
+    assert_eq!(Err(Error::InvalidEscape(1, 3)), "a\\0".parse::<Expr>());
+    assert_eq!(Err(Error::InvalidEscape(1, 3)), "[\\1]".parse::<Expr>());
+    assert_eq!(Err(Error::InvalidEscape(1, 3)), "a\\q".parse::<Expr>());
+    assert_eq!(Err(Error::InvalidEscape(2, 5)), "[a\\é]".parse::<Expr>());
+}
+
+#[test]
+fn parse_escapes() {
+    let digits = || Expr::class("0123456789".chars());
+    assert_eq!(Ok(Expr::literal(".+*?()|[]{}^$\\")), "\\.\\+\\*\\?\\(\\)\\|\\[\\]\\{\\}\\^\\$\\\\".parse::<Expr>());
+    assert_eq!(Ok(Expr::literal("a\n\r\t")), "a\\n\\r\\t".parse::<Expr>());
+    assert_eq!(Ok(Expr::one_or_more(digits())), "\\d+".parse::<Expr>());
+    assert_eq!(Ok(Expr::class(" \t\n\r\x0b\x0c".chars())), "\\s".parse::<Expr>());
+    assert_eq!(Ok(Expr::class("0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ_abcdefghijklmnopqrstuvwxyz".chars())),
+               "\\w".parse::<Expr>());
+
+    // in a class, they add their chars to it
+    assert_eq!(Ok(Expr::class("0123456789_-]".chars())), "[\\d_\\-\\]]".parse::<Expr>());
+    assert_eq!(Ok(Expr::NegatedClass(vec!['\n', '^'])), "[^\\n\\^]".parse::<Expr>());
+}
+
+#[test]
+fn parse_empty_group() {
+    assert_eq!(Expr::sequence(Expr::Single('a'),
+                              Expr::sequence(Expr::group(1, Expr::Empty), Expr::Single('b'))),
+               "a()b".parse::<Expr>().unwrap());
+
+    assert_eq!(Expr::group(1, Expr::Empty), "()".parse::<Expr>().unwrap());
+}
+
+#[test]
+fn parse_empty_alternatives() {
+    assert_eq!(Expr::or(Expr::Empty, Expr::Single('a')), "|a".parse::<Expr>().unwrap());
+    assert_eq!(Expr::or(Expr::Single('a'), Expr::Empty), "a|".parse::<Expr>().unwrap());
+    assert_eq!(Expr::group(1, Expr::or(Expr::Single('a'), Expr::Empty)), "(a|)".parse::<Expr>().unwrap());
+    assert_eq!(Expr::or(Expr::or(Expr::Single('a'), Expr::Empty), Expr::Single('b')),
+               "a||b".parse::<Expr>().unwrap());
+}
+
+#[test]
+fn parse_sequence_after_quantifier() {
+    assert_eq!(Expr::or(Expr::sequence(Expr::zero_or_more(Expr::Single('a')),
+                                       Expr::Single('b')),
+                        Expr::Single('c')),
+               "a*b|c".parse::<Expr>().unwrap());
+}
+
+#[test]
+fn diagnoses_valid_pattern() {
+    assert_eq!(Vec::<Diagnostic>::new(), diagnose("(ab|c)+[de]"));
+}
+
+#[test]
+fn diagnoses_every_problem() {
+    let errors = |pattern: &str| {
+        diagnose(pattern).iter().map(|d| d.error()).collect::<Vec<_>>()
+    };
+
+    assert_eq!(vec![Error::DanglingQuantifier(0, 1),
+                    Error::UnmatchedGroupClose(2, 3),
+                    Error::UnclosedClass(4, 6)],
+               errors("*a)b[c"));
+
+    assert_eq!(vec![Error::UnmatchedClassClose(5, 6)],
+               errors("|(a|)]"));
+
+    assert_eq!(vec![Error::UnclosedGroup(0, 6),
+                    Error::UnclosedGroup(3, 6)],
+               errors("(a|(b|"));
+
+    assert_eq!(vec![Error::InvalidEscape(2, 3)],
+               errors("a+\\"));
+
+    assert_eq!(Vec::<Error>::new(), errors("()"));
+    assert_eq!(vec![Error::UnclosedGroup(1, 2)], errors("a("));
+}
+
+#[test]
+fn parses_patterns_being_typed() {
+    assert_eq!(Partial::Complete("a|b".parse().unwrap()), parse_partial("a|b"));
+    assert_eq!(Partial::Complete(Expr::Empty), parse_partial(""));
+    assert_eq!(Partial::Incomplete(Error::UnclosedClass(1, 3)), parse_partial("a[b"));
+    assert_eq!(Partial::Incomplete(Error::UnclosedGroup(0, 4)), parse_partial("(a(b"));
+    assert_eq!(Partial::Incomplete(Error::InvalidEscape(1, 2)), parse_partial("a\\"));
+    assert_eq!(Partial::Incomplete(Error::InvalidEscape(2, 3)), parse_partial("[a\\"));
+    assert_eq!(Partial::Invalid(Error::InvalidEscape(1, 3)), parse_partial("a\\q"));
+    assert_eq!(Partial::Invalid(Error::UnmatchedGroupClose(1, 2)), parse_partial("a)("));
+    assert_eq!(Partial::Invalid(Error::DanglingQuantifier(1, 2)), parse_partial("(*"));
+}
+
+#[test]
+fn simplifies_expressions() {
+    let simplify = |pattern: &str| pattern.parse::<Expr>().unwrap().simplify().to_pattern();
+
+    assert_eq!("a*", simplify("(?:a*)*"));
+    assert_eq!("a*", simplify("(?:a+)?"));
+    assert_eq!("a+", simplify("(?:a+)+"));
+    assert_eq!("a?", simplify("(?:a?)?"));
+    assert_eq!("a*", simplify("(?:(?:a?)+)*"));
+    assert_eq!("(a*)*", simplify("(a*)*"));
+    assert_eq!("[ab]|cd|[efa]", simplify("a|b|cd|e|f|cd|a"));
+    assert_eq!("[abc]|d.", simplify("a|[bc]|d.|d."));
+    assert_eq!("ab", simplify("a(?:)?b"));
+    assert_eq!("a()?b", simplify("a()?b"));
+    assert_eq!("ab(c)", simplify("a(?:)b(c)"));
+}
+
+#[test]
+fn tokenizes_patterns() {
+    fn tokens(pattern: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(pattern).iter().map(|token| (token.kind(), &pattern[token.span().range()])).collect()
+    }
+
+    assert_eq!(vec![(TokenKind::GroupDelimiter, "(?:"), (TokenKind::Literal, "ab"), (TokenKind::Metachar, "|"),
+                    (TokenKind::Literal, "é{"), (TokenKind::GroupDelimiter, ")"), (TokenKind::Quantifier, "+"),
+                    (TokenKind::Escape, "\\."), (TokenKind::Class, "[x\\]y]"), (TokenKind::Quantifier, "*"),
+                    (TokenKind::Metachar, ".")],
+               tokens("(?:ab|é{)+\\.[x\\]y]*."));
+    assert_eq!(vec![(TokenKind::Metachar, "]"), (TokenKind::Class, "[a")], tokens("][a"));
+    assert_eq!(vec![(TokenKind::Literal, "a"), (TokenKind::Escape, "\\")], tokens("a\\"));
+    assert!(tokens("").is_empty());
+    assert_eq!(vec![(TokenKind::GroupDelimiter, "(?P<d>"), (TokenKind::Literal, "a"), (TokenKind::GroupDelimiter, ")"),
+                    (TokenKind::GroupDelimiter, "(?P<x")],
+               tokens("(?P<d>a)(?P<x"));
+}
+
+#[test]
+fn parses_named_groups() {
+    assert_eq!(Expr::concat(vec![Expr::group(1, Expr::Single('a')), Expr::group(2, Expr::Single('b'))]),
+               "(?P<x_1>a)(b)".parse::<Expr>().unwrap());
+    assert_eq!(Err(Error::InvalidGroupName(0, 5)), "(?P<>a)".parse::<Expr>());
+    assert_eq!(Err(Error::InvalidGroupName(0, 6)), "(?P<1>a)".parse::<Expr>());
+    assert_eq!(Err(Error::InvalidGroupName(0, 8)), "(?P<a-b>a)".parse::<Expr>());
+    assert_eq!(Err(Error::InvalidGroupName(7, 13)), "(?P<a>)(?P<a>)".parse::<Expr>());
+    assert_eq!(Err(Error::InvalidGroupName(0, 5)), "(?P<a".parse::<Expr>());
+    assert_eq!(Partial::Incomplete(Error::InvalidGroupName(1, 6)), parse_partial("a(?P<a"));
+    assert_eq!(Partial::Invalid(Error::InvalidGroupName(0, 6)), parse_partial("(?P<1>a"));
+}
+
+#[test]
+fn names_groups() {
+    assert_eq!(vec![None, Some("x".to_owned()), None, Some("y".to_owned())],
+               group_names("(?P<x>a)(?:b)(c[(?P<z>])\\((?P<y>d)"));
+}
+
+#[test]
+fn diagnostic_describes_problem() {
+    let diagnostics = diagnose("(*a)");
+
+    assert_eq!(1, diagnostics.len());
+    assert_eq!(Span::new(1, 2), diagnostics[0].span());
+    assert_eq!("quantifier has nothing to repeat", diagnostics[0].message());
+}
+
+#[test]
+fn parse_reports_first_problem() {
+    assert_eq!(Err(Error::DanglingQuantifier(0, 1)), "*a)b[c".parse::<Expr>());
+}
+
+#[test]
+fn parse_flattens_sequences_and_alternations() {
+    assert_eq!(Expr::Concat(vec![Expr::Single('a'), Expr::Single('b'), Expr::Single('c')]),
+               "abc".parse::<Expr>().unwrap());
+    assert_eq!(Expr::Alternation(vec![Expr::Single('a'), Expr::Single('b'), Expr::Single('c')]),
+               "a|b|c".parse::<Expr>().unwrap());
+    assert_eq!(Expr::Alternation(vec![Expr::Single('a'), Expr::Single('b'), Expr::Single('c')]),
+               "(?:a|b)|c".parse::<Expr>().unwrap());
+}
+
+#[test]
+fn concat_splices_nested_items() {
+    let ab = Expr::concat(vec![Expr::Single('a'), Expr::Single('b')]);
+
+    assert_eq!(Expr::Concat(vec![Expr::Single('a'), Expr::Single('b'), Expr::Single('c')]),
+               Expr::sequence(ab, Expr::Single('c')));
+    assert_eq!(Expr::Single('a'), Expr::concat(vec![Expr::Single('a')]));
+    assert_eq!(Expr::Single('a'), Expr::alternation(vec![Expr::Single('a')]));
+}
+
+#[test]
+fn lists_alternatives_in_order() {
+    let expr = "a|bc|d".parse::<Expr>().unwrap();
+
+    assert_eq!(vec![&Expr::Single('a'), &Expr::sequence(Expr::Single('b'), Expr::Single('c')), &Expr::Single('d')],
+               expr.alternatives());
+    assert_eq!(vec![&Expr::Single('a')], Expr::Single('a').alternatives());
+}
+
+#[test]
+fn measures_nest_depth() {
+    assert_eq!(0, "abc".parse::<Expr>().unwrap().nest_depth());
+    assert_eq!(1, "a|b|c|d".parse::<Expr>().unwrap().nest_depth());
+    assert_eq!(3, "x(a|b+)".parse::<Expr>().unwrap().nest_depth());
+    assert_eq!(3, "a***".parse::<Expr>().unwrap().nest_depth());
+}
+
+#[test]
+fn knows_when_empty_string_matches() {
+    for pattern in ["a*", "(a?)+", "(a|b*)", "a?b*", "((a*)*)*"].iter() {
+        assert!(pattern.parse::<Expr>().unwrap().can_match_empty(), "{}", pattern);
+    }
+    for pattern in ["a", "[ab]", ".", "a*b", "(a|b)+", "(a?)b"].iter() {
+        assert!(!pattern.parse::<Expr>().unwrap().can_match_empty(), "{}", pattern);
+    }
+}
+
+struct RejectAny;
+
+impl Visitor for RejectAny {
+    type Output = ();
+    type Err = String;
+
+    fn finish(self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn visit_pre(&mut self, expr: &Expr) -> Result<(), String> {
+        match expr {
+            Expr::Any => Err("`.` is not allowed".to_owned()),
+            _ => Ok(())
+        }
+    }
+}
+
+// the nodes in the order they are entered and left
+struct Trace(Vec<String>);
+
+impl Visitor for Trace {
+    type Output = Vec<String>;
+    type Err = ();
+
+    fn finish(self) -> Result<Vec<String>, ()> {
+        Ok(self.0)
+    }
+
+    fn visit_pre(&mut self, expr: &Expr) -> Result<(), ()> {
+        self.0.push(format!("pre {}", name(expr)));
+        Ok(())
+    }
+
+    fn visit_post(&mut self, expr: &Expr) -> Result<(), ()> {
+        self.0.push(format!("post {}", name(expr)));
+        Ok(())
+    }
+}
+
+fn name(expr: &Expr) -> String {
+    match expr {
+        Expr::Single(c) => c.to_string(),
+        Expr::Alternation(_) => "or".to_owned(),
+        Expr::Group(index, _) => format!("group {}", index),
+        Expr::ZeroOrMore(_) => "star".to_owned(),
+        other => format!("{:?}", other)
+    }
+}
+
+#[test]
+fn visitor_rejects_pattern() {
+    assert_eq!(Ok(()), visit(&"a(b|c)*".parse::<Expr>().unwrap(), RejectAny));
+    assert_eq!(Err("`.` is not allowed".to_owned()), visit(&"a(b|.)*".parse::<Expr>().unwrap(), RejectAny));
+}
+
+#[test]
+fn visits_depth_first_in_order() {
+    let trace = visit(&"(a|b)*".parse::<Expr>().unwrap(), Trace(Vec::new())).unwrap();
+
+    assert_eq!(vec!["pre star", "pre group 1", "pre or", "pre a", "post a", "pre b", "post b",
+                    "post or", "post group 1", "post star"],
+               trace);
+}
+
+#[test]
+fn visits_long_pattern() {
+    let expr = "ab".repeat(50000).parse::<Expr>().unwrap();
+
+    assert_eq!(Ok(()), visit(&expr, RejectAny));
+}
+
+#[test]
+fn parse_non_capturing_group() {
+    assert_eq!(Expr::sequence(Expr::zero_or_more(Expr::sequence(Expr::Single('a'), Expr::Single('b'))),
+                              Expr::group(1, Expr::Single('c'))),
+               "(?:ab)*(c)".parse::<Expr>().unwrap());
+    assert_eq!(Expr::Empty, "(?:)".parse::<Expr>().unwrap());
+}
+
+#[test]
+fn builds_expressions_in_code() {
+    assert_eq!("a\\.b".parse::<Expr>().unwrap(), Expr::literal("a.b"));
+    assert_eq!("a|b|(c)".parse::<Expr>().unwrap(),
+               Expr::alternation(vec![Expr::Single('a'), Expr::Single('b'), Expr::group(1, Expr::Single('c'))]));
+    assert_eq!("[xy]+z".parse::<Expr>().unwrap(),
+               Expr::concat(vec![Expr::one_or_more(Expr::class("xy".chars())), Expr::Single('z')]));
+    assert_eq!(Expr::Empty, Expr::literal(""));
+    assert_eq!(Expr::Class(Vec::new()), Expr::alternation(Vec::new()));
+}
+
+#[test]
+fn displays_as_pattern() {
+    let expr = "a(b|[c.])*\\.".parse::<Expr>().unwrap();
+
+    assert_eq!("a(b|[c.])*\\.", expr.to_string());
+    assert_eq!("\\.(b|[c.])*a", expr.reverse().to_string());
+    assert_eq!(expr.reverse(), expr.reverse().to_string().parse::<Expr>().unwrap());
+}
+
+#[test]
+fn writes_pattern_that_parses_back() {
+    let exprs = [Expr::literal("a.b*"),
+        Expr::zero_or_more(Expr::literal("ab")),
+        Expr::concat(vec![Expr::Single('x'), Expr::alternation(vec![Expr::Single('a'), Expr::Empty])]),
+        Expr::one_or_more(Expr::Empty),
+        Expr::class(vec![']', '\\', 'a', '-']),
+        Expr::group(1, Expr::alternation(vec![Expr::literal("ab"), Expr::optional(Expr::Any)]))];
+    let patterns = ["a\\.b\\*", "(?:ab)*", "x(?:a|)", "(?:)+", "[\\]\\\\a-]", "(ab|.?)"];
+
+    for (expr, &pattern) in exprs.iter().zip(patterns.iter()) {
+        assert_eq!(pattern, expr.to_pattern());
+        assert_eq!(expr, &pattern.parse::<Expr>().unwrap());
+    }
+}
+
+fn ere(pattern: &str) -> Result<Expr, Error> {
+    Expr::parse_with_syntax(pattern, Syntax::PosixExtended, 250, 1 << 20)
+}
+
+fn bre(pattern: &str) -> Result<Expr, Error> {
+    Expr::parse_with_syntax(pattern, Syntax::PosixBasic, 250, 1 << 20)
+}
+
+#[test]
+fn parse_posix_extended() {
+    assert_eq!("(a|b)+c?".parse::<Expr>(), ere("(a|b)+c?"));
+    assert_eq!(Ok(Expr::literal("a{b")), ere("a{b"));
+    assert_eq!(Ok(Expr::concat(vec![Expr::Single('a'), Expr::Single('('), Expr::Any])), ere("a\\(."));
+}
+
+#[test]
+fn parse_posix_basic() {
+    assert_eq!("(a|b)*c".parse::<Expr>(), bre("\\(a\\|b\\)*c"));
+    assert_eq!(Ok(Expr::literal("(a|b)+?")), bre("(a|b)+?"));
+    // `*` with nothing to repeat is a literal
+    assert_eq!(Ok(Expr::concat(vec![Expr::Single('*'), Expr::group(1, Expr::Single('*'))])), bre("*\\(*\\)"));
+}
+
+#[test]
+fn expands_intervals() {
+    let a = || Expr::Single('a');
+    assert_eq!(Ok(Expr::literal("aaa")), ere("a{3}"));
+    assert_eq!(Ok(Expr::concat(vec![a(), a(), Expr::zero_or_more(a())])), ere("a{2,}"));
+    assert_eq!(Ok(Expr::concat(vec![a(), Expr::optional(a()), Expr::optional(a())])), ere("a{1,3}"));
+    assert_eq!(Ok(1), ere("a{0,255}").map(|expr| expr.nest_depth()));
+    assert_eq!(ere("a{1,3}"), bre("a\\{1,3\\}"));
+    assert_eq!(Ok(Expr::Empty), ere("a{0}"));
+}
+
+#[test]
+fn parse_bracket_expressions() {
+    assert_eq!(Ok(Expr::class("abcxyz".chars())), ere("[a-cx-z]"));
+    assert_eq!(Ok(Expr::class("]a-".chars())), ere("[]a-]"));
+    assert_eq!(Ok(Expr::class("0123456789_".chars())), ere("[[:digit:]_]"));
+    assert_eq!(Ok(Expr::class("\\]".chars())), bre("[\\[.].]]"));
+    assert_eq!(Ok(Expr::NegatedClass(vec!['a', 'b', 'c'])), ere("[^a-c]"));
+    assert_eq!(Ok(Expr::NegatedClass(vec![']', 'a'])), bre("[^]a]"));
+}
+
+#[test]
+fn parse_posix_anchors() {
+    let anchored = |rest: Expr| Ok(Expr::concat(vec![Expr::Single('^'), rest]));
+    assert_eq!(anchored(Expr::literal("ab")), ere("^ab"));
+    assert_eq!(anchored(Expr::literal("*a")), bre("^*a"));
+    assert_eq!(Ok(Expr::Single('^')), ere("^"));
+
+    // in basic syntax, only where they would anchor are they not chars
+    assert_eq!(Ok(Expr::literal("a^b$c")), bre("a^b$c"));
+    assert_eq!(Ok(Expr::literal("$a")), bre("$a"));
+}
+
+#[test]
+fn reports_posix_problems() {
+    assert_eq!(Err(Error::InvalidInterval(1, 6)), ere("a{3,2}"));
+    assert_eq!(Err(Error::InvalidInterval(1, 3)), bre("a\\{x\\}"));
+    assert_eq!(Err(Error::DanglingQuantifier(0, 1)), ere("*a"));
+    assert_eq!(Err(Error::UnsupportedAnchor(3, 4)), ere("a|b$"));
+    assert_eq!(Err(Error::UnsupportedAnchor(1, 2)), ere("a^b"));
+    assert_eq!(Err(Error::UnsupportedAnchor(0, 1)), ere("^a|b"));
+    assert_eq!(Err(Error::UnsupportedAnchor(1, 2)), ere("(^a)"));
+    assert_eq!(Err(Error::UnsupportedAnchor(1, 2)), bre("a$"));
+    assert_eq!(Err(Error::UnsupportedAnchor(3, 4)), bre("\\(a$\\)"));
+    assert_eq!(Err(Error::UnsupportedAnchor(3, 4)), bre("a\\|^b"));
+    assert_eq!(Err(Error::InvalidBracket(1, 9)), ere("[[:word:]]"));
+    assert_eq!(Err(Error::UnclosedGroup(0, 4)), bre("\\(ab"));
+    assert_eq!(Err(Error::UnmatchedGroupClose(1, 2)), ere("a)"));
+    assert_eq!(Err(Error::UnsupportedBackreference(3, 5)), ere("(a)\\1"));
+    assert_eq!(Err(Error::UnsupportedBackreference(5, 7)), bre("\\(a\\)\\1"));
+    assert_eq!(Err(Error::NestLimitExceeded), Expr::parse_with_syntax("((a))", Syntax::PosixExtended, 1, 1 << 20));
+    assert_eq!(Err(Error::SizeLimitExceeded), ere("a{255}{255}{255}"));
+    assert_eq!(Err(Error::SizeLimitExceeded), ere("(a{255}){255}{20}"));
+    assert_eq!(Err(Error::SizeLimitExceeded), ere(&"(a{255}){255}".repeat(20)));
+    assert!(ere("(a{255}){10}").is_ok());
+}
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn arbitrary_expressions_compile_as_their_patterns_do() {
+    use ::regex::{Regex, RegexBuilder};
+
+    for seed in 0..200 {
+        let expr = Expr::arbitrary(seed, 12);
+        assert_eq!(expr, Expr::arbitrary(seed, 12));
+
+        let compiled = RegexBuilder::from_expr(expr.clone()).build().unwrap();
+        let parsed = Regex::from(&expr.to_pattern()).unwrap();
+        assert!(compiled.is_equivalent(&parsed), "{:?} as {}", expr, expr.to_pattern());
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn builds_expressions_from_fuzzer_input() {
+    use ::arbitrary::{Arbitrary, Unstructured};
+    use ::regex::RegexBuilder;
+
+    let bytes = (0..=255).collect::<Vec<u8>>();
+    for input in bytes.chunks(9) {
+        let expr = Expr::arbitrary_take_rest(Unstructured::new(input)).unwrap();
+        assert_eq!(expr, Expr::arbitrary_take_rest(Unstructured::new(input)).unwrap());
+        assert!(RegexBuilder::from_expr(expr.clone()).build().is_ok(), "{:?}", expr);
+    }
+    assert_eq!(Expr::arbitrary(0, 0), Expr::arbitrary_take_rest(Unstructured::new(&[])).unwrap());
+}
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn shrinks_to_smaller_expressions() {
+    for seed in 0..50 {
+        let expr = Expr::arbitrary(seed, 10);
+        for smaller in expr.shrink() {
+            assert!(smaller.size() < expr.size(), "{:?} from {:?}", smaller, expr);
+        }
+    }
+
+    let expr = "(a|b)(c)".parse::<Expr>().unwrap();
+    let shrunk = expr.shrink();
+    assert_eq!(Expr::Empty, shrunk[0]);
+    assert!(shrunk.contains(&"(c)".parse::<Expr>().unwrap()));
+    assert!(shrunk.contains(&"(a)(c)".parse::<Expr>().unwrap()));
+    assert_eq!(Vec::<Expr>::new(), Expr::Empty.shrink());
+}
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn shrinking_finds_a_smallest_failing_expression() {
+    use ::regex::RegexBuilder;
+
+    // fails on anything that matches two `a`s in a row
+    let fails = |expr: &Expr| {
+        let regex = RegexBuilder::from_expr(expr.clone()).build().unwrap();
+        regex.find("aa").map(|m| m.len()) == Some(2)
+    };
+
+    let mut expr = "b(c|a+)d".parse::<Expr>().unwrap();
+    while let Some(smaller) = expr.shrink().into_iter().find(|smaller| fails(smaller)) {
+        expr = smaller;
+    }
+    assert_eq!("a+".parse::<Expr>().unwrap(), expr);
+}
@@ -0,0 +1,99 @@
+use ::span::Span;
+
+/// What a piece of a pattern is to the parser.
+#[derive(PartialEq,Eq,Debug,Clone,Copy,Hash)]
+pub enum TokenKind {
+    /// Chars matching themselves, a run of them taken together.
+    Literal,
+    /// `.` or `|`, or a `]` closing no class.
+    Metachar,
+    /// A whole bracketed class, from `[` to `]` or the end if it is left
+    /// open.
+    Class,
+    Quantifier,
+    /// `(`, `(?:`, `(?P<name>` or `)`.
+    GroupDelimiter,
+    /// A `\` and the char it escapes, or a `\` ending the pattern.
+    Escape
+}
+
+/// A piece of a pattern and where it is.
+#[derive(PartialEq,Eq,Debug,Clone,Copy,Hash)]
+pub struct PatternToken {
+    kind: TokenKind,
+    span: Span
+}
+
+impl PatternToken {
+    pub fn kind(&self) -> TokenKind {
+        self.kind
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+/// Splits `pattern` into the pieces the parser reads it as, for
+/// highlighting it, without parsing it. Every byte of the pattern is in
+/// exactly one piece, even where the pattern is invalid.
+pub fn tokenize(pattern: &str) -> Vec<PatternToken> {
+    let mut tokens = Vec::<PatternToken>::new();
+    let mut push = |kind: TokenKind, start: usize, end: usize| {
+        match tokens.last_mut() {
+            Some(last) if kind == TokenKind::Literal && last.kind == kind && last.span.end() == start => {
+                last.span = Span::new(last.span.start(), end);
+                return;
+            },
+            _ => ()
+        }
+        tokens.push(PatternToken { kind, span: Span::new(start, end) });
+    };
+
+    let mut chars = pattern.char_indices();
+    while let Some((offset, c)) = chars.next() {
+        let end = offset + c.len_utf8();
+        match c {
+            '\\' => {
+                let end = chars.next().map_or(end, |(offset, c)| offset + c.len_utf8());
+                push(TokenKind::Escape, offset, end);
+            },
+            '[' => {
+                // escapes inside the class take the char after them in,
+                // even a `]`
+                let mut end = pattern.len();
+                while let Some((offset, c)) = chars.next() {
+                    if c == '\\' {
+                        chars.next();
+                    } else if c == ']' {
+                        end = offset + 1;
+                        break;
+                    }
+                }
+                push(TokenKind::Class, offset, end);
+            },
+            '(' if pattern[end..].starts_with("?:") => {
+                chars.next();
+                chars.next();
+                push(TokenKind::GroupDelimiter, offset, end + 2);
+            },
+            '(' if pattern[end..].starts_with("?P<") => {
+                // the name runs to the `>`, or to the end if it is left open
+                let mut end = pattern.len();
+                for (offset, c) in chars.by_ref() {
+                    if c == '>' {
+                        end = offset + 1;
+                        break;
+                    }
+                }
+                push(TokenKind::GroupDelimiter, offset, end);
+            },
+            '(' | ')' => push(TokenKind::GroupDelimiter, offset, end),
+            '?' | '*' | '+' => push(TokenKind::Quantifier, offset, end),
+            '.' | '|' | ']' => push(TokenKind::Metachar, offset, end),
+            _ => push(TokenKind::Literal, offset, end)
+        }
+    }
+
+    tokens
+}
@@ -0,0 +1,49 @@
+use super::Expr;
+
+/// Walks an expression tree with `visit`, for analysing or rewriting patterns
+/// without matching on every kind of node by hand.
+pub trait Visitor {
+    type Output;
+    type Err;
+
+    /// Called once every node has been visited, to produce the result.
+    fn finish(self) -> Result<Self::Output, Self::Err>;
+
+    /// Called on a node before any of the nodes inside it.
+    fn visit_pre(&mut self, _expr: &Expr) -> Result<(), Self::Err> {
+        Ok(())
+    }
+
+    /// Called on a node after all of the nodes inside it.
+    fn visit_post(&mut self, _expr: &Expr) -> Result<(), Self::Err> {
+        Ok(())
+    }
+}
+
+/// Visits every node of `expr` depth first, left to right, stopping at the
+/// first error the visitor returns. The nodes are kept on the heap rather
+/// than the call stack, so long patterns cannot overflow it.
+pub fn visit<V: Visitor>(expr: &Expr, mut visitor: V) -> Result<V::Output, V::Err> {
+    // each node is pushed twice: to be entered, then to be left
+    let mut stack = vec![(expr, false)];
+
+    while let Some((expr, done)) = stack.pop() {
+        if done {
+            visitor.visit_post(expr)?;
+            continue;
+        }
+
+        visitor.visit_pre(expr)?;
+        stack.push((expr, true));
+        match expr {
+            Expr::Concat(items) | Expr::Alternation(items) => {
+                stack.extend(items.iter().rev().map(|item| (item, false)));
+            },
+            Expr::Optional(item) | Expr::ZeroOrMore(item) |
+            Expr::OneOrMore(item) | Expr::Group(_, item) => stack.push((item, false)),
+            Expr::Empty | Expr::Single(_) | Expr::Class(_) | Expr::NegatedClass(_) | Expr::Any => ()
+        }
+    }
+
+    visitor.finish()
+}
@@ -0,0 +1,153 @@
+use std::collections::VecDeque;
+
+use ::nfa::{NFA, State, Transition, Condition};
+use ::regex::Regex;
+use ::rng::Rng;
+
+#[cfg(test)] mod spec;
+
+const DEFAULT_MAX_REPEAT: usize = 8;
+
+/// Produces random strings that a regex matches in full, by walking its
+/// automaton from the start to the end and choosing at random at each
+/// alternative and byte. The same seed always gives the same strings.
+pub struct Generator<'n> {
+    nfa: &'n NFA,
+    max_repeat: usize,
+    rng: Rng,
+    to_end: Vec<usize> // steps to the end from each state, `usize::MAX` if it is out of reach
+}
+
+impl<'n> Generator<'n> {
+    pub fn new(regex: &'n Regex) -> Generator<'n> {
+        let nfa = regex.nfa();
+        Generator {
+            nfa,
+            max_repeat: DEFAULT_MAX_REPEAT,
+            rng: Rng::default(),
+            to_end: steps_to_end(nfa)
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Generator<'n> {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    /// How often a repetition may go around before the walk heads for the
+    /// end by the shortest way, so unbounded ones stay short.
+    pub fn with_max_repeat(mut self, max_repeat: usize) -> Generator<'n> {
+        self.max_repeat = max_repeat;
+        self
+    }
+
+    /// A random string the regex matches, `None` if it matches none.
+    pub fn generate(&mut self) -> Option<String> {
+        if self.nfa.num_states() == 0 {
+            return Some(String::new());
+        }
+        if self.to_end[self.nfa.get_start_id()] == usize::MAX {
+            return None;
+        }
+
+        // every path is a sequence of whole chars, as long as `Any` is kept
+        // to ASCII, but that is checked rather than trusted
+        loop {
+            if let Ok(text) = String::from_utf8(self.walk()) {
+                return Some(text);
+            }
+        }
+    }
+
+    fn walk(&mut self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut visits = vec![0; self.nfa.num_states()];
+        let mut edge = Transition::id(self.nfa.get_start_id());
+
+        while let Transition::Id(id) = edge {
+            let id = id.index();
+            visits[id] += 1;
+            edge = match self.nfa.get_state(id) {
+                Some(&State::State{ref condition, out}) => {
+                    self.choose_bytes(condition, &mut bytes);
+                    out
+                },
+                Some(&State::Split{out1, out2}) => {
+                    let live = |edge: Transition| self.steps(edge) != usize::MAX;
+                    let (first, second) = (live(out1), live(out2));
+                    if visits[id] > self.max_repeat {
+                        // the way to the end is one step shorter from here
+                        if self.steps(out1) < self.steps(out2) { out1 } else { out2 }
+                    } else if first && second {
+                        if self.rng.below(2) == 0 { out1 } else { out2 }
+                    } else if first {
+                        out1
+                    } else {
+                        out2
+                    }
+                },
+                Some(&State::Save{out, ..}) => out,
+                None => panic!("transition to missing state {}", id)
+            };
+        }
+
+        bytes
+    }
+
+    fn steps(&self, edge: Transition) -> usize {
+        match edge {
+            Transition::Id(id) => self.to_end[id.index()],
+            _ => 0
+        }
+    }
+
+    // adds to `text` bytes meeting `condition`, none if it consumes nothing
+    fn choose_bytes(&mut self, condition: &Condition, text: &mut Vec<u8>) {
+        let bytes = match condition {
+            Condition::One(byte) => return text.push(*byte),
+            Condition::Literal(literal) => return text.extend_from_slice(literal.as_bytes()),
+            Condition::Class(bytes) => bytes.iter().collect::<Vec<u8>>(),
+            Condition::Any => (b' '..b'~' + 1).collect(),
+            Condition::None => return
+        };
+        text.push(bytes[self.rng.below(bytes.len())])
+    }
+}
+
+// The fewest steps from each state to the end, by a breadth first search
+// backwards from it. States whose condition no byte meets lead nowhere.
+fn steps_to_end(nfa: &NFA) -> Vec<usize> {
+    let n = nfa.num_states();
+    let mut predecessors = vec![Vec::new(); n];
+    let mut pending = VecDeque::new();
+    let mut steps = vec![usize::MAX; n];
+
+    for (id, step) in steps.iter_mut().enumerate() {
+        let outs = match nfa.get_state(id) {
+            Some(&State::State{condition: Condition::Class(ref bytes), ..}) if bytes.is_empty() => vec![],
+            Some(&State::State{out, ..}) | Some(&State::Save{out, ..}) => vec![out],
+            Some(&State::Split{out1, out2}) => vec![out1, out2],
+            None => vec![]
+        };
+        for out in outs {
+            match out {
+                Transition::Id(next) => predecessors[next.index()].push(id),
+                _ => if *step == usize::MAX {
+                    *step = 1;
+                    pending.push_back(id);
+                }
+            }
+        }
+    }
+
+    while let Some(id) = pending.pop_front() {
+        for &previous in &predecessors[id] {
+            if steps[previous] == usize::MAX {
+                steps[previous] = steps[id] + 1;
+                pending.push_back(previous);
+            }
+        }
+    }
+
+    steps
+}
@@ -0,0 +1,74 @@
+use ::dfa::DenseDFA;
+use ::regex::{Regex, RegexBuilder};
+use super::Generator;
+
+// whether `regex` matches all of `text`
+fn accepts(regex: &Regex, text: &str) -> bool {
+    DenseDFA::build_longest(regex.nfa(), 1 << 20).unwrap().accepts(text.as_bytes())
+}
+
+#[test]
+fn generates_strings_the_regex_matches() {
+    let patterns = ["abc", "a|ab|abc", "(ab|cd)*e", "x+y?z*", "[abc]+@[xyz]+.com", "é(ü|ö)*", "a.b", ""];
+
+    for pattern in patterns.iter() {
+        let regex = Regex::from(pattern).unwrap();
+        let mut generator = Generator::new(&regex);
+        for _ in 0..50 {
+            let text = generator.generate().unwrap();
+            assert!(accepts(&regex, &text), "{} generated {:?}", pattern, text);
+        }
+    }
+}
+
+#[test]
+fn generates_every_alternative() {
+    let regex = Regex::from("red|green|blue").unwrap();
+    let mut generator = Generator::new(&regex);
+    let mut seen = (0..100).map(|_| generator.generate().unwrap()).collect::<Vec<_>>();
+    seen.sort();
+    seen.dedup();
+
+    assert_eq!(vec!["blue", "green", "red"], seen);
+}
+
+#[test]
+fn bounds_repetitions() {
+    let regex = Regex::from("a*").unwrap();
+    let mut generator = Generator::new(&regex).with_max_repeat(3);
+    for _ in 0..50 {
+        assert!(generator.generate().unwrap().len() <= 3);
+    }
+
+    let mut generator = Generator::new(&regex).with_max_repeat(0);
+    assert_eq!(Some(String::new()), generator.generate());
+}
+
+#[test]
+fn repeats_with_the_same_seed() {
+    let regex = Regex::from("(a|b|c)*").unwrap();
+    let generate = |seed: u64| {
+        let mut generator = Generator::new(&regex).with_seed(seed);
+        (0..10).map(|_| generator.generate().unwrap()).collect::<Vec<_>>()
+    };
+
+    assert_eq!(generate(7), generate(7));
+    assert!(generate(7) != generate(8));
+}
+
+#[test]
+fn follows_builder_options() {
+    let regex = RegexBuilder::new("ab").case_insensitive(true).build().unwrap();
+    let mut generator = Generator::new(&regex);
+    let mut seen = (0..100).map(|_| generator.generate().unwrap()).collect::<Vec<_>>();
+    seen.sort();
+    seen.dedup();
+
+    assert_eq!(vec!["AB", "Ab", "aB", "ab"], seen);
+}
+
+#[test]
+fn generates_nothing_when_nothing_matches() {
+    let regex = Regex::from("[]").unwrap();
+    assert_eq!(None, Generator::new(&regex).generate());
+}
@@ -0,0 +1,92 @@
+use ::error::Error;
+use ::expr::Expr;
+use ::regex::{Regex, RegexBuilder, Input, Anchored};
+
+#[cfg(test)] mod spec;
+
+/// A shell glob compiled with the same engine as patterns. `*` matches any
+/// run of chars and `?` any one char, `[abc]` and `[a-z]` match one of the
+/// chars listed, and `\` makes the next char match itself. Like `fnmatch`
+/// without `FNM_PATHNAME`, `*` and `?` match `/` too. `?` compiles to `.`,
+/// so it matches a whole non-ASCII char like `é` but not a newline. A glob
+/// has to match the whole path.
+#[derive(PartialEq,Debug,Clone)]
+pub struct Glob {
+    glob: String,
+    regex: Regex
+}
+
+impl Glob {
+    pub fn new(glob: &str) -> Result<Glob, Error> {
+        // the longest match from the start reaches the end if any match does
+        let regex = RegexBuilder::from_expr(to_expr(glob)?).leftmost_longest(true).build()?;
+
+        Ok(Glob {
+            glob: glob.to_owned(),
+            regex
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.glob
+    }
+
+    // the regex the glob compiled to, which matches anywhere in a text
+    pub fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        let input = Input::new(path).anchored(Anchored::Yes);
+        self.regex.search(&mut self.regex.create_cache(), &input)
+            .is_some_and(|m| m.end() == path.len())
+    }
+}
+
+/// The expression a glob matches the same strings as, apart from having to
+/// match them whole.
+pub fn to_expr(glob: &str) -> Result<Expr, Error> {
+    let mut items = Vec::new();
+    let mut chars = glob.char_indices().peekable();
+
+    while let Some((offset, c)) = chars.next() {
+        items.push(match c {
+            '*' => Expr::zero_or_more(Expr::Any),
+            '?' => Expr::Any,
+            '\\' => match chars.next() {
+                Some((_, c)) => Expr::Single(c),
+                None => return Err(Error::InvalidEscape(offset, glob.len()))
+            },
+            '[' => {
+                if let Some(&(at, '!')) = chars.peek() {
+                    return Err(Error::UnsupportedNegation(offset, at + 1));
+                }
+
+                let mut class: Vec<char> = Vec::new();
+                loop {
+                    match chars.next() {
+                        // a `]` first in the class is one of its chars
+                        Some((_, ']')) if !class.is_empty() => break,
+                        Some((_, first)) => {
+                            let is_range = matches!(chars.peek(), Some(&(_, '-')));
+                            let mut rest = chars.clone();
+                            rest.next();
+                            match rest.next() {
+                                Some((_, last)) if is_range && last != ']' => {
+                                    chars = rest;
+                                    class.extend(first..=last);
+                                },
+                                _ => class.push(first)
+                            }
+                        },
+                        None => return Err(Error::UnclosedClass(offset, glob.len()))
+                    }
+                }
+                Expr::class(class)
+            },
+            c => Expr::Single(c)
+        });
+    }
+
+    Ok(Expr::concat(items))
+}
@@ -0,0 +1,60 @@
+use ::error::Error;
+use ::expr::Expr;
+use super::{Glob, to_expr};
+
+fn matches(glob: &str, path: &str) -> bool {
+    Glob::new(glob).unwrap().is_match(path)
+}
+
+#[test]
+fn matches_whole_path() {
+    assert!(matches("*.log", "server.log"));
+    assert!(matches("*.log", ".log"));
+    assert!(!matches("*.log", "server.log.1"));
+    assert!(!matches("*.log", "server.txt"));
+    assert!(matches("", ""));
+    assert!(!matches("", "a"));
+}
+
+#[test]
+fn question_mark_matches_one_char() {
+    assert!(matches("data/??.csv", "data/01.csv"));
+    assert!(!matches("data/??.csv", "data/1.csv"));
+    assert!(!matches("data/??.csv", "data/123.csv"));
+    assert!(matches("caf?.txt", "café.txt"));
+    assert!(!matches("caf??.txt", "café.txt"));
+}
+
+#[test]
+fn matches_classes_and_ranges() {
+    assert!(matches("[ab]*", "apple"));
+    assert!(!matches("[ab]*", "cherry"));
+    assert!(matches("file[0-9].txt", "file7.txt"));
+    assert!(!matches("file[0-9].txt", "filex.txt"));
+    // `]` first and `-` last are chars of the class
+    assert!(matches("[]-]", "]"));
+    assert!(matches("[]-]", "-"));
+}
+
+#[test]
+fn escapes_and_metachars_are_literal() {
+    assert!(matches("a\\*b", "a*b"));
+    assert!(!matches("a\\*b", "axb"));
+    assert!(matches("(a|b)+.c", "(a|b)+.c"));
+    assert!(!matches("(a|b)+.c", "ab.c"));
+}
+
+#[test]
+fn converts_to_expression() {
+    assert_eq!(Expr::concat(vec![Expr::zero_or_more(Expr::Any), Expr::literal(".rs")]),
+               to_expr("*.rs").unwrap());
+    assert_eq!(".*\\.rs", Glob::new("*.rs").unwrap().regex().as_str());
+}
+
+#[test]
+fn reports_invalid_globs() {
+    assert_eq!(Err(Error::UnclosedClass(2, 5)), to_expr("ab[cd"));
+    assert_eq!(Err(Error::UnsupportedNegation(0, 2)), to_expr("[!a]"));
+    assert_eq!(Err(Error::InvalidEscape(1, 2)), to_expr("a\\"));
+}
+
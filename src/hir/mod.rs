@@ -0,0 +1,242 @@
+use std::cmp;
+
+use ::expr::Expr;
+
+#[cfg(test)] mod spec;
+
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub enum Repetition {
+    ZeroOrOne,
+    ZeroOrMore,
+    OneOrMore
+}
+
+/// The form of a pattern the NFA is compiled from. Unlike `Expr`, which
+/// follows the syntax, it has the options resolved: classes are sorted
+/// ranges, case folding has been applied, and sequences and alternations are
+/// flat lists, so that any syntax can be compiled by translating it to this.
+#[derive(PartialEq,Debug,Clone)]
+pub enum Hir {
+    Empty,
+    Literal(char),
+    /// Sorted ranges of chars, both ends included, that neither overlap nor
+    /// touch.
+    Class(Vec<(char, char)>),
+    /// Any char but the line terminator, a newline by default.
+    Any(u8),
+    Concat(Vec<Hir>),
+    Alternation(Vec<Hir>), // in order of priority
+    Repeat(Repetition, Box<Hir>),
+    Group(usize, Box<Hir>)
+}
+
+impl Hir {
+    // the translation of `expr` with the default options
+    pub fn from_expr(expr: &Expr) -> Hir {
+        Translator::new().translate(expr)
+    }
+
+    // a class of the given chars in any order, with repeats
+    pub fn class<I: IntoIterator<Item=char>>(chars: I) -> Hir {
+        Hir::Class(ranges(chars))
+    }
+
+    // a class of every char but the given ones
+    pub fn negated_class<I: IntoIterator<Item=char>>(chars: I) -> Hir {
+        let mut negated = Vec::new();
+        let mut next = Some('\0'); // the first char after the last range
+        for (first, last) in ranges(chars) {
+            match next {
+                Some(from) if from < first => negated.push((from, before(first))),
+                _ => ()
+            }
+            next = after(last);
+        }
+        if let Some(from) = next {
+            negated.push((from, char::MAX));
+        }
+        Hir::Class(negated)
+    }
+
+    // whether the empty string is among the strings this matches
+    pub fn can_match_empty(&self) -> bool {
+        match self {
+            Hir::Empty => true,
+            Hir::Literal(_) | Hir::Class(_) | Hir::Any(_) => false,
+            Hir::Concat(items) => items.iter().all(|item| item.can_match_empty()),
+            Hir::Alternation(alternatives) => alternatives.iter().any(|item| item.can_match_empty()),
+            Hir::Repeat(Repetition::OneOrMore, item) | Hir::Group(_, item) => item.can_match_empty(),
+            Hir::Repeat(..) => true
+        }
+    }
+
+    // matches the reversal of every string this one matches
+    pub fn reverse(&self) -> Hir {
+        match self {
+            Hir::Concat(items) => Hir::Concat(items.iter().rev().map(|item| item.reverse()).collect()),
+            Hir::Alternation(alternatives) => Hir::Alternation(alternatives.iter().map(|item| item.reverse()).collect()),
+            Hir::Repeat(repetition, item) => Hir::Repeat(*repetition, Box::new(item.reverse())),
+            Hir::Group(index, item) => Hir::Group(*index, Box::new(item.reverse())),
+            other => other.clone()
+        }
+    }
+
+    // matches the same strings, with none of its groups capturing
+    pub fn without_groups(&self) -> Hir {
+        match self {
+            Hir::Group(_, item) => item.without_groups(),
+            Hir::Concat(items) => Hir::Concat(items.iter().map(|item| item.without_groups()).collect()),
+            Hir::Alternation(alternatives) => {
+                Hir::Alternation(alternatives.iter().map(|item| item.without_groups()).collect())
+            },
+            Hir::Repeat(repetition, item) => Hir::Repeat(*repetition, Box::new(item.without_groups())),
+            other => other.clone()
+        }
+    }
+}
+
+/// Translates an `Expr` to an `Hir`, applying the options that change what
+/// the parts of a pattern match.
+pub struct Translator {
+    case_insensitive: bool,
+    line_terminator: u8
+}
+
+impl Default for Translator {
+    fn default() -> Translator {
+        Translator::new()
+    }
+}
+
+impl Translator {
+    pub fn new() -> Translator {
+        Translator {
+            case_insensitive: false,
+            line_terminator: b'\n'
+        }
+    }
+
+    /// Makes letters match both their upper and lower case forms.
+    pub fn case_insensitive(mut self, yes: bool) -> Translator {
+        self.case_insensitive = yes;
+        self
+    }
+
+    /// The byte `.` does not match, a newline by default.
+    pub fn line_terminator(mut self, byte: u8) -> Translator {
+        self.line_terminator = byte;
+        self
+    }
+
+    pub fn translate(&self, expr: &Expr) -> Hir {
+        match expr {
+            Expr::Empty => Hir::Empty,
+            Expr::Any => Hir::Any(self.line_terminator),
+            Expr::Single(c) if self.case_insensitive => match case_variants(*c) {
+                ref variants if variants.len() > 1 => Hir::class(variants.iter().cloned()),
+                _ => Hir::Literal(*c)
+            },
+            Expr::Single(c) => Hir::Literal(*c),
+            Expr::Class(chars) if self.case_insensitive => {
+                Hir::class(chars.iter().flat_map(|&c| case_variants(c)))
+            },
+            Expr::Class(chars) => Hir::class(chars.iter().cloned()),
+            Expr::NegatedClass(chars) if self.case_insensitive => {
+                Hir::negated_class(chars.iter().flat_map(|&c| case_variants(c)))
+            },
+            Expr::NegatedClass(chars) => Hir::negated_class(chars.iter().cloned()),
+            Expr::Concat(items) => Hir::Concat(items.iter().map(|item| self.translate(item)).collect()),
+            Expr::Alternation(alternatives) => {
+                Hir::Alternation(alternatives.iter().map(|item| self.translate(item)).collect())
+            },
+            Expr::Optional(item) => Hir::Repeat(Repetition::ZeroOrOne, Box::new(self.translate(item))),
+            Expr::ZeroOrMore(item) => Hir::Repeat(Repetition::ZeroOrMore, Box::new(self.translate(item))),
+            Expr::OneOrMore(item) => Hir::Repeat(Repetition::OneOrMore, Box::new(self.translate(item))),
+            Expr::Group(index, item) => Hir::Group(*index, Box::new(self.translate(item)))
+        }
+    }
+}
+
+// the sorted ranges of the given chars, apart from each other
+fn ranges<I: IntoIterator<Item=char>>(chars: I) -> Vec<(char, char)> {
+    let mut chars = chars.into_iter().collect::<Vec<char>>();
+    chars.sort();
+
+    let mut ranges: Vec<(char, char)> = Vec::new();
+    for c in chars {
+        match ranges.last_mut() {
+            Some(&mut (_, ref mut last)) if c as u32 <= *last as u32 + 1 => *last = cmp::max(*last, c),
+            _ => ranges.push((c, c))
+        }
+    }
+    ranges
+}
+
+// the chars just before and after `c`, skipping the surrogates
+fn before(c: char) -> char {
+    match c {
+        '\u{e000}' => '\u{d7ff}',
+        c => char::from_u32(c as u32 - 1).unwrap()
+    }
+}
+
+fn after(c: char) -> Option<char> {
+    match c {
+        '\u{d7ff}' => Some('\u{e000}'),
+        c => char::from_u32(c as u32 + 1)
+    }
+}
+
+// the other case of each ascii letter, and the byte itself for the rest
+const ASCII_FOLD: [u8; 128] = ascii_fold();
+
+const fn ascii_fold() -> [u8; 128] {
+    let mut table = [0; 128];
+    let mut byte = 0u8;
+    while byte < 128 {
+        table[byte as usize] = if byte.is_ascii_alphabetic() { byte ^ 0x20 } else { byte };
+        byte += 1;
+    }
+    table
+}
+
+// the chars outside ascii that are the same letter as an ascii one
+const ASCII_FOLD_EXTRA: [(char, char); 4] = [('K', '\u{212a}'), ('k', '\u{212a}'), ('S', '\u{17f}'), ('s', '\u{17f}')];
+
+// `c` and the chars that are the same letter in another case, where that is a
+// single char. Ascii letters are looked up in tables: only the kelvin sign and
+// the long s are the same letter as one of them from outside ascii.
+fn case_variants(c: char) -> Vec<char> {
+    if c.is_ascii() {
+        let mut variants = vec![c];
+        let other = ASCII_FOLD[c as usize] as char;
+        if other != c {
+            variants.push(other);
+        }
+        variants.extend(ASCII_FOLD_EXTRA.iter().filter(|&&(letter, _)| letter == c).map(|&(_, extra)| extra));
+        return variants;
+    }
+    unicode_case_variants(c)
+}
+
+fn unicode_case_variants(c: char) -> Vec<char> {
+    let mut variants = vec![c];
+    let mut i = 0;
+    while i < variants.len() {
+        let v = variants[i];
+        for other in single(v.to_lowercase()).into_iter().chain(single(v.to_uppercase())) {
+            if !variants.contains(&other) {
+                variants.push(other);
+            }
+        }
+        i += 1;
+    }
+    variants
+}
+
+fn single<I: Iterator<Item=char>>(mut chars: I) -> Option<char> {
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None
+    }
+}
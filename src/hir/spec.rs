@@ -0,0 +1,81 @@
+use ::expr::Expr;
+use super::{Hir, Repetition, Translator, case_variants, unicode_case_variants};
+
+fn hir(pattern: &str) -> Hir {
+    Hir::from_expr(&pattern.parse::<Expr>().unwrap())
+}
+
+fn case_insensitive(pattern: &str) -> Hir {
+    Translator::new().case_insensitive(true).translate(&pattern.parse::<Expr>().unwrap())
+}
+
+#[test]
+fn normalizes_classes_to_ranges() {
+    assert_eq!(Hir::Class(vec![('a', 'c'), ('x', 'x')]), hir("[xcabx]"));
+    assert_eq!(Hir::Class(vec![('0', '1'), ('é', 'é')]), hir("[é10]"));
+}
+
+#[test]
+fn negates_classes_around_the_surrogates() {
+    assert_eq!(Hir::Class(vec![('\0', '`'), ('c', char::MAX)]), hir("[^ba]"));
+    assert_eq!(Hir::Class(vec![('\0', char::MAX)]), hir("[^]"));
+    assert_eq!(Hir::Class(vec![('\0', '\u{d7fe}'), ('\u{e001}', char::MAX)]),
+               Hir::negated_class(vec!['\u{d7ff}', '\u{e000}']));
+    assert_eq!(Hir::Class(vec![('\u{1}', '\u{10fffe}')]), Hir::negated_class(vec!['\0', char::MAX]));
+    assert_eq!(Hir::Class(vec![('\0', '@'), ('B', '`'), ('b', char::MAX)]), case_insensitive("[^a]"));
+}
+
+#[test]
+fn flattens_sequences_and_alternations() {
+    assert_eq!(Hir::Concat(vec![Hir::Literal('a'), Hir::Literal('b'), Hir::Literal('c')]), hir("abc"));
+    assert_eq!(Hir::Alternation(vec![Hir::Literal('a'), Hir::Empty, Hir::Concat(vec![Hir::Literal('b'), Hir::Any(b'\n')])]),
+               hir("a||b."));
+    assert_eq!(Hir::Group(1, Box::new(Hir::Repeat(Repetition::ZeroOrMore, Box::new(Hir::Literal('a'))))),
+               hir("(a*)"));
+}
+
+#[test]
+fn folds_case() {
+    assert_eq!(Hir::Concat(vec![Hir::Class(vec![('A', 'A'), ('a', 'a')]), Hir::Literal('1')]),
+               case_insensitive("a1"));
+    assert_eq!(Hir::Class(vec![('B', 'C'), ('b', 'c')]), case_insensitive("[bC]"));
+    // the kelvin sign is an upper case k
+    assert_eq!(Hir::Class(vec![('K', 'K'), ('k', 'k'), ('\u{212a}', '\u{212a}')]), case_insensitive("\u{212a}"));
+    assert_eq!(Hir::Class(vec![('K', 'K'), ('k', 'k'), ('\u{212a}', '\u{212a}')]), case_insensitive("k"));
+    // and the long s a lower case s
+    assert_eq!(Hir::Class(vec![('S', 'S'), ('s', 's'), ('\u{17f}', '\u{17f}')]), case_insensitive("\u{17f}"));
+    assert_eq!(Hir::Class(vec![('S', 'S'), ('s', 's'), ('\u{17f}', '\u{17f}')]), case_insensitive("S"));
+}
+
+#[test]
+fn folds_ascii_case_by_table() {
+    for byte in 0..128u8 {
+        let c = byte as char;
+        let mut fast = case_variants(c);
+        for &variant in &fast {
+            // the same letters, from whichever of them
+            let mut back = case_variants(variant);
+            back.sort();
+            let mut all = fast.clone();
+            all.sort();
+            assert_eq!(all, back, "{:?} from {:?}", variant, c);
+        }
+        fast.retain(|v| v.is_ascii());
+        let mut slow = unicode_case_variants(c);
+        fast.sort();
+        slow.sort();
+        assert_eq!(slow, fast, "{:?}", c);
+    }
+}
+
+#[test]
+fn reverses() {
+    assert_eq!(hir("(c|de)*ba"), hir("ab(c|ed)*").reverse());
+}
+
+#[test]
+fn knows_when_empty_string_matches() {
+    assert!(hir("(a?)+").can_match_empty());
+    assert!(hir("a||b").can_match_empty());
+    assert!(!hir("a*b").can_match_empty());
+}
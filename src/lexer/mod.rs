@@ -0,0 +1,104 @@
+use ::error::Error;
+use ::pikevm::{self, PikeVM};
+use ::regex::RegexSet;
+use ::span::Span;
+
+#[cfg(test)] mod spec;
+
+/// Splits text into tokens, each matching one of a list of patterns. The
+/// patterns are compiled into one automaton, and each token is the longest
+/// match of any of them at the end of the one before. Among patterns matching
+/// the same longest text, the one given first wins, so keywords listed before
+/// a pattern for identifiers take precedence over it.
+#[derive(PartialEq,Debug,Clone)]
+pub struct Lexer {
+    set: RegexSet
+}
+
+impl Lexer {
+    pub fn new<I, S>(patterns: I) -> Result<Lexer, Error>
+        where I: IntoIterator<Item=S>, S: AsRef<str> {
+
+        Ok(Lexer {
+            set: RegexSet::new(patterns)?
+        })
+    }
+
+    pub fn patterns(&self) -> &[String] {
+        self.set.patterns()
+    }
+
+    pub fn scanner<'l, 't>(&'l self, text: &'t str) -> Scanner<'l, 't> {
+        Scanner {
+            vm: PikeVM::new(self.set.nfa()).with_longest(true),
+            cache: pikevm::Cache::new(self.set.nfa()),
+            text,
+            position: 0
+        }
+    }
+}
+
+// A token found by a scanner: the index of the pattern it matched, and the
+// text it matched.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub struct Token<'t> {
+    id: usize,
+    text: &'t str,
+    start: usize
+}
+
+impl<'t> Token<'t> {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn as_str(&self) -> &'t str {
+        self.text
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.start, self.start + self.text.len())
+    }
+}
+
+// Iterator over the tokens at the start of a text. It stops at the end of the
+// text, or where no pattern matches a non-empty token, leaving the rest of the
+// text for `rest` to tell.
+pub struct Scanner<'l, 't> {
+    vm: PikeVM<'l>,
+    cache: pikevm::Cache,
+    text: &'t str,
+    position: usize
+}
+
+impl<'l, 't> Scanner<'l, 't> {
+    // the text after the last token
+    pub fn rest(&self) -> &'t str {
+        &self.text[self.position..]
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl<'l, 't> Iterator for Scanner<'l, 't> {
+    type Item = Token<'t>;
+
+    fn next(&mut self) -> Option<Token<'t>> {
+        let slots = self.vm.captures_with(&mut self.cache, self.text.as_bytes(), self.position, true)?;
+        let end = slots[1].unwrap();
+        if end == self.position {
+            return None; // an empty token would never move on
+        }
+
+        let id = (1..slots.len() / 2).find(|&i| slots[2 * i].is_some()).unwrap() - 1;
+        let token = Token {
+            id,
+            text: &self.text[self.position..end],
+            start: self.position
+        };
+        self.position = end;
+        Some(token)
+    }
+}
@@ -0,0 +1,54 @@
+use ::span::Span;
+use super::Lexer;
+
+const IF: usize = 0;
+const IDENT: usize = 1;
+const SPACE: usize = 2;
+const ARROW: usize = 3;
+const MINUS: usize = 4;
+
+fn lexer() -> Lexer {
+    Lexer::new(["if", "[abcfikxyz]+", " +", "->", "-"]).unwrap()
+}
+
+fn tokens(lexer: &Lexer, text: &str) -> Vec<(usize, String)> {
+    lexer.scanner(text).map(|token| (token.id(), token.as_str().to_owned())).collect()
+}
+
+#[test]
+fn takes_longest_token() {
+    let lexer = lexer();
+
+    assert_eq!(vec![(IDENT, "ifx".to_owned()), (SPACE, " ".to_owned()), (MINUS, "-".to_owned()),
+                    (ARROW, "->".to_owned()), (IDENT, "y".to_owned())],
+               tokens(&lexer, "ifx -->y"));
+}
+
+#[test]
+fn breaks_ties_by_pattern_order() {
+    let lexer = lexer();
+
+    assert_eq!(vec![(IF, "if".to_owned()), (SPACE, "  ".to_owned()), (IDENT, "fi".to_owned())],
+               tokens(&lexer, "if  fi"));
+}
+
+#[test]
+fn stops_where_no_token_matches() {
+    let lexer = lexer();
+    let mut scanner = lexer.scanner("ab -?x");
+
+    assert_eq!(Some(Span::new(0, 2)), scanner.next().map(|token| token.span()));
+    assert_eq!(2, scanner.by_ref().count());
+    assert_eq!("?x", scanner.rest());
+    assert_eq!(4, scanner.position());
+}
+
+#[test]
+fn never_returns_empty_tokens() {
+    let lexer = Lexer::new(["a*", "b"]).unwrap();
+    let mut scanner = lexer.scanner("aabc");
+
+    assert_eq!(vec![(0, "aa".to_owned()), (1, "b".to_owned())], tokens(&lexer, "aabc"));
+    assert_eq!(2, scanner.by_ref().count());
+    assert_eq!("c", scanner.rest());
+}
@@ -1,14 +1,42 @@
-#![feature(unicode)]
-#![feature(test)]
+#![cfg_attr(test, feature(test))]
+
+extern crate memchr;
+#[cfg(feature = "serde")] extern crate serde;
+#[cfg(all(test, feature = "serde"))] extern crate serde_json;
+#[cfg(feature = "arbitrary")] extern crate arbitrary;
+#[cfg(feature = "parallel")] extern crate rayon;
 
 pub mod regex;
+pub mod stream;
+pub mod nfa;
+pub mod hir;
+pub mod dfa;
+pub mod pikevm;
+pub mod backtrack;
+pub mod onepass;
+pub mod meta;
+pub mod prefilter;
+pub mod lexer;
+pub mod symbol;
+pub mod glob;
+pub mod generate;
+pub mod cached;
+#[cfg(feature = "differential")] pub mod differential;
 
+mod error;
+mod span;
+mod encode;
+mod rng;
+mod smallvec;
 mod expr;
-mod nfa;
-mod matcher;
 
-pub use regex::Regex;
-pub use expr::Expr;
+pub use regex::{Regex, RegexBuilder, RegexSet, Cache, Match, Captures, CaptureLocations, Anchored, Input, Quit, Segment};
+pub use lexer::{Lexer, Scanner, Token};
+pub use glob::Glob;
+pub use expr::{Expr, Syntax, Visitor, visit, escape, diagnose, parse_partial, Partial, tokenize, PatternToken, TokenKind};
+pub use error::{Error, Diagnostic};
+pub use span::Span;
+pub use encode::DecodeError;
 
 #[cfg(test)] mod spec;
 
@@ -0,0 +1,264 @@
+use ::nfa::NFA;
+use ::dfa::{self, LazyDFA, DenseDFA};
+use ::pikevm::{self, PikeVM};
+use ::backtrack::{self, Backtracker};
+use ::onepass::OnePass;
+use ::prefilter::{Prefilter, LiteralPrefilter, InnerLiteral};
+
+#[cfg(test)] mod spec;
+
+// The engines a search can run on.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub enum Strategy {
+    DenseDFA,
+    LazyDFA,
+    Literals,
+    ReverseInner,
+    OnePass,
+    Backtrack,
+    PikeVM
+}
+
+// Picks the engine for each search. The DFAs are fastest, but only know where
+// a match ends, so searches that need groups go to the backtracker when the
+// text is short enough for it and to the Pike VM otherwise. A pattern that is
+// only an alternation of literals is searched for with the prefilter alone,
+// and one with a literal inside it is searched for from that literal.
+// Anchored searches for groups take the one-pass DFA when there is one.
+pub struct Meta<'r> {
+    nfa: &'r NFA,
+    literal_nfa: &'r NFA, // what the Pike VM runs, the NFA with literal conditions if given
+    dfa: Option<&'r DenseDFA>,
+    onepass: Option<&'r OnePass>,
+    prefilter: Option<&'r LiteralPrefilter>,
+    candidates: Option<&'r dyn Prefilter>, // where the engines skip ahead to, the literals unless given
+    inner: Option<&'r InnerLiteral>,
+    longest: bool,
+    earliest: bool,
+    anchored_start: bool
+}
+
+// What the engines keep between searches: the states the lazy DFA built and
+// the buffers of the others. Everything a search changes is in here, so the
+// compiled pattern itself can be shared between threads, each with a cache of
+// its own.
+pub struct Cache {
+    dfa: Option<dfa::Cache>, // taken by the lazy DFA while it searches
+    pikevm: pikevm::Cache,
+    backtrack: backtrack::Cache
+}
+
+impl Cache {
+    pub fn new(nfa: &NFA) -> Cache {
+        Cache {
+            dfa: Some(dfa::Cache::new(nfa)),
+            pikevm: pikevm::Cache::new(nfa),
+            backtrack: backtrack::Cache::new()
+        }
+    }
+}
+
+impl<'r> Meta<'r> {
+    pub fn new(nfa: &'r NFA, dfa: Option<&'r DenseDFA>, prefilter: Option<&'r LiteralPrefilter>) -> Meta<'r> {
+        Meta {
+            nfa,
+            literal_nfa: nfa,
+            dfa,
+            onepass: None,
+            prefilter,
+            candidates: prefilter.map(|prefilter| prefilter as &dyn Prefilter),
+            inner: None,
+            longest: false,
+            earliest: false,
+            anchored_start: false
+        }
+    }
+
+    // runs the Pike VM on `nfa`, made from the NFA by `NFA::with_literals`
+    pub fn with_literal_nfa(mut self, nfa: &'r NFA) -> Meta<'r> {
+        self.literal_nfa = nfa;
+        self
+    }
+
+    pub fn with_inner_literal(mut self, inner: Option<&'r InnerLiteral>) -> Meta<'r> {
+        self.inner = inner;
+        self
+    }
+
+    // skips ahead with `prefilter` instead of the literals, if there is one
+    pub fn with_prefilter(mut self, prefilter: Option<&'r dyn Prefilter>) -> Meta<'r> {
+        if prefilter.is_some() {
+            self.candidates = prefilter;
+        }
+        self
+    }
+
+    pub fn with_onepass(mut self, onepass: Option<&'r OnePass>) -> Meta<'r> {
+        self.onepass = onepass;
+        self
+    }
+
+    // looks for leftmost-longest matches instead of leftmost-first ones. A
+    // dense DFA has to have been built for the longest match too.
+    pub fn with_longest(mut self, longest: bool) -> Meta<'r> {
+        self.longest = longest;
+        self
+    }
+
+    // stops searches for groups as soon as a match is known. Only the Pike
+    // VM can do that, so it runs every such search.
+    pub fn with_earliest(mut self, earliest: bool) -> Meta<'r> {
+        self.earliest = earliest;
+        self
+    }
+
+    // only finds matches starting at the start of the text, so that a
+    // search from anywhere else fails at once without looking at it
+    pub fn with_anchored_start(mut self, anchored_start: bool) -> Meta<'r> {
+        self.anchored_start = anchored_start;
+        self
+    }
+
+    pub fn match_strategy(&self) -> Strategy {
+        match self.dfa {
+            Some(_) => Strategy::DenseDFA,
+            None => Strategy::LazyDFA
+        }
+    }
+
+    pub fn captures_strategy(&self, len: usize) -> Strategy {
+        let literals = self.prefilter.is_some_and(|prefilter| prefilter.is_exact());
+
+        // the prefilter prefers the literal given first, not the longest
+        if self.earliest {
+            Strategy::PikeVM
+        } else if literals && self.nfa.num_groups() == 1 && !self.longest {
+            Strategy::Literals
+        } else if self.prefilter.is_none() && self.inner.is_some() {
+            Strategy::ReverseInner
+        } else {
+            self.engine(len)
+        }
+    }
+
+    // the engine that finds groups
+    fn engine(&self, len: usize) -> Strategy {
+        if Backtracker::new(self.nfa).fits(len) {
+            Strategy::Backtrack
+        } else {
+            Strategy::PikeVM
+        }
+    }
+
+    // the engine that finds groups in a match starting at a given offset
+    fn anchored_engine(&self, len: usize) -> Strategy {
+        match self.onepass {
+            Some(_) if !self.longest => Strategy::OnePass,
+            _ => self.engine(len)
+        }
+    }
+
+    // whether a match starts at the beginning of `text`
+    pub fn is_match(&self, text: &[u8]) -> bool {
+        self.is_match_with(&mut Cache::new(self.nfa), text)
+    }
+
+    pub fn is_match_with(&self, cache: &mut Cache, text: &[u8]) -> bool {
+        match (self.match_strategy(), self.dfa) {
+            (Strategy::DenseDFA, Some(dfa)) => dfa.is_match(text),
+            _ => self.with_lazy_dfa(cache, |dfa| dfa.is_match(text))
+        }
+    }
+
+    // the end of the shortest match starting at the beginning of `text`
+    pub fn earliest_end(&self, text: &[u8]) -> Option<usize> {
+        self.earliest_end_with(&mut Cache::new(self.nfa), text)
+    }
+
+    pub fn earliest_end_with(&self, cache: &mut Cache, text: &[u8]) -> Option<usize> {
+        match (self.match_strategy(), self.dfa) {
+            (Strategy::DenseDFA, Some(dfa)) => dfa.earliest_end(text),
+            _ => self.with_lazy_dfa(cache, |dfa| dfa.earliest_end(text))
+        }
+    }
+
+    // the end of the leftmost-first (or longest) match starting at the beginning of `text`
+    pub fn match_end(&self, text: &[u8]) -> Option<usize> {
+        self.match_end_with(&mut Cache::new(self.nfa), text)
+    }
+
+    pub fn match_end_with(&self, cache: &mut Cache, text: &[u8]) -> Option<usize> {
+        match (self.match_strategy(), self.dfa) {
+            (Strategy::DenseDFA, Some(dfa)) => dfa.match_end(text),
+            _ => self.with_lazy_dfa(cache, |dfa| dfa.match_end(text))
+        }
+    }
+
+    // runs `search` on a lazy DFA that starts from the states in the cache,
+    // and keeps the states it builds there
+    fn with_lazy_dfa<T, F: FnOnce(&mut LazyDFA) -> T>(&self, cache: &mut Cache, search: F) -> T {
+        let mut dfa = match cache.dfa.take() {
+            Some(states) => LazyDFA::with_cache(self.nfa, states),
+            None => LazyDFA::new(self.nfa)
+        };
+        if self.longest {
+            dfa = dfa.longest();
+        }
+        let found = search(&mut dfa);
+        cache.dfa = Some(dfa.into_cache());
+        found
+    }
+
+    pub fn captures(&self, text: &[u8], start: usize, anchored: bool) -> Option<Vec<Option<usize>>> {
+        self.captures_with(&mut Cache::new(self.nfa), text, start, anchored)
+    }
+
+    pub fn captures_with(&self, cache: &mut Cache, text: &[u8], start: usize, anchored: bool) -> Option<Vec<Option<usize>>> {
+        if self.anchored_start && start > 0 {
+            return None;
+        }
+        let anchored = anchored || self.anchored_start;
+
+        match self.captures_strategy(text.len()) {
+            Strategy::Literals => {
+                self.prefilter.and_then(|prefilter| prefilter.find_literal(text, start))
+                    .filter(|m| !anchored || m.start() == start)
+                    .map(|m| vec![Some(m.start()), Some(m.end())])
+            },
+            Strategy::ReverseInner => self.captures_reverse_inner(cache, text, start, anchored),
+            Strategy::PikeVM if self.earliest => self.run(cache, Strategy::PikeVM, text, start, anchored),
+            _ if anchored => self.run(cache, self.anchored_engine(text.len()), text, start, anchored),
+            strategy => self.run(cache, strategy, text, start, anchored)
+        }
+    }
+
+    // No match starts between `start` and the first place the literal's
+    // leading part matches backwards from it, so the engine searches once
+    // from there. Trying each such place anchored instead would search the
+    // rest of the text again for every occurrence of the literal.
+    fn captures_reverse_inner(&self, cache: &mut Cache, text: &[u8], start: usize, anchored: bool) -> Option<Vec<Option<usize>>> {
+        let (candidate, _) = self.inner.unwrap().find_start(text, start)?;
+        if anchored && candidate != start {
+            None
+        } else if anchored {
+            self.run(cache, self.anchored_engine(text.len()), text, start, true)
+        } else {
+            self.run(cache, self.engine(text.len()), text, candidate, false)
+        }
+    }
+
+    fn run(&self, cache: &mut Cache, engine: Strategy, text: &[u8], start: usize, anchored: bool) -> Option<Vec<Option<usize>>> {
+        match (engine, self.onepass) {
+            (Strategy::OnePass, Some(onepass)) if anchored => onepass.captures(text, start),
+            (Strategy::Backtrack, _) => Backtracker::new(self.nfa)
+                .with_prefilter(self.candidates)
+                .with_longest(self.longest)
+                .captures_with(&mut cache.backtrack, text, start, anchored),
+            _ => PikeVM::new(self.literal_nfa)
+                .with_prefilter(self.candidates)
+                .with_longest(self.longest)
+                .with_earliest(self.earliest)
+                .captures_with(&mut cache.pikevm, text, start, anchored)
+        }
+    }
+}
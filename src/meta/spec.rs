@@ -0,0 +1,118 @@
+use ::expr::Expr;
+use ::hir::Hir;
+use ::nfa::NFA;
+use ::dfa::DenseDFA;
+use ::prefilter::{LiteralPrefilter, InnerLiteral};
+use ::onepass::OnePass;
+use super::{Meta, Strategy, Cache};
+
+fn nfa(pattern: &str) -> NFA {
+    NFA::from_expr(&pattern.parse::<Expr>().unwrap())
+}
+
+#[test]
+fn uses_dense_dfa_when_compiled() {
+    let nfa = nfa("a(b|c)+");
+    let dfa = DenseDFA::build(&nfa, 1 << 20).unwrap();
+
+    assert_eq!(Strategy::LazyDFA, Meta::new(&nfa, None, None).match_strategy());
+    assert_eq!(Strategy::DenseDFA, Meta::new(&nfa, Some(&dfa), None).match_strategy());
+    assert_eq!(Some(3), Meta::new(&nfa, Some(&dfa), None).match_end(b"abcd"));
+    assert_eq!(Some(3), Meta::new(&nfa, None, None).match_end(b"abcd"));
+}
+
+#[test]
+fn backtracks_only_over_short_texts() {
+    let nfa = nfa("a(b|c)+");
+    let meta = Meta::new(&nfa, None, None);
+
+    assert_eq!(Strategy::Backtrack, meta.captures_strategy(100));
+    assert_eq!(Strategy::PikeVM, meta.captures_strategy(10_000_000));
+}
+
+#[test]
+fn finds_captures_with_either_engine() {
+    let nfa = nfa("a(b|c)+");
+    let meta = Meta::new(&nfa, None, None);
+    let expected = Some(vec![Some(1), Some(4), Some(3), Some(4)]);
+
+    assert_eq!(expected, meta.captures(b"xabc", 0, false));
+
+    let long = "xabc".to_owned() + &"x".repeat(1_000_000);
+    assert_eq!(Strategy::PikeVM, meta.captures_strategy(long.len()));
+    assert_eq!(expected, meta.captures(long.as_bytes(), 0, false));
+}
+
+#[test]
+fn searches_literal_alternations_with_prefilter_alone() {
+    let nfa = nfa("foo|bar|foobar");
+    let prefilter = LiteralPrefilter::new(&nfa);
+    let meta = Meta::new(&nfa, None, prefilter.as_ref());
+
+    assert_eq!(Strategy::Literals, meta.captures_strategy(100));
+    assert_eq!(Some(vec![Some(2), Some(5)]), meta.captures(b"xxfoobar", 0, false));
+    assert_eq!(Some(vec![Some(3), Some(6)]), meta.captures(b"xxfbar", 0, false));
+    assert_eq!(None, meta.captures(b"xxfbar", 2, true));
+}
+
+#[test]
+fn needs_an_engine_for_groups() {
+    let nfa = nfa("(foo)|bar");
+    let prefilter = LiteralPrefilter::new(&nfa);
+
+    assert_eq!(Strategy::Backtrack, Meta::new(&nfa, None, prefilter.as_ref()).captures_strategy(100));
+}
+
+#[test]
+fn searches_from_inner_literal() {
+    let expr = "[abc]+@(x|yz)".parse::<Expr>().unwrap();
+    let nfa = NFA::from_expr(&expr);
+    let inner = InnerLiteral::new(&Hir::from_expr(&expr));
+    let meta = Meta::new(&nfa, None, None).with_inner_literal(inner.as_ref());
+
+    assert_eq!(Strategy::ReverseInner, meta.captures_strategy(100));
+    assert_eq!(Some(vec![Some(2), Some(6), Some(5), Some(6)]), meta.captures(b"x-ab@x", 0, false));
+    assert_eq!(Some(vec![Some(6), Some(11), Some(9), Some(11)]), meta.captures(b"a@ b@wcc@yz", 0, false));
+    assert_eq!(None, meta.captures(b"ab@w", 0, false));
+    assert_eq!(None, meta.captures(b"-ab@x", 0, true));
+    assert_eq!(Some(vec![Some(1), Some(5), Some(4), Some(5)]), meta.captures(b"-ab@x", 1, true));
+}
+
+#[test]
+fn searches_with_cache() {
+    let nfa = nfa("a(b|c)+");
+    let meta = Meta::new(&nfa, None, None);
+    let mut cache = Cache::new(&nfa);
+
+    assert_eq!(Some(3), meta.match_end_with(&mut cache, b"abcd"));
+    assert!(meta.is_match_with(&mut cache, b"ac"));
+    assert_eq!(Some(vec![Some(1), Some(3), Some(2), Some(3)]), meta.captures_with(&mut cache, b"xab", 0, false));
+    assert_eq!(None, meta.captures_with(&mut cache, b"xab", 0, true));
+}
+
+#[test]
+fn finds_anchored_captures_with_one_pass_dfa() {
+    let nfa = nfa("a(b|c)+");
+    let onepass = OnePass::new(&nfa).unwrap();
+    let meta = Meta::new(&nfa, None, None).with_onepass(Some(&onepass));
+
+    assert_eq!(Some(vec![Some(1), Some(4), Some(3), Some(4)]), meta.captures(b"xabc", 1, true));
+    assert_eq!(None, meta.captures(b"xabc", 0, true));
+    assert_eq!(Some(vec![Some(1), Some(4), Some(3), Some(4)]), meta.captures(b"xabc", 0, false));
+}
+
+#[test]
+fn searches_from_inner_literal_in_linear_time() {
+    use std::time::{Duration, Instant};
+
+    let expr = "[abcdefghijklmnopqrstuvwxyz]+@.*c".parse::<Expr>().unwrap();
+    let nfa = NFA::from_expr(&expr);
+    let inner = InnerLiteral::new(&Hir::from_expr(&expr));
+    let meta = Meta::new(&nfa, None, None).with_inner_literal(inner.as_ref());
+    let text = "a@".repeat(20_000); // took half a minute trying each @ anchored
+
+    let started = Instant::now();
+    assert_eq!(Strategy::ReverseInner, meta.captures_strategy(text.len()));
+    assert_eq!(None, meta.captures(text.as_bytes(), 0, false));
+    assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+}
@@ -0,0 +1,337 @@
+use std::collections::VecDeque;
+
+use super::{NFA, State, Transition, Condition};
+
+// The edges of the NFA as a graph, with the end as one more node after the
+// states, weighted by how many bytes following them consumes. A condition
+// no byte meets is no edge at all.
+pub fn edges(nfa: &NFA) -> Vec<Vec<(usize, usize)>> {
+    let end = nfa.states.len();
+    let node = |edge: Transition| match edge {
+        Transition::Id(id) => id.index(),
+        _ => end
+    };
+
+    let mut edges = nfa.states.iter().map(|state| match *state {
+        State::State{condition: Condition::None, out} => vec![(node(out), 0)],
+        State::State{condition: Condition::Class(ref bytes), ..} if bytes.is_empty() => Vec::new(),
+        State::State{condition: Condition::Literal(literal), out} => vec![(node(out), literal.len())],
+        State::State{out, ..} => vec![(node(out), 1)],
+        State::Split{out1, out2} => vec![(node(out1), 0), (node(out2), 0)],
+        State::Save{out, ..} => vec![(node(out), 0)]
+    }).collect::<Vec<_>>();
+    edges.push(Vec::new());
+    edges
+}
+
+// the nodes reachable from `from` along `edges`
+pub fn reachable(edges: &[Vec<(usize, usize)>], from: usize) -> Vec<bool> {
+    let mut seen = vec![false; edges.len()];
+    let mut pending = vec![from];
+    seen[from] = true;
+    while let Some(node) = pending.pop() {
+        for &(next, _) in &edges[node] {
+            if !seen[next] {
+                seen[next] = true;
+                pending.push(next);
+            }
+        }
+    }
+    seen
+}
+
+pub fn reversed(edges: &[Vec<(usize, usize)>]) -> Vec<Vec<(usize, usize)>> {
+    let mut reversed = vec![Vec::new(); edges.len()];
+    for (node, out) in edges.iter().enumerate() {
+        for &(next, weight) in out {
+            reversed[next].push((node, weight));
+        }
+    }
+    reversed
+}
+
+// The fewest bytes a match can take, found by a breadth first search that
+// visits nodes reached without consuming anything before the others.
+pub fn min_len(nfa: &NFA) -> Option<usize> {
+    if nfa.states.is_empty() {
+        return Some(0);
+    }
+    let edges = edges(nfa);
+    let end = nfa.states.len();
+
+    let mut lengths = vec![usize::MAX; edges.len()];
+    let mut pending = VecDeque::new();
+    lengths[nfa.start] = 0;
+    pending.push_back(nfa.start);
+
+    while let Some(node) = pending.pop_front() {
+        for &(next, weight) in &edges[node] {
+            if lengths[node] + weight < lengths[next] {
+                lengths[next] = lengths[node] + weight;
+                if weight == 0 {
+                    pending.push_front(next);
+                } else {
+                    pending.push_back(next);
+                }
+            }
+        }
+    }
+
+    match lengths[end] {
+        usize::MAX => None,
+        length => Some(length)
+    }
+}
+
+// The most bytes a match can take, `None` if there is no match or no most.
+// Only the nodes on some path from the start to the end count. Within each
+// of their strongly connected components every node reaches every other,
+// so a component with an edge consuming a byte repeats it without bound.
+// Otherwise the components form a DAG to find the longest path through.
+pub fn max_len(nfa: &NFA) -> Option<usize> {
+    if nfa.states.is_empty() {
+        return Some(0);
+    }
+    let edges = edges(nfa);
+    let reversed = reversed(&edges);
+    let end = nfa.states.len();
+
+    let from_start = reachable(&edges, nfa.start);
+    let to_end = reachable(&reversed, end);
+    if !from_start[end] {
+        return None;
+    }
+    let useful = (0..edges.len()).map(|node| from_start[node] && to_end[node]).collect::<Vec<_>>();
+
+    let components = components(&edges, &reversed, &useful);
+    let mut lengths = vec![0; edges.len()]; // by component
+    for &node in &components.order {
+        let component = components.of[node];
+        for &(next, weight) in &edges[node] {
+            if !useful[next] {
+                continue;
+            }
+            if components.of[next] == component {
+                if weight > 0 {
+                    return None;
+                }
+                continue;
+            }
+            let length = lengths[component] + weight;
+            if length > lengths[components.of[next]] {
+                lengths[components.of[next]] = length;
+            }
+        }
+    }
+
+    Some(lengths[components.of[end]])
+}
+
+// The strongly connected components of the useful nodes, numbered so that
+// edges between components always lead to a higher one, and the nodes in
+// order of their components.
+struct Components {
+    of: Vec<usize>,
+    order: Vec<usize>
+}
+
+// Kosaraju's algorithm: nodes are ordered by when a depth first search
+// finishes with them, and each component is whatever the reversed edges
+// reach from the last finished node not yet in a component.
+fn components(edges: &[Vec<(usize, usize)>], reversed: &[Vec<(usize, usize)>], useful: &[bool]) -> Components {
+    let mut finished = Vec::new();
+    let mut seen = vec![false; edges.len()];
+    for root in 0..edges.len() {
+        if !useful[root] || seen[root] {
+            continue;
+        }
+        seen[root] = true;
+        let mut stack = vec![(root, 0)]; // nodes and how many edges are done
+        while let Some(&mut (node, ref mut i)) = stack.last_mut() {
+            match edges[node].get(*i) {
+                Some(&(next, _)) => {
+                    *i += 1;
+                    if useful[next] && !seen[next] {
+                        seen[next] = true;
+                        stack.push((next, 0));
+                    }
+                },
+                None => {
+                    finished.push(node);
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    let mut of = vec![usize::MAX; edges.len()];
+    let mut order = Vec::new();
+    let mut component = 0;
+    for &root in finished.iter().rev() {
+        if of[root] != usize::MAX {
+            continue;
+        }
+        of[root] = component;
+        let mut pending = vec![root];
+        while let Some(node) = pending.pop() {
+            order.push(node);
+            for &(previous, _) in &reversed[node] {
+                if useful[previous] && of[previous] == usize::MAX {
+                    of[previous] = component;
+                    pending.push(previous);
+                }
+            }
+        }
+        component += 1;
+    }
+
+    Components { of, order }
+}
+
+// By group, whether some match sets it: the whole match if there is any,
+// and each other group if a path from the start to the end passes the
+// state saving where it starts.
+pub fn participating_groups(nfa: &NFA) -> Vec<bool> {
+    let mut groups = vec![false; nfa.num_groups()];
+    if nfa.states.is_empty() {
+        groups[0] = true;
+        return groups;
+    }
+    let edges = edges(nfa);
+    let end = nfa.states.len();
+    let from_start = reachable(&edges, nfa.start);
+    let to_end = reachable(&reversed(&edges), end);
+
+    groups[0] = from_start[end];
+    for (id, state) in nfa.states.iter().enumerate() {
+        if let &State::Save{slot, ..} = state {
+            if slot % 2 == 0 && from_start[id] && to_end[id] {
+                groups[slot / 2] = true;
+            }
+        }
+    }
+    groups
+}
+
+// Whether some loop of the NFA holds another. A depth first search from the
+// start finds the edges going back to a node still being searched, each of
+// which closes a loop through the nodes reaching it without passing the node
+// it goes back to. A loop holds another when one of those nodes closes one
+// too, as the state repeating `a+` in `(a+)+` does.
+pub fn has_nested_loops(nfa: &NFA) -> bool {
+    if nfa.states.is_empty() {
+        return false;
+    }
+    let edges = edges(nfa);
+    let reversed = reversed(&edges);
+
+    let mut back_edges = Vec::new();
+    let mut seen = vec![false; edges.len()];
+    let mut on_stack = vec![false; edges.len()];
+    let mut stack = vec![(nfa.start, 0)]; // nodes and how many edges are done
+    seen[nfa.start] = true;
+    on_stack[nfa.start] = true;
+    while let Some(&mut (node, ref mut i)) = stack.last_mut() {
+        match edges[node].get(*i) {
+            Some(&(next, _)) => {
+                *i += 1;
+                if on_stack[next] {
+                    back_edges.push((node, next));
+                } else if !seen[next] {
+                    seen[next] = true;
+                    on_stack[next] = true;
+                    stack.push((next, 0));
+                }
+            },
+            None => {
+                on_stack[node] = false;
+                stack.pop();
+            }
+        }
+    }
+
+    let mut closes = vec![false; edges.len()];
+    for &(from, _) in &back_edges {
+        closes[from] = true;
+    }
+    let mut in_loop = vec![usize::MAX; edges.len()]; // the last back edge whose loop has the node
+    for (i, &(from, to)) in back_edges.iter().enumerate() {
+        in_loop[to] = i;
+        in_loop[from] = i;
+        let mut pending = vec![from];
+        while let Some(node) = pending.pop() {
+            if node != from && closes[node] {
+                return true;
+            }
+            for &(previous, _) in &reversed[node] {
+                if in_loop[previous] != i {
+                    in_loop[previous] = i;
+                    pending.push(previous);
+                }
+            }
+        }
+    }
+    false
+}
+
+// The strings the NFA matches, in order of preference, if it matches no more
+// than `limit` of them and every path to the end takes bytes it names only.
+// Paths are followed from the start one at a time, the preferred first, and
+// split at each class, as its bytes all match as long a string; a path
+// longer than there are states has gone round a loop.
+pub fn literals(nfa: &NFA, limit: usize) -> Option<Vec<Vec<u8>>> {
+    if nfa.states.is_empty() {
+        return Some(vec![Vec::new()]);
+    }
+
+    let mut literals: Vec<Vec<u8>> = Vec::new();
+    let mut paths = vec![(Transition::id(nfa.start), Vec::new())];
+    let mut num_paths = 0;
+    while let Some((mut edge, mut literal)) = paths.pop() {
+        num_paths += 1;
+        if num_paths > 4 * limit {
+            return None; // paths can lead to the same literals
+        }
+
+        let mut steps = 0;
+        while edge != Transition::End {
+            steps += 1;
+            let id = match edge {
+                Transition::Id(id) if steps <= nfa.states.len() => id.index(),
+                _ => return None
+            };
+            edge = match nfa.states[id] {
+                State::State{condition: Condition::One(byte), out} => {
+                    literal.push(byte);
+                    out
+                },
+                State::State{condition: Condition::None, out} | State::Save{out, ..} => out,
+                State::State{condition: Condition::Class(bytes), out} if bytes.len() <= limit => {
+                    let bytes = bytes.iter().collect::<Vec<u8>>();
+                    let (&first, rest) = bytes.split_first()?;
+                    for &byte in rest.iter().rev() {
+                        let mut longer = literal.clone();
+                        longer.push(byte);
+                        paths.push((out, longer));
+                    }
+                    literal.push(first);
+                    out
+                },
+                State::Split{out1, out2} => {
+                    paths.push((out2, literal.clone()));
+                    out1
+                },
+                State::State{..} => return None
+            };
+        }
+
+        // a literal found again is never preferred to the first
+        if !literals.contains(&literal) {
+            literals.push(literal);
+        }
+        if literals.len() > limit {
+            return None;
+        }
+    }
+    Some(literals)
+}
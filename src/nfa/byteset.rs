@@ -0,0 +1,79 @@
+use std::iter::FromIterator;
+
+// A set of bytes as a 256 bit bitmap, so that membership is a single lookup
+// and two sets with the same members are equal whatever order they were
+// added in.
+#[derive(PartialEq,Debug,Clone,Copy,Eq,Hash,Default)]
+pub struct ByteSet {
+    bits: [u64; 4]
+}
+
+impl ByteSet {
+    pub fn new() -> ByteSet {
+        ByteSet { bits: [0; 4] }
+    }
+
+    // the bytes from `first` to `last`, both included
+    pub fn range(first: u8, last: u8) -> ByteSet {
+        (first..=last).collect()
+    }
+
+    pub fn insert(&mut self, byte: u8) {
+        self.bits[byte as usize / 64] |= 1 << (byte % 64);
+    }
+
+    pub fn contains(&self, byte: u8) -> bool {
+        self.bits[byte as usize / 64] & (1 << (byte % 64)) != 0
+    }
+
+    pub fn union(&self, other: &ByteSet) -> ByteSet {
+        let mut bits = self.bits;
+        for (word, &other) in bits.iter_mut().zip(&other.bits) {
+            *word |= other;
+        }
+        ByteSet { bits }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == [0; 4]
+    }
+
+    // the members, in increasing order
+    pub fn iter(&self) -> ByteSetIter {
+        ByteSetIter { set: *self, next: 0 }
+    }
+}
+
+impl FromIterator<u8> for ByteSet {
+    fn from_iter<I: IntoIterator<Item=u8>>(bytes: I) -> ByteSet {
+        let mut set = ByteSet::new();
+        for byte in bytes {
+            set.insert(byte);
+        }
+        set
+    }
+}
+
+pub struct ByteSetIter {
+    set: ByteSet,
+    next: usize // the next byte to look at, 256 once done
+}
+
+impl Iterator for ByteSetIter {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        while self.next < 256 {
+            let byte = self.next as u8;
+            self.next += 1;
+            if self.set.contains(byte) {
+                return Some(byte);
+            }
+        }
+        None
+    }
+}
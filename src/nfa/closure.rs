@@ -0,0 +1,77 @@
+use super::{State, Transition, Condition};
+
+// The states reached from a state without consuming input that do consume
+// it, in order of priority, and whether and where the end of the NFA is
+// reached among them.
+#[derive(PartialEq,Debug,Clone)]
+pub struct Closure {
+    states: Vec<usize>,
+    end: Option<usize> // how many states are preferred over reaching the end
+}
+
+impl Closure {
+    pub fn states(&self) -> &[usize] {
+        &self.states
+    }
+
+    pub fn matches(&self) -> bool {
+        self.end.is_some()
+    }
+
+    // the states preferred over reaching the end; all of them if it is not
+    // reached
+    pub fn before_end(&self) -> &[usize] {
+        &self.states[..self.end.unwrap_or(self.states.len())]
+    }
+}
+
+// The closure of every state, or none at all if the NFA is incomplete or
+// more than `limit` states would be visited to find them. Nested optional
+// items make closures overlap, so they can grow quadratically, and so can
+// the work to find small ones, when many alternatives lead to the same
+// states.
+pub fn closures(states: &[State], limit: usize) -> Vec<Closure> {
+    let mut closures = Vec::with_capacity(states.len());
+    let mut visited = vec![usize::MAX; states.len()]; // the closure each state was last seen in
+    let mut size = 0;
+
+    for id in 0..states.len() {
+        let mut closure = Closure { states: Vec::new(), end: None };
+        let mut edges = vec![Transition::id(id)];
+
+        while let Some(edge) = edges.pop() {
+            let next = match edge {
+                Transition::Id(next) if next.index() < states.len() => next.index(),
+                Transition::End => {
+                    if closure.end.is_none() {
+                        closure.end = Some(closure.states.len());
+                    }
+                    continue;
+                },
+                _ => return Vec::new() // left for the matchers to report
+            };
+
+            if visited[next] == id {
+                continue;
+            }
+            visited[next] = id;
+            size += 1;
+            if size > limit {
+                return Vec::new();
+            }
+
+            match states[next] {
+                State::State{condition: Condition::None, out} | State::Save{out, ..} => edges.push(out),
+                State::State{..} => closure.states.push(next),
+                State::Split{out1, out2} => {
+                    edges.push(out2);
+                    edges.push(out1); // out1 is preferred
+                }
+            }
+        }
+
+        closures.push(closure);
+    }
+
+    closures
+}
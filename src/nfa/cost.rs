@@ -0,0 +1,34 @@
+// A rough measure of how much searching with an NFA costs, for turning away
+// patterns that are likely to be slow before running them. Every engine here
+// takes time linear in the text, but the factor grows with the states, and a
+// loop inside another, as in `(a+)+`, lets many threads take the same bytes.
+#[derive(PartialEq,Debug,Clone,Copy,Eq,Hash)]
+pub struct Cost {
+    states: usize,
+    nested_loops: bool,
+    largest_class: usize
+}
+
+impl Cost {
+    pub fn new(states: usize, nested_loops: bool, largest_class: usize) -> Cost {
+        Cost {
+            states,
+            nested_loops,
+            largest_class
+        }
+    }
+
+    pub fn states(&self) -> usize {
+        self.states
+    }
+
+    // whether some unbounded repetition repeats another
+    pub fn has_nested_repetition(&self) -> bool {
+        self.nested_loops
+    }
+
+    // the most bytes any one state takes
+    pub fn largest_class(&self) -> usize {
+        self.largest_class
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use super::{State, Transition, Condition};
+
+// The states with every state consuming a byte merged into the first one
+// alike, and where the start went. Two are alike when they take the same
+// bytes to states that are alike, so that threads in either go on the same
+// way. Repeated items, like the `bc` in `(abc|dbc)`, each build their own
+// states, which are merged here in one pass, each state after the one it
+// leads to. States that consume nothing are kept apart: matchers tell the
+// paths through them apart by state, to stop an item that matched empty
+// from repeating.
+pub fn dedup(states: &[State], start: usize) -> (Vec<State>, usize) {
+    let consumes = |id: usize| match &states[id] {
+        State::State{condition, ..} => *condition != Condition::None,
+        _ => false
+    };
+
+    // a state alike to each, found from the end of each run of consuming
+    // states backwards; such a run never loops, as loops go through a split
+    let mut alike = (0..states.len()).collect::<Vec<usize>>();
+    let mut done = vec![false; states.len()];
+    let mut firsts = HashMap::new();
+    let mut run = Vec::new();
+    for id in 0..states.len() {
+        let mut next = Some(id);
+        while let Some(id) = next.filter(|&id| consumes(id) && !done[id]) {
+            run.push(id);
+            next = match &states[id] {
+                State::State{out: Transition::Id(out), ..} => Some(out.index()),
+                _ => None
+            };
+        }
+        while let Some(id) = run.pop() {
+            alike[id] = *firsts.entry(rename(&states[id], &alike)).or_insert(id);
+            done[id] = true;
+        }
+    }
+
+    // the first state alike, by state
+    let mut first = vec![usize::MAX; states.len()];
+    for id in 0..states.len() {
+        if first[alike[id]] > id {
+            first[alike[id]] = id;
+        }
+    }
+    let same = alike.iter().map(|&id| first[id]).collect::<Vec<usize>>();
+
+    // the states left keep their order
+    let mut ids = vec![0; states.len()];
+    let mut kept = Vec::new();
+    for (id, state) in states.iter().enumerate() {
+        if same[id] == id {
+            ids[id] = kept.len();
+            kept.push(*state);
+        }
+    }
+    let new_ids = same.iter().map(|&first| ids[first]).collect::<Vec<usize>>();
+    let kept = kept.iter().map(|state| rename(state, &new_ids)).collect();
+    (kept, new_ids[start])
+}
+
+// `state` with the states it leads to renamed
+fn rename(state: &State, ids: &[usize]) -> State {
+    let edge = |edge: Transition| match edge {
+        Transition::Id(id) => Transition::id(ids[id.index()]),
+        edge => edge
+    };
+    match *state {
+        State::State{condition, out} => State::state(condition, edge(out)),
+        State::Split{out1, out2} => State::split(edge(out1), edge(out2)),
+        State::Save{slot, out} => State::save(slot, edge(out))
+    }
+}
@@ -0,0 +1,240 @@
+use std::mem;
+
+use ::dfa::ByteClasses;
+use super::{NFA, State, Transition, Condition, ByteSet, Closure};
+
+#[derive(PartialEq,Debug,Clone,Copy)]
+enum Kind {
+    Consume,
+    Empty, // one edge taken without consuming anything
+    Split,
+    Save(usize)
+}
+
+// An NFA laid out for running rather than building: each state's edges sit
+// side by side in one array, two to a state, and the bytes a consuming state
+// takes are looked up by byte class in a table, instead of matching on its
+// condition. A literal condition becomes a chain of states taking a byte
+// each, the first in the literal's place and the rest after all the others.
+// The NFA's closures are kept, so following empty edges is a lookup too.
+// Streams run on it. It cannot be changed; convert it back with `to_sparse`
+// for that.
+#[derive(PartialEq,Debug,Clone)]
+pub struct DenseNFA {
+    start: Transition,
+    classes: ByteClasses,
+    kinds: Vec<Kind>,
+    outs: Vec<Transition>, // by state, the edge or the two edges of a split in order of priority
+    accepts: Vec<bool>, // by state and byte class, whether a consuming state takes the byte
+    closures: Vec<Closure> // by state of the NFA, or empty if it has none
+}
+
+impl DenseNFA {
+    pub fn new(nfa: &NFA) -> DenseNFA {
+        let classes = ByteClasses::new(nfa);
+        let representatives = classes.representatives();
+        let mut kinds = Vec::with_capacity(nfa.num_states());
+        let mut outs = Vec::with_capacity(2 * nfa.num_states());
+        let mut accepts = vec![false; nfa.num_states() * classes.len()];
+        let mut rest = Vec::new(); // the bytes of literals after their first, and where each goes
+
+        for (id, state) in nfa.states() {
+            let (kind, out1, out2) = match *state {
+                State::State{condition: Condition::None, out} => (Kind::Empty, out, Transition::Detached),
+                State::State{condition: Condition::Literal(literal), out} => {
+                    let bytes = literal.as_bytes();
+                    for (class, &byte) in representatives.iter().enumerate() {
+                        accepts[id * classes.len() + class] = byte == bytes[0];
+                    }
+                    let mut next = out;
+                    for &byte in bytes[1..].iter().rev() {
+                        rest.push((byte, next));
+                        next = Transition::id(nfa.num_states() + rest.len() - 1);
+                    }
+                    (Kind::Consume, next, Transition::Detached)
+                },
+                State::State{ref condition, out} => {
+                    for (class, &byte) in representatives.iter().enumerate() {
+                        accepts[id * classes.len() + class] = condition.accepts(byte);
+                    }
+                    (Kind::Consume, out, Transition::Detached)
+                },
+                State::Split{out1, out2} => (Kind::Split, out1, out2),
+                State::Save{slot, out} => (Kind::Save(slot), out, Transition::Detached)
+            };
+            kinds.push(kind);
+            outs.push(out1);
+            outs.push(out2);
+        }
+
+        for &(byte, out) in &rest {
+            kinds.push(Kind::Consume);
+            outs.push(out);
+            outs.push(Transition::Detached);
+            accepts.extend(representatives.iter().map(|&representative| representative == byte));
+        }
+
+        DenseNFA {
+            start: match nfa.num_states() {
+                0 => Transition::End,
+                _ => Transition::id(nfa.get_start_id())
+            },
+            classes,
+            kinds,
+            outs,
+            accepts,
+            closures: nfa.closures.clone()
+        }
+    }
+
+    pub fn num_states(&self) -> usize {
+        self.kinds.len()
+    }
+
+    // where a search starts, the end if there are no states
+    pub fn start(&self) -> Transition {
+        self.start
+    }
+
+    // the heap memory used by the tables and closures
+    pub fn memory_usage(&self) -> usize {
+        self.kinds.len() * mem::size_of::<Kind>() +
+        self.outs.len() * mem::size_of::<Transition>() +
+        self.accepts.len() +
+        self.closures.iter()
+            .map(|closure| mem::size_of::<Closure>() + mem::size_of_val(closure.states()))
+            .sum::<usize>()
+    }
+
+    // The NFA to build on, with the same states and edges. A consuming
+    // state's condition becomes the bytes it takes.
+    pub fn to_sparse(&self) -> NFA {
+        let mut states = Vec::with_capacity(self.num_states());
+        for (id, &kind) in self.kinds.iter().enumerate() {
+            let (out1, out2) = (self.outs[2 * id], self.outs[2 * id + 1]);
+            states.push(match kind {
+                Kind::Consume => {
+                    let bytes = (0..=255u8).filter(|&byte| self.takes(id, byte)).collect::<ByteSet>();
+                    let condition = match bytes.len() {
+                        1 => Condition::One(bytes.iter().next().unwrap()),
+                        _ => Condition::Class(bytes)
+                    };
+                    State::state(condition, out1)
+                },
+                Kind::Empty => State::state(Condition::None, out1),
+                Kind::Split => State::split(out1, out2),
+                Kind::Save(slot) => State::save(slot, out1)
+            });
+        }
+
+        let mut nfa = NFA::from_states(states);
+        if let Transition::Id(start) = self.start {
+            nfa.start = start.index();
+        }
+        nfa
+    }
+
+    // the end of the leftmost-first match starting at the beginning of `text`
+    pub fn match_end(&self, text: &[u8]) -> Option<usize> {
+        let mut seen = vec![false; self.num_states()];
+        let mut edges = Vec::new();
+        let mut current = Vec::new();
+        let mut end = None;
+        if self.closure(self.start, false, &mut seen, &mut edges, |id| current.push(id)) {
+            end = Some(0);
+        }
+
+        let mut next = Vec::new();
+        for (i, &byte) in text.iter().enumerate() {
+            if current.is_empty() {
+                break;
+            }
+            for flag in seen.iter_mut() {
+                *flag = false;
+            }
+            for &id in &current {
+                if self.takes(id, byte) && self.closure(self.out(id), false, &mut seen, &mut edges, |id| next.push(id)) {
+                    end = Some(i + 1);
+                    break; // lower priority threads lose to this match
+                }
+            }
+            current.clear();
+            mem::swap(&mut current, &mut next);
+        }
+
+        end
+    }
+
+    // whether the consuming state `id` takes `byte`
+    pub fn takes(&self, id: usize, byte: u8) -> bool {
+        self.accepts[id * self.classes.len() + self.classes.get(byte)]
+    }
+
+    // where the consuming state `id` goes after its byte
+    pub fn out(&self, id: usize) -> Transition {
+        self.outs[2 * id]
+    }
+
+    // passes the consuming states reachable from `edge` and not `seen` yet
+    // to `add` in order of priority, stopping at the end unless looking for
+    // the `longest` match, and marks them seen. Returns whether the end was
+    // reached. `edges` is the stack used where the closure was not computed
+    // ahead.
+    pub fn closure<F: FnMut(usize)>(&self, edge: Transition, longest: bool, seen: &mut [bool], edges: &mut Vec<Transition>, mut add: F) -> bool {
+        let computed = match edge {
+            Transition::Id(id) => self.closures.get(id.index()),
+            _ => None
+        };
+        if let Some(closure) = computed {
+            let states = if longest { closure.states() } else { closure.before_end() };
+            for &id in states {
+                if !seen[id] {
+                    seen[id] = true;
+                    add(id);
+                }
+            }
+            return closure.matches();
+        }
+
+        edges.clear();
+        edges.push(edge);
+        let mut matched = false;
+        while let Some(edge) = edges.pop() {
+            let id = match edge {
+                Transition::Id(id) => id.index(),
+                Transition::End if longest => {
+                    matched = true;
+                    continue;
+                },
+                Transition::End => return true,
+                Transition::Detached => panic!("cannot evaluate incomplete NFA")
+            };
+            if seen[id] {
+                continue;
+            }
+            seen[id] = true;
+
+            match self.kinds[id] {
+                Kind::Consume => add(id),
+                Kind::Split => {
+                    edges.push(self.outs[2 * id + 1]);
+                    edges.push(self.outs[2 * id]); // out1 is preferred
+                },
+                Kind::Empty | Kind::Save(_) => edges.push(self.outs[2 * id])
+            }
+        }
+        matched
+    }
+}
+
+impl From<DenseNFA> for NFA {
+    fn from(dense: DenseNFA) -> NFA {
+        dense.to_sparse()
+    }
+}
+
+impl<'n> From<&'n NFA> for DenseNFA {
+    fn from(nfa: &'n NFA) -> DenseNFA {
+        DenseNFA::new(nfa)
+    }
+}
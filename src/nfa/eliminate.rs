@@ -0,0 +1,264 @@
+use std::collections::BTreeMap;
+use std::str;
+
+use ::expr::Expr;
+use super::{NFA, State, Transition, Condition, ByteSet};
+
+// An expression over bytes, as the edges of the automaton are labeled while
+// states are eliminated. Built only with the functions below, which keep it
+// small by leaving out what cannot change what it matches.
+#[derive(PartialEq,Debug,Clone)]
+enum Label {
+    Empty,
+    Byte(u8),
+    Bytes(Vec<u8>), // any one of them, at least two
+    Any,
+    Concat(Vec<Label>),
+    Alternation(Vec<Label>),
+    Star(Box<Label>)
+}
+
+fn concat(items: Vec<Label>) -> Label {
+    let mut flat = Vec::new();
+    for item in items {
+        match item {
+            Label::Empty => (),
+            Label::Concat(nested) => flat.extend(nested),
+            item => flat.push(item)
+        }
+    }
+
+    match flat.len() {
+        0 => Label::Empty,
+        1 => flat.pop().unwrap(),
+        _ => Label::Concat(flat)
+    }
+}
+
+fn alternation(alternatives: Vec<Label>) -> Label {
+    let mut flat: Vec<Label> = Vec::new();
+    for alternative in alternatives {
+        let nested = match alternative {
+            Label::Alternation(nested) => nested,
+            alternative => vec![alternative]
+        };
+        for alternative in nested {
+            if !flat.contains(&alternative) {
+                flat.push(alternative);
+            }
+        }
+    }
+
+    match flat.len() {
+        1 => flat.pop().unwrap(),
+        _ => Label::Alternation(flat)
+    }
+}
+
+fn star(item: Label) -> Label {
+    match item {
+        Label::Empty => Label::Empty,
+        Label::Star(_) => item,
+        // matching empty adds nothing to a repetition
+        Label::Alternation(alternatives) => {
+            let rest = alternatives.into_iter().filter(|a| a != &Label::Empty).collect::<Vec<_>>();
+            match rest.len() {
+                0 => Label::Empty,
+                _ => Label::Star(Box::new(alternation(rest)))
+            }
+        },
+        item => Label::Star(Box::new(item))
+    }
+}
+
+// The expression matching what `nfa` matches, found by state elimination:
+// each state in turn is removed, and every path through it replaced by an
+// edge labeled with the expression for that path. `None` if some byte
+// outside ASCII cannot be put back together into a char.
+pub fn to_expr(nfa: &NFA) -> Option<Expr> {
+    let n = nfa.states.len();
+    if n == 0 {
+        return Some(Expr::Empty);
+    }
+    let (start, end) = (n, n + 1);
+
+    let mut edges: BTreeMap<(usize, usize), Label> = BTreeMap::new();
+    {
+        let mut add = |from: usize, to: Transition, label: Label| {
+            let to = match to {
+                Transition::Id(id) => id.index(),
+                _ => end
+            };
+            let label = match edges.remove(&(from, to)) {
+                Some(existing) => alternation(vec![existing, label]),
+                None => label
+            };
+            edges.insert((from, to), label);
+        };
+
+        add(start, Transition::id(nfa.start), Label::Empty);
+        for (id, state) in nfa.states.iter().enumerate() {
+            if let Some(out) = utf8_any(nfa, id) {
+                add(id, out, Label::Any);
+                continue;
+            }
+            match *state {
+                State::State{ref condition, out} => add(id, out, match condition {
+                    Condition::One(byte) => Label::Byte(*byte),
+                    Condition::Class(bytes) if bytes.len() == 1 => Label::Byte(bytes.iter().next().unwrap()),
+                    Condition::Class(bytes) => Label::Bytes(bytes.iter().collect()),
+                    Condition::Any => Label::Any,
+                    Condition::None => Label::Empty,
+                    Condition::Literal(literal) => concat(literal.as_bytes().iter().map(|&byte| Label::Byte(byte)).collect())
+                }),
+                State::Split{out1, out2} => {
+                    add(id, out1, Label::Empty);
+                    add(id, out2, Label::Empty);
+                },
+                State::Save{out, ..} => add(id, out, Label::Empty)
+            }
+        }
+    }
+
+    let mut remaining = (0..n).collect::<Vec<usize>>();
+    while !remaining.is_empty() {
+        // the state with the fewest paths through it, to keep labels small
+        let paths = |k: usize| {
+            let incoming = edges.keys().filter(|&&(from, to)| to == k && from != k).count();
+            let outgoing = edges.keys().filter(|&&(from, to)| from == k && to != k).count();
+            incoming * outgoing
+        };
+        let i = (0..remaining.len()).min_by_key(|&i| paths(remaining[i])).unwrap();
+        let k = remaining.remove(i);
+
+        let looped = edges.remove(&(k, k)).map(star).unwrap_or(Label::Empty);
+        let incoming = edges.iter().filter(|&(&(_, to), _)| to == k).map(|(&(from, _), l)| (from, l.clone())).collect::<Vec<_>>();
+        let outgoing = edges.iter().filter(|&(&(from, _), _)| from == k).map(|(&(_, to), l)| (to, l.clone())).collect::<Vec<_>>();
+        for &(from, _) in &incoming {
+            edges.remove(&(from, k));
+        }
+        for &(to, _) in &outgoing {
+            edges.remove(&(k, to));
+        }
+
+        for &(from, ref into) in &incoming {
+            for &(to, ref out) in &outgoing {
+                let path = concat(vec![into.clone(), looped.clone(), out.clone()]);
+                let label = match edges.remove(&(from, to)) {
+                    Some(existing) => alternation(vec![existing, path]),
+                    None => path
+                };
+                edges.insert((from, to), label);
+            }
+        }
+    }
+
+    match edges.remove(&(start, end)) {
+        Some(label) => to_chars(&label),
+        None => Some(Expr::Class(Vec::new())) // no path to the end
+    }
+}
+
+// Where the alternatives starting at `id` go, if they are the ones `.`
+// builds: a char but newline by the length of its utf-8 encoding, each a
+// lead byte followed by continuation bytes.
+fn utf8_any(nfa: &NFA, id: usize) -> Option<Transition> {
+    let mut leaves = Vec::new();
+    let mut splits = vec![id];
+    while let Some(id) = splits.pop() {
+        match &nfa.states[id] {
+            State::Split{out1: Transition::Id(out1), out2: Transition::Id(out2)} if leaves.len() + splits.len() < 4 => {
+                splits.push(out2.index());
+                splits.push(out1.index());
+            },
+            _ => leaves.push(id)
+        }
+    }
+    if leaves.len() != 4 {
+        return None;
+    }
+
+    let class = |id: usize| match nfa.states[id] {
+        State::State{condition: Condition::Class(bytes), out} => Some((bytes, out)),
+        _ => None
+    };
+    let continuation = ByteSet::range(0x80, 0xbf);
+    let ascii = ByteSet::range(0, 0x7f).iter().filter(|&byte| byte != b'\n').collect();
+    let leads = [ascii, ByteSet::range(0xc0, 0xdf), ByteSet::range(0xe0, 0xef), ByteSet::range(0xf0, 0xf7)];
+
+    let mut end = None;
+    let mut found = [false; 4];
+    for &leaf in &leaves {
+        let (lead, mut out) = class(leaf)?;
+        let len = leads.iter().position(|l| l == &lead)?;
+        for _ in 0..len {
+            let (bytes, next) = match out {
+                Transition::Id(next) => class(next.index())?,
+                _ => return None
+            };
+            if bytes != continuation {
+                return None;
+            }
+            out = next;
+        }
+        if found[len] || end.is_some_and(|end| end != out) {
+            return None;
+        }
+        found[len] = true;
+        end = Some(out);
+    }
+    end
+}
+
+// the expression over chars matching the same bytes as `label`
+fn to_chars(label: &Label) -> Option<Expr> {
+    match label {
+        Label::Empty => Some(Expr::Empty),
+        Label::Byte(byte) if byte.is_ascii() => Some(Expr::Single(*byte as char)),
+        Label::Bytes(bytes) if bytes.iter().all(|b| b.is_ascii()) => {
+            Some(Expr::class(bytes.iter().map(|&b| b as char)))
+        },
+        Label::Byte(_) | Label::Bytes(_) => None,
+        Label::Any => Some(Expr::Any),
+        Label::Concat(items) => {
+            // a char outside ASCII is a run of single bytes
+            let mut exprs = Vec::new();
+            let mut i = 0;
+            while i < items.len() {
+                match items[i] {
+                    Label::Byte(lead) if !lead.is_ascii() => {
+                        let len = match lead {
+                            0xc0..=0xdf => 2,
+                            0xe0..=0xef => 3,
+                            _ => 4
+                        };
+                        let bytes = items[i..].iter().take(len).map(|item| match item {
+                            Label::Byte(byte) => Some(*byte),
+                            _ => None
+                        }).collect::<Option<Vec<u8>>>()?;
+                        let c = str::from_utf8(&bytes).ok()?.chars().next()?;
+                        exprs.push(Expr::Single(c));
+                        i += len;
+                    },
+                    ref item => {
+                        exprs.push(to_chars(item)?);
+                        i += 1;
+                    }
+                }
+            }
+            Some(Expr::concat(exprs))
+        },
+        Label::Alternation(alternatives) => {
+            let rest = alternatives.iter().filter(|a| a != &&Label::Empty)
+                .map(to_chars)
+                .collect::<Option<Vec<Expr>>>()?;
+            let expr = Expr::alternation(rest);
+            if alternatives.contains(&Label::Empty) {
+                Some(Expr::optional(expr))
+            } else {
+                Some(expr)
+            }
+        },
+        Label::Star(item) => Some(Expr::zero_or_more(to_chars(item)?))
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt;
+
+use super::{NFA, State, Transition, Condition};
+use super::analysis::edges;
+
+// the most bytes one literal condition takes
+pub const MAX_LITERAL_LEN: usize = 16;
+
+// The bytes a literal condition takes one after another, kept inline so that
+// conditions stay small and copyable.
+#[derive(PartialEq,Debug,Clone,Copy,Eq,Hash)]
+pub struct Literal {
+    bytes: [u8; MAX_LITERAL_LEN],
+    len: u8
+}
+
+impl Literal {
+    // panics if there are more than `MAX_LITERAL_LEN` bytes
+    pub fn new(bytes: &[u8]) -> Literal {
+        assert!(bytes.len() <= MAX_LITERAL_LEN, "literal of {} bytes is too long", bytes.len());
+        let mut literal = Literal { bytes: [0; MAX_LITERAL_LEN], len: bytes.len() as u8 };
+        literal.bytes[..bytes.len()].copy_from_slice(bytes);
+        literal
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bytes = self.as_bytes().iter().map(|&b| super::escape_byte(b)).collect::<String>();
+        write!(f, "\"{}\"", bytes)
+    }
+}
+
+// The NFA with each run of states taking one byte each, one after another,
+// collapsed into a state with a literal condition. A state joins the run
+// before it only if nothing else leads to it, so no path starts partway into
+// a literal. The states left keep their order.
+pub fn collapse(nfa: &NFA) -> NFA {
+    let n = nfa.states.len();
+    let mut incoming = vec![0; n + 1];
+    if n > 0 {
+        incoming[nfa.start] += 1;
+    }
+    for &(to, _) in edges(nfa).iter().flatten() {
+        incoming[to] += 1;
+    }
+
+    let one = |id: usize| match nfa.states[id] {
+        State::State{condition: Condition::One(byte), out} => Some((byte, out)),
+        _ => None
+    };
+    // the state after `id` in a run, if it has one
+    let next = |id: usize| match one(id) {
+        Some((_, Transition::Id(out))) if incoming[out.index()] == 1 => one(out.index()).map(|_| out.index()),
+        _ => None
+    };
+
+    let mut merged = vec![false; n]; // taken into the run before it
+    let mut literals = Vec::new(); // the first state of each run, its bytes and where it goes
+    for id in 0..n {
+        if merged[id] || one(id).is_none() {
+            continue;
+        }
+        let mut bytes = Vec::new();
+        let mut last = id;
+        let mut at = Some(id);
+        // a run that comes back around to where it started, which only a
+        // loop the start does not reach could, stops short of it
+        while let Some(k) = at.filter(|&k| bytes.len() < MAX_LITERAL_LEN && (k != id || bytes.is_empty())) {
+            bytes.push(one(k).unwrap().0);
+            if k != id {
+                merged[k] = true;
+            }
+            last = k;
+            at = next(k);
+        }
+        if bytes.len() > 1 {
+            literals.push((id, Literal::new(&bytes), one(last).unwrap().1));
+        }
+    }
+
+    let mut ids = vec![0; n];
+    let mut kept = 0;
+    for id in 0..n {
+        if !merged[id] {
+            ids[id] = kept;
+            kept += 1;
+        }
+    }
+    let edge = |edge: Transition| match edge {
+        Transition::Id(id) => Transition::id(ids[id.index()]),
+        edge => edge
+    };
+
+    let mut literals = literals.into_iter().peekable();
+    let mut states = Vec::with_capacity(kept);
+    for (id, state) in nfa.states.iter().enumerate() {
+        if merged[id] {
+            continue;
+        }
+        let state = match *state {
+            _ if literals.peek().is_some_and(|&(first, _, _)| first == id) => {
+                let (_, literal, out) = literals.next().unwrap();
+                State::state(Condition::Literal(literal), edge(out))
+            },
+            State::State{condition, out} => State::state(condition, edge(out)),
+            State::Split{out1, out2} => State::split(edge(out1), edge(out2)),
+            State::Save{slot, out} => State::save(slot, edge(out))
+        };
+        states.push(state);
+    }
+
+    NFA {
+        start: if n > 0 { ids[nfa.start] } else { 0 },
+        closures: NFA::closures(&states),
+        states: states.into()
+    }
+}
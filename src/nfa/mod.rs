@@ -1,92 +1,187 @@
+use std::ascii;
 use std::cmp;
+use std::fmt;
+use std::mem;
+use std::num::NonZeroUsize;
 
 use ::expr::Expr;
-
+use ::hir::{Hir, Repetition};
+use ::encode::{Encoder, Decoder, DecodeError};
+use ::smallvec::SmallVec;
+
+mod analysis;
+mod byteset;
+mod closure;
+mod cost;
+mod dedup;
+mod dense;
+mod eliminate;
+mod literal;
+mod reverse;
+mod stateset;
+mod trim;
+mod utf8;
+mod walk;
 #[cfg(test)] mod spec;
 
+pub use self::byteset::{ByteSet, ByteSetIter};
+pub use self::closure::Closure;
+pub use self::cost::Cost;
+pub use self::walk::{States, Transitions, Edge};
+pub use self::dense::DenseNFA;
+pub use self::literal::{Literal, MAX_LITERAL_LEN};
+pub use self::utf8::sequences as utf8_sequences;
+use self::stateset::StateSet;
+
+// closures are only kept while they average at most this many states
+const CLOSURE_STATES_PER_STATE: usize = 16;
+
+// most patterns compile to fewer states than this, which are kept in place
+const INLINE_STATES: usize = 16;
 
-#[derive(PartialEq,Debug,Clone,Eq,Hash)]
+// no decoded state saves to a slot past this, as every thread of a search
+// keeps as many slots as the highest one
+const MAX_SLOTS: usize = 1 << 16;
+
+
+#[derive(PartialEq,Debug,Clone,Copy,Eq,Hash)]
 pub enum Condition {
-    One(u8), // ascii encoded char
-    Class(Vec<u8>), // list of valid ascii encoded chars
+    One(u8), // a byte, of a char or of its utf-8 encoding
+    Class(ByteSet), // any of a set of bytes
     Any,
-    None
+    None,
+    Literal(Literal) // bytes taken one after another, only made by `NFA::with_literals`
 }
 
 impl Condition {
-    pub fn one(c: char) -> Condition {
-        Condition::One(Self::to_ascii(c))
+    pub fn one(byte: u8) -> Condition {
+        Condition::One(byte)
+    }
+
+    pub fn class(bytes: &[u8]) -> Condition {
+        Condition::Class(bytes.iter().cloned().collect())
+    }
+
+    // whether a state with this condition can consume `byte`
+    pub fn accepts(&self, byte: u8) -> bool {
+        match self {
+            Condition::One(c) => *c == byte,
+            Condition::Class(bytes) => bytes.contains(byte),
+            Condition::Any => byte != b'\n', // `.` should not match newline
+            Condition::None => false,
+            Condition::Literal(literal) => literal.as_bytes()[0] == byte // only the first
+        }
     }
+}
 
-    fn to_ascii(c: char) -> u8 {
-        let mut buf = [0; 1];
-        match c.encode_utf8(&mut buf) {
-            Some(1) => buf[0],
-            _ => panic!("attempted to create condition with non-ascii char")
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Condition::One(c) => write!(f, "'{}'", escape_byte(*c)),
+            Condition::Class(chars) => {
+                let chars = chars.iter().map(escape_byte).collect::<String>();
+                write!(f, "[{}]", chars)
+            },
+            Condition::Any => write!(f, "."),
+            Condition::None => write!(f, "ε"),
+            Condition::Literal(literal) => write!(f, "{}", literal)
         }
     }
+}
+
+fn escape_byte(c: u8) -> String {
+    ascii::escape_default(c).map(|b| b as char).collect()
+}
+
+
+// The index of a state in an NFA, kept apart from other numbers so that
+// they are not mixed up. Stored plus one, so that `Option<StateID>` takes no
+// more room than a `usize`.
+#[derive(PartialEq,Debug,Clone,Copy,Eq,Hash,PartialOrd,Ord)]
+pub struct StateID(NonZeroUsize);
+
+impl StateID {
+    pub fn new(index: usize) -> StateID {
+        StateID(NonZeroUsize::new(index + 1).expect("state index overflows"))
+    }
 
-    pub fn class(chars: Vec<char>) -> Condition {
-        let ascii_bytes = chars.iter().map(|&c| Self::to_ascii(c)).collect::<Vec<_>>();
+    pub fn index(self) -> usize {
+        self.0.get() - 1
+    }
+}
 
-        Condition::Class(ascii_bytes)
+impl fmt::Display for StateID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.index())
     }
 }
 
 
-#[derive(PartialEq,Debug,Clone,Eq,Hash)]
+#[derive(PartialEq,Debug,Clone,Copy,Eq,Hash)]
 pub enum Transition {
-    Id(usize),
+    Id(StateID),
     Detached,
     End
 }
 
+impl Transition {
+    pub fn id(index: usize) -> Transition {
+        Transition::Id(StateID::new(index))
+    }
+}
+
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Transition::Id(id) => write!(f, "{}", id),
+            Transition::Detached => write!(f, "DETACHED"),
+            Transition::End => write!(f, "END")
+        }
+    }
+}
+
 
-#[derive(PartialEq,Debug,Clone,Eq,Hash)]
+#[derive(PartialEq,Debug,Clone,Copy,Eq,Hash)]
 pub enum State {
     State{condition: Condition, out: Transition},
-    Split{out1: Transition, out2: Transition}
+    Split{out1: Transition, out2: Transition},
+    Save{slot: usize, out: Transition} // records the offset reached in a capture slot
 }
 
 impl State {
+    #[allow(clippy::self_named_constructors)]
     pub fn state(condition: Condition, out: Transition) -> State {
-        State::State{condition: condition, out: out}
+        State::State{condition, out}
     }
 
     pub fn split(out1: Transition, out2: Transition) -> State {
-        State::Split{out1: out1,
-                     out2: out2}
+        State::Split{out1,
+                     out2}
     }
 
-    pub fn get_priority_key(&self, nfa: &NFA) -> usize { 
-        // key by greediness and lexographical order of condition char
-       
-        match self {
-            &State::State{ref condition, ref out} => {
-                Self::get_transition_priority_key(condition, out, nfa)
-            },
-            &State::Split{ref out1, ref out2} => {
-                cmp::min(
-                    Self::get_transition_priority_key(&Condition::None, out1, nfa),
-                    Self::get_transition_priority_key(&Condition::None, out2, nfa))
-            }
-        }
+    pub fn save(slot: usize, out: Transition) -> State {
+        State::Save{slot, out}
     }
+}
 
-    fn get_transition_priority_key(condition: &Condition, out: &Transition, nfa: &NFA) -> usize {
-        match condition {
-            &Condition::One(c) => c as usize, // there is a cost
-            &Condition::Any => 0, // prioritize any
-            &Condition::None => {
-                match out {
-                    &Transition::Id(id) => {
-                        let next_state = nfa.get_state(id).unwrap();
-                        next_state.get_priority_key(&nfa)
-                    },
-                    _ => usize::max_value() // state terminates with no cost
-                }
-            },
-            &Condition::Class(ref chars) => 0
+// a state that consumes nothing and leads nowhere yet
+impl Default for State {
+    fn default() -> State {
+        State::state(Condition::None, Transition::Detached)
+    }
+}
+
+const MAGIC: &[u8; 4] = b"RMNF";
+const VERSION: u8 = 2;
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            State::State{condition, out} => write!(f, "{} -> {}", condition, out),
+            State::Split{out1, out2} => write!(f, "split({}, {})", out1, out2),
+            State::Save{slot, out} => write!(f, "save({}) -> {}", slot, out)
         }
     }
 }
@@ -94,7 +189,14 @@ impl State {
 #[derive(PartialEq,Debug,Clone)]
 pub struct NFA {
     start: usize,
-    states: Vec<State>
+    states: SmallVec<State, INLINE_STATES>,
+    closures: Vec<Closure> // by state, or empty if there are too many
+}
+
+impl Default for NFA {
+    fn default() -> NFA {
+        NFA::new()
+    }
 }
 
 impl NFA {
@@ -102,157 +204,647 @@ impl NFA {
     pub fn new() -> NFA {
         NFA {
             start: 0,
-            states: Vec::new()
+            states: SmallVec::new(),
+            closures: Vec::new()
         }
     }
 
     pub fn from_states(states: Vec<State>) -> NFA {
         NFA {
             start: 0,
-            states: states
+            closures: Self::closures(&states),
+            states: states.into()
         }
     }
 
-    pub fn get_start(&self) -> Option<State> {
-        if self.start >= self.states.len() {
-            None
-        } else {
-            Some(self.states[self.start].clone())
-        }
+    fn closures(states: &[State]) -> Vec<Closure> {
+        closure::closures(states, CLOSURE_STATES_PER_STATE * states.len())
     }
 
-    pub fn get_state(&self, index: usize) -> Option<State> {
-        if index >= self.states.len() {
-            None
-        } else {
-            Some(self.states[index].clone())
-        }
+    pub fn get_start(&self) -> Option<&State> {
+        self.states.get(self.start)
+    }
 
+    pub fn get_start_id(&self) -> usize {
+        self.start
+    }
+
+    pub fn get_state(&self, index: usize) -> Option<&State> {
+        self.states.get(index)
     }
 
     pub fn num_states(&self) -> usize {
         self.states.len()
     }
 
+    // the same automaton laid out for running, see `DenseNFA`
+    pub fn to_dense(&self) -> DenseNFA {
+        DenseNFA::new(self)
+    }
+
+    /// The states with their ids, for walking the whole automaton.
+    pub fn states<'n>(&'n self) -> States<'n> {
+        States::new(&self.states)
+    }
+
+    /// The edges out of state `id`, in order of priority, with what each
+    /// consumes or saves. None if there is no such state.
+    pub fn transitions_from(&self, id: usize) -> Transitions {
+        Transitions::new(self.states.get(id))
+    }
+
+    // The states that consume input reached from state `id` without
+    // consuming any, so that matchers do not have to follow splits on every
+    // step. `None` if closures were not computed for this NFA.
+    pub fn closure(&self, id: usize) -> Option<&Closure> {
+        self.closures.get(id)
+    }
+
+    // the heap memory used by the states and their closures
+    pub fn memory_usage(&self) -> usize {
+        let closures = self.closures.iter()
+            .map(|closure| mem::size_of::<Closure>() + std::mem::size_of_val(closure.states()))
+            .sum::<usize>();
+
+        self.states.len() * mem::size_of::<State>() + closures
+    }
+
+    // the number of capturing groups, counting the whole match as group 0
+    pub fn num_groups(&self) -> usize {
+        self.states.iter()
+            .filter_map(|state| match state {
+                State::Save{slot, ..} => Some(slot / 2 + 1),
+                _ => None
+            })
+            .max()
+            .unwrap_or(1)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new(MAGIC, VERSION);
+        self.encode(&mut encoder);
+        encoder.finish()
+    }
+
+    // loads an NFA encoded by `to_bytes`, checking that it is complete and
+    // consistent
+    pub fn from_bytes(bytes: &[u8]) -> Result<NFA, DecodeError> {
+        let mut decoder = Decoder::new(bytes, MAGIC, VERSION)?;
+        let nfa = Self::decode(&mut decoder)?;
+        decoder.finish()?;
+        Ok(nfa)
+    }
+
+    pub fn encode(&self, encoder: &mut Encoder) {
+        encoder.u32(self.start);
+        encoder.u32(self.states.len());
+
+        for state in &self.states {
+            match state {
+                State::State{condition, out} => {
+                    encoder.u8(0);
+                    match condition {
+                        Condition::One(c) => { encoder.u8(0); encoder.u8(*c); },
+                        Condition::Class(chars) => {
+                            encoder.u8(1);
+                            encoder.bytes(&chars.iter().collect::<Vec<u8>>());
+                        },
+                        Condition::Any => encoder.u8(2),
+                        Condition::None => encoder.u8(3),
+                        Condition::Literal(literal) => { encoder.u8(4); encoder.bytes(literal.as_bytes()); }
+                    }
+                    encode_transition(encoder, out);
+                },
+                State::Split{out1, out2} => {
+                    encoder.u8(1);
+                    encode_transition(encoder, out1);
+                    encode_transition(encoder, out2);
+                },
+                State::Save{slot, out} => {
+                    encoder.u8(2);
+                    encoder.u32(*slot);
+                    encode_transition(encoder, out);
+                }
+            }
+        }
+    }
+
+    pub fn decode(decoder: &mut Decoder) -> Result<NFA, DecodeError> {
+        let start = decoder.u32()?;
+        let num_states = decoder.u32()?;
+
+        let mut states = Vec::new();
+        for id in 0..num_states {
+            let state = match decode_tag(decoder, 2)? {
+                0 => {
+                    let condition = match decode_tag(decoder, 4)? {
+                        0 => Condition::One(decoder.u8()?),
+                        1 => Condition::Class(decoder.bytes()?.iter().cloned().collect()),
+                        2 => Condition::Any,
+                        3 => Condition::None,
+                        _ => match decoder.bytes()? {
+                            bytes if !bytes.is_empty() && bytes.len() <= MAX_LITERAL_LEN => Condition::Literal(Literal::new(bytes)),
+                            _ => return Err(DecodeError::InvalidTransition(id))
+                        }
+                    };
+                    State::state(condition, decode_transition(decoder, id, num_states)?)
+                },
+                1 => {
+                    let out1 = decode_transition(decoder, id, num_states)?;
+                    let out2 = decode_transition(decoder, id, num_states)?;
+                    State::split(out1, out2)
+                },
+                _ => {
+                    let slot = decoder.u32()?;
+                    if slot >= MAX_SLOTS {
+                        return Err(DecodeError::InvalidSlot(id));
+                    }
+                    State::save(slot, decode_transition(decoder, id, num_states)?)
+                }
+            };
+            states.push(state);
+        }
+
+        // an NFA without states has no start, which the engines search from
+        if start >= num_states {
+            return Err(DecodeError::InvalidTransition(start));
+        }
+
+        Ok(NFA {
+            start,
+            closures: Self::closures(&states),
+            states: states.into()
+        })
+    }
+
+    /// An expression matching the same strings, found by eliminating the
+    /// states one at a time. Which of several matches is preferred is not
+    /// kept, nor are groups. `None` if some bytes outside ASCII do not
+    /// make up whole chars, as no pattern can match them.
+    pub fn to_expr(&self) -> Option<Expr> {
+        eliminate::to_expr(self)
+    }
+
+    /// The pattern of `to_expr`.
+    pub fn to_pattern(&self) -> Option<String> {
+        self.to_expr().map(|expr| expr.to_pattern())
+    }
+
+    /// Whether the NFA matches the empty string.
+    pub fn matches_empty(&self) -> bool {
+        self.min_len() == Some(0)
+    }
+
+    /// Whether the NFA matches only finitely many strings.
+    pub fn is_finite(&self) -> bool {
+        self.max_len().is_some() || self.min_len().is_none()
+    }
+
+    /// The length in bytes of the shortest string the NFA matches, `None`
+    /// if it matches none.
+    pub fn min_len(&self) -> Option<usize> {
+        analysis::min_len(self)
+    }
+
+    /// The length in bytes of the longest string the NFA matches, `None`
+    /// if it matches none or strings of any length.
+    pub fn max_len(&self) -> Option<usize> {
+        analysis::max_len(self)
+    }
+
+    /// A rough measure of how costly searching with the NFA is, for
+    /// turning away patterns likely to be slow.
+    pub fn estimated_cost(&self) -> Cost {
+        let largest_class = self.states.iter()
+            .map(|state| match *state {
+                State::State{condition: Condition::One(_), ..} => 1,
+                State::State{condition: Condition::Class(ref bytes), ..} => bytes.len(),
+                State::State{condition: Condition::Any, ..} => 255,
+                _ => 0
+            })
+            .max()
+            .unwrap_or(0);
+        Cost::new(self.states.len(), analysis::has_nested_loops(self), largest_class)
+    }
+
+    /// The strings the NFA matches, in order of preference, if it matches
+    /// no more than `limit` of them and none goes through a repetition or
+    /// `.`, as for a literal or an alternation of them.
+    pub fn literals(&self, limit: usize) -> Option<Vec<Vec<u8>>> {
+        analysis::literals(self, limit)
+    }
+
+    /// By group, counting the whole match as group 0, whether any match
+    /// sets it.
+    pub fn participating_groups(&self) -> Vec<bool> {
+        analysis::participating_groups(self)
+    }
+
+    /// The NFA without the states that no match goes through, either
+    /// because the start does not lead to them or because they lead to no
+    /// match, and with the ids of the rest closed up. Matches the same
+    /// strings in the same order of preference. Groups no match sets go
+    /// with their states.
+    pub fn trim(&self) -> NFA {
+        trim::trim(self)
+    }
+
+    // The NFA with runs of states taking one byte each collapsed into
+    // states with literal conditions, for the Pike VM to run. Only it knows
+    // how to run literal conditions; the other engines run this one.
+    pub fn with_literals(&self) -> NFA {
+        literal::collapse(self)
+    }
+
+    /// An NFA matching the strings this one matches, reversed byte by byte,
+    /// so running it backwards from the end of a match finds where the
+    /// match starts. Groups and which match is preferred are not kept, so
+    /// it is meant to be searched for the longest match.
+    pub fn reverse(&self) -> NFA {
+        reverse::reverse(self)
+    }
+
+    // An NFA matching what either matches, preferring this one's matches.
+    // Intersections and complements need a DFA, see `DenseDFA`.
+    pub fn union(&self, other: &NFA) -> NFA {
+        let offset = self.states.len();
+        let shift = |edge: Transition| match edge {
+            Transition::Id(id) => Transition::id(id.index() + offset),
+            edge => edge
+        };
+
+        let mut states = self.states.clone();
+        states.extend(other.states.iter().map(|state| match *state {
+            State::State{ref condition, out} => State::state(*condition, shift(out)),
+            State::Split{out1, out2} => State::split(shift(out1), shift(out2)),
+            State::Save{slot, out} => State::save(slot, shift(out))
+        }));
+
+        // an NFA without states matches the empty string
+        let start = |nfa: &NFA, offset: usize| match nfa.states.len() {
+            0 => Transition::End,
+            _ => Transition::id(nfa.start + offset)
+        };
+        states.push(State::split(start(self, 0), start(other, offset)));
+
+        NFA {
+            start: states.len() - 1,
+            closures: Self::closures(&states),
+            states
+        }
+    }
+
+    // An NFA matching wherever this one matches in a text, as if it started
+    // with a lazy loop over every byte. Its leftmost-first match ends where
+    // the leftmost-first match of this one ends, as the loop has the lowest
+    // priority and is dropped once a match is found.
+    pub fn unanchored(&self) -> NFA {
+        let mut states = self.states.clone();
+        let split = states.len();
+        let start = match states.len() {
+            0 => Transition::End,
+            _ => Transition::id(self.start)
+        };
+        states.push(State::split(start, Transition::id(split + 1)));
+        states.push(State::state(Condition::Class(ByteSet::range(0, 255)), Transition::id(split)));
+
+        NFA {
+            start: split,
+            closures: Self::closures(&states),
+            states
+        }
+    }
+
+    /// Renders the NFA as a Graphviz digraph. Splits are drawn as diamonds
+    /// whose edges are numbered in order of preference.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph nfa {\n    rankdir=LR;\n");
+        dot.push_str("    start [shape=point];\n");
+        dot.push_str("    end [shape=doublecircle, label=\"END\"];\n");
+
+        for (id, state) in self.states.iter().enumerate() {
+            let shape = match *state {
+                State::State{..} => "circle",
+                State::Split{..} => "diamond",
+                State::Save{..} => "box"
+            };
+            dot.push_str(&format!("    s{} [shape={}, label=\"{}\"];\n", id, shape, id));
+        }
+
+        if !self.states.is_empty() {
+            dot.push_str(&format!("    start -> s{};\n", self.start));
+        }
+
+        for (id, state) in self.states.iter().enumerate() {
+            match state {
+                State::State{condition, out} => {
+                    let label = condition.to_string().replace('\\', "\\\\").replace('"', "\\\"");
+                    dot.push_str(&format!("    s{} -> {} [label=\"{}\"];\n", id, dot_node(out), label));
+                },
+                State::Split{out1, out2} => {
+                    dot.push_str(&format!("    s{} -> {} [label=\"1\", style=dashed];\n", id, dot_node(out1)));
+                    dot.push_str(&format!("    s{} -> {} [label=\"2\", style=dashed];\n", id, dot_node(out2)));
+                },
+                State::Save{slot, out} => {
+                    dot.push_str(&format!("    s{} -> {} [label=\"save {}\", style=dashed];\n", id, dot_node(out), slot));
+                }
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
     pub fn from_expr(expr: &Expr) -> NFA {
+        Self::from_hir(&Hir::from_expr(expr))
+    }
+
+    pub fn from_hir(hir: &Hir) -> NFA {
         let mut nfa = Self::new();
 
-        let start = nfa.build_expr(expr);
-        nfa.update_outputs(start, Transition::End);
+        let start = nfa.build_hir(hir);
+        nfa.update_outputs(0, start, Transition::End);
+        let (states, start) = dedup::dedup(&nfa.states, start);
         nfa.start = start;
+        nfa.closures = Self::closures(&states);
+        nfa.states = states.into();
         nfa
     }
 
-    fn build_expr(&mut self, expr: &Expr) -> usize {
-        let id = match expr {
-            &Expr::Any => {
-                let s = State::state(Condition::Any, Transition::Detached);
-                self.states.push(s);
-
-                self.states.len() - 1
+    fn build_hir(&mut self, hir: &Hir) -> usize {
+        let id = match hir {
+            Hir::Empty => self.build_empty(),
+            Hir::Concat(items) | Hir::Alternation(items) if items.is_empty() => self.build_empty(),
+            Hir::Any(terminator) => self.build_utf8_any(*terminator),
+            Hir::Literal(c) if !c.is_ascii() => {
+                self.build_utf8_sequence(*c)
             },
-            &Expr::Single(c) => {
-                let s = State::state(Condition::one(c), Transition::Detached);
+            Hir::Literal(c) => {
+                let s = State::state(Condition::one(*c as u8), Transition::Detached);
                 self.states.push(s);
 
                 self.states.len() - 1
             },
-            &Expr::Class(ref chars) => {
-                let s = State::state(Condition::class(chars.clone()),
-                                     Transition::Detached);
-                self.states.push(s);
+            Hir::Class(ranges) => {
+                // the ascii members share one state, other chars need a
+                // sequence of byte ranges for each run of them
+                let ascii = ranges.iter()
+                    .filter(|&&(first, _)| first.is_ascii())
+                    .flat_map(|&(first, last)| first as u8..=cmp::min(last, '\x7f') as u8)
+                    .collect::<Vec<u8>>();
+                let others = ranges.iter()
+                    .filter(|&&(_, last)| !last.is_ascii())
+                    .flat_map(|&(first, last)| utf8::sequences(cmp::max(first, '\u{80}'), last))
+                    .collect::<Vec<_>>();
+
+                // an empty class still gets a state, which accepts no byte
+                let mut alternatives = Vec::new();
+                if !ascii.is_empty() || others.is_empty() {
+                    self.states.push(State::state(Condition::class(&ascii), Transition::Detached));
+                    alternatives.push(self.states.len() - 1);
+                }
+                for sequence in others {
+                    alternatives.push(self.build_utf8_ranges(&sequence));
+                }
 
-                self.states.len() - 1
+                self.link_alternatives(alternatives)
             },
-            &Expr::Sequence(ref a, ref b) => {
-                let left_id = self.build_expr(a);
-                let right_id = self.build_expr(b);
-                self.update_outputs(left_id, Transition::Id(right_id));
+            Hir::Concat(items) => {
+                // a long sequence has many items, so they are built in a
+                // loop, each one linked to the next
+                let mut last_first = self.states.len();
+                let first_id = self.build_hir(&items[0]);
+                let mut last_id = first_id;
+                for item in &items[1..] {
+                    let first = self.states.len();
+                    let id = self.build_hir(item);
+                    self.update_outputs(last_first, last_id, Transition::id(id));
+                    last_first = first;
+                    last_id = id;
+                }
 
-                left_id
+                first_id
             },
-            &Expr::Optional(ref expr) => {
-                let expr_id = self.build_expr(expr);
-                let s = State::split(Transition::Id(expr_id), Transition::Detached);
+            Hir::Alternation(alternatives) => {
+                let ids = alternatives.iter().map(|alternative| self.build_hir(alternative)).collect();
+                self.link_alternatives(ids)
+            },
+            Hir::Repeat(Repetition::ZeroOrOne, hir) => {
+                let hir_id = self.build_hir(hir);
+                let s = State::split(Transition::id(hir_id), Transition::Detached);
                 self.states.push(s);
 
                 self.states.len() - 1
             },
-            &Expr::OneOrMore(ref expr) => {
-                let expr_id = self.build_expr(expr);
-                let s = State::split(Transition::Id(expr_id), Transition::Detached);
+            Hir::Repeat(Repetition::OneOrMore, hir) => {
+                let first = self.states.len();
+                let hir_id = self.build_hir(hir);
+                let s = State::split(Transition::id(hir_id), Transition::Detached);
 
                 self.states.push(s);
                 let split_id = self.states.len() - 1;
-                self.update_outputs(expr_id, Transition::Id(split_id));
+                self.update_outputs(first, hir_id, Transition::id(split_id));
 
-                expr_id
+                hir_id
             },
-            &Expr::ZeroOrMore(ref expr) => {
-                let expr_id = self.build_expr(expr);
-                let s = State::split(Transition::Id(expr_id), Transition::Detached);
+            Hir::Repeat(Repetition::ZeroOrMore, hir) if hir.can_match_empty() => {
+                // matchers never enter a state twice at the same offset, so
+                // an item that matched empty cannot loop back to the split it
+                // came from. Built as `(x+)?`, it leaves through a split of
+                // its own, keeping the groups it set on the way.
+                let first = self.states.len();
+                let hir_id = self.build_hir(hir);
+                self.states.push(State::split(Transition::id(hir_id), Transition::Detached));
+                let loop_id = self.states.len() - 1;
+                self.update_outputs(first, hir_id, Transition::id(loop_id));
+
+                self.states.push(State::split(Transition::id(hir_id), Transition::Detached));
+                self.states.len() - 1
+            },
+            Hir::Repeat(Repetition::ZeroOrMore, hir) => {
+                let first = self.states.len();
+                let hir_id = self.build_hir(hir);
+                let s = State::split(Transition::id(hir_id), Transition::Detached);
 
                 self.states.push(s);
                 let split_id = self.states.len() - 1;
-                self.update_outputs(expr_id, Transition::Id(split_id));
+                self.update_outputs(first, hir_id, Transition::id(split_id));
 
                 split_id
             },
-            &Expr::Or(ref expr1, ref expr2) => {
-                let expr1_id = self.build_expr(expr1);
-                let expr2_id = self.build_expr(expr2);
+            Hir::Group(index, hir) => {
+                let first = self.states.len();
+                let hir_id = self.build_hir(hir);
+                self.states.push(State::save(2 * index + 1, Transition::Detached));
+                let close_id = self.states.len() - 1;
+                self.update_outputs(first, hir_id, Transition::id(close_id));
+
+                self.states.push(State::save(2 * index, Transition::id(hir_id)));
+                self.states.len() - 1
+            },
+        };
 
-                let s = State::split(Transition::Id(expr1_id),
-                                     Transition::Id(expr2_id));
+        id
+    }
 
-                self.states.push(s);
+    // an empty edge, to give an item that matches nothing a state to start from
+    fn build_empty(&mut self) -> usize {
+        self.states.push(State::state(Condition::None, Transition::Detached));
+        self.states.len() - 1
+    }
 
-                self.states.len() - 1
-            }
-        };
+    // joins alternatives with splits, each preferring everything before it
+    fn link_alternatives(&mut self, ids: Vec<usize>) -> usize {
+        let mut id = ids[0];
+        for &alternative_id in &ids[1..] {
+            let s = State::split(Transition::id(id),
+                                 Transition::id(alternative_id));
+
+            self.states.push(s);
+            id = self.states.len() - 1;
+        }
 
         id
     }
 
-    fn update_outputs(&mut self, start_id: usize, new_edge: Transition) {
-        self.update_outputs_rec(start_id, &mut vec![start_id], new_edge);
+    // a chain of states matching the utf-8 encoding of `c` one byte at a time
+    fn build_utf8_sequence(&mut self, c: char) -> usize {
+        let mut buf = [0; 4];
+        let bytes = c.encode_utf8(&mut buf).as_bytes();
+        let first_id = self.states.len();
+
+        for (i, &byte) in bytes.iter().enumerate() {
+            let out = if i + 1 < bytes.len() {
+                Transition::id(first_id + i + 1)
+            } else {
+                Transition::Detached
+            };
+            self.states.push(State::state(Condition::One(byte), out));
+        }
+
+        first_id
     }
 
-    fn update_outputs_rec(&mut self, start_id: usize, visited: &mut Vec<usize>, new_edge: Transition) {
-        let state = self.states[start_id].clone();
-        let state = match state {
-            State::State{ref condition, ref out} => {
-                State::state(condition.clone(), 
-                             self.replace_edge(out.clone(), new_edge, visited)
-)
-            },
-            State::Split{ref out1, ref out2} => {
-                let edge1 = self.replace_edge(out1.clone(), new_edge.clone(), visited);
-                let edge2 = self.replace_edge(out2.clone(), new_edge.clone(), visited);
+    // a chain of states taking a byte from each of `ranges` in turn
+    fn build_utf8_ranges(&mut self, ranges: &[(u8, u8)]) -> usize {
+        let first_id = self.states.len();
+
+        for (i, &(first, last)) in ranges.iter().enumerate() {
+            let out = if i + 1 < ranges.len() {
+                Transition::id(first_id + i + 1)
+            } else {
+                Transition::Detached
+            };
+            let condition = if first == last { Condition::One(first) } else { Condition::Class(ByteSet::range(first, last)) };
+            self.states.push(State::state(condition, out));
+        }
 
-                State::split(edge1,
-                             edge2)
-            }
+        first_id
+    }
+
+    // alternatives matching any one char but `terminator` by its utf-8
+    // encoding, so that `.` never ends a match within a char. The texts
+    // searched are valid utf-8, so lead bytes are only told apart by length.
+    fn build_utf8_any(&mut self, terminator: u8) -> usize {
+        let bytes = |first: u8, last: u8| {
+            Condition::Class(ByteSet::range(first, last).iter().filter(|&byte| byte != terminator).collect())
         };
-        self.states[start_id] = state;
+        let continuation = bytes(0x80, 0xbf);
+
+        let mut alternatives = Vec::new();
+        for &(lead, len) in [(bytes(0, 0x7f), 0), (bytes(0xc0, 0xdf), 1), (bytes(0xe0, 0xef), 2), (bytes(0xf0, 0xf7), 3)].iter() {
+            let first_id = self.states.len();
+            alternatives.push(first_id);
+            for i in 0..len + 1 {
+                let out = if i < len { Transition::id(first_id + i + 1) } else { Transition::Detached };
+                let condition = if i == 0 { lead } else { continuation };
+                self.states.push(State::state(condition, out));
+            }
+        }
+
+        self.link_alternatives(alternatives)
     }
 
-    fn replace_edge(&mut self, edge: Transition, replacement: Transition, visited: &mut Vec<usize>) -> Transition {
-        match &edge {
-            &Transition::Detached => {
-                replacement
-            },
-            &Transition::Id(id) => {
-                if !visited.contains(&id) { // don't recurse further
-                    visited.push(id);
-                    self.update_outputs_rec(id, visited, replacement);
+    // points every detached edge reachable from `start_id` at `new_edge`.
+    // Works through the states with a stack of its own rather than recursing,
+    // since long patterns give long chains of states. The states of the item
+    // starting there were built from `first` on and lead only to each other,
+    // so a set of those is all that is visited.
+    fn update_outputs(&mut self, first: usize, start_id: usize, new_edge: Transition) {
+        let mut visited = StateSet::new(self.states.len() - first);
+        let mut pending = vec![start_id];
+        visited.insert(start_id - first);
+
+        while let Some(id) = pending.pop() {
+            match self.states[id] {
+                State::State{ref mut out, ..} | State::Save{ref mut out, ..} => {
+                    replace_edge(out, new_edge, first, &mut visited, &mut pending);
+                },
+                State::Split{ref mut out1, ref mut out2} => {
+                    replace_edge(out1, new_edge, first, &mut visited, &mut pending);
+                    replace_edge(out2, new_edge, first, &mut visited, &mut pending);
                 }
+            }
+        }
+    }
 
-                edge
-            },
-            _ => edge
+}
+
+
+// one line per state, e.g. `0: 'a' -> 1` or `1: split(0, END)`
+impl fmt::Display for NFA {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "start: {}", self.start)?;
+
+        for (id, state) in self.states.iter().enumerate() {
+            write!(f, "\n{}: {}", id, state)?;
         }
+
+        Ok(())
     }
+}
 
+// replaces `edge` if it is detached, or queues the state it leads to
+fn replace_edge(edge: &mut Transition, replacement: Transition, first: usize, visited: &mut StateSet, pending: &mut Vec<usize>) {
+    match *edge {
+        Transition::Detached => *edge = replacement,
+        Transition::Id(id) => if visited.insert(id.index() - first) { pending.push(id.index()) },
+        Transition::End => ()
+    }
 }
 
+fn dot_node(transition: &Transition) -> String {
+    match *transition {
+        Transition::Id(id) => format!("s{}", id),
+        Transition::End => "end".to_owned(),
+        Transition::Detached => "detached".to_owned()
+    }
+}
+
+fn encode_transition(encoder: &mut Encoder, transition: &Transition) {
+    match *transition {
+        Transition::End => encoder.u8(0),
+        Transition::Id(id) => { encoder.u8(1); encoder.u32(id.index()); },
+        Transition::Detached => encoder.u8(2) // rejected when decoding
+    }
+}
+
+fn decode_transition(decoder: &mut Decoder, state: usize, num_states: usize) -> Result<Transition, DecodeError> {
+    match decode_tag(decoder, 2)? {
+        0 => Ok(Transition::End),
+        1 => match decoder.u32()? {
+            id if id < num_states => Ok(Transition::id(id)),
+            _ => Err(DecodeError::InvalidTransition(state))
+        },
+        _ => Err(DecodeError::InvalidTransition(state))
+    }
+}
+
+// reads a tag byte, which must be at most `max`
+fn decode_tag(decoder: &mut Decoder, max: u8) -> Result<u8, DecodeError> {
+    let offset = decoder.offset();
+    match decoder.u8()? {
+        tag if tag <= max => Ok(tag),
+        _ => Err(DecodeError::InvalidTag(offset))
+    }
+}
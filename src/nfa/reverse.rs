@@ -0,0 +1,97 @@
+use super::{NFA, State, Transition, Condition, ByteSet, Literal};
+
+// What a node of the reversed automaton can go on to: consume the byte of
+// the state that led to it and go back to that state, go back to a state
+// without consuming anything, or match.
+enum Back {
+    Consume(usize),
+    Empty(usize),
+    Match
+}
+
+// The NFA matching every string the given one matches, reversed byte by
+// byte. Each state and the end become a node, entered by a chain of splits
+// over the edges that led into it in the forward NFA, in the order of the
+// states they came from. Which of several matches is preferred is not
+// kept, nor are groups, so it is searched for the longest match.
+pub fn reverse(nfa: &NFA) -> NFA {
+    let n = nfa.states.len();
+    if n == 0 {
+        return NFA::new();
+    }
+    let node = |edge: Transition| match edge {
+        Transition::Id(id) => Some(id.index()),
+        Transition::End => Some(n),
+        Transition::Detached => None
+    };
+
+    let mut back = (0..n + 1).map(|_| Vec::new()).collect::<Vec<_>>();
+    back[nfa.start].push(Back::Match);
+    for (id, state) in nfa.states.iter().enumerate() {
+        let (outs, consumes) = match *state {
+            State::State{condition: Condition::None, out} | State::Save{out, ..} => (vec![out], false),
+            State::State{condition: Condition::Class(bytes), ..} if bytes.is_empty() => (vec![], false),
+            State::State{out, ..} => (vec![out], true),
+            State::Split{out1, out2} => (vec![out1, out2], false)
+        };
+        for out in outs.into_iter().filter_map(&node) {
+            back[out].push(if consumes { Back::Consume(id) } else { Back::Empty(id) });
+        }
+    }
+
+    // the entries of the nodes come first, then the consuming states, one
+    // for each state of the forward NFA, then the rest of the chains
+    let entry = |node: usize| Transition::id(node);
+    let consume = |id: usize| Transition::id(n + 1 + id);
+    let mut states = Vec::with_capacity(2 * n + 1);
+    let mut chains = Vec::new();
+
+    for edges in &back {
+        let mut targets = edges.iter().map(|edge| match *edge {
+            Back::Consume(id) => consume(id),
+            Back::Empty(id) => entry(id),
+            Back::Match => Transition::End
+        }).collect::<Vec<_>>();
+
+        let state = match targets.len() {
+            // nothing leads here: a state no byte gets through
+            0 => State::state(Condition::Class(ByteSet::new()), Transition::End),
+            1 => State::state(Condition::None, targets[0]),
+            _ => {
+                // split(first, split(second, ... split(last but one, last)))
+                let last = targets.pop().unwrap();
+                let first = targets.remove(0);
+                let base = 2 * n + 1 + chains.len();
+                let mut rest = last;
+                for (i, &target) in targets.iter().enumerate().rev() {
+                    chains.push((base + i, State::split(target, rest)));
+                    rest = Transition::id(base + i);
+                }
+                State::split(first, rest)
+            }
+        };
+        states.push(state);
+    }
+
+    for (id, state) in nfa.states.iter().enumerate() {
+        states.push(match state {
+            // a literal is taken from its last byte back to its first
+            State::State{condition: Condition::Literal(literal), ..} => {
+                let bytes = literal.as_bytes().iter().rev().cloned().collect::<Vec<u8>>();
+                State::state(Condition::Literal(Literal::new(&bytes)), entry(id))
+            },
+            State::State{condition, ..} => State::state(*condition, entry(id)),
+            // never entered; kept so the ids line up
+            _ => State::state(Condition::Class(ByteSet::new()), Transition::End)
+        });
+    }
+
+    chains.sort_by_key(|&(id, _)| id);
+    states.extend(chains.into_iter().map(|(_, state)| state));
+
+    NFA {
+        start: n,
+        closures: NFA::closures(&states),
+        states: states.into()
+    }
+}
@@ -1,11 +1,15 @@
+use std::mem;
+use std::time::{Duration, Instant};
+
 use ::expr::Expr;
-use super::{State, Transition, NFA, Condition};
+use ::encode::DecodeError;
+use super::{State, Transition, StateID, NFA, Condition, ByteSet, Edge, Literal, MAX_LITERAL_LEN, utf8_sequences};
 
 #[test]
 fn build_single() {
     let nfa = NFA::from_expr(&Expr::Single('a'));
 
-    assert_eq!(vec![State::state(Condition::one('a'), Transition::End)], nfa.states);
+    assert_eq!(vec![State::state(Condition::one(b'a'), Transition::End)], nfa.states);
     assert_eq!(0, nfa.start);
 }
 
@@ -13,7 +17,7 @@ fn build_single() {
 fn build_sequence() {
     let nfa = NFA::from_expr(&Expr::sequence(Expr::Single('a'),Expr::Single('b')));
     
-    assert_eq!(vec![State::state(Condition::one('a'), Transition::Id(1)), State::state(Condition::one('b'), Transition::End)], nfa.states);
+    assert_eq!(vec![State::state(Condition::one(b'a'), Transition::id(1)), State::state(Condition::one(b'b'), Transition::End)], nfa.states);
     assert_eq!(0, nfa.start);
 }
 
@@ -21,7 +25,7 @@ fn build_sequence() {
 fn build_option() {
     let nfa = NFA::from_expr(&Expr::optional(Expr::Single('a')));
 
-    assert_eq!(vec![State::state(Condition::one('a'), Transition::End), State::split(Transition::Id(0), Transition::End)],
+    assert_eq!(vec![State::state(Condition::one(b'a'), Transition::End), State::split(Transition::id(0), Transition::End)],
         nfa.states);
     assert_eq!(1, nfa.start);
 }
@@ -36,10 +40,10 @@ fn build_complex_option() {
              Expr::Single('a')));
 
     assert_eq!(vec![
-        State::state(Condition::one('a'), Transition::Id(1)),
-        State::state(Condition::one('b'), Transition::Id(3)),
-        State::split(Transition::Id(0), Transition::Id(3)),
-        State::state(Condition::one('a'), Transition::End)
+        State::state(Condition::one(b'a'), Transition::id(1)),
+        State::state(Condition::one(b'b'), Transition::id(3)),
+        State::split(Transition::id(0), Transition::id(3)),
+        State::state(Condition::one(b'a'), Transition::End)
     ], nfa.states);
     assert_eq!(2, nfa.start);
 }
@@ -51,8 +55,8 @@ fn build_one_or_more() {
     // nfa for 'a+'
 
     assert_eq!(vec![
-        State::state(Condition::one('a'), Transition::Id(1)),
-        State::split(Transition::Id(0), Transition::End)
+        State::state(Condition::one(b'a'), Transition::id(1)),
+        State::split(Transition::id(0), Transition::End)
     ], nfa.states);
     assert_eq!(0, nfa.start);
 }
@@ -62,11 +66,11 @@ fn build_more_complex_one_or_more() {
     let nfa = NFA::from_expr(&"a+a+b".parse::<Expr>().unwrap());
 
     assert_eq!(vec![
-        State::state(Condition::one('a'), Transition::Id(1)),
-        State::split(Transition::Id(0), Transition::Id(2)),
-        State::state(Condition::one('a'), Transition::Id(3)),
-        State::split(Transition::Id(2), Transition::Id(4)),
-        State::state(Condition::one('b'), Transition::End)
+        State::state(Condition::one(b'a'), Transition::id(1)),
+        State::split(Transition::id(0), Transition::id(2)),
+        State::state(Condition::one(b'a'), Transition::id(3)),
+        State::split(Transition::id(2), Transition::id(4)),
+        State::state(Condition::one(b'b'), Transition::End)
     ], nfa.states);
     assert_eq!(0, nfa.start);
 }
@@ -78,8 +82,8 @@ fn build_zero_or_more() {
     // 'a*'
     
     assert_eq!(vec![
-        State::state(Condition::one('a'), Transition::Id(1)),
-        State::split(Transition::Id(0), Transition::End)
+        State::state(Condition::one(b'a'), Transition::id(1)),
+        State::split(Transition::id(0), Transition::End)
     ], nfa.states);
     assert_eq!(1, nfa.start);
 }
@@ -89,19 +93,19 @@ fn build_more_complex_zero_or_more() {
     let nfa = NFA::from_expr(&"b*cd*".parse::<Expr>().unwrap());
 
     assert_eq!(vec![
-        State::state(Condition::one('b'), Transition::Id(1)), // 0
-        State::split(Transition::Id(0), Transition::Id(2)), // 1
-        State::state(Condition::one('c'), Transition::Id(4)), // 2
-        State::state(Condition::one('d'), Transition::Id(4)), // 3
-        State::split(Transition::Id(3), Transition::End)// 4
+        State::state(Condition::one(b'b'), Transition::id(1)), // 0
+        State::split(Transition::id(0), Transition::id(2)), // 1
+        State::state(Condition::one(b'c'), Transition::id(4)), // 2
+        State::state(Condition::one(b'd'), Transition::id(4)), // 3
+        State::split(Transition::id(3), Transition::End)// 4
     ], nfa.states);
     assert_eq!(1, nfa.start);
 }
 
 #[test]
 fn build_doesnt_infinite_loop() {
-    let nfa = NFA::from_expr(&"(ab)*".parse::<Expr>().unwrap());
-    let nfa = NFA::from_expr(&"..+.".parse::<Expr>().unwrap());
+    let _nfa = NFA::from_expr(&"(ab)*".parse::<Expr>().unwrap());
+    let _nfa = NFA::from_expr(&"..+.".parse::<Expr>().unwrap());
 }
 
 #[test]
@@ -110,73 +114,603 @@ fn build_or() {
         &Expr::or(Expr::Single('a'), Expr::Single('b')));
 
     assert_eq!(vec![
-        State::state(Condition::one('a'), Transition::End),
-        State::state(Condition::one('b'), Transition::End),
-        State::split(Transition::Id(0), Transition::Id(1))
+        State::state(Condition::one(b'a'), Transition::End),
+        State::state(Condition::one(b'b'), Transition::End),
+        State::split(Transition::id(0), Transition::id(1))
+    ], nfa.states);
+    assert_eq!(2, nfa.start);
+}
+
+#[test]
+fn merges_states_that_go_on_alike() {
+    let nfa = |pattern: &str| NFA::from_expr(&pattern.parse::<Expr>().unwrap());
+
+    // `bc` is built once, then the states before it are told apart
+    assert_eq!(vec![
+        State::state(Condition::one(b'a'), Transition::id(1)),
+        State::state(Condition::one(b'b'), Transition::id(2)),
+        State::state(Condition::one(b'c'), Transition::End),
+        State::state(Condition::one(b'd'), Transition::id(1)),
+        State::split(Transition::id(0), Transition::id(3))
+    ], nfa("abc|dbc").states);
+
+    // states that consume nothing are kept, like the two alike splits
+    // repeating an item that can match empty
+    assert_eq!(6, nfa("(a*)*").num_states());
+}
+
+#[test]
+fn collapses_runs_of_bytes_into_literals() {
+    let nfa = |pattern: &str| NFA::from_expr(&pattern.parse::<Expr>().unwrap()).with_literals();
+
+    assert_eq!(vec![State::state(Condition::Literal(Literal::new(b"abcd")), Transition::End)], nfa("abcd").states);
+    // the loop comes back to `b`, so it starts a literal of its own
+    assert_eq!(vec![
+        State::state(Condition::one(b'a'), Transition::id(1)),
+        State::state(Condition::Literal(Literal::new(b"bc")), Transition::id(2)),
+        State::split(Transition::id(1), Transition::id(3)),
+        State::state(Condition::one(b'd'), Transition::End)
+    ], nfa("a(?:bc)+d").states);
+    // no more than `MAX_LITERAL_LEN` bytes each
+    let long = nfa(&"x".repeat(MAX_LITERAL_LEN + 1));
+    assert_eq!(2, long.num_states());
+    assert_eq!("\"xxxxxxxxxxxxxxxx\"", Condition::Literal(Literal::new(&[b'x'; MAX_LITERAL_LEN])).to_string());
+
+    let literals = nfa("a(?:bc)+d");
+    assert_eq!(Ok(literals.clone()), NFA::from_bytes(&literals.to_bytes()));
+}
+
+#[test]
+fn merges_long_repeated_items_in_one_pass() {
+    let tail = "x".repeat(8000);
+    let expr = format!("a{}|b{}", tail, tail).parse::<Expr>().unwrap();
+
+    let started = Instant::now();
+    let nfa = NFA::from_expr(&expr);
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(8003, nfa.num_states());
+}
+
+#[test]
+fn build_group() {
+    let nfa = NFA::from_expr(&Expr::group(1, Expr::Single('a')));
+
+    assert_eq!(vec![
+        State::state(Condition::one(b'a'), Transition::id(1)),
+        State::save(3, Transition::End),
+        State::save(2, Transition::id(0))
     ], nfa.states);
     assert_eq!(2, nfa.start);
+    assert_eq!(2, nfa.num_groups());
+}
+
+#[test]
+fn borrows_states() {
+    let nfa = NFA::from_expr(&Expr::optional(Expr::Single('a')));
+
+    assert_eq!(Some(&State::split(Transition::id(0), Transition::End)), nfa.get_start());
+    assert_eq!(Some(&State::state(Condition::one(b'a'), Transition::End)), nfa.get_state(0));
+    assert_eq!(None, nfa.get_state(2));
+    assert_eq!(None, NFA::new().get_start());
+}
+
+#[test]
+fn counts_groups() {
+    assert_eq!(1, NFA::new().num_groups());
+    assert_eq!(1, NFA::from_expr(&Expr::Single('a')).num_groups());
+    assert_eq!(4, NFA::from_expr(&"(a(b))|(c)".parse::<Expr>().unwrap()).num_groups());
+}
+
+#[test]
+fn finds_groups_that_take_part() {
+    use ::hir::Hir;
+    let group = |index, hir| Hir::Group(index, Box::new(hir));
+    let hir = Hir::Alternation(vec![
+        group(1, Hir::Literal('a')),
+        Hir::Concat(vec![Hir::Class(Vec::new()), group(2, Hir::Literal('b'))])
+    ]);
+    assert_eq!(vec![true, true, false], NFA::from_hir(&hir).participating_groups());
+    assert_eq!(vec![false, false], NFA::from_hir(&group(1, Hir::Class(Vec::new()))).participating_groups());
+    assert_eq!(vec![true], NFA::new().participating_groups());
+}
+
+#[test]
+fn estimates_cost() {
+    let cost = |pattern: &str| NFA::from_expr(&pattern.parse::<Expr>().unwrap()).estimated_cost();
+    for &(pattern, nested) in [("(a+)+", true), ("(a*b)*c", true), ("((ab)+c)*", true),
+                               ("(a|b)*", false), ("a*b*", false), ("(ab)+", false), ("abc", false)].iter() {
+        assert_eq!(nested, cost(pattern).has_nested_repetition(), "{}", pattern);
+    }
+    assert_eq!(3, cost("abc").states());
+    assert_eq!(1, cost("abc").largest_class());
+    assert_eq!(3, cost("a[bcd]").largest_class());
+    assert_eq!(0, NFA::new().estimated_cost().states());
 }
 
 #[test]
 fn build_any() {
     let nfa = NFA::from_expr(&Expr::Any);
+    let bytes = |first: u8, last: u8| Condition::Class(ByteSet::range(first, last));
+    let ascii = Condition::Class(ByteSet::range(0, 0x7f).iter().filter(|&byte| byte != b'\n').collect());
 
+    // a char of each length, ascii first, sharing the continuation bytes
     assert_eq!(vec![
-        State::state(Condition::Any, Transition::End)
+        State::state(ascii, Transition::End),
+        State::state(bytes(0xc0, 0xdf), Transition::id(2)),
+        State::state(bytes(0x80, 0xbf), Transition::End),
+        State::state(bytes(0xe0, 0xef), Transition::id(4)),
+        State::state(bytes(0x80, 0xbf), Transition::id(2)),
+        State::state(bytes(0xf0, 0xf7), Transition::id(6)),
+        State::state(bytes(0x80, 0xbf), Transition::id(4)),
+        State::split(Transition::id(0), Transition::id(1)),
+        State::split(Transition::id(7), Transition::id(3)),
+        State::split(Transition::id(8), Transition::id(5))
+    ], nfa.states);
+}
+
+#[test]
+fn build_char_class() {
+    let nfa = NFA::from_expr(&Expr::Class(vec!['a','b']));
+
+    assert_eq!(vec![
+        State::state(Condition::class(b"ab"), Transition::End)
     ], nfa.states);
 }
 
+
+#[test]
+fn class_ignores_member_order() {
+    assert_eq!(Condition::class(b"aba"), Condition::class(b"ba"));
+    assert!(Condition::class(b"xy").accepts(b'y'));
+    assert!(!Condition::class(b"xy").accepts(b'z'));
+    // bytes of utf-8 encoded chars too
+    assert!(Condition::class(&[0xc3, 0xa9]).accepts(0xa9));
+    assert!(Condition::one(0xc3).accepts(0xc3));
+}
+
 #[test]
-fn prioritizes_state() {
-    let s = State::state(Condition::one('a'), Transition::End);
-    assert_eq!(97, s.get_priority_key(&NFA::new()));
+fn builds_byte_sets() {
+    let digits = ByteSet::range(b'0', b'9');
+    let set = digits.union(&vec![b'_', 0, 255].into_iter().collect());
 
-    let s = State::state(Condition::Any, Transition::End);
-    assert_eq!(0, s.get_priority_key(&NFA::new()));
+    assert_eq!(10, digits.len());
+    assert_eq!(13, set.len());
+    assert!(set.contains(b'5') && set.contains(0) && set.contains(255));
+    assert!(!set.contains(b'a'));
+    assert_eq!(vec![0, b'0', b'1', b'2'], set.iter().take(4).collect::<Vec<u8>>());
+    assert!(ByteSet::new().is_empty());
+    assert!(!digits.is_empty());
+}
+
+#[test]
+fn builds_state_sets() {
+    let mut set = super::stateset::StateSet::new(130);
+    assert!(set.insert(0) && set.insert(64) && set.insert(129));
+    assert!(!set.insert(0) && !set.insert(64) && !set.insert(129));
+    assert!(set.insert(1) && set.insert(63));
+}
+
+#[test]
+fn build_non_ascii_single() {
+    let nfa = NFA::from_expr(&Expr::Single('é'));
+
+    assert_eq!(vec![
+        State::state(Condition::One(0xc3), Transition::id(1)),
+        State::state(Condition::One(0xa9), Transition::End)
+    ], nfa.states);
+    assert_eq!(0, nfa.start);
+}
+
+#[test]
+fn build_non_ascii_char_class() {
+    let nfa = NFA::from_expr(&Expr::Class(vec!['a', 'é', 'b']));
+
+    assert_eq!(vec![
+        State::state(Condition::class(b"ab"), Transition::End),
+        State::state(Condition::One(0xc3), Transition::id(2)),
+        State::state(Condition::One(0xa9), Transition::End),
+        State::split(Transition::id(0), Transition::id(1))
+    ], nfa.states);
+    assert_eq!(3, nfa.start);
+}
 
-    let s = State::state(Condition::None, Transition::End);
-    assert_eq!(usize::max_value(), s.get_priority_key(&NFA::new()));
+#[test]
+fn splits_char_ranges_into_utf8_byte_ranges() {
+    assert_eq!(vec![vec![(0xc3, 0xc3), (0xa8, 0xa9)]], utf8_sequences('è', 'é'));
+    assert_eq!(vec![vec![(0x61, 0x7f)], vec![(0xc2, 0xc2), (0x80, 0x84)]], utf8_sequences('a', '\u{84}'));
+    assert_eq!(vec![
+        vec![(0xed, 0xed), (0x9f, 0x9f), (0xbf, 0xbf)],
+        vec![(0xee, 0xee), (0x80, 0x80), (0x80, 0x80)]
+    ], utf8_sequences('\u{d7ff}', '\u{e000}'));
 
-    // recursive
-    let s = State::state(Condition::None, Transition::Id(0));
-    assert_eq!(98, s.get_priority_key(&NFA::from_states(vec![
-        State::state(Condition::None, Transition::Id(1)),
-        State::state(Condition::one('b'), Transition::End)
-    ])));
+    // every char of a range is encoded by exactly one sequence
+    let sequences = utf8_sequences('\u{7f}', '\u{10ffff}');
+    assert!(sequences.len() < 20);
+    for &c in &['\u{7f}', '\u{80}', 'é', '\u{7ff}', '\u{800}', '\u{d7ff}', '\u{e000}', '\u{ffff}', '😀', '\u{10ffff}'] {
+        let mut buf = [0; 4];
+        let bytes = c.encode_utf8(&mut buf).as_bytes();
+        let encoding = |sequence: &&Vec<(u8, u8)>| {
+            sequence.len() == bytes.len() && sequence.iter().zip(bytes).all(|(&(first, last), &b)| first <= b && b <= last)
+        };
+        assert_eq!(1, sequences.iter().filter(encoding).count(), "{:?}", c);
+    }
+}
 
-    // does not infinitely loop (assuming no free cycles)
-    let s = State::state(Condition::None, Transition::Id(0));
-    assert_eq!(0, s.get_priority_key(&NFA::from_states(vec![
-        State::state(Condition::Any, Transition::Id(0))
-    ])));
+#[test]
+fn builds_negated_class_from_byte_ranges() {
+    let nfa = NFA::from_expr(&Expr::NegatedClass(vec!['a']));
+    assert!(nfa.num_states() < 40, "{}", nfa.num_states());
 }
 
 #[test]
-fn prioritizes_split() {
+fn round_trips_through_bytes() {
+    let nfa = NFA::from_expr(&"(ab|[cd])*.é?".parse::<Expr>().unwrap());
+    assert_eq!(Ok(nfa.clone()), NFA::from_bytes(&nfa.to_bytes()));
+
     let nfa = NFA::from_states(vec![
-        State::state(Condition::one('a'), Transition::End)
+        State::state(Condition::None, Transition::id(1)),
+        State::split(Transition::id(0), Transition::End)
     ]);
-    let s = State::split(Transition::Id(0), Transition::End);
-    assert_eq!(97, s.get_priority_key(&nfa));
+    assert_eq!(Ok(nfa.clone()), NFA::from_bytes(&nfa.to_bytes()));
+}
 
-    let s = State::split(Transition::End, Transition::End);
-    assert_eq!(usize::max_value(), s.get_priority_key(&NFA::new()));
+#[test]
+fn encodes_compactly() {
+    let nfa = NFA::from_expr(&Expr::Single('a'));
+
+    assert_eq!(&b"RMNF\x02\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00a\x00"[..], &nfa.to_bytes()[..]);
+}
+
+#[test]
+fn rejects_invalid_bytes() {
+    let bytes = NFA::from_expr(&Expr::Single('a')).to_bytes();
 
-    // recursive
-    let s = State::split(Transition::Id(0), Transition::End);
-    assert_eq!(0, s.get_priority_key(&NFA::from_states(vec![
-        State::split(Transition::Id(1), Transition::Id(2)),
-        State::state(Condition::one('a'), Transition::End),
+    assert_eq!(Err(DecodeError::BadMagic), NFA::from_bytes(b"RMRX\x01"));
+    assert_eq!(Err(DecodeError::UnsupportedVersion(9)), NFA::from_bytes(b"RMNF\x09"));
+    assert_eq!(Err(DecodeError::UnexpectedEnd), NFA::from_bytes(&bytes[..bytes.len() - 1]));
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(Err(DecodeError::TrailingBytes(bytes.len())), NFA::from_bytes(&trailing));
+
+    let mut bad_tag = bytes.clone();
+    bad_tag[13] = 7;
+    assert_eq!(Err(DecodeError::InvalidTag(13)), NFA::from_bytes(&bad_tag));
+}
+
+#[test]
+fn rejects_inconsistent_nfa() {
+    let dangling = NFA::from_states(vec![
+        State::state(Condition::Any, Transition::id(1))
+    ]);
+    assert_eq!(Err(DecodeError::InvalidTransition(0)), NFA::from_bytes(&dangling.to_bytes()));
+
+    let incomplete = NFA::from_states(vec![
+        State::state(Condition::Any, Transition::End),
+        State::split(Transition::id(0), Transition::Detached)
+    ]);
+    assert_eq!(Err(DecodeError::InvalidTransition(1)), NFA::from_bytes(&incomplete.to_bytes()));
+
+    let far_slot = NFA::from_states(vec![
+        State::save(0x7ffffff0, Transition::id(1)),
         State::state(Condition::Any, Transition::End)
-    ])));
+    ]);
+    assert_eq!(Err(DecodeError::InvalidSlot(0)), NFA::from_bytes(&far_slot.to_bytes()));
+
+    assert_eq!(Err(DecodeError::InvalidTransition(0)), NFA::from_bytes(&NFA::new().to_bytes()));
+    assert_eq!(Err(DecodeError::InvalidTransition(0)), NFA::from_bytes(b"RMNF\x02\x00\x00\x00\x00\x00\x00\x00\x00"));
 }
 
 #[test]
-fn build_char_class() {
-    let nfa = NFA::from_expr(&Expr::Class(vec!['a','b']));
+fn displays_conditions() {
+    assert_eq!("'a'", Condition::one(b'a').to_string());
+    assert_eq!("'\\n'", Condition::one(b'\n').to_string());
+    assert_eq!("'\\xc3'", Condition::One(0xc3).to_string());
+    assert_eq!("[\\'ab]", Condition::class(b"ab'").to_string());
+    assert_eq!(".", Condition::Any.to_string());
+    assert_eq!("ε", Condition::None.to_string());
+}
 
-    assert_eq!(vec![
-        State::state(Condition::class(vec!['a','b']), Transition::End)
-    ], nfa.states);
+#[test]
+fn numbers_states_compactly() {
+    assert_eq!(7, StateID::new(7).index());
+    assert_eq!(Transition::Id(StateID::new(3)), Transition::id(3));
+    assert_eq!(mem::size_of::<usize>(), mem::size_of::<Option<StateID>>());
+}
+
+#[test]
+fn reverses_and_lays_out_literals() {
+    let nfa = |pattern: &str| NFA::from_expr(&pattern.parse::<Expr>().unwrap());
+
+    assert_eq!(Some("dcba".to_owned()), nfa("abcd").with_literals().reverse().to_pattern());
+
+    let dense = nfa("abcd").with_literals().to_dense();
+    assert_eq!(None, dense.match_end(b"axxx"));
+    assert_eq!(Some(4), dense.match_end(b"abcde"));
+
+    let texts = [&b"xyzyz"[..], b"xyzy", b"abcd", b"ab", b"x"];
+    for pattern in ["abcd|x(?:yz)+", "a(?:bc)+d", &"x".repeat(MAX_LITERAL_LEN + 3)].iter() {
+        let plain = nfa(pattern);
+        let dense = plain.with_literals().to_dense();
+        let mut dfa = ::dfa::LazyDFA::new(&plain);
+        for text in texts.iter() {
+            assert_eq!(dfa.match_end(text), dense.match_end(text), "{} in {:?}", pattern, text);
+        }
+        assert_eq!(plain.reverse().to_pattern(), plain.with_literals().reverse().to_pattern(), "{}", pattern);
+    }
+}
+
+#[test]
+fn converts_between_sparse_and_dense() {
+    let patterns = ["a(b|c)*d", "(a|ab)(c|bcd)", "x*", "", "(é|a)+.", "[abc]+c"];
+    let texts = [&b"abcbd"[..], b"abcd", b"xxy", b"", "éaéb".as_bytes(), b"abcc"];
+    for pattern in patterns.iter() {
+        let nfa = NFA::from_expr(&pattern.parse::<Expr>().unwrap());
+        let dense = nfa.to_dense();
+        let sparse = dense.to_sparse();
+        assert_eq!(nfa.num_states(), dense.num_states());
+        assert_eq!(dense, sparse.to_dense(), "{}", pattern);
+
+        let mut dfa = ::dfa::LazyDFA::new(&nfa);
+        for text in texts.iter() {
+            assert_eq!(dfa.match_end(text), dense.match_end(text), "{} in {:?}", pattern, text);
+            assert_eq!(dfa.match_end(text), ::dfa::LazyDFA::new(&sparse).match_end(text), "{} in {:?}", pattern, text);
+        }
+    }
+}
+
+#[test]
+fn walks_states_and_edges() {
+    let nfa = NFA::from_expr(&"(a|b)*".parse::<Expr>().unwrap());
+    assert_eq!(nfa.num_states(), nfa.states().count());
+    assert!(nfa.states().all(|(id, &state)| nfa.get_state(id) == Some(&state)));
+
+    // every state is reached by following the edges from the start
+    let mut seen = vec![false; nfa.num_states()];
+    let mut pending = vec![nfa.get_start_id()];
+    while let Some(id) = pending.pop() {
+        if !seen[id] {
+            seen[id] = true;
+            pending.extend(nfa.transitions_from(id).filter_map(|edge| match edge.target() {
+                Transition::Id(next) => Some(next.index()),
+                _ => None
+            }));
+        }
+    }
+    assert!(seen.iter().all(|&seen| seen));
+
+    let a = NFA::from_states(vec![State::state(Condition::One(b'a'), Transition::id(1)), State::split(Transition::id(0), Transition::End)]);
+    assert_eq!(vec![Edge::Consume(Condition::One(b'a'), Transition::id(1))], a.transitions_from(0).collect::<Vec<_>>());
+    assert_eq!(vec![Edge::Empty(Transition::id(0)), Edge::Empty(Transition::End)], a.transitions_from(1).collect::<Vec<_>>());
+    assert_eq!(0, a.transitions_from(2).count());
+}
+
+#[test]
+fn exports_dot() {
+    let nfa = NFA::from_expr(&"a*\"".parse::<Expr>().unwrap());
+
+    assert_eq!("digraph nfa {
+    rankdir=LR;
+    start [shape=point];
+    end [shape=doublecircle, label=\"END\"];
+    s0 [shape=circle, label=\"0\"];
+    s1 [shape=diamond, label=\"1\"];
+    s2 [shape=circle, label=\"2\"];
+    start -> s1;
+    s0 -> s1 [label=\"'a'\"];
+    s1 -> s0 [label=\"1\", style=dashed];
+    s1 -> s2 [label=\"2\", style=dashed];
+    s2 -> end [label=\"'\\\\\\\"'\"];
+}
+", nfa.to_dot());
+}
+
+#[test]
+fn exports_empty_dot() {
+    assert_eq!("digraph nfa {
+    rankdir=LR;
+    start [shape=point];
+    end [shape=doublecircle, label=\"END\"];
+}
+", NFA::new().to_dot());
+}
+
+#[test]
+fn displays_states() {
+    assert_eq!("'a' -> 1", State::state(Condition::one(b'a'), Transition::id(1)).to_string());
+    assert_eq!(". -> END", State::state(Condition::Any, Transition::End).to_string());
+    assert_eq!("split(0, DETACHED)", State::split(Transition::id(0), Transition::Detached).to_string());
+    assert_eq!("save(2) -> 0", State::save(2, Transition::id(0)).to_string());
+}
+
+#[test]
+fn displays_nfa() {
+    let nfa = NFA::from_expr(&"(a|[bc])+".parse::<Expr>().unwrap());
+
+    assert_eq!("start: 4
+0: 'a' -> 3
+1: [bc] -> 3
+2: split(0, 1)
+3: save(3) -> 5
+4: save(2) -> 2
+5: split(4, END)", nfa.to_string());
+}
+
+#[test]
+fn builds_long_sequence_without_recursing() {
+    let pattern = "ab".repeat(50000);
+    let nfa = NFA::from_expr(&pattern.parse::<Expr>().unwrap());
+
+    assert_eq!(100000, nfa.num_states());
+    assert_eq!(Some(&State::state(Condition::one(b'a'), Transition::id(1))), nfa.get_start());
+    assert_eq!(Some(&State::state(Condition::one(b'b'), Transition::End)), nfa.get_state(99999));
+}
+
+#[test]
+fn trims_states_no_match_goes_through() {
+    let mut nfa = NFA::from_states(vec![
+        State::state(Condition::one(b'x'), Transition::id(2)),
+        State::split(Transition::id(3), Transition::id(2)),
+        State::state(Condition::one(b'a'), Transition::End),
+        State::state(Condition::Class(ByteSet::new()), Transition::id(4)),
+        State::state(Condition::one(b'b'), Transition::End)
+    ]);
+    nfa.start = 1;
+
+    let trimmed = nfa.trim();
+    assert_eq!(NFA::from_states(vec![
+        State::state(Condition::None, Transition::id(1)),
+        State::state(Condition::one(b'a'), Transition::End)
+    ]), trimmed);
+    assert_eq!(0, trimmed.get_start_id());
+
+    let nothing = NFA::from_states(vec![State::state(Condition::Class(ByteSet::new()), Transition::End)]);
+    assert_eq!(nothing, NFA::from_states(vec![State::state(Condition::one(b'a'), Transition::id(1)),
+                                              State::state(Condition::Class(ByteSet::new()), Transition::End)]).trim());
+    assert_eq!(NFA::new(), NFA::new().trim());
+    let nfa = NFA::from_expr(&"(a|b)*c".parse::<Expr>().unwrap());
+    assert_eq!(nfa, nfa.trim());
+}
+
+#[test]
+fn precomputes_closures_in_order_of_priority() {
+    let nfa = NFA::from_states(vec![
+        State::state(Condition::one(b'a'), Transition::End),
+        State::state(Condition::one(b'b'), Transition::End),
+        State::split(Transition::id(0), Transition::End),
+        State::split(Transition::id(2), Transition::id(1))
+    ]);
+
+    let closure = nfa.closure(3).unwrap();
+    assert_eq!(&[0, 1], closure.states());
+    assert!(closure.matches());
+    assert_eq!(&[0], closure.before_end());
+
+    let closure = nfa.closure(0).unwrap();
+    assert_eq!(&[0], closure.states());
+    assert!(!closure.matches());
+    assert_eq!(&[0], closure.before_end());
+}
+
+#[test]
+fn skips_closures_that_grow_too_large() {
+    let nfa = NFA::from_expr(&"a?".repeat(100).parse::<Expr>().unwrap());
+    assert_eq!(None, nfa.closure(0));
+
+    let incomplete = NFA::from_states(vec![State::split(Transition::id(0), Transition::Detached)]);
+    assert_eq!(None, incomplete.closure(0));
+}
+
+#[test]
+fn reports_memory_usage() {
+    let small = NFA::from_expr(&"ab".parse::<Expr>().unwrap());
+    let large = NFA::from_expr(&"ab".repeat(10).parse::<Expr>().unwrap());
+
+    assert_eq!(0, NFA::new().memory_usage());
+    assert!(small.memory_usage() >= 2 * ::std::mem::size_of::<State>());
+    assert!(large.memory_usage() > small.memory_usage());
+}
+
+#[test]
+fn writes_pattern_by_eliminating_states() {
+    let pattern = |p: &str| NFA::from_expr(&p.parse::<Expr>().unwrap()).to_pattern();
+
+    assert_eq!(Some("ab*c".to_owned()), pattern("ab*c"));
+    assert_eq!(Some("(?:a|b)*c".to_owned()), pattern("(a|b)*c"));
+    assert_eq!(Some("a?b".to_owned()), pattern("(a|)b"));
+    assert_eq!(Some("xab(?:ab)*".to_owned()), pattern("x(ab)+"));
+    assert_eq!(Some("a|é".to_owned()), pattern("[aé]"));
+    assert_eq!(Some("".to_owned()), pattern(""));
+}
+
+#[test]
+fn writes_any_char_back_as_dot() {
+    let pattern = |p: &str| NFA::from_expr(&p.parse::<Expr>().unwrap()).to_pattern();
+
+    assert_eq!(Some("a.b".to_owned()), pattern("a.b"));
+    assert_eq!(Some("..".to_owned()), pattern(".."));
+    assert_eq!(Some("x.*".to_owned()), pattern("x.*"));
+    for p in ["a.b", "(.|é)c", "..é"].iter() {
+        let round_trip = pattern(&pattern(p).unwrap()).unwrap();
+        assert_eq!(pattern(p), Some(round_trip));
+    }
+}
+
+#[test]
+fn writes_pattern_of_reversed_automaton() {
+    let hir = ::hir::Hir::from_expr(&"ab(c|de)*".parse::<Expr>().unwrap());
+
+    assert_eq!(Some("(?:c|ed)*ba".to_owned()), NFA::from_hir(&hir.reverse()).to_pattern());
+}
+
+#[test]
+fn writes_no_pattern_for_partial_chars() {
+    let nfa = NFA::from_states(vec![State::state(Condition::One(0xc3), Transition::End)]);
+    assert_eq!(None, nfa.to_pattern());
+
+    // nothing reaches the end, so nothing matches
+    let nfa = NFA::from_states(vec![State::state(Condition::One(b'a'), Transition::id(0))]);
+    assert_eq!(Some("[]".to_owned()), nfa.to_pattern());
 }
 
+#[test]
+fn unites_nfas() {
+    let nfa = |pattern: &str| NFA::from_expr(&pattern.parse::<Expr>().unwrap());
+    let nfa = nfa("ab").union(&nfa("c*"));
+    let dfa = ::dfa::DenseDFA::build_longest(&nfa, 1 << 20).unwrap();
+
+    for text in [&b"ab"[..], b"", b"ccc"].iter() {
+        assert!(dfa.accepts(text), "{:?}", text);
+    }
+    assert!(!dfa.accepts(b"abc"));
+    assert_eq!(Some("ab|c*".to_string()), nfa.to_pattern());
+}
+
+#[test]
+fn reverses_byte_by_byte() {
+    let nfa = NFA::from_expr(&"(é|ab)+c?".parse::<Expr>().unwrap()).reverse();
+    let dfa = ::dfa::DenseDFA::build_longest(&nfa, 1 << 20).unwrap();
+    let reversed = |text: &str| text.bytes().rev().collect::<Vec<u8>>();
+
+    for text in ["é", "abé", "éc", "éabc"].iter() {
+        assert!(dfa.accepts(&reversed(text)), "{:?}", text);
+        assert!(!dfa.accepts(text.as_bytes()), "{:?}", text);
+    }
+    for text in ["", "c", "ba", "éé\n"].iter() {
+        assert!(!dfa.accepts(&reversed(text)), "{:?}", text);
+    }
+    assert_eq!(0, NFA::new().reverse().num_states());
+}
+
+#[test]
+fn analyzes_what_nfas_match() {
+    let nfa = |pattern: &str| NFA::from_expr(&pattern.parse::<Expr>().unwrap());
+    let cases = [
+        // pattern, matches empty, finite, min, max
+        ("abc", false, true, Some(3), Some(3)),
+        ("a|bc|", true, true, Some(0), Some(2)),
+        ("ab*c", false, false, Some(2), None),
+        ("(a|bb)?c", false, true, Some(1), Some(3)),
+        ("(()*)*a", false, true, Some(1), Some(1)),
+        ("(a*)*", true, false, Some(0), None),
+        ("é", false, true, Some(2), Some(2)),
+        ("", true, true, Some(0), Some(0))
+    ];
+
+    for &(pattern, matches_empty, finite, min, max) in cases.iter() {
+        let nfa = nfa(pattern);
+        assert_eq!(matches_empty, nfa.matches_empty(), "{}", pattern);
+        assert_eq!(finite, nfa.is_finite(), "{}", pattern);
+        assert_eq!(min, nfa.min_len(), "{}", pattern);
+        assert_eq!(max, nfa.max_len(), "{}", pattern);
+    }
+}
+
+#[test]
+fn analyzes_nfas_matching_nothing() {
+    let nfa = NFA::from_hir(&::hir::Hir::Class(Vec::new()));
+    assert!(!nfa.matches_empty());
+    assert!(nfa.is_finite());
+    assert_eq!(None, nfa.min_len());
+    assert_eq!(None, nfa.max_len());
+}
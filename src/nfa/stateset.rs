@@ -0,0 +1,20 @@
+// A set of state ids below some bound, one bit each.
+#[derive(PartialEq,Debug,Clone)]
+pub struct StateSet {
+    bits: Vec<u64>
+}
+
+impl StateSet {
+    // an empty set with room for the ids below `len`
+    pub fn new(len: usize) -> StateSet {
+        StateSet { bits: vec![0; len.div_ceil(64)] }
+    }
+
+    // adds `id`, returning whether it was not there yet
+    pub fn insert(&mut self, id: usize) -> bool {
+        let (word, bit) = (id / 64, 1 << (id % 64));
+        let absent = self.bits[word] & bit == 0;
+        self.bits[word] |= bit;
+        absent
+    }
+}
@@ -0,0 +1,53 @@
+use super::{NFA, State, Transition, Condition, ByteSet};
+use super::analysis::{edges, reachable, reversed};
+
+// The NFA without the states no match goes through: those the start does not
+// reach and those that do not reach the end, with the ids of the others
+// closed up in order. A split left with one way on becomes an empty edge
+// along it. An NFA matching nothing keeps one state, taking no byte.
+pub fn trim(nfa: &NFA) -> NFA {
+    if nfa.states.is_empty() {
+        return nfa.clone();
+    }
+    let edges = edges(nfa);
+    let end = nfa.states.len();
+    let from_start = reachable(&edges, nfa.start);
+    let to_end = reachable(&reversed(&edges), end);
+    let live = |id: usize| from_start[id] && to_end[id];
+    if !live(nfa.start) {
+        return NFA::from_states(vec![State::state(Condition::Class(ByteSet::new()), Transition::End)]);
+    }
+
+    let mut ids = vec![0; end];
+    let mut kept = 0;
+    for (id, new) in ids.iter_mut().enumerate() {
+        if live(id) {
+            *new = kept;
+            kept += 1;
+        }
+    }
+    let edge = |edge: Transition| match edge {
+        Transition::Id(id) if !live(id.index()) => None,
+        Transition::Id(id) => Some(Transition::id(ids[id.index()])),
+        edge => Some(edge)
+    };
+
+    // the edge out of a live state that only has one leads on to the end,
+    // so it is live too
+    let states = nfa.states.iter().enumerate()
+        .filter(|&(id, _)| live(id))
+        .map(|(_, state)| match *state {
+            State::State{condition, out} => State::state(condition, edge(out).unwrap()),
+            State::Save{slot, out} => State::save(slot, edge(out).unwrap()),
+            State::Split{out1, out2} => match (edge(out1), edge(out2)) {
+                (Some(out1), Some(out2)) => State::split(out1, out2),
+                (Some(out), None) | (None, Some(out)) => State::state(Condition::None, out),
+                (None, None) => unreachable!("live split with no live branch")
+            }
+        })
+        .collect();
+
+    let mut trimmed = NFA::from_states(states);
+    trimmed.start = ids[nfa.start];
+    trimmed
+}
@@ -0,0 +1,58 @@
+use std::char;
+
+// the last char of each length of encoding, and the last before the surrogates
+const SPLITS: [u32; 4] = [0x7f, 0x7ff, 0xd7ff, 0xffff];
+
+// The utf-8 encodings of the chars from `first` to `last`, as sequences of
+// byte ranges: each byte sequence taking one byte from each range in turn
+// encodes one of the chars, and each of the chars is encoded by one such
+// byte sequence. A range of many chars takes few sequences, where a class
+// listing each of its chars would take one per char.
+pub fn sequences(first: char, last: char) -> Vec<Vec<(u8, u8)>> {
+    let mut sequences = Vec::new();
+    let mut pending = vec![(first as u32, last as u32)];
+    while let Some((mut first, last)) = pending.pop() {
+        if (0xd800..0xe000).contains(&first) {
+            first = 0xe000; // surrogates are not chars
+        }
+        if first > last {
+            continue;
+        }
+        if let Some(&split) = SPLITS.iter().find(|&&split| first <= split && split < last) {
+            pending.push((split + 1, last));
+            pending.push((first, split));
+            continue;
+        }
+
+        // the ranges are taken apart until the bytes after the first are
+        // all of theirs, so that each byte ranges independently
+        let mut split = None;
+        for i in 1..4 {
+            let mask = (1 << (6 * i)) - 1;
+            if first & !mask == last & !mask {
+                continue;
+            }
+            if first & mask != 0 {
+                split = Some(first | mask);
+                break;
+            }
+            if last & mask != mask {
+                split = Some((last & !mask) - 1);
+                break;
+            }
+        }
+        match split {
+            Some(split) => {
+                pending.push((split + 1, last));
+                pending.push((first, split));
+            },
+            None => sequences.push(encode(first).into_iter().zip(encode(last)).collect())
+        }
+    }
+    sequences
+}
+
+fn encode(c: u32) -> Vec<u8> {
+    let mut buf = [0; 4];
+    char::from_u32(c).unwrap().encode_utf8(&mut buf).as_bytes().to_vec()
+}
@@ -0,0 +1,69 @@
+use std::iter::Enumerate;
+use std::slice;
+
+use super::{State, Transition, Condition};
+
+// An edge out of a state, and what following it takes.
+#[derive(PartialEq,Debug,Clone,Copy,Eq,Hash)]
+pub enum Edge {
+    Consume(Condition, Transition), // a byte the condition accepts
+    Empty(Transition),
+    Save(usize, Transition) // records the offset in the slot on the way
+}
+
+impl Edge {
+    pub fn target(&self) -> Transition {
+        match self {
+            Edge::Consume(_, target) | Edge::Empty(target) | Edge::Save(_, target) => *target
+        }
+    }
+}
+
+// Iterator over the states of an NFA with their ids, in order of id.
+pub struct States<'n> {
+    states: Enumerate<slice::Iter<'n, State>>
+}
+
+impl<'n> States<'n> {
+    pub fn new(states: &'n [State]) -> States<'n> {
+        States { states: states.iter().enumerate() }
+    }
+}
+
+impl<'n> Iterator for States<'n> {
+    type Item = (usize, &'n State);
+
+    fn next(&mut self) -> Option<(usize, &'n State)> {
+        self.states.next()
+    }
+}
+
+// Iterator over the edges out of a state, in order of priority. A missing
+// state has none.
+pub struct Transitions {
+    edges: [Option<Edge>; 2],
+    next: usize
+}
+
+impl Transitions {
+    pub fn new(state: Option<&State>) -> Transitions {
+        let edges = match state {
+            Some(&State::State{condition: Condition::None, out}) => [Some(Edge::Empty(out)), None],
+            Some(&State::State{condition, out}) => [Some(Edge::Consume(condition, out)), None],
+            Some(&State::Split{out1, out2}) => [Some(Edge::Empty(out1)), Some(Edge::Empty(out2))],
+            Some(&State::Save{slot, out}) => [Some(Edge::Save(slot, out)), None],
+            None => [None, None]
+        };
+        Transitions { edges, next: 0 }
+    }
+}
+
+impl Iterator for Transitions {
+    type Item = Edge;
+
+    fn next(&mut self) -> Option<Edge> {
+        let edge = *self.edges.get(self.next)?;
+        self.next += 1;
+        edge
+    }
+}
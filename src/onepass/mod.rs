@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::mem;
+
+use ::nfa::{State, Transition, NFA, Condition};
+use ::dfa::ByteClasses;
+
+#[cfg(test)] mod spec;
+
+// the most bytes the table may take
+const SIZE_LIMIT: usize = 1 << 20;
+
+// Where a byte leads: the state whose empty closure comes next, and the
+// slots to set at the offset before consuming the byte.
+#[derive(PartialEq,Debug,Clone)]
+struct Step {
+    next: usize,
+    saves: Vec<usize>
+}
+
+// A DFA for anchored searches with groups, built when the NFA is one-pass:
+// from any state, each byte is consumed by at most one of the states the
+// Pike VM would keep a thread in. Only one thread is then ever alive, so
+// the search needs no thread list, and the groups are set in place along
+// the way. A state of the DFA is the empty closure of an NFA state that
+// comes after a consuming one, or of the start.
+#[derive(PartialEq,Debug,Clone)]
+pub struct OnePass {
+    classes: ByteClasses,
+    num_slots: usize,
+    table: Vec<Option<usize>>, // index into `steps`, by state and byte class
+    steps: Vec<Step>,
+    matches: Vec<Option<Vec<usize>>> // by state: the slots to set on matching, if it matches
+}
+
+impl OnePass {
+    // the DFA, or `None` if the NFA is not one-pass or the table would be
+    // too big
+    pub fn new(nfa: &NFA) -> Option<OnePass> {
+        let start = match nfa.num_states() {
+            0 => Transition::End,
+            _ => Transition::id(nfa.get_start_id())
+        };
+        let classes = ByteClasses::new(nfa);
+        let representatives = classes.representatives();
+
+        let mut onepass = OnePass {
+            num_slots: nfa.num_groups() * 2,
+            classes,
+            table: Vec::new(),
+            steps: Vec::new(),
+            matches: Vec::new()
+        };
+        let mut ids = HashMap::new();
+        let mut pending = vec![start];
+        ids.insert(start, 0);
+
+        while let Some(root) = pending.pop() {
+            let id = ids[&root];
+            let len = onepass.classes.len();
+            if (id + 1) * len * mem::size_of::<Option<usize>>() > SIZE_LIMIT {
+                return None;
+            }
+            if onepass.table.len() < (id + 1) * len {
+                onepass.table.resize((id + 1) * len, None);
+                onepass.matches.resize(id + 1, None);
+            }
+
+            for (out, saves, condition) in closure(nfa, root, &mut onepass.matches[id]) {
+                let next = ids.len();
+                let next = *ids.entry(out).or_insert_with(|| {
+                    pending.push(out);
+                    next
+                });
+
+                let step = onepass.steps.len();
+                onepass.steps.push(Step { next, saves });
+                for (class, &byte) in representatives.iter().enumerate() {
+                    if !condition.accepts(byte) {
+                        continue;
+                    }
+                    let entry = &mut onepass.table[id * len + class];
+                    if entry.is_some() {
+                        return None; // a second thread would take the byte
+                    }
+                    *entry = Some(step);
+                }
+            }
+        }
+
+        Some(onepass)
+    }
+
+    // the heap memory used by the table
+    pub fn memory_usage(&self) -> usize {
+        self.table.len() * mem::size_of::<Option<usize>>() +
+        self.steps.iter().map(|step| mem::size_of::<Step>() + step.saves.len() * mem::size_of::<usize>()).sum::<usize>() +
+        self.matches.len() * mem::size_of::<Option<Vec<usize>>>()
+    }
+
+    // the slots of the leftmost-first match starting at `start`
+    pub fn captures(&self, text: &[u8], start: usize) -> Option<Vec<Option<usize>>> {
+        let len = self.classes.len();
+        let mut slots = vec![None; self.num_slots];
+        slots[0] = Some(start);
+        let mut found = None;
+        let mut state = 0;
+
+        for at in start..text.len() + 1 {
+            if let Some(ref saves) = self.matches[state] {
+                let mut matched = slots.clone();
+                for &slot in saves {
+                    matched[slot] = Some(at);
+                }
+                matched[1] = Some(at);
+                found = Some(matched);
+            }
+            if at == text.len() {
+                break;
+            }
+
+            match self.table[state * len + self.classes.get(text[at])] {
+                Some(step) => {
+                    let step = &self.steps[step];
+                    for &slot in &step.saves {
+                        slots[slot] = Some(at);
+                    }
+                    state = step.next;
+                },
+                None => break
+            }
+        }
+
+        found
+    }
+}
+
+enum Follow {
+    Edge(Transition),
+    Unsave // the last slot saved is not saved on the paths that remain
+}
+
+// The states consuming a byte that the Pike VM would keep a thread in after
+// following the empty edges from `root`, in order of priority, each with the
+// slots saved on the way to it and what comes after it. States of lower
+// priority than a match are left out, as the match wins over them; the
+// slots saved on the way to it are put in `matched`.
+fn closure(nfa: &NFA, root: Transition, matched: &mut Option<Vec<usize>>) -> Vec<(Transition, Vec<usize>, Condition)> {
+    let mut consuming = Vec::new();
+    let mut seen = vec![false; nfa.num_states()];
+    let mut saves = Vec::new();
+    let mut pending = vec![Follow::Edge(root)];
+
+    while let Some(follow) = pending.pop() {
+        let edge = match follow {
+            Follow::Edge(edge) => edge,
+            Follow::Unsave => {
+                saves.pop();
+                continue;
+            }
+        };
+
+        match edge {
+            Transition::End => {
+                *matched = Some(saves.clone());
+                break;
+            },
+            Transition::Id(id) => {
+                let id = id.index();
+                if seen[id] {
+                    continue;
+                }
+                seen[id] = true;
+
+                match nfa.get_state(id) {
+                    Some(&State::State{condition: Condition::None, out}) => pending.push(Follow::Edge(out)),
+                    Some(&State::State{ref condition, out}) => consuming.push((out, saves.clone(), *condition)),
+                    Some(&State::Split{out1, out2}) => {
+                        pending.push(Follow::Edge(out2));
+                        pending.push(Follow::Edge(out1)); // out1 is preferred
+                    },
+                    Some(&State::Save{slot, out}) => {
+                        saves.push(slot);
+                        pending.push(Follow::Unsave);
+                        pending.push(Follow::Edge(out));
+                    },
+                    None => panic!("transition to missing state {}", id)
+                }
+            },
+            Transition::Detached => panic!("cannot evaluate incomplete NFA")
+        }
+    }
+
+    consuming
+}
@@ -0,0 +1,36 @@
+use ::expr::Expr;
+use ::nfa::NFA;
+use ::pikevm::PikeVM;
+use super::OnePass;
+
+fn nfa(pattern: &str) -> NFA {
+    NFA::from_expr(&pattern.parse::<Expr>().unwrap())
+}
+
+#[test]
+fn builds_only_for_one_pass_patterns() {
+    for pattern in ["ab", "(a)(bc)", "(ab)+", "([ab])*c", "(a)|(b)", "b(a)?", "x*", "", "a(b|c)*d", "(é|a)+", "a.b"].iter() {
+        assert!(OnePass::new(&nfa(pattern)).is_some(), "{}", pattern);
+    }
+    for pattern in ["a|ab", "ab|a", "(é|ü)+", "(a*)(a)", "(a|b)*b", "a*a", ".*a"].iter() {
+        assert!(OnePass::new(&nfa(pattern)).is_none(), "{}", pattern);
+    }
+}
+
+#[test]
+fn finds_same_captures_as_pike_vm() {
+    let patterns = ["ab", "(a)(bc)", "(ab)+", "([ab])*c", "(a)|(b)", "b(a)?", "x*", "", "a(b|c)*d", "(é|a)+", "(a*)b|c"];
+    let texts = ["", "ab", "abc", "abab", "abb", "b", "aaab", "ba", "abcbd", "éüx", "c"];
+
+    for pattern in patterns.iter() {
+        let nfa = nfa(pattern);
+        let onepass = OnePass::new(&nfa).unwrap();
+        for text in texts.iter() {
+            for start in 0..text.len() + 1 {
+                assert_eq!(PikeVM::new(&nfa).captures(text.as_bytes(), start, true),
+                           onepass.captures(text.as_bytes(), start),
+                           "{} on {:?} from {}", pattern, text, start);
+            }
+        }
+    }
+}
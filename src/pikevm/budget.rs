@@ -0,0 +1,102 @@
+use std::error;
+use std::fmt;
+use std::time::Instant;
+
+use super::observer::Observer;
+
+// the offsets stepped over between looks at the clock
+const CLOCK_INTERVAL: usize = 256;
+
+/// How much work a search may do before giving up: at most a number of
+/// steps, each a state followed while adding threads, and until a
+/// deadline. A search within budget finds what any other would.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub struct Budget {
+    steps: usize,
+    deadline: Option<Instant>
+}
+
+impl Default for Budget {
+    fn default() -> Budget {
+        Budget::new()
+    }
+}
+
+impl Budget {
+    /// A budget without limits.
+    pub fn new() -> Budget {
+        Budget { steps: usize::MAX, deadline: None }
+    }
+
+    pub fn steps(mut self, steps: usize) -> Budget {
+        self.steps = steps;
+        self
+    }
+
+    pub fn deadline(mut self, deadline: Instant) -> Budget {
+        self.deadline = Some(deadline);
+        self
+    }
+}
+
+/// A search that ran out of its budget before it knew the answer.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub struct BudgetExceeded {
+    offset: usize
+}
+
+impl BudgetExceeded {
+    /// Where in the text the search gave up.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "search ran out of budget at offset {}", self.offset)
+    }
+}
+
+impl error::Error for BudgetExceeded {}
+
+// Watches a search spend its budget, stopping it once it is gone.
+pub struct Spending {
+    budget: Budget,
+    steps: usize,
+    scanned: usize,
+    exceeded: Option<BudgetExceeded>
+}
+
+impl Spending {
+    pub fn new(budget: Budget) -> Spending {
+        Spending { budget, steps: 0, scanned: 0, exceeded: None }
+    }
+
+    pub fn exceeded(&self) -> Option<BudgetExceeded> {
+        self.exceeded
+    }
+}
+
+impl Observer for Spending {
+    fn scan(&mut self, at: usize) {
+        if self.scanned.is_multiple_of(CLOCK_INTERVAL) && self.budget.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            self.exceeded = Some(BudgetExceeded { offset: at });
+        }
+        self.scanned += 1;
+    }
+
+    fn visit(&mut self, _: usize) {
+        self.steps += 1;
+    }
+
+    fn step(&mut self, at: usize, _: Option<u8>, _: &[usize], _: &Option<Vec<Option<usize>>>) {
+        if self.steps > self.budget.steps && self.exceeded.is_none() {
+            self.exceeded = Some(BudgetExceeded { offset: at });
+        }
+    }
+
+    fn stop(&self) -> bool {
+        self.exceeded.is_some()
+    }
+}
@@ -0,0 +1,53 @@
+use super::observer::Observer;
+
+/// Counts of the work a search did, to tell patterns that make searches
+/// slow from those that do not.
+#[derive(PartialEq,Eq,Debug,Clone,Copy,Default)]
+pub struct Metrics {
+    states_visited: usize,
+    threads_spawned: usize,
+    prefilter_hits: usize,
+    bytes_scanned: usize
+}
+
+impl Metrics {
+    /// How many times states were followed while adding threads, at most
+    /// the number of states per offset.
+    pub fn states_visited(&self) -> usize {
+        self.states_visited
+    }
+
+    /// How many threads were left waiting for a byte.
+    pub fn threads_spawned(&self) -> usize {
+        self.threads_spawned
+    }
+
+    /// How many places the prefilter found where a match could start.
+    pub fn prefilter_hits(&self) -> usize {
+        self.prefilter_hits
+    }
+
+    /// How many bytes were given to the threads, leaving out those the
+    /// prefilter skipped.
+    pub fn bytes_scanned(&self) -> usize {
+        self.bytes_scanned
+    }
+}
+
+impl Observer for Metrics {
+    fn scan(&mut self, _: usize) {
+        self.bytes_scanned += 1;
+    }
+
+    fn visit(&mut self, _: usize) {
+        self.states_visited += 1;
+    }
+
+    fn spawn(&mut self, _: usize) {
+        self.threads_spawned += 1;
+    }
+
+    fn candidate(&mut self, _: usize) {
+        self.prefilter_hits += 1;
+    }
+}
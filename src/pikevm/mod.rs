@@ -0,0 +1,369 @@
+use std::mem;
+
+use ::nfa::{State, Transition, NFA, Condition};
+use ::prefilter::Prefilter;
+use ::smallvec::SmallVec;
+
+mod observer;
+mod trace;
+mod metrics;
+mod budget;
+#[cfg(test)] mod spec;
+
+pub use self::trace::{Trace, TraceStep};
+pub use self::metrics::Metrics;
+pub use self::budget::{Budget, BudgetExceeded};
+use self::budget::Spending;
+use self::observer::Observer;
+
+// The threads of the simulation at one offset: the states they wait in, and
+// the offsets each recorded in the capture slots on the way there. Both lists
+// are allocated once per search and reused for every offset, and for small
+// patterns not allocated at all. A thread partway through a literal condition
+// waits in its state with the bytes it has taken, so a state can hold a
+// thread for each byte of its literal.
+struct Threads {
+    states: SmallVec<usize, INLINE_STATES>, // in order of priority
+    taken: SmallVec<usize, INLINE_STATES>, // by thread, the bytes of a literal it has taken
+    seen: SmallVec<bool, INLINE_STATES>, // the states visited while adding threads
+    slots: SmallVec<Option<usize>, INLINE_SLOTS> // `num_slots` per thread
+}
+
+const INLINE_STATES: usize = 16;
+const INLINE_SLOTS: usize = 64;
+
+impl Threads {
+    fn new(num_states: usize, num_threads: usize, num_slots: usize) -> Threads {
+        Threads {
+            states: SmallVec::with_capacity(num_threads),
+            taken: SmallVec::with_capacity(num_threads),
+            seen: SmallVec::from_elem(false, num_states),
+            slots: SmallVec::from_elem(None, num_threads * num_slots)
+        }
+    }
+
+    fn clear(&mut self) {
+        self.states.clear();
+        self.taken.clear();
+        for seen in self.seen.iter_mut() {
+            *seen = false;
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    // queues a thread in state `id`, having taken `taken` bytes of it
+    fn push(&mut self, id: usize, taken: usize, slots: &[Option<usize>]) {
+        let thread = self.states.len();
+        self.states.push(id);
+        self.taken.push(taken);
+        self.slots[thread * slots.len()..(thread + 1) * slots.len()].copy_from_slice(slots);
+    }
+}
+
+// The buffers a search works in, which can be kept for later searches with
+// the same NFA instead of allocating them again.
+pub struct Cache {
+    threads: Threads,
+    next: Threads,
+    scratch: Scratch
+}
+
+// What adding a thread works in: the steps still to take, and the slots of
+// the path being followed.
+struct Scratch {
+    steps: Vec<Step>,
+    slots: Vec<Option<usize>>
+}
+
+impl Cache {
+    pub fn new(nfa: &NFA) -> Cache {
+        let num_slots = nfa.num_groups() * 2;
+        let num_threads = max_threads(nfa);
+        Cache {
+            threads: Threads::new(nfa.num_states(), num_threads, num_slots),
+            next: Threads::new(nfa.num_states(), num_threads, num_slots),
+            scratch: Scratch {
+                steps: Vec::new(),
+                slots: vec![None; num_slots]
+            }
+        }
+    }
+
+    // whether the buffers are large enough for a search of `nfa`, as those
+    // for an NFA are for the one `NFA::with_literals` makes of it
+    fn fits(&self, nfa: &NFA) -> bool {
+        let num_slots = self.scratch.slots.len();
+        num_slots == nfa.num_groups() * 2
+            && self.threads.seen.len() >= nfa.num_states()
+            && self.threads.slots.len() >= max_threads(nfa) * num_slots
+    }
+}
+
+// the most threads there can be at one offset: one per state, or per byte of
+// a literal condition
+fn max_threads(nfa: &NFA) -> usize {
+    (0..nfa.num_states()).map(|id| match nfa.get_state(id) {
+        Some(&State::State{condition: Condition::Literal(literal), ..}) => literal.len(),
+        _ => 1
+    }).sum()
+}
+
+enum Step {
+    Follow(Transition),
+    Restore(usize, Option<usize>) // a slot's value before a save was followed
+}
+
+// Runs every thread of the NFA in lock step, in order of priority. A state
+// holds at most one thread per offset, so the work per byte is bounded by
+// the size of the NFA, and each thread carries its own capture slots: slot
+// 2i is where group i started and slot 2i + 1 where it ended.
+pub struct PikeVM<'n> {
+    nfa: &'n NFA,
+    num_slots: usize,
+    prefilter: Option<&'n dyn Prefilter>,
+    longest: bool,
+    earliest: bool
+}
+
+impl<'n> PikeVM<'n> {
+    pub fn new(nfa: &'n NFA) -> PikeVM<'n> {
+        PikeVM {
+            nfa,
+            num_slots: nfa.num_groups() * 2,
+            prefilter: None,
+            longest: false,
+            earliest: false
+        }
+    }
+
+    // skips ahead to where the prefilter finds a candidate whenever no
+    // thread is left
+    pub fn with_prefilter(mut self, prefilter: Option<&'n dyn Prefilter>) -> PikeVM<'n> {
+        self.prefilter = prefilter;
+        self
+    }
+
+    // finds the leftmost-longest match instead of the leftmost-first: threads
+    // keep running after a match, and a later one replaces it if it is longer.
+    // Groups are those of the highest priority thread that reached it.
+    pub fn with_longest(mut self, longest: bool) -> PikeVM<'n> {
+        self.longest = longest;
+        self
+    }
+
+    // stops as soon as any match is known, rather than going on to find
+    // which match the search would prefer. The match may then end early,
+    // and another thread may have started before it.
+    pub fn with_earliest(mut self, earliest: bool) -> PikeVM<'n> {
+        self.earliest = earliest;
+        self
+    }
+
+    pub fn num_slots(&self) -> usize {
+        self.num_slots
+    }
+
+    // the slots of the leftmost-first match starting at or after `start`,
+    // or only at `start` if anchored
+    pub fn captures(&self, text: &[u8], start: usize, anchored: bool) -> Option<Vec<Option<usize>>> {
+        self.captures_with(&mut Cache::new(self.nfa), text, start, anchored)
+    }
+
+    // like `captures`, but working in the buffers of `cache`
+    pub fn captures_with(&self, cache: &mut Cache, text: &[u8], start: usize, anchored: bool) -> Option<Vec<Option<usize>>> {
+        self.search(cache, text, start, anchored, &mut ())
+    }
+
+    // like `captures`, also recording what the threads did at each offset
+    pub fn trace(&self, text: &[u8], start: usize, anchored: bool) -> Trace {
+        let mut trace = Trace::new();
+        let found = self.search(&mut Cache::new(self.nfa), text, start, anchored, &mut trace);
+        trace.finish(found);
+        trace
+    }
+
+    // like `captures`, also counting the work the search did
+    pub fn metrics(&self, text: &[u8], start: usize, anchored: bool) -> (Option<Vec<Option<usize>>>, Metrics) {
+        let mut metrics = Metrics::default();
+        let found = self.search(&mut Cache::new(self.nfa), text, start, anchored, &mut metrics);
+        (found, metrics)
+    }
+
+    // like `captures`, giving up once the search has spent `budget`
+    pub fn captures_within(&self, text: &[u8], start: usize, anchored: bool, budget: Budget)
+                           -> Result<Option<Vec<Option<usize>>>, BudgetExceeded> {
+        let mut spending = Spending::new(budget);
+        let found = self.search(&mut Cache::new(self.nfa), text, start, anchored, &mut spending);
+        match spending.exceeded() {
+            Some(exceeded) => Err(exceeded),
+            None => Ok(found)
+        }
+    }
+
+    fn search<O: Observer>(&self, cache: &mut Cache, text: &[u8], start: usize, anchored: bool,
+                           observer: &mut O) -> Option<Vec<Option<usize>>> {
+        if self.nfa.num_states() == 0 {
+            return Some(self.matched_empty(start));
+        }
+
+        if !cache.fits(self.nfa) {
+            *cache = Cache::new(self.nfa);
+        }
+        let Cache { ref mut threads, ref mut next, ref mut scratch } = *cache;
+        threads.clear();
+        let mut found = None;
+
+        let mut at = start;
+        while at <= text.len() {
+            if threads.is_empty() && found.is_none() && !anchored {
+                match self.prefilter.map(|prefilter| prefilter.find(text, at)) {
+                    Some(Some(candidate)) => {
+                        observer.candidate(candidate);
+                        at = candidate
+                    },
+                    Some(None) => break,
+                    None => ()
+                }
+            }
+
+            if found.is_none() && (at == start || !anchored) {
+                for slot in scratch.slots.iter_mut() {
+                    *slot = None;
+                }
+                scratch.slots[0] = Some(at);
+                let start = Transition::id(self.nfa.get_start_id());
+                self.add_thread(threads, scratch, start, at, &mut found, observer);
+            }
+            observer.step(at, text.get(at).cloned(), &threads.states, &found);
+            if observer.stop() {
+                return None;
+            }
+
+            if threads.is_empty() && (found.is_some() || anchored) {
+                break;
+            }
+            if self.earliest && found.is_some() {
+                break;
+            }
+            if at == text.len() {
+                break;
+            }
+
+            observer.scan(at);
+            next.clear();
+            for (thread, &id) in threads.states.iter().enumerate() {
+                if let Some(&State::State{ref condition, out}) = self.nfa.get_state(id) {
+                    // a literal is checked whole as a thread starts it
+                    let taken = threads.taken[thread];
+                    let accepted = match condition {
+                        Condition::Literal(literal) => taken > 0 || text[at..].starts_with(literal.as_bytes()),
+                        condition => condition.accepts(text[at])
+                    };
+                    if !accepted {
+                        continue;
+                    }
+                    let thread_slots = &threads.slots[thread * self.num_slots..(thread + 1) * self.num_slots];
+                    if found.as_ref().is_some_and(|found| thread_slots[0] > found[0]) {
+                        continue; // started after the match, so it cannot win
+                    }
+                    if let Condition::Literal(literal) = condition {
+                        if taken + 1 < literal.len() {
+                            next.push(id, taken + 1, thread_slots);
+                            continue;
+                        }
+                    }
+
+                    observer.take(id, out);
+                    scratch.slots.copy_from_slice(thread_slots);
+                    if self.add_thread(next, scratch, out, at + 1, &mut found, observer) {
+                        break; // lower priority threads lose to this match
+                    }
+                }
+            }
+            mem::swap(threads, next);
+            at += 1;
+        }
+
+        found
+    }
+
+    fn matched_empty(&self, at: usize) -> Vec<Option<usize>> {
+        let mut slots = vec![None; self.num_slots];
+        slots[0] = Some(at);
+        slots[1] = Some(at);
+        slots
+    }
+
+    // follows empty edges from `edge`, queueing a thread for every state that
+    // consumes a byte. Returns true if a match was reached that lower
+    // priority threads lose to, which is then stored in `found`. The scratch slots may be changed on the way.
+    fn add_thread<O: Observer>(&self, threads: &mut Threads, scratch: &mut Scratch, edge: Transition, at: usize,
+                               found: &mut Option<Vec<Option<usize>>>, observer: &mut O) -> bool {
+        let Scratch { ref mut steps, ref mut slots } = *scratch;
+        steps.clear();
+        steps.push(Step::Follow(edge));
+
+        while let Some(step) = steps.pop() {
+            let edge = match step {
+                Step::Restore(slot, value) => {
+                    slots[slot] = value;
+                    continue;
+                },
+                Step::Follow(edge) => edge
+            };
+
+            match edge {
+                Transition::End => {
+                    slots[1] = Some(at);
+                    if !self.longest {
+                        *found = Some(slots.clone());
+                        return true;
+                    }
+                    // a thread that started earlier can still match after one
+                    // that started later
+                    let better = found.as_ref().is_none_or(|found| {
+                        slots[0] < found[0] || (slots[0] == found[0] && slots[1] > found[1])
+                    });
+                    if better {
+                        *found = Some(slots.clone());
+                    }
+                },
+                Transition::Id(id) => {
+                    let id = id.index();
+                    if threads.seen[id] {
+                        continue;
+                    }
+                    threads.seen[id] = true;
+                    observer.visit(id);
+
+                    match self.nfa.get_state(id) {
+                        Some(&State::State{condition: Condition::None, out}) => {
+                            steps.push(Step::Follow(out));
+                        },
+                        Some(&State::State{..}) => {
+                            threads.push(id, 0, slots);
+                            observer.spawn(id);
+                        },
+                        Some(&State::Split{out1, out2}) => {
+                            steps.push(Step::Follow(out2));
+                            steps.push(Step::Follow(out1)); // out1 is preferred
+                        },
+                        Some(&State::Save{slot, out}) => {
+                            // the slot is set only for the states reached from here
+                            steps.push(Step::Restore(slot, slots[slot]));
+                            slots[slot] = Some(at);
+                            steps.push(Step::Follow(out));
+                        },
+                        None => panic!("transition to missing state {}", id)
+                    }
+                },
+                Transition::Detached => panic!("cannot evaluate incomplete NFA")
+            }
+        }
+
+        false
+    }
+}
@@ -0,0 +1,22 @@
+use ::nfa::Transition;
+
+// What a search reports to as it goes, each observer taking what it needs.
+// Searches that nobody watches report to `()`, which compiles to nothing.
+pub trait Observer {
+    // the threads waiting at `at` for `byte`, after a thread was started there
+    fn step(&mut self, _at: usize, _byte: Option<u8>, _threads: &[usize], _found: &Option<Vec<Option<usize>>>) {}
+    // the threads are given the byte at `at`
+    fn scan(&mut self, _at: usize) {}
+    // a thread in state `from` consumed the byte and went on along `to`
+    fn take(&mut self, _from: usize, _to: Transition) {}
+    // state `id` was reached while adding threads
+    fn visit(&mut self, _id: usize) {}
+    // a thread now waits in state `id`
+    fn spawn(&mut self, _id: usize) {}
+    // the prefilter found where a match could start
+    fn candidate(&mut self, _at: usize) {}
+    // whether the search should give up, asked once per offset
+    fn stop(&self) -> bool { false }
+}
+
+impl Observer for () {}
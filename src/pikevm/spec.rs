@@ -0,0 +1,196 @@
+use ::expr::Expr;
+use ::nfa::{NFA, Transition};
+use ::prefilter::LiteralPrefilter;
+use ::regex::Match;
+use super::PikeVM;
+
+fn nfa(pattern: &str) -> NFA {
+    NFA::from_expr(&pattern.parse::<Expr>().unwrap())
+}
+
+fn captures(pattern: &str, text: &str) -> Option<Vec<Option<usize>>> {
+    PikeVM::new(&nfa(pattern)).captures(text.as_bytes(), 0, false)
+}
+
+#[test]
+fn finds_leftmost_match() {
+    assert_eq!(Some(vec![Some(2), Some(4)]), captures("ab", "xxabab"));
+    assert_eq!(Some(vec![Some(1), Some(4)]), captures("a+", "baaab"));
+    assert_eq!(None, captures("ab", "ba"));
+}
+
+#[test]
+fn prefers_left_alternative() {
+    assert_eq!(Some(vec![Some(0), Some(1)]), captures("a|ab", "ab"));
+    assert_eq!(Some(vec![Some(0), Some(2)]), captures("ab|a", "ab"));
+}
+
+#[test]
+fn records_groups() {
+    assert_eq!(Some(vec![Some(0), Some(3), Some(0), Some(1), Some(1), Some(3)]),
+               captures("(a)(bc)", "abc"));
+    assert_eq!(Some(vec![Some(1), Some(3), Some(2), Some(3)]), captures("a(b|c)", "xac"));
+}
+
+#[test]
+fn records_last_iteration_of_repeated_group() {
+    assert_eq!(Some(vec![Some(0), Some(6), Some(4), Some(6)]), captures("(ab)+", "ababab"));
+    assert_eq!(Some(vec![Some(0), Some(3), Some(2), Some(3)]), captures("([ab])*", "abb"));
+}
+
+#[test]
+fn leaves_unused_groups_unset() {
+    assert_eq!(Some(vec![Some(0), Some(1), None, None, Some(0), Some(1)]), captures("(a)|(b)", "b"));
+    assert_eq!(Some(vec![Some(0), Some(1), None, None]), captures("b(a)?", "b"));
+}
+
+#[test]
+fn searches_anchored() {
+    let nfa = nfa("a(b)");
+    let vm = PikeVM::new(&nfa);
+
+    assert_eq!(4, vm.num_slots());
+    assert_eq!(None, vm.captures(b"xab", 0, true));
+    assert_eq!(Some(vec![Some(1), Some(3), Some(2), Some(3)]), vm.captures(b"xab", 1, true));
+}
+
+#[test]
+fn matches_empty_nfa_everywhere() {
+    assert_eq!(Some(vec![Some(2), Some(2)]), PikeVM::new(&NFA::new()).captures(b"abc", 2, false));
+}
+
+#[test]
+fn runs_in_linear_time() {
+    let pattern = "a?".repeat(50) + &"a".repeat(50);
+    let text = "a".repeat(50);
+
+    assert_eq!(Some(vec![Some(0), Some(50)]), captures(&pattern, &text));
+}
+
+#[test]
+fn skips_ahead_with_prefilter() {
+    let nfa = nfa("ab+(c)");
+    let prefilter = LiteralPrefilter::new(&nfa);
+    let vm = PikeVM::new(&nfa).with_prefilter(prefilter.as_ref().map(|prefilter| prefilter as &dyn super::Prefilter));
+
+    assert_eq!(Some(vec![Some(4), Some(8), Some(7), Some(8)]), vm.captures(b"xxa-abbcab", 0, false));
+    assert_eq!(None, vm.captures(b"xxa-abbcab", 5, false));
+    assert_eq!(None, vm.captures(b"xxa-abbcab", 2, true));
+}
+
+#[test]
+fn finds_longest_match() {
+    let longest = |pattern: &str, text: &str| {
+        PikeVM::new(&nfa(pattern)).with_longest(true).captures(text.as_bytes(), 0, false)
+    };
+
+    assert_eq!(Some(vec![Some(0), Some(2)]), longest("a|ab", "ab"));
+    assert_eq!(Some(vec![Some(1), Some(4)]), longest("a|abc|ab", "xabcd"));
+    assert_eq!(Some(vec![Some(0), Some(3)]), longest("a*?", "aaa"));
+    // a match that starts earlier wins even if one starting later ends first
+    assert_eq!(Some(vec![Some(0), Some(4)]), longest("abcd|bc", "abcd"));
+    assert_eq!(Some(vec![Some(0), Some(2), Some(0), Some(2)]), longest("(a|ab)", "ab"));
+}
+
+#[test]
+fn stops_at_earliest_match() {
+    let earliest = |pattern: &str, text: &str| {
+        PikeVM::new(&nfa(pattern)).with_earliest(true).captures(text.as_bytes(), 0, false)
+    };
+
+    assert_eq!(Some(vec![Some(1), Some(2)]), earliest("a+", "-aaa"));
+    // known to match at 3 before the earlier start reaches its end
+    assert_eq!(Some(vec![Some(2), Some(3)]), earliest("abcd|c", "abcd"));
+    assert_eq!(None, earliest("ab", "aa"));
+}
+
+#[test]
+fn traces_threads_at_each_offset() {
+    let nfa = nfa("ab");
+    let trace = PikeVM::new(&nfa).trace(b"xab", 0, false);
+    let (a, b) = (nfa.get_start_id(), 1);
+
+    let offsets = trace.steps().iter().map(|step| step.at()).collect::<Vec<_>>();
+    assert_eq!(vec![0, 1, 2, 3], offsets);
+    assert_eq!(Some(b'x'), trace.steps()[0].byte());
+    assert_eq!(None, trace.steps()[3].byte());
+
+    assert_eq!(&[a][..], trace.steps()[0].threads());
+    assert!(trace.steps()[0].taken().is_empty());
+    assert_eq!(&[(a, Transition::id(b))][..], trace.steps()[1].taken());
+    assert_eq!(&[b, a][..], trace.steps()[2].threads());
+    assert_eq!(&[(b, Transition::End)][..], trace.steps()[2].taken());
+
+    assert_eq!(None, trace.steps()[2].found());
+    assert_eq!(Some(Match::new(1, 3)), trace.steps()[3].found());
+    assert_eq!(Some(Match::new(1, 3)), trace.found());
+}
+
+#[test]
+fn traces_searches_that_find_nothing() {
+    let nfa = nfa("a+b");
+    let trace = PikeVM::new(&nfa).trace(b"aa", 0, false);
+
+    assert_eq!(None, trace.found());
+    assert_eq!(3, trace.steps().len());
+    assert_eq!(2, trace.steps()[1].threads().len());
+}
+
+#[test]
+fn counts_work_done() {
+    let nfa = nfa("ab");
+    let (found, metrics) = PikeVM::new(&nfa).metrics(b"xab", 0, false);
+
+    assert_eq!(Some(1), found.and_then(|slots| slots[0]));
+    assert_eq!(3, metrics.bytes_scanned());
+    assert_eq!(0, metrics.prefilter_hits());
+    assert_eq!(4, metrics.threads_spawned()); // `a` at 0, 1 and 2, `b` at 2
+    assert!(metrics.states_visited() >= metrics.threads_spawned());
+}
+
+#[test]
+fn counts_prefilter_hits() {
+    let nfa = nfa("ab");
+    let prefilter = LiteralPrefilter::new(&nfa).unwrap();
+    let (_, metrics) = PikeVM::new(&nfa).with_prefilter(Some(&prefilter)).metrics(b"xxxxab", 0, false);
+
+    assert_eq!(1, metrics.prefilter_hits());
+    assert_eq!(2, metrics.bytes_scanned());
+}
+
+#[test]
+fn gives_up_once_out_of_budget() {
+    use std::time::Instant;
+    use super::Budget;
+
+    let nfa = nfa("(a|b)*c");
+    let vm = PikeVM::new(&nfa);
+    let text = "ab".repeat(1000) + "c";
+    assert_eq!(Ok(vm.captures(text.as_bytes(), 0, true)), vm.captures_within(text.as_bytes(), 0, true, Budget::new()));
+
+    let exceeded = vm.captures_within(text.as_bytes(), 0, true, Budget::new().steps(100)).unwrap_err();
+    assert!(exceeded.offset() > 0 && exceeded.offset() < 100);
+    assert_eq!(Err(0), vm.captures_within(text.as_bytes(), 0, true, Budget::new().deadline(Instant::now()))
+        .map_err(|exceeded| exceeded.offset()));
+}
+
+#[test]
+fn runs_literal_conditions_as_the_bytes_they_stand_for() {
+    let cases = [
+        ("abcd", "xabcabcdx"), ("(abc)+d", "abcabcabd abcabcd"), ("a(bc|bd)e", "abdabde"),
+        ("aab|aaab", "aaaab"), ("(aa)*ab", "aaaaab"), ("x[ab]yzw|xayz", "xayzxbyzw"),
+        ("abcdefghijklmnopqrstu", "abcdefghijklmnopqrstu"), ("éa|eé", "eéa")
+    ];
+    for &(pattern, text) in cases.iter() {
+        let nfa = nfa(pattern);
+        let literals = nfa.with_literals();
+        assert!(literals.num_states() < nfa.num_states(), "{}", pattern);
+        for &longest in [false, true].iter() {
+            for start in 0..text.len() + 1 {
+                assert_eq!(PikeVM::new(&nfa).with_longest(longest).captures(text.as_bytes(), start, false),
+                           PikeVM::new(&literals).with_longest(longest).captures(text.as_bytes(), start, false),
+                           "{} on {:?} from {}", pattern, text, start);
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use ::nfa::Transition;
+use ::regex::Match;
+use super::observer::Observer;
+
+/// One offset of a traced search.
+#[derive(PartialEq,Eq,Debug,Clone)]
+pub struct TraceStep {
+    at: usize,
+    byte: Option<u8>,
+    threads: Vec<usize>,
+    taken: Vec<(usize, Transition)>,
+    found: Option<Match>
+}
+
+impl TraceStep {
+    pub fn at(&self) -> usize {
+        self.at
+    }
+
+    /// The byte at the offset, `None` at the end of the text.
+    pub fn byte(&self) -> Option<u8> {
+        self.byte
+    }
+
+    /// The states with a thread waiting for the byte, in order of priority.
+    pub fn threads(&self) -> &[usize] {
+        &self.threads
+    }
+
+    /// The states whose thread consumed the byte, each with the edge it
+    /// went on along. Threads that lost to a match are not in it.
+    pub fn taken(&self) -> &[(usize, Transition)] {
+        &self.taken
+    }
+
+    /// The match that the search would report if it stopped here.
+    pub fn found(&self) -> Option<Match> {
+        self.found
+    }
+}
+
+/// Everything a search did, an offset at a time, and what it found.
+#[derive(PartialEq,Eq,Debug,Clone)]
+pub struct Trace {
+    steps: Vec<TraceStep>,
+    found: Option<Match>
+}
+
+impl Default for Trace {
+    fn default() -> Trace {
+        Trace::new()
+    }
+}
+
+impl Trace {
+    pub fn new() -> Trace {
+        Trace { steps: Vec::new(), found: None }
+    }
+
+    pub fn finish(&mut self, found: Option<Vec<Option<usize>>>) {
+        self.found = to_match(&found);
+    }
+
+    pub fn steps(&self) -> &[TraceStep] {
+        &self.steps
+    }
+
+    pub fn found(&self) -> Option<Match> {
+        self.found
+    }
+}
+
+impl Observer for Trace {
+    fn step(&mut self, at: usize, byte: Option<u8>, threads: &[usize], found: &Option<Vec<Option<usize>>>) {
+        self.steps.push(TraceStep {
+            at,
+            byte,
+            threads: threads.to_vec(),
+            taken: Vec::new(),
+            found: to_match(found)
+        });
+    }
+
+    fn take(&mut self, from: usize, to: Transition) {
+        if let Some(step) = self.steps.last_mut() {
+            step.taken.push((from, to));
+        }
+    }
+}
+
+fn to_match(found: &Option<Vec<Option<usize>>>) -> Option<Match> {
+    found.as_ref().map(|slots| Match::new(slots[0].unwrap(), slots[1].unwrap()))
+}
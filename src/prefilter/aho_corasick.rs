@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+use std::mem;
+
+const ROOT: usize = 0;
+
+#[derive(PartialEq,Debug,Clone)]
+struct Node {
+    next: Vec<(u8, usize)>, // sorted by byte
+    fail: usize, // the node for the longest proper suffix that is in the trie
+    outputs: Vec<usize> // literals ending here, including through `fail`
+}
+
+// Finds any of a set of literals in one pass over the text: a trie of the
+// literals, where a byte with no edge falls back to the longest suffix of
+// what was read so far that is still a prefix of some literal.
+#[derive(PartialEq,Debug,Clone)]
+pub struct AhoCorasick {
+    literals: Vec<Vec<u8>>,
+    nodes: Vec<Node>,
+    max_len: usize
+}
+
+impl AhoCorasick {
+    pub fn new(literals: Vec<Vec<u8>>) -> AhoCorasick {
+        let mut nodes = vec![Node { next: Vec::new(), fail: ROOT, outputs: Vec::new() }];
+
+        for (id, literal) in literals.iter().enumerate() {
+            let mut node = ROOT;
+            for &byte in literal {
+                node = match Self::edge(&nodes, node, byte) {
+                    Some(next) => next,
+                    None => {
+                        nodes.push(Node { next: Vec::new(), fail: ROOT, outputs: Vec::new() });
+                        let next = nodes.len() - 1;
+
+                        let edges = &mut nodes[node].next;
+                        let at = edges.binary_search_by_key(&byte, |&(b, _)| b).unwrap_err();
+                        edges.insert(at, (byte, next));
+                        next
+                    }
+                };
+            }
+            nodes[node].outputs.push(id);
+        }
+
+        // fail links, breadth first so that shorter suffixes are done first
+        let mut queue = nodes[ROOT].next.iter().map(|&(_, next)| next).collect::<VecDeque<_>>();
+        while let Some(node) = queue.pop_front() {
+            for (byte, next) in nodes[node].next.clone() {
+                let mut fail = nodes[node].fail;
+                while fail != ROOT && Self::edge(&nodes, fail, byte).is_none() {
+                    fail = nodes[fail].fail;
+                }
+                let fail = Self::edge(&nodes, fail, byte).unwrap_or(ROOT);
+
+                nodes[next].fail = fail;
+                let inherited = nodes[fail].outputs.clone();
+                nodes[next].outputs.extend(inherited);
+                queue.push_back(next);
+            }
+        }
+
+        AhoCorasick {
+            max_len: literals.iter().map(|literal| literal.len()).max().unwrap_or(0),
+            literals,
+            nodes
+        }
+    }
+
+    pub fn literals(&self) -> &[Vec<u8>] {
+        &self.literals
+    }
+
+    // the heap memory used by the literals and the trie
+    pub fn memory_usage(&self) -> usize {
+        let literals = self.literals.iter()
+            .map(|literal| mem::size_of::<Vec<u8>>() + literal.len())
+            .sum::<usize>();
+        let nodes = self.nodes.iter()
+            .map(|node| {
+                mem::size_of::<Node>() +
+                node.next.len() * mem::size_of::<(u8, usize)>() +
+                node.outputs.len() * mem::size_of::<usize>()
+            })
+            .sum::<usize>();
+
+        literals + nodes
+    }
+
+    // the leftmost occurrence of a literal at or after `from` as its start
+    // and the literal's index. Of the literals starting there, the first one
+    // given wins.
+    pub fn find(&self, text: &[u8], from: usize) -> Option<(usize, usize)> {
+        let mut node = ROOT;
+        let mut best: Option<(usize, usize)> = None;
+
+        for (i, &byte) in text.iter().enumerate().skip(from) {
+            if let Some((start, _)) = best {
+                if i >= start + self.max_len {
+                    break; // nothing ending later can start earlier
+                }
+            }
+
+            while node != ROOT && Self::edge(&self.nodes, node, byte).is_none() {
+                node = self.nodes[node].fail;
+            }
+            node = Self::edge(&self.nodes, node, byte).unwrap_or(ROOT);
+
+            for &id in &self.nodes[node].outputs {
+                let found = (i + 1 - self.literals[id].len(), id);
+                if best.is_none_or(|best| found < best) {
+                    best = Some(found);
+                }
+            }
+        }
+
+        best
+    }
+
+    fn edge(nodes: &[Node], node: usize, byte: u8) -> Option<usize> {
+        let edges = &nodes[node].next;
+        edges.binary_search_by_key(&byte, |&(b, _)| b).ok().map(|i| edges[i].1)
+    }
+}
@@ -0,0 +1,133 @@
+use memchr::memmem;
+
+use ::dfa::LazyDFA;
+use ::encode::{Encoder, Decoder, DecodeError};
+use ::hir::Hir;
+use ::nfa::{NFA, utf8_sequences};
+
+// A literal that every match contains after some leading part, which cannot
+// contain the literal's first byte. Searches look for the literal first and
+// then run the leading part backwards from it to find where the match starts.
+//
+// Since the leading part cannot run over the literal, a match starting at or
+// after `from` contains the first occurrence of the literal after its start.
+// Matches starting up to the first occurrence after `from` therefore all end
+// their leading part there, and the longest backwards match of it is the
+// leftmost start.
+#[derive(PartialEq,Debug,Clone)]
+pub struct InnerLiteral {
+    literal: Vec<u8>,
+    reverse_prefix: NFA // the leading part, reversed
+}
+
+impl InnerLiteral {
+    // `None` unless the pattern is a sequence with a literal after its start
+    pub fn new(hir: &Hir) -> Option<InnerLiteral> {
+        let items = match hir {
+            Hir::Concat(items) => items.iter().collect::<Vec<&Hir>>(),
+            other => vec![other]
+        };
+
+        // the longest run of single chars that does not start the pattern
+        let mut best: Option<(usize, usize)> = None;
+        let mut start = 0;
+        for end in 0..items.len() + 1 {
+            let is_char = matches!(items.get(end), Some(&&Hir::Literal(_)));
+            if is_char {
+                continue;
+            }
+
+            let len = end - start;
+            if start > 0 && len > 0 && best.is_none_or(|(a, b)| len > b - a) {
+                best = Some((start, end));
+            }
+            start = end + 1;
+        }
+
+        let (start, end) = best?;
+        let mut literal = String::new();
+        for item in &items[start..end] {
+            if let &&Hir::Literal(c) = item {
+                literal.push(c);
+            }
+        }
+        let literal = literal.into_bytes();
+
+        if items[..start].iter().any(|item| can_contain(item, literal[0])) {
+            return None;
+        }
+
+        let prefix = Hir::Concat(items[..start].iter().map(|&item| item.clone()).collect());
+
+        Some(InnerLiteral {
+            literal,
+            reverse_prefix: NFA::from_hir(&prefix).reverse()
+        })
+    }
+
+    pub fn literal(&self) -> &[u8] {
+        &self.literal
+    }
+
+    // the heap memory used by the literal and the reversed leading part
+    pub fn memory_usage(&self) -> usize {
+        self.literal.len() + self.reverse_prefix.memory_usage()
+    }
+
+    // the start of the leftmost match at or after `from`, if there is one,
+    // and the offset of the literal in it. The rest of the pattern still has
+    // to match after the literal; if it does not, no match starts before it.
+    pub fn find_start(&self, text: &[u8], from: usize) -> Option<(usize, usize)> {
+        let mut dfa = LazyDFA::new(&self.reverse_prefix).longest();
+        let mut at = from;
+
+        while at <= text.len() {
+            let found = memmem::find(&text[at..], &self.literal)? + at;
+
+            if let Some(start) = dfa.rmatch_start(&text[at..found]) {
+                return Some((at + start, found));
+            }
+
+            at = found + 1; // no match starts before this occurrence
+        }
+
+        None
+    }
+
+    pub fn encode(&self, encoder: &mut Encoder) {
+        encoder.bytes(&self.literal);
+        self.reverse_prefix.encode(encoder);
+    }
+
+    pub fn decode(decoder: &mut Decoder) -> Result<InnerLiteral, DecodeError> {
+        let offset = decoder.offset();
+        let literal = decoder.bytes()?.to_vec();
+        if literal.is_empty() {
+            return Err(DecodeError::InvalidTag(offset));
+        }
+
+        Ok(InnerLiteral {
+            literal,
+            reverse_prefix: NFA::decode(decoder)?
+        })
+    }
+}
+
+// whether any string `hir` matches could contain `byte`
+fn can_contain(hir: &Hir, byte: u8) -> bool {
+    let encodes_to = |c: char| {
+        let mut buf = [0; 4];
+        c.encode_utf8(&mut buf).as_bytes().contains(&byte)
+    };
+
+    match hir {
+        Hir::Empty => false,
+        Hir::Literal(c) => encodes_to(*c),
+        Hir::Class(ranges) => ranges.iter()
+            .flat_map(|&(first, last)| utf8_sequences(first, last))
+            .any(|sequence| sequence.iter().any(|&(first, last)| first <= byte && byte <= last)),
+        Hir::Any(terminator) => byte != *terminator,
+        Hir::Concat(items) | Hir::Alternation(items) => items.iter().any(|item| can_contain(item, byte)),
+        Hir::Repeat(_, item) | Hir::Group(_, item) => can_contain(item, byte)
+    }
+}
@@ -0,0 +1,214 @@
+use std::fmt;
+
+use memchr::{memchr, memmem};
+
+use ::nfa::{State, Transition, NFA, Condition};
+use ::regex::Match;
+
+mod aho_corasick;
+mod inner;
+#[cfg(feature = "simd")] mod teddy;
+#[cfg(test)] mod spec;
+
+pub use self::aho_corasick::AhoCorasick;
+pub use self::inner::InnerLiteral;
+#[cfg(feature = "simd")] use self::teddy::Teddy;
+
+// past these, searching for the literals is not worth it
+const MAX_LITERALS: usize = 32;
+const MAX_LITERAL_LEN: usize = 16;
+// paths can lead to the same literals, and there can be exponentially many
+const MAX_PATHS: usize = 4 * MAX_LITERALS;
+// past two to a bucket, most of what Teddy finds are not matches
+#[cfg(feature = "simd")]
+const MAX_TEDDY_LITERALS: usize = 16;
+
+/// Finds the places in a text where a match could start, so that searches
+/// skip straight to them instead of trying every offset. One given to
+/// `RegexBuilder::prefilter` takes the place of the literals the pattern
+/// starts with, such as to scan with SIMD or look up an index of the text.
+pub trait Prefilter: fmt::Debug + Send + Sync {
+    /// The first offset at or after `from` where a match could start,
+    /// `None` if no match starts after it. Skipping a place a match starts
+    /// makes searches miss it.
+    fn find(&self, text: &[u8], from: usize) -> Option<usize>;
+}
+
+#[derive(PartialEq,Debug,Clone)]
+enum Searcher {
+    Substring(Vec<u8>),
+    Literals(AhoCorasick),
+    #[cfg(feature = "simd")] Teddy(Teddy)
+}
+
+// The literals that every match starts with one of. Searches use them to
+// skip straight to the places a match could start instead of trying every
+// offset. When the pattern is nothing but an alternation of literals, the
+// prefilter finds the matches by itself.
+#[derive(PartialEq,Debug,Clone)]
+pub struct LiteralPrefilter {
+    searcher: Searcher,
+    exact: bool // the literals are the whole matches
+}
+
+impl LiteralPrefilter {
+    // `None` if matches can start with any byte, or with too many literals
+    pub fn new(nfa: &NFA) -> Option<LiteralPrefilter> {
+        let (literals, exact) = literals(nfa)?;
+
+        let searcher = if literals.len() == 1 {
+            Searcher::Substring(literals[0].clone())
+        } else {
+            match common_prefix(&literals) {
+                ref prefix if !exact && !prefix.is_empty() => Searcher::Substring(prefix.clone()),
+                _ => Self::searcher(literals)
+            }
+        };
+
+        Some(LiteralPrefilter {
+            searcher,
+            exact
+        })
+    }
+
+    // a few literals are scanned for with SIMD when it is enabled
+    #[cfg(feature = "simd")]
+    fn searcher(literals: Vec<Vec<u8>>) -> Searcher {
+        if literals.len() <= MAX_TEDDY_LITERALS {
+            Searcher::Teddy(Teddy::new(literals))
+        } else {
+            Searcher::Literals(AhoCorasick::new(literals))
+        }
+    }
+
+    #[cfg(not(feature = "simd"))]
+    fn searcher(literals: Vec<Vec<u8>>) -> Searcher {
+        Searcher::Literals(AhoCorasick::new(literals))
+    }
+
+    pub fn literals(&self) -> Vec<&[u8]> {
+        match self.searcher {
+            Searcher::Substring(ref prefix) => vec![&prefix[..]],
+            Searcher::Literals(ref literals) => literals.literals().iter().map(|l| &l[..]).collect(),
+            #[cfg(feature = "simd")]
+            Searcher::Teddy(ref literals) => literals.literals().iter().map(|l| &l[..]).collect()
+        }
+    }
+
+    // the heap memory used by the literals and what searches for them
+    pub fn memory_usage(&self) -> usize {
+        match self.searcher {
+            Searcher::Substring(ref literal) => literal.len(),
+            Searcher::Literals(ref literals) => literals.memory_usage(),
+            #[cfg(feature = "simd")]
+            Searcher::Teddy(ref literals) => literals.memory_usage()
+        }
+    }
+
+    // whether each literal found is a match of the whole pattern
+    pub fn is_exact(&self) -> bool {
+        self.exact
+    }
+
+    // the leftmost literal at or after `from`, preferring the one given first
+    // among those starting at the same offset
+    pub fn find_literal(&self, text: &[u8], from: usize) -> Option<Match> {
+        match self.searcher {
+            Searcher::Substring(ref literal) => {
+                let rest = &text[from..];
+                let found = if literal.len() == 1 {
+                    memchr(literal[0], rest)
+                } else {
+                    memmem::find(rest, literal)
+                };
+
+                found.map(|offset| Match::new(from + offset, from + offset + literal.len()))
+            },
+            Searcher::Literals(ref literals) => {
+                literals.find(text, from)
+                    .map(|(start, id)| Match::new(start, start + literals.literals()[id].len()))
+            },
+            #[cfg(feature = "simd")]
+            Searcher::Teddy(ref literals) => {
+                literals.find(text, from)
+                    .map(|(start, id)| Match::new(start, start + literals.literals()[id].len()))
+            }
+        }
+    }
+}
+
+impl Prefilter for LiteralPrefilter {
+    // the first offset at or after `from` where a literal occurs
+    fn find(&self, text: &[u8], from: usize) -> Option<usize> {
+        self.find_literal(text, from).map(|m| m.start())
+    }
+}
+
+// The literals on the paths from the start of the NFA, in order of priority,
+// each ending where its path reaches anything but a single byte. Also returns
+// whether every path ends at the end of the NFA.
+fn literals(nfa: &NFA) -> Option<(Vec<Vec<u8>>, bool)> {
+    if nfa.num_states() == 0 {
+        return None;
+    }
+
+    let mut literals: Vec<Vec<u8>> = Vec::new();
+    let mut exact = true;
+    // the paths left to follow, with the states each went through
+    let mut paths = vec![(Transition::id(nfa.get_start_id()), Vec::new(), Vec::new())];
+    let mut num_paths = 0;
+
+    while let Some((mut edge, mut literal, mut seen)) = paths.pop() {
+        num_paths += 1;
+        if num_paths > MAX_PATHS {
+            return None;
+        }
+
+        while let Transition::Id(id) = edge {
+            let id = id.index();
+            if seen.contains(&id) || literal.len() == MAX_LITERAL_LEN {
+                exact = false;
+                break;
+            }
+            seen.push(id);
+
+            edge = match nfa.get_state(id) {
+                Some(&State::State{condition: Condition::One(byte), out}) => {
+                    literal.push(byte);
+                    out
+                },
+                Some(&State::State{condition: Condition::None, out}) => out,
+                Some(&State::Save{out, ..}) => out,
+                Some(&State::Split{out1, out2}) => {
+                    paths.push((out2, literal.clone(), seen.clone()));
+                    out1 // out1 is preferred, so it is followed first
+                },
+                _ => {
+                    exact = false;
+                    break;
+                }
+            };
+        }
+
+        if literal.is_empty() {
+            return None; // a match can start anywhere
+        }
+        if !literals.contains(&literal) {
+            literals.push(literal);
+        }
+        if literals.len() > MAX_LITERALS {
+            return None;
+        }
+    }
+
+    Some((literals, exact))
+}
+
+fn common_prefix(literals: &[Vec<u8>]) -> Vec<u8> {
+    let mut prefix = literals[0].clone();
+    for literal in &literals[1..] {
+        let len = prefix.iter().zip(literal).take_while(|&(a, b)| a == b).count();
+        prefix.truncate(len);
+    }
+    prefix
+}
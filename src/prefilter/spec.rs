@@ -0,0 +1,163 @@
+use ::expr::Expr;
+use ::hir::Hir;
+use ::nfa::NFA;
+use ::regex::Match;
+use super::{Prefilter, LiteralPrefilter, AhoCorasick, InnerLiteral};
+
+fn prefilter(pattern: &str) -> Option<LiteralPrefilter> {
+    LiteralPrefilter::new(&NFA::from_expr(&pattern.parse::<Expr>().unwrap()))
+}
+
+fn literals(pattern: &str) -> Vec<Vec<u8>> {
+    prefilter(pattern).map(|p| p.literals().iter().map(|l| l.to_vec()).collect()).unwrap_or_default()
+}
+
+#[test]
+fn extracts_literal_prefix() {
+    assert_eq!(vec![b"ERROR: ".to_vec()], literals("ERROR: [0123456789]+"));
+    assert_eq!(vec![b"ab".to_vec()], literals("(ab)c*"));
+    assert_eq!(vec![b"abc".to_vec()], literals("abc"));
+    assert_eq!(vec![b"a".to_vec()], literals("a+b"));
+    assert_eq!(vec!["é".as_bytes().to_vec()], literals("é+"));
+}
+
+#[test]
+fn has_no_prefilter_when_a_match_can_start_anywhere() {
+    assert!(prefilter("a*").is_none());
+    assert!(prefilter("ab?|c?").is_none());
+    assert!(prefilter(".b").is_none());
+    assert!(prefilter("[ab]c").is_none());
+    assert!(prefilter("a|b*").is_none());
+}
+
+#[test]
+fn extracts_alternatives_in_order() {
+    assert_eq!(vec![b"foo".to_vec(), b"bar".to_vec(), b"baz".to_vec()], literals("foo|bar|baz"));
+    assert_eq!(vec![b"xa".to_vec(), b"xb".to_vec(), b"y".to_vec()], literals("(x(a|b)|y)[cd]"));
+}
+
+#[test]
+fn gives_up_on_too_many_alternatives() {
+    assert!(prefilter("(a|b)(c|d)(e|f)(g|h)(i|j)(k|l)").is_none());
+}
+
+#[test]
+fn uses_common_prefix_of_alternatives() {
+    assert_eq!(vec![b"ab".to_vec()], literals("ab(c|d)."));
+    assert_eq!(vec![b"abc".to_vec(), b"abd".to_vec()], literals("ab(c|d)"));
+}
+
+#[test]
+fn knows_when_literals_are_whole_matches() {
+    assert!(prefilter("foo|bar").unwrap().is_exact());
+    assert!(prefilter("(foo)").unwrap().is_exact());
+    assert!(!prefilter("foo|bar+").unwrap().is_exact());
+    assert!(!prefilter("foo.").unwrap().is_exact());
+}
+
+#[test]
+fn finds_candidates() {
+    let single = prefilter("x+").unwrap();
+    assert_eq!(Some(3), single.find(b"abcxx", 0));
+    assert_eq!(Some(4), single.find(b"abcxx", 4));
+    assert_eq!(None, single.find(b"abcxx", 5));
+
+    let many = prefilter("ERROR: .").unwrap();
+    assert_eq!(Some(8), many.find(b"ok\nERRORERROR: 1", 3));
+    assert_eq!(None, many.find(b"ERROR 1", 0));
+
+    let alternatives = prefilter("foo|bar.").unwrap();
+    assert_eq!(Some(2), alternatives.find(b"fobarfoo", 0));
+    assert_eq!(Some(5), alternatives.find(b"fobarfoo", 3));
+}
+
+#[test]
+fn finds_leftmost_first_literal() {
+    let prefilter = prefilter("foobar|foo|oob").unwrap();
+
+    assert_eq!(Some(Match::new(1, 7)), prefilter.find_literal(b"xfoobar", 0));
+    assert_eq!(Some(Match::new(1, 4)), prefilter.find_literal(b"xfooba", 0));
+    assert_eq!(Some(Match::new(2, 5)), prefilter.find_literal(b"xfoobar", 2));
+}
+
+#[test]
+fn aho_corasick_finds_leftmost_start() {
+    let ac = AhoCorasick::new(vec![b"bcd".to_vec(), b"abcdef".to_vec(), b"c".to_vec()]);
+
+    assert_eq!(Some((0, 1)), ac.find(b"abcdefg", 0));
+    assert_eq!(Some((1, 0)), ac.find(b"abcdx", 0));
+    assert_eq!(Some((2, 2)), ac.find(b"abcdx", 2));
+    assert_eq!(Some((3, 2)), ac.find(b"xbxc", 0));
+    assert_eq!(None, ac.find(b"xyz", 0));
+}
+
+#[test]
+fn aho_corasick_follows_fail_links() {
+    let ac = AhoCorasick::new(vec![b"he".to_vec(), b"she".to_vec(), b"hers".to_vec()]);
+
+    assert_eq!(Some((1, 1)), ac.find(b"ushers", 0));
+    assert_eq!(Some((2, 0)), ac.find(b"ushers", 2));
+    assert_eq!(None, ac.find(b"ushers", 3));
+    assert_eq!(Some((4, 0)), ac.find(b"hhshhe", 0));
+}
+
+#[test]
+fn gives_up_on_too_many_paths() {
+    assert!(prefilter(&("a?".repeat(100) + &"a".repeat(100))).is_none());
+}
+
+fn inner(pattern: &str) -> Option<InnerLiteral> {
+    InnerLiteral::new(&Hir::from_expr(&pattern.parse::<Expr>().unwrap()))
+}
+
+#[test]
+fn picks_longest_inner_literal() {
+    assert_eq!(b"@example.com", inner("[abc]+@example\\.com").unwrap().literal());
+    assert_eq!(b"bcd", inner("[xy]*a[xy]bcd").unwrap().literal());
+    assert_eq!(b"@", inner("([ab]+)@([ab]+)").unwrap().literal());
+}
+
+#[test]
+fn needs_inner_literal_the_prefix_cannot_contain() {
+    assert!(inner("abc[xy]").is_none());
+    assert!(inner("[ab]+").is_none());
+    assert!(inner(".*@x").is_none());
+    assert!(inner("[ab@]+@x").is_none());
+}
+
+#[test]
+fn finds_leftmost_start_from_inner_literal() {
+    let inner = inner("[ab]+@").unwrap();
+
+    assert_eq!(Some((2, 4)), inner.find_start(b"xxab@", 0));
+    assert_eq!(Some((3, 4)), inner.find_start(b"xxab@", 3));
+    assert_eq!(Some((5, 6)), inner.find_start(b"x@ @ a@", 0));
+    assert_eq!(None, inner.find_start(b"x@ @", 0));
+    assert_eq!(None, inner.find_start(b"ab@", 3));
+}
+
+#[test]
+fn reports_memory_usage() {
+    assert_eq!(3, prefilter("foo[ab]").unwrap().memory_usage());
+    assert!(prefilter("foo|bar").unwrap().memory_usage() > 6);
+    assert!(inner("[ab]+@x").unwrap().memory_usage() > 2);
+}
+
+#[cfg(feature = "simd")]
+#[test]
+fn teddy_finds_what_aho_corasick_finds() {
+    use ::rng::Rng;
+    use super::teddy::Teddy;
+
+    let mut rng = Rng::new(7);
+    for _ in 0..200 {
+        let literals = (0..1 + rng.below(12))
+            .map(|_| (0..1 + rng.below(5)).map(|_| b"abcq\xe9"[rng.below(5)]).collect::<Vec<u8>>())
+            .collect::<Vec<_>>();
+        let text = (0..rng.below(80)).map(|_| b"abcdq\xe9"[rng.below(6)]).collect::<Vec<u8>>();
+        let (teddy, ac) = (Teddy::new(literals.clone()), AhoCorasick::new(literals.clone()));
+        for from in 0..text.len() + 1 {
+            assert_eq!(ac.find(&text, from), teddy.find(&text, from), "{:?} in {:?} from {}", literals, text, from);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::mem;
+
+// Finds which of a few literals could start at each of 16 offsets at once,
+// from their first bytes, as the Teddy algorithm does. Each literal is put in
+// one of 8 buckets, a bit each. For each of the first `len` bytes, two tables
+// give the buckets with a literal having each low and each high nibble
+// there. Looking up the nibbles of 16 bytes in one instruction and and-ing
+// what comes out gives the buckets that could start at each offset, whose
+// literals are then compared in full. Without SIMD, or near the end of the
+// text, the same tables are looked up one offset at a time.
+#[derive(PartialEq,Debug,Clone)]
+pub struct Teddy {
+    literals: Vec<Vec<u8>>,
+    buckets: [Vec<usize>; 8], // the literals in each bucket, in the order given
+    masks: Vec<([u8; 16], [u8; 16])> // by leading byte, the buckets by low and by high nibble
+}
+
+// the most leading bytes looked up
+const MAX_LEN: usize = 3;
+
+impl Teddy {
+    // the literals cannot be empty
+    pub fn new(literals: Vec<Vec<u8>>) -> Teddy {
+        let len = literals.iter().map(|literal| literal.len()).min().unwrap_or(0).min(MAX_LEN);
+        let mut buckets: [Vec<usize>; 8] = Default::default();
+        let mut masks = vec![([0; 16], [0; 16]); len];
+
+        for (id, literal) in literals.iter().enumerate() {
+            let bucket = id % 8;
+            buckets[bucket].push(id);
+            for (&byte, &mut (ref mut low, ref mut high)) in literal.iter().zip(masks.iter_mut()) {
+                low[(byte & 0xf) as usize] |= 1 << bucket;
+                high[(byte >> 4) as usize] |= 1 << bucket;
+            }
+        }
+
+        Teddy {
+            literals,
+            buckets,
+            masks
+        }
+    }
+
+    pub fn literals(&self) -> &[Vec<u8>] {
+        &self.literals
+    }
+
+    // the heap memory used by the literals and the tables
+    pub fn memory_usage(&self) -> usize {
+        let literals = self.literals.iter()
+            .map(|literal| mem::size_of::<Vec<u8>>() + literal.len())
+            .sum::<usize>();
+        literals +
+        self.literals.len() * mem::size_of::<usize>() +
+        self.masks.len() * mem::size_of::<([u8; 16], [u8; 16])>()
+    }
+
+    // the leftmost occurrence of a literal at or after `from` as its start
+    // and the literal's index. Of the literals starting there, the first one
+    // given wins.
+    pub fn find(&self, text: &[u8], from: usize) -> Option<(usize, usize)> {
+        let mut at = from;
+        // a block needs 16 bytes from each leading byte on
+        let block = 16 + self.masks.len() - 1;
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("ssse3") {
+                while at + block <= text.len() {
+                    let buckets = unsafe { self.buckets_ssse3(text, at) };
+                    if let Some(found) = self.verify_block(text, at, &buckets) {
+                        return Some(found);
+                    }
+                    at += 16;
+                }
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            while at + block <= text.len() {
+                let buckets = unsafe { self.buckets_neon(text, at) };
+                if let Some(found) = self.verify_block(text, at, &buckets) {
+                    return Some(found);
+                }
+                at += 16;
+            }
+        }
+
+        while at < text.len() {
+            let buckets = self.buckets_at(text, at);
+            if let Some(id) = self.verify(text, at, buckets) {
+                return Some((at, id));
+            }
+            at += 1;
+        }
+        None
+    }
+
+    // the buckets that could start at `at`, looked up one byte at a time
+    fn buckets_at(&self, text: &[u8], at: usize) -> u8 {
+        if at + self.masks.len() > text.len() {
+            return 0; // no literal fits
+        }
+        self.masks.iter().zip(&text[at..])
+            .fold(0xff, |buckets, ((low, high), &byte)| {
+                buckets & low[(byte & 0xf) as usize] & high[(byte >> 4) as usize]
+            })
+    }
+
+    // the first candidate in a block of 16 that is a literal
+    fn verify_block(&self, text: &[u8], at: usize, buckets: &[u8; 16]) -> Option<(usize, usize)> {
+        buckets.iter().enumerate()
+            .filter(|&(_, &buckets)| buckets != 0)
+            .filter_map(|(offset, &buckets)| self.verify(text, at + offset, buckets).map(|id| (at + offset, id)))
+            .next()
+    }
+
+    // the first literal given, of those in `buckets`, starting at `at`
+    fn verify(&self, text: &[u8], at: usize, buckets: u8) -> Option<usize> {
+        (0..8).filter(|&bucket| buckets & (1 << bucket) != 0)
+            .flat_map(|bucket| self.buckets[bucket].iter().cloned())
+            .filter(|&id| text[at..].starts_with(&self.literals[id]))
+            .min()
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "ssse3")]
+    unsafe fn buckets_ssse3(&self, text: &[u8], at: usize) -> [u8; 16] {
+        use std::arch::x86_64::*;
+
+        let nibble = _mm_set1_epi8(0xf);
+        let mut found = _mm_set1_epi8(-1);
+        for (i, (low, high)) in self.masks.iter().enumerate() {
+            let bytes = _mm_loadu_si128(text[at + i..].as_ptr() as *const __m128i);
+            let lows = _mm_and_si128(bytes, nibble);
+            let highs = _mm_and_si128(_mm_srli_epi16(bytes, 4), nibble);
+            let buckets = _mm_and_si128(
+                _mm_shuffle_epi8(_mm_loadu_si128(low.as_ptr() as *const __m128i), lows),
+                _mm_shuffle_epi8(_mm_loadu_si128(high.as_ptr() as *const __m128i), highs));
+            found = _mm_and_si128(found, buckets);
+        }
+
+        let mut buckets = [0; 16];
+        _mm_storeu_si128(buckets.as_mut_ptr() as *mut __m128i, found);
+        buckets
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    unsafe fn buckets_neon(&self, text: &[u8], at: usize) -> [u8; 16] {
+        use std::arch::aarch64::*;
+
+        let nibble = vdupq_n_u8(0xf);
+        let mut found = vdupq_n_u8(0xff);
+        for (i, &(ref low, ref high)) in self.masks.iter().enumerate() {
+            let bytes = vld1q_u8(text[at + i..].as_ptr());
+            let lows = vandq_u8(bytes, nibble);
+            let highs = vshrq_n_u8::<4>(bytes);
+            let buckets = vandq_u8(vqtbl1q_u8(vld1q_u8(low.as_ptr()), lows),
+                                   vqtbl1q_u8(vld1q_u8(high.as_ptr()), highs));
+            found = vandq_u8(found, buckets);
+        }
+
+        let mut buckets = [0; 16];
+        vst1q_u8(buckets.as_mut_ptr(), found);
+        buckets
+    }
+}
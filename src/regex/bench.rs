@@ -1,4 +1,3 @@
-use std::iter;
 
 extern crate test;
 use self::test::Bencher;
@@ -28,8 +27,8 @@ fn bench_100(b: &mut Bencher) {
 fn bench_difficult_regex(b: &mut Bencher, size: usize) {
     // 'a?a?aa' maching text "aa"
 
-    let text: String = iter::repeat("a").take(size).collect();
-    let mut regex: String = iter::repeat("a?").take(size).collect();
+    let text: String = std::iter::repeat_n("a", size).collect();
+    let mut regex: String = std::iter::repeat_n("a?", size).collect();
     regex.push_str(&text.clone());
 
     let regex = Regex::from(&regex).unwrap();
@@ -41,3 +40,51 @@ fn bench_difficult_regex(b: &mut Bencher, size: usize) {
 }
 
 
+
+#[bench]
+fn bench_captures_backtracking(b: &mut Bencher) {
+    let regex = Regex::from("([abc]+)@([abc]+)").unwrap();
+
+    b.iter(|| {
+        assert!(regex.captures("xx abcab@bca yy").is_some());
+    });
+}
+
+#[bench]
+fn bench_captures_pike_vm(b: &mut Bencher) {
+    let nfa = ::nfa::NFA::from_expr(&"([abc]+)@([abc]+)".parse::<::expr::Expr>().unwrap());
+
+    b.iter(|| {
+        assert!(::pikevm::PikeVM::new(&nfa).captures(b"xx abcab@bca yy", 0, false).is_some());
+    });
+}
+
+#[bench]
+fn bench_captures_with_prefix(b: &mut Bencher) {
+    let regex = Regex::from("ERROR: ([0123456789]+)").unwrap();
+    let text: String = std::iter::repeat_n("INFO: all good\n", 1000).collect::<String>() + "ERROR: 42\n";
+
+    b.iter(|| {
+        assert!(regex.captures(&text).is_some());
+    });
+}
+
+#[bench]
+fn bench_captures_keywords(b: &mut Bencher) {
+    let regex = Regex::from("foo|bar|baz|quux").unwrap();
+    let text: String = std::iter::repeat_n("lorem ipsum dolor ", 1000).collect::<String>() + "quux";
+
+    b.iter(|| {
+        assert!(regex.captures(&text).is_some());
+    });
+}
+
+#[bench]
+fn bench_captures_inner_literal(b: &mut Bencher) {
+    let regex = Regex::from("([abcdefghijklmnopqrstuvwxyz]+)@example\\.com").unwrap();
+    let text: String = std::iter::repeat_n("lorem ipsum dolor ", 1000).collect::<String>() + "joe@example.com";
+
+    b.iter(|| {
+        assert!(regex.captures(&text).is_some());
+    });
+}
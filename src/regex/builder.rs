@@ -0,0 +1,200 @@
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use ::error::Error;
+use ::expr::{Expr, Syntax};
+use ::hir::Translator;
+use ::nfa::NFA;
+use ::prefilter::Prefilter;
+use super::{Regex, CustomPrefilter, group_names};
+
+pub const DEFAULT_NEST_LIMIT: usize = 250;
+const DEFAULT_SIZE_LIMIT: usize = 10 * (1 << 20);
+
+/// Compiles a pattern with options other than the defaults. The limits make
+/// compiling fail with an error instead of running out of stack or memory on
+/// patterns from untrusted sources.
+#[derive(Clone)]
+pub struct RegexBuilder {
+    pattern: String,
+    expr: Option<Expr>, // already parsed, or built without a pattern
+    nest_limit: usize,
+    size_limit: usize,
+    longest: bool,
+    case_insensitive: bool,
+    line_terminator: u8,
+    syntax: Syntax,
+    prefilter: Option<Arc<dyn Prefilter>>
+}
+
+impl RegexBuilder {
+    pub fn new(pattern: &str) -> RegexBuilder {
+        RegexBuilder {
+            pattern: pattern.to_owned(),
+            expr: None,
+            nest_limit: DEFAULT_NEST_LIMIT,
+            size_limit: DEFAULT_SIZE_LIMIT,
+            longest: false,
+            case_insensitive: false,
+            line_terminator: b'\n',
+            syntax: Syntax::Default,
+            prefilter: None
+        }
+    }
+
+    /// Compiles an expression built in code rather than parsed from a
+    /// pattern, so that nothing has to be escaped. The regex keeps an
+    /// equivalent pattern as its text.
+    pub fn from_expr(expr: Expr) -> RegexBuilder {
+        let mut builder = RegexBuilder::new(&expr.to_pattern());
+        builder.expr = Some(expr);
+        builder
+    }
+
+    /// How deeply groups, repetitions and alternations may nest, 250 by default.
+    pub fn nest_limit(mut self, limit: usize) -> RegexBuilder {
+        self.nest_limit = limit;
+        self
+    }
+
+    /// The most bytes the compiled NFA may take, 10MB by default.
+    pub fn size_limit(mut self, limit: usize) -> RegexBuilder {
+        self.size_limit = limit;
+        self
+    }
+
+    /// Makes `match_offset`, `find` and `captures` report the longest of the
+    /// matches starting earliest, as POSIX tools like awk and grep do,
+    /// instead of the leftmost-first one. Groups are set as on the path the
+    /// leftmost-first search would prefer among those reaching that match.
+    pub fn leftmost_longest(mut self, yes: bool) -> RegexBuilder {
+        self.longest = yes;
+        self
+    }
+
+    /// Makes letters match in either case.
+    pub fn case_insensitive(mut self, yes: bool) -> RegexBuilder {
+        self.case_insensitive = yes;
+        self
+    }
+
+    /// The byte `.` does not match, a newline by default. With `b'\0'`,
+    /// `.` stops at the ends of names listed by `find -print0`. It must be
+    /// an ascii byte, as any other is part of a char; building fails with
+    /// `Error::InvalidLineTerminator` otherwise. It only affects `.`: the
+    /// one anchor, a `^` starting the pattern, anchors at the start of the
+    /// text whatever the terminator.
+    pub fn line_terminator(mut self, byte: u8) -> RegexBuilder {
+        self.line_terminator = byte;
+        self
+    }
+
+    /// The syntax the pattern is written in, this crate's own by default.
+    /// Patterns from `grep` and `sed` scripts compile unchanged with the
+    /// POSIX syntaxes.
+    pub fn syntax(mut self, syntax: Syntax) -> RegexBuilder {
+        self.syntax = syntax;
+        self
+    }
+
+    /// Skips ahead to the places `prefilter` finds instead of those the
+    /// literals the pattern starts with give. It is not kept by `to_bytes`.
+    pub fn prefilter(mut self, prefilter: Arc<dyn Prefilter>) -> RegexBuilder {
+        self.prefilter = Some(prefilter);
+        self
+    }
+
+    pub fn build(&self) -> Result<Regex, Error> {
+        if !self.line_terminator.is_ascii() {
+            return Err(Error::InvalidLineTerminator);
+        }
+        let parsed;
+        let mut anchored_start = false;
+        let expr = match self.expr {
+            Some(ref expr) => expr,
+            None => {
+                let expr = Expr::parse_with_syntax(&self.pattern, self.syntax, self.nest_limit, self.size_limit)?;
+                let (expr, anchored) = strip_start_anchor(&self.pattern, expr);
+                parsed = expr;
+                anchored_start = anchored;
+                &parsed
+            }
+        };
+        if expr.nest_depth() > self.nest_limit {
+            return Err(Error::NestLimitExceeded);
+        }
+        let expr = expr.simplify();
+
+        let hir = Translator::new()
+            .case_insensitive(self.case_insensitive)
+            .line_terminator(self.line_terminator)
+            .translate(&expr);
+        let nfa = NFA::from_hir(&hir);
+        if nfa.memory_usage() > self.size_limit {
+            return Err(Error::SizeLimitExceeded);
+        }
+
+        let mut regex = Regex::from_hir(&self.pattern, &hir, nfa, self.longest);
+        regex.anchored_start = anchored_start;
+        regex.case_insensitive = self.case_insensitive;
+        regex.line_terminator = self.line_terminator;
+        // a pattern made from an expression is in this crate's own syntax
+        regex.syntax = if self.expr.is_some() { Syntax::Default } else { self.syntax };
+        regex.names = group_names(&self.pattern, regex.syntax, regex.capture_len());
+        if let Some(ref prefilter) = self.prefilter {
+            let program = Arc::get_mut(&mut regex.program).unwrap();
+            program.custom_prefilter = Some(CustomPrefilter(prefilter.clone()));
+            program.inner = None;
+        }
+        Ok(regex)
+    }
+}
+
+// The expression without the `^` the pattern starts with, if it does, and
+// whether it did. The parsers read `^` as a char, and turn it away where
+// it would anchor less than the whole pattern, as within an alternation or
+// a group.
+pub fn strip_start_anchor(pattern: &str, expr: Expr) -> (Expr, bool) {
+    if !pattern.starts_with('^') {
+        return (expr, false);
+    }
+    match expr {
+        Expr::Single('^') => (Expr::Empty, true),
+        Expr::Concat(ref items) if items[0] == Expr::Single('^') => (Expr::concat(items[1..].iter().cloned()), true),
+        _ => (expr, false)
+    }
+}
+
+// Builders are equal when they build the same regex. An expression built in
+// code is left out, as the pattern is made from it. Prefilters are only the
+// same if they are the very same one, and are not hashed.
+impl PartialEq for RegexBuilder {
+    fn eq(&self, other: &RegexBuilder) -> bool {
+        self.pattern == other.pattern &&
+            self.nest_limit == other.nest_limit &&
+            self.size_limit == other.size_limit &&
+            self.longest == other.longest &&
+            self.case_insensitive == other.case_insensitive &&
+            self.line_terminator == other.line_terminator &&
+            self.syntax == other.syntax &&
+            match (&self.prefilter, &other.prefilter) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (&None, &None) => true,
+                _ => false
+            }
+    }
+}
+
+impl Eq for RegexBuilder {}
+
+impl Hash for RegexBuilder {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.pattern.hash(state);
+        self.nest_limit.hash(state);
+        self.size_limit.hash(state);
+        self.longest.hash(state);
+        self.case_insensitive.hash(state);
+        self.line_terminator.hash(state);
+        self.syntax.hash(state);
+    }
+}
@@ -0,0 +1,46 @@
+use ::span::Span;
+use super::Match;
+
+// A piece of a highlighted text: either a match or the text between two.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub struct Segment<'t> {
+    text: &'t str,
+    start: usize,
+    matched: bool
+}
+
+impl<'t> Segment<'t> {
+    pub fn as_str(&self) -> &'t str {
+        self.text
+    }
+
+    // where the segment is in the whole text
+    pub fn span(&self) -> Span {
+        Span::new(self.start, self.start + self.text.len())
+    }
+
+    pub fn is_match(&self) -> bool {
+        self.matched
+    }
+}
+
+// Cuts `text` at the ends of `matches`, which are in order and do not
+// overlap. Empty matches give no segment, and no segment is empty.
+pub fn segments<'t>(text: &'t str, matches: &[Match]) -> Vec<Segment<'t>> {
+    let mut segments = Vec::with_capacity(2 * matches.len() + 1);
+    let mut push = |start: usize, end: usize, matched: bool| {
+        if start < end {
+            segments.push(Segment { text: &text[start..end], start, matched });
+        }
+    };
+
+    let mut at = 0;
+    for m in matches {
+        push(at, m.start(), false);
+        push(m.start(), m.end(), true);
+        at = m.end();
+    }
+    push(at, text.len(), false);
+
+    segments
+}
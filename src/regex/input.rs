@@ -0,0 +1,115 @@
+use std::error;
+use std::fmt;
+
+use ::nfa::ByteSet;
+use ::span::Span;
+use super::Anchored;
+
+/// What to search and how: the text, the part of it a match has to lie in,
+/// whether the match has to start where the search starts, and whether the
+/// search stops as soon as a match is known, and which bytes end it early.
+/// Offsets in matches are into the whole text.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub struct Input<'t> {
+    text: &'t str,
+    start: usize,
+    end: usize,
+    anchored: Anchored,
+    earliest: bool,
+    quit: ByteSet
+}
+
+impl<'t> Input<'t> {
+    /// Searches all of `text`, unanchored, for the leftmost match.
+    pub fn new(text: &'t str) -> Input<'t> {
+        Input {
+            text,
+            start: 0,
+            end: text.len(),
+            anchored: Anchored::No,
+            earliest: false,
+            quit: ByteSet::new()
+        }
+    }
+
+    /// Only looks for a match within `start..end`. Panics if the span is not
+    /// within the text or does not fall on char boundaries.
+    pub fn span(mut self, start: usize, end: usize) -> Input<'t> {
+        assert!(start <= end && self.text.is_char_boundary(start) && self.text.is_char_boundary(end),
+                "invalid span {}..{} for text of length {}", start, end, self.text.len());
+        self.start = start;
+        self.end = end;
+        self
+    }
+
+    pub fn anchored(mut self, anchored: Anchored) -> Input<'t> {
+        self.anchored = anchored;
+        self
+    }
+
+    /// Stops at the first match found, which may end before the match a
+    /// full search would report. Enough to tell whether there is one.
+    pub fn earliest(mut self, yes: bool) -> Input<'t> {
+        self.earliest = yes;
+        self
+    }
+
+    /// Makes the search stop at `byte`, as if the text ended there, so
+    /// that no match takes it in. If there is no match before it, the
+    /// search fails with a `Quit` at its offset rather than finding none,
+    /// for the caller to decide where to go on from.
+    pub fn quit(mut self, byte: u8) -> Input<'t> {
+        self.quit.insert(byte);
+        self
+    }
+
+    pub fn text(&self) -> &'t str {
+        self.text
+    }
+
+    pub fn get_span(&self) -> Span {
+        Span::new(self.start, self.end)
+    }
+
+    pub fn get_anchored(&self) -> Anchored {
+        self.anchored
+    }
+
+    pub fn get_earliest(&self) -> bool {
+        self.earliest
+    }
+
+    pub fn get_quit(&self) -> ByteSet {
+        self.quit
+    }
+}
+
+/// A search that reached one of its quit bytes without finding a match
+/// before it.
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub struct Quit {
+    byte: u8,
+    offset: usize
+}
+
+impl Quit {
+    pub fn new(byte: u8, offset: usize) -> Quit {
+        Quit { byte, offset }
+    }
+
+    pub fn byte(&self) -> u8 {
+        self.byte
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for Quit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "search quit at byte {:?} at offset {}", self.byte as char, self.offset)
+    }
+}
+
+impl error::Error for Quit {}
@@ -0,0 +1,57 @@
+use std::io::{self, BufRead};
+
+use super::{Regex, Cache, Match};
+
+// Iterator over the lines of a reader that have a match, as the line number
+// counting from 1, the line without its `\n`, and the non-overlapping matches
+// in it. Fails on the first line that cannot be read or is not UTF-8.
+pub struct SearchLines<'r, R> {
+    regex: &'r Regex,
+    cache: Cache,
+    reader: R,
+    line_number: usize,
+    done: bool
+}
+
+impl<'r, R: BufRead> SearchLines<'r, R> {
+    pub fn new(regex: &'r Regex, reader: R) -> SearchLines<'r, R> {
+        SearchLines {
+            cache: regex.create_cache(),
+            regex,
+            reader,
+            line_number: 0,
+            done: false
+        }
+    }
+}
+
+impl<'r, R: BufRead> Iterator for SearchLines<'r, R> {
+    type Item = io::Result<(usize, String, Vec<Match>)>;
+
+    fn next(&mut self) -> Option<io::Result<(usize, String, Vec<Match>)>> {
+        let mut line = String::new();
+        while !self.done {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => self.done = true,
+                Ok(_) => {
+                    self.line_number += 1;
+                    if line.ends_with('\n') {
+                        line.pop();
+                    }
+
+                    let matches = self.regex.find_all(&mut self.cache, &line);
+                    if !matches.is_empty() {
+                        return Some(Ok((self.line_number, line, matches)));
+                    }
+                },
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        None
+    }
+}
@@ -1,29 +1,922 @@
-use ::expr::Expr;
-use ::nfa::NFA;
-use ::matcher::Matcher;
+use std::cmp::Reverse;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{Read, BufRead};
+use std::ops::{Index, Range};
+use std::str::FromStr;
+use std::sync::Arc;
 
+use ::encode::{Encoder, Decoder, DecodeError};
+use ::error::Error;
+use ::expr::Syntax;
+use ::hir::Hir;
+use ::nfa::{NFA, DenseNFA, Cost};
+use ::dfa::{DenseDFA, LazyDFA};
+use ::stream::{Stream, StreamMatches, StreamSplit};
+use ::meta::Meta;
+use ::pikevm::{PikeVM, Trace, Metrics, Budget, BudgetExceeded};
+use ::prefilter::{Prefilter, LiteralPrefilter, InnerLiteral};
+use ::onepass::OnePass;
+use ::span::Span;
+
+pub use self::builder::RegexBuilder;
+pub use self::set::{RegexSet, SetMatches};
+pub use self::input::{Input, Quit};
+pub use self::lines::SearchLines;
+pub use self::highlight::Segment;
+pub use ::meta::Cache;
+
+
+mod builder;
+mod set;
+mod input;
+mod lines;
+mod highlight;
 #[cfg(test)] mod spec;
 #[cfg(test)] mod bench;
+#[cfg(feature = "serde")] mod serialize;
+#[cfg(feature = "parallel")] mod parallel;
 
-#[derive(PartialEq,Debug)]
+const MAGIC: &[u8; 4] = b"RMRX";
+const VERSION: u8 = 8;
+// patterns matching more strings than this are not taken for literals
+const MAX_LITERALS: usize = 256;
+
+// Cloning a regex shares its automata, so each thread can have its own
+// clone cheaply.
+#[derive(PartialEq,Debug,Clone)]
 pub struct Regex {
-    nfa: NFA
+    pattern: String,
+    program: Arc<Program>,
+    longest: bool, // leftmost-longest rather than leftmost-first
+    case_insensitive: bool,
+    line_terminator: u8, // the byte `.` does not match
+    syntax: Syntax, // the syntax `pattern` is written in
+    anchored_start: bool, // the pattern starts with `^`, so matches only start at 0
+    names: Arc<Vec<Option<String>>> // of each group, shared with every `Captures`
+}
+
+// What a pattern compiles to.
+#[derive(PartialEq,Debug,Clone)]
+struct Program {
+    nfa: NFA,
+    dense_nfa: DenseNFA, // laid out for the stream search
+    literal_nfa: NFA, // with runs of bytes taken as literals, for the Pike VM
+    reverse_nfa: NFA, // matches the reversed pattern, for searching backwards
+    dfa: Option<DenseDFA>,
+    onepass: Option<OnePass>, // only for leftmost-first searches
+    prefilter: Option<LiteralPrefilter>,
+    inner: Option<InnerLiteral>, // only when there is no prefilter
+    custom_prefilter: Option<CustomPrefilter> // used instead of both, if given
+}
+
+// A prefilter from `RegexBuilder::prefilter`, the same as another only if it
+// is the very same one.
+#[derive(Debug,Clone)]
+struct CustomPrefilter(Arc<dyn Prefilter>);
+
+impl PartialEq for CustomPrefilter {
+    fn eq(&self, other: &CustomPrefilter) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
 }
 
 impl Regex {
-    pub fn from(pattern: &str) -> Result<Regex, String> {
-        let expr = pattern.parse::<Expr>();
-        expr.map(|expr| Regex {
-            nfa: NFA::from_expr(&expr)
+    pub fn from(pattern: &str) -> Result<Regex, Error> {
+        RegexBuilder::new(pattern).build()
+    }
+
+    // compiles a pattern that was checked against the builder's limits
+    fn from_hir(pattern: &str, hir: &Hir, nfa: NFA, longest: bool) -> Regex {
+        let prefilter = LiteralPrefilter::new(&nfa);
+        let inner = match prefilter {
+            Some(_) => None,
+            None => InnerLiteral::new(hir)
+        };
+
+        Regex {
+            pattern: pattern.to_owned(),
+            names: Arc::new(vec![None; nfa.num_groups()]),
+            program: Arc::new(Program {
+                prefilter,
+                inner,
+                custom_prefilter: None,
+                reverse_nfa: nfa.reverse(),
+                onepass: if longest { None } else { OnePass::new(&nfa) },
+                literal_nfa: nfa.with_literals(),
+                dense_nfa: nfa.to_dense(),
+                nfa,
+                dfa: None
+            }),
+            longest,
+            case_insensitive: false,
+            line_terminator: b'\n',
+            syntax: Syntax::Default,
+            anchored_start: false
+        }
+    }
+
+    /// The pattern the regex was compiled from.
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Determinizes the whole pattern ahead of time, so that matching is a
+    /// table lookup per byte. Fails if the tables would take more than
+    /// `size_limit` bytes.
+    pub fn compile_dfa(mut self, size_limit: usize) -> Result<Regex, Error> {
+        let dfa = if self.longest {
+            DenseDFA::build_longest(&self.program.nfa, size_limit)
+        } else {
+            DenseDFA::build(&self.program.nfa, size_limit)
+        };
+        let dfa = dfa.ok_or(Error::SizeLimitExceeded)?;
+        Arc::make_mut(&mut self.program).dfa = Some(dfa.minimize());
+        Ok(self)
+    }
+
+    pub fn has_dfa(&self) -> bool {
+        self.program.dfa.is_some()
+    }
+
+    /// The automaton the pattern compiled to, for inspecting what it
+    /// matches.
+    pub fn nfa(&self) -> &NFA {
+        &self.program.nfa
+    }
+
+    /// Whether the pattern is a literal, or an alternation of them, so that
+    /// searching for `literals` finds the same matches.
+    pub fn is_literal(&self) -> bool {
+        self.literals().is_some()
+    }
+
+    /// The strings the pattern matches when it is a literal or an
+    /// alternation of them, in order of preference: the leftmost place
+    /// one of them occurs is the leftmost match, and of those starting
+    /// there the first listed is the one a search finds. A pattern starting
+    /// with `^` is not a literal, as it only matches at the start.
+    pub fn literals(&self) -> Option<Vec<String>> {
+        if self.anchored_start {
+            return None;
+        }
+        let mut literals = self.program.nfa.literals(MAX_LITERALS)?;
+        if self.longest {
+            literals.sort_by_key(|literal| Reverse(literal.len()));
+        }
+        literals.into_iter().map(|literal| String::from_utf8(literal).ok()).collect()
+    }
+
+    /// A rough measure of how costly searching with the regex is, for
+    /// services taking patterns from users to turn away or sandbox those
+    /// likely to be slow.
+    pub fn complexity(&self) -> Cost {
+        self.program.nfa.estimated_cost()
+    }
+
+    /// The number of groups, counting the whole match as group 0, and so
+    /// the length of every `Captures`.
+    pub fn capture_len(&self) -> usize {
+        self.program.nfa.num_groups()
+    }
+
+    /// The names of the groups in order, `None` for the whole match and
+    /// for each group without a `(?P<name>`.
+    pub fn capture_names(&self) -> Vec<Option<&str>> {
+        self.names.iter().map(|name| name.as_ref().map(|name| &name[..])).collect()
+    }
+
+    /// Whether some match sets group `i`. A group in an alternative that
+    /// never matches cannot, nor can any group past `capture_len`.
+    pub fn can_participate(&self, i: usize) -> bool {
+        self.program.nfa.participating_groups().get(i).cloned().unwrap_or(false)
+    }
+
+    /// Whether both regexes match exactly the same strings, as whole texts.
+    /// Which match a search prefers is not compared, nor are groups. Both
+    /// patterns are determinized, which takes exponential time for some.
+    pub fn is_equivalent(&self, other: &Regex) -> bool {
+        let (a, b) = (self.language(), other.language());
+        a.intersect(&b.complement()).is_empty() && b.intersect(&a.complement()).is_empty()
+    }
+
+    /// Whether every string this regex matches as a whole text, `other`
+    /// matches too. Determinizes both patterns, as `is_equivalent` does.
+    pub fn is_subset(&self, other: &Regex) -> bool {
+        self.language().intersect(&other.language().complement()).is_empty()
+    }
+
+    // a DFA accepting exactly the texts the pattern matches in full
+    fn language(&self) -> DenseDFA {
+        DenseDFA::build_longest(&self.program.nfa, usize::MAX).unwrap()
+    }
+
+    /// The heap memory taken by the compiled regex, in bytes. Caches for
+    /// searching with it are not included.
+    pub fn memory_usage(&self) -> usize {
+        self.pattern.len() +
+        self.program.nfa.memory_usage() +
+        self.program.literal_nfa.memory_usage() +
+        self.program.dense_nfa.memory_usage() +
+        self.program.reverse_nfa.memory_usage() +
+        self.program.dfa.as_ref().map_or(0, |dfa| dfa.memory_usage()) +
+        self.program.onepass.as_ref().map_or(0, |onepass| onepass.memory_usage()) +
+        self.program.prefilter.as_ref().map_or(0, |prefilter| prefilter.memory_usage()) +
+        self.program.inner.as_ref().map_or(0, |inner| inner.memory_usage())
+    }
+
+    /// Encodes the compiled regex, so that it can be loaded with `from_bytes`
+    /// without parsing and compiling the pattern again.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new(MAGIC, VERSION);
+        encoder.bytes(self.pattern.as_bytes());
+        self.program.nfa.encode(&mut encoder);
+        self.program.reverse_nfa.encode(&mut encoder);
+        match self.program.dfa {
+            Some(ref dfa) => { encoder.u8(1); dfa.encode(&mut encoder); },
+            None => encoder.u8(0)
+        }
+        match self.program.inner {
+            Some(ref inner) => { encoder.u8(1); inner.encode(&mut encoder); },
+            None => encoder.u8(0)
+        }
+        encoder.u8(self.longest as u8);
+        encoder.u8(self.anchored_start as u8);
+        encoder.u8(self.case_insensitive as u8);
+        encoder.u8(self.line_terminator);
+        encoder.u8(match self.syntax {
+            Syntax::Default => 0,
+            Syntax::PosixExtended => 1,
+            Syntax::PosixBasic => 2
+        });
+        encoder.finish()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Regex, DecodeError> {
+        let mut decoder = Decoder::new(bytes, MAGIC, VERSION)?;
+
+        let pattern = String::from_utf8(decoder.bytes()?.to_vec())
+            .map_err(|_| DecodeError::InvalidPattern)?;
+        let nfa = NFA::decode(&mut decoder)?;
+        let reverse_nfa = NFA::decode(&mut decoder)?;
+        let offset = decoder.offset();
+        let dfa = match decoder.u8()? {
+            0 => None,
+            1 => Some(DenseDFA::decode(&mut decoder)?),
+            _ => return Err(DecodeError::InvalidTag(offset))
+        };
+        let offset = decoder.offset();
+        let inner = match decoder.u8()? {
+            0 => None,
+            1 => Some(InnerLiteral::decode(&mut decoder)?),
+            _ => return Err(DecodeError::InvalidTag(offset))
+        };
+        let offset = decoder.offset();
+        let longest = match decoder.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(DecodeError::InvalidTag(offset))
+        };
+        let offset = decoder.offset();
+        let anchored_start = match decoder.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(DecodeError::InvalidTag(offset))
+        };
+        let offset = decoder.offset();
+        let case_insensitive = match decoder.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(DecodeError::InvalidTag(offset))
+        };
+        let offset = decoder.offset();
+        let line_terminator = match decoder.u8()? {
+            byte if byte.is_ascii() => byte,
+            _ => return Err(DecodeError::InvalidTag(offset))
+        };
+        let offset = decoder.offset();
+        let syntax = match decoder.u8()? {
+            0 => Syntax::Default,
+            1 => Syntax::PosixExtended,
+            2 => Syntax::PosixBasic,
+            _ => return Err(DecodeError::InvalidTag(offset))
+        };
+        decoder.finish()?;
+
+        Ok(Regex {
+            names: group_names(&pattern, syntax, nfa.num_groups()),
+            pattern,
+            program: Arc::new(Program {
+                prefilter: LiteralPrefilter::new(&nfa),
+                inner,
+                custom_prefilter: None,
+                onepass: if longest { None } else { OnePass::new(&nfa) },
+                literal_nfa: nfa.with_literals(),
+                dense_nfa: nfa.to_dense(),
+                nfa,
+                reverse_nfa,
+                dfa
+            }),
+            longest,
+            case_insensitive,
+            line_terminator,
+            syntax,
+            anchored_start
         })
     }
 
     pub fn is_match(&self, text: &str) -> bool {
-        self.match_offset(text).is_some()
+        self.meta().is_match(text.as_bytes())
+    }
+
+    /// Like `is_match`, but gives up with an error once the search has
+    /// spent `budget`, for a bound on the work beyond the pattern's size.
+    pub fn try_is_match(&self, text: &str, budget: Budget) -> Result<bool, BudgetExceeded> {
+        PikeVM::new(&self.program.literal_nfa).with_earliest(true)
+            .captures_within(text.as_bytes(), 0, true, budget)
+            .map(|found| found.is_some())
     }
 
     pub fn match_offset(&self, text: &str) -> Option<usize> {
-        Matcher::new(self.nfa.clone(), text).run()
+        self.meta().match_end(text.as_bytes())
+    }
+
+    /// The end of the shortest match starting at the start of `text`,
+    /// known as soon as the search gets there, without reading on to see
+    /// whether the match `match_offset` reports goes on. Enough to tell
+    /// whether some prefix of the text already matches.
+    pub fn shortest_match(&self, text: &str) -> Option<usize> {
+        self.meta().earliest_end(text.as_bytes())
+    }
+
+    /// Finds the leftmost-first match in `text`: of the matches starting
+    /// earliest, the one a backtracking search would find first, trying the
+    /// left side of each alternation first and repeating as often as
+    /// possible before trying fewer repetitions.
+    pub fn find(&self, text: &str) -> Option<Match> {
+        self.search_with(&mut self.create_cache(), text)
+    }
+
+    /// Like `find`, but only finds a match within `range` of `text`. The
+    /// rest of the text is still there for the search to see, so a `^` at
+    /// the start of the pattern does not match at the start of the range
+    /// unless that is the start of the text. Panics if the range is not
+    /// within the text or does not fall on char boundaries.
+    pub fn find_in(&self, text: &str, range: Range<usize>) -> Option<Match> {
+        self.search(&mut self.create_cache(), &Input::new(text).span(range.start, range.end))
+    }
+
+    /// Like `find`, but with `Anchored::Yes` only finds a match starting at
+    /// the start of `text`, as if the pattern started with `^`.
+    pub fn find_anchored(&self, text: &str, anchored: Anchored) -> Option<Match> {
+        self.search(&mut self.create_cache(), &Input::new(text).anchored(anchored))
+    }
+
+    /// Like `captures`, but anchored as `find_anchored` is.
+    pub fn captures_anchored<'t>(&self, text: &'t str, anchored: Anchored) -> Option<Captures<'t>> {
+        self.search_captures(&mut self.create_cache(), &Input::new(text).anchored(anchored))
+    }
+
+    /// Finds a match as `input` says to, reusing the states and buffers in
+    /// `cache`. A search that quits finds none.
+    pub fn search(&self, cache: &mut Cache, input: &Input) -> Option<Match> {
+        self.try_search(cache, input).unwrap_or(None)
+    }
+
+    /// Like `search`, along with where each group matched.
+    pub fn search_captures<'t>(&self, cache: &mut Cache, input: &Input<'t>) -> Option<Captures<'t>> {
+        self.try_search_captures(cache, input).unwrap_or(None)
+    }
+
+    /// Like `search`, but tells a search that quit at one of the input's
+    /// quit bytes apart from one that found no match.
+    pub fn try_search(&self, cache: &mut Cache, input: &Input) -> Result<Option<Match>, Quit> {
+        self.search_slots(cache, input)
+            .map(|found| found.map(|slots| Match::new(slots[0].unwrap(), slots[1].unwrap())))
+    }
+
+    /// Like `search_captures`, telling quitting apart as `try_search` does.
+    pub fn try_search_captures<'t>(&self, cache: &mut Cache, input: &Input<'t>) -> Result<Option<Captures<'t>>, Quit> {
+        self.search_slots(cache, input)
+            .map(|found| found.map(|slots| Captures { text: input.text(), slots, names: self.names.clone() }))
+    }
+
+    /// The successive non-overlapping matches in `text`. After an empty
+    /// match the next search starts a char further on.
+    pub fn find_all(&self, cache: &mut Cache, text: &str) -> Vec<Match> {
+        let mut matches = Vec::new();
+        let mut at = Some(0);
+        while let Some(found) = at.and_then(|at| self.search(cache, &Input::new(text).span(at, text.len()))) {
+            matches.push(found);
+            at = next_start(text, found);
+        }
+        matches
+    }
+
+    /// How many matches `find_all` finds in `text`, counted by a lazy DFA
+    /// that only looks for where each match ends, without working out where
+    /// matches start or where groups match.
+    pub fn count(&self, text: &str) -> usize {
+        if self.longest || self.anchored_start {
+            return self.find_all(&mut self.create_cache(), text).len();
+        }
+
+        // patterns have no assertions, so one matching empty matches at the
+        // start of the search, and a match ending further on is not empty:
+        // a match from there to its end is empty when the real one is
+        let unanchored = self.program.nfa.unanchored();
+        let mut dfa = LazyDFA::new(&unanchored);
+        let mut count = 0;
+        let mut at = Some(0);
+        while let Some(start) = at {
+            let end = match dfa.match_end(&text.as_bytes()[start..]) {
+                Some(len) => start + len,
+                None => break
+            };
+            count += 1;
+            at = next_start(text, Match::new(start, end));
+        }
+        count
+    }
+
+    /// Iterates over the parts of `text` between the matches `find_all`
+    /// finds, as spans of it. There is one more than there are matches,
+    /// counting the parts before the first and after the last, so some may
+    /// be empty.
+    pub fn non_matches_iter<'r, 't>(&'r self, text: &'t str) -> NonMatches<'r, 't> {
+        NonMatches {
+            regex: self,
+            cache: self.create_cache(),
+            text,
+            at: Some(0),
+            last_end: Some(0)
+        }
+    }
+
+    fn search_slots(&self, cache: &mut Cache, input: &Input) -> Result<Option<Vec<Option<usize>>>, Quit> {
+        let span = input.get_span();
+        let (start, end) = (span.start(), span.end());
+        let text = &input.text().as_bytes()[..end];
+
+        // the text is cut short at the first quit byte
+        let quit = input.get_quit();
+        let quit_at = if quit.is_empty() {
+            None
+        } else {
+            text[start..].iter().position(|&byte| quit.contains(byte)).map(|i| start + i)
+        };
+        let text = &text[..quit_at.unwrap_or(end)];
+
+        let found = self.meta().with_earliest(input.get_earliest())
+            .captures_with(cache, text, start, input.get_anchored() == Anchored::Yes);
+        match (found, quit_at) {
+            (None, Some(at)) => Err(Quit::new(input.text().as_bytes()[at], at)),
+            (found, _) => Ok(found)
+        }
+    }
+
+    /// Finds the match that ends last in `text`, and of those the longest,
+    /// by running the reversed pattern backwards from the end.
+    pub fn rfind(&self, text: &str) -> Option<Match> {
+        if self.anchored_start {
+            return PikeVM::new(&self.program.literal_nfa).with_longest(true).captures(text.as_bytes(), 0, true)
+                .map(|slots| Match::new(0, slots[1].unwrap()));
+        }
+        let reversed = text.bytes().rev().collect::<Vec<u8>>();
+        PikeVM::new(&self.program.reverse_nfa).with_longest(true).captures(&reversed, 0, false)
+            .map(|slots| Match::new(text.len() - slots[1].unwrap(), text.len() - slots[0].unwrap()))
+    }
+
+    /// Finds the leftmost-first match anywhere in `text`, along with where
+    /// each group matched in it.
+    pub fn captures<'t>(&self, text: &'t str) -> Option<Captures<'t>> {
+        self.captures_with(&mut self.create_cache(), text)
+    }
+
+    /// Searches `text` as `find` does, recording at each offset which
+    /// states of `nfa()` had a thread and which of them consumed the byte,
+    /// to see why the search found what it did.
+    pub fn trace(&self, text: &str) -> Trace {
+        PikeVM::new(&self.program.nfa)
+            .with_prefilter(self.candidates())
+            .with_longest(self.longest)
+            .trace(text.as_bytes(), 0, self.anchored_start)
+    }
+
+    /// Searches `text` as `find` does, also counting the work it took, so
+    /// that patterns too slow on some input can be found. The search always
+    /// runs the Pike VM to count in, which `find` may not.
+    pub fn find_with_metrics(&self, text: &str) -> (Option<Match>, Metrics) {
+        let (slots, metrics) = PikeVM::new(&self.program.nfa)
+            .with_prefilter(self.candidates())
+            .with_longest(self.longest)
+            .metrics(text.as_bytes(), 0, self.anchored_start);
+        (slots.map(|slots| Match::new(slots[0].unwrap(), slots[1].unwrap())), metrics)
+    }
+
+    /// Creates the scratch space that searches keep their state in. A regex
+    /// can be shared between threads, as long as each searches with a cache
+    /// of its own, created by this regex.
+    pub fn create_cache(&self) -> Cache {
+        Cache::new(&self.program.nfa)
+    }
+
+    /// Finds the leftmost-first match anywhere in `text`, reusing the states
+    /// and buffers earlier searches left in `cache`.
+    pub fn search_with(&self, cache: &mut Cache, text: &str) -> Option<Match> {
+        self.meta().captures_with(cache, text.as_bytes(), 0, false)
+            .map(|slots| Match::new(slots[0].unwrap(), slots[1].unwrap()))
+    }
+
+    /// Like `captures`, but reusing the states and buffers in `cache`.
+    pub fn captures_with<'t>(&self, cache: &mut Cache, text: &'t str) -> Option<Captures<'t>> {
+        self.meta().captures_with(cache, text.as_bytes(), 0, false)
+            .map(|slots| Captures { text, slots, names: self.names.clone() })
+    }
+
+    /// Creates a buffer for `captures_read` to put the groups of a match in.
+    pub fn capture_locations(&self) -> CaptureLocations {
+        CaptureLocations(vec![None; 2 * self.program.nfa.num_groups()])
+    }
+
+    /// Like `captures`, but puts where each group matched in `locations`
+    /// rather than a new `Captures`, so that searches in a loop can reuse it.
+    pub fn captures_read(&self, locations: &mut CaptureLocations, text: &str) -> Option<Match> {
+        self.captures_read_with(&mut self.create_cache(), locations, text)
+    }
+
+    /// Like `captures_read`, reusing the states and buffers in `cache` too.
+    pub fn captures_read_with(&self, cache: &mut Cache, locations: &mut CaptureLocations, text: &str) -> Option<Match> {
+        let CaptureLocations(ref mut locations) = *locations;
+        for location in locations.iter_mut() {
+            *location = None;
+        }
+
+        // a search by literals alone only finds the whole match
+        let slots = self.meta().captures_with(cache, text.as_bytes(), 0, false)?;
+        for (location, &slot) in locations.iter_mut().zip(&slots) {
+            *location = slot;
+        }
+        Some(Match::new(slots[0].unwrap(), slots[1].unwrap()))
+    }
+
+    /// Finds every match in `text`, including those that overlap, by trying
+    /// each position in turn, so it can take time quadratic in the text.
+    pub fn find_overlapping_iter<'r, 't>(&'r self, text: &'t str) -> OverlappingMatches<'r, 't> {
+        OverlappingMatches {
+            nfa: &self.program.dense_nfa,
+            text,
+            position: Some(0),
+            anchored_start: self.anchored_start,
+            longest: self.longest
+        }
+    }
+
+    fn meta<'r>(&'r self) -> Meta<'r> {
+        Meta::new(&self.program.nfa, self.program.dfa.as_ref(), self.program.prefilter.as_ref())
+            .with_inner_literal(self.program.inner.as_ref())
+            .with_literal_nfa(&self.program.literal_nfa)
+            .with_onepass(self.program.onepass.as_ref())
+            .with_longest(self.longest)
+            .with_anchored_start(self.anchored_start)
+            .with_prefilter(self.program.custom_prefilter.as_ref().map(|custom| &*custom.0))
+    }
+
+    // what searches skip ahead with: the prefilter given to the builder, or
+    // else the literals matches start with
+    fn candidates(&self) -> Option<&dyn Prefilter> {
+        match self.program.custom_prefilter {
+            Some(ref custom) => Some(&*custom.0),
+            None => self.program.prefilter.as_ref().map(|prefilter| prefilter as &dyn Prefilter)
+        }
+    }
+
+    /// Starts a search over input that is fed to it one chunk at a time.
+    pub fn stream<'r>(&'r self) -> Stream<'r> {
+        let stream = Stream::starting_at(&self.program.dense_nfa, 0, self.anchored_start);
+        if self.longest { stream.longest() } else { stream }
+    }
+
+    /// Cuts `text` into the segments that match and those between them, in
+    /// order, so that the matches can be shown differently. The segments
+    /// cover the whole text and none is empty.
+    pub fn highlight<'t>(&self, text: &'t str) -> Vec<Segment<'t>> {
+        highlight::segments(text, &self.find_all(&mut self.create_cache(), text))
+    }
+
+    /// Reads lines from `reader` and yields those with a match, with their
+    /// line numbers and where in them the matches are.
+    pub fn search_lines<'r, R: BufRead>(&'r self, reader: R) -> SearchLines<'r, R> {
+        SearchLines::new(self, reader)
+    }
+
+    /// Searches a reader for matches without buffering the whole input.
+    pub fn stream_matches<'r, R: Read>(&'r self, reader: R) -> StreamMatches<'r, R> {
+        StreamMatches::new(self.stream(), reader)
+    }
+
+    /// Splits what is read from `reader` into the records between matches,
+    /// keeping only the record being read in memory.
+    pub fn stream_split<'r, R: Read>(&'r self, reader: R) -> StreamSplit<'r, R> {
+        StreamSplit::new(self.stream(), reader)
+    }
+}
+
+// Shows the pattern the regex was compiled from.
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+impl FromStr for Regex {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Regex, Error> {
+        Regex::from(s)
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Regex {
+    type Error = Error;
+
+    fn try_from(pattern: &'a str) -> Result<Regex, Error> {
+        Regex::from(pattern)
+    }
+}
+
+impl TryFrom<String> for Regex {
+    type Error = Error;
+
+    fn try_from(pattern: String) -> Result<Regex, Error> {
+        Regex::from(&pattern)
+    }
+}
+
+/// Where a search lets a match start: anywhere, or only where the search
+/// starts. The same compiled regex can be searched either way.
+#[derive(PartialEq,Eq,Debug,Clone,Copy,Hash)]
+pub enum Anchored {
+    No,
+    Yes
+}
+
+// The location of a match, as absolute byte offsets into the searched input.
+// Both fall on char boundaries, so the matched text can always be sliced out.
+#[derive(PartialEq,Eq,Debug,Clone,Copy,Hash)]
+pub struct Match {
+    start: usize,
+    end: usize
+}
+
+impl Match {
+    pub fn new(start: usize, end: usize) -> Match {
+        Match {
+            start,
+            end
+        }
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.start, self.end)
     }
 }
 
+// The spans matched by each group of a regex, with the whole match as group 0.
+#[derive(PartialEq,Debug)]
+pub struct Captures<'t> {
+    text: &'t str,
+    slots: Vec<Option<usize>>,
+    names: Arc<Vec<Option<String>>>
+}
+
+impl<'t> Captures<'t> {
+    // `None` if group `i` did not take part in the match
+    pub fn get(&self, i: usize) -> Option<Match> {
+        match (self.slots.get(2 * i), self.slots.get(2 * i + 1)) {
+            (Some(&Some(start)), Some(&Some(end))) => Some(Match::new(start, end)),
+            _ => None
+        }
+    }
+
+    pub fn get_str(&self, i: usize) -> Option<&'t str> {
+        self.get(i).map(|m| &self.text[m.start()..m.end()])
+    }
+
+    pub fn span(&self, i: usize) -> Option<Span> {
+        self.get(i).map(|m| m.span())
+    }
+
+    /// What the group `(?P<name>` opens matched, `None` if there is no
+    /// such group or it did not take part in the match.
+    pub fn name(&self, name: &str) -> Option<Match> {
+        self.index_of(name).and_then(|i| self.get(i))
+    }
+
+    fn index_of(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|group| group.as_ref().is_some_and(|group| group == name))
+    }
+
+    // the number of groups, including group 0
+    pub fn len(&self) -> usize {
+        self.slots.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Appends `template` to `dst` with each `$n` or `${n}` replaced by what
+    /// group `n` matched, `$name` or `${name}` by what the group named so
+    /// matched, and `$$` by `$`. Groups that do not exist or did not take
+    /// part in the match are replaced by nothing. A `$` that starts none of
+    /// these is kept as it is.
+    pub fn expand(&self, template: &str, dst: &mut String) {
+        let mut rest = template;
+        while let Some(at) = rest.find('$') {
+            dst.push_str(&rest[..at]);
+            rest = &rest[at + 1..];
+
+            if rest.starts_with('$') {
+                dst.push('$');
+                rest = &rest[1..];
+                continue;
+            }
+
+            let (name, after) = if rest.starts_with('{') {
+                match rest.find('}') {
+                    Some(end) => (&rest[1..end], &rest[end + 1..]),
+                    None => ("", rest)
+                }
+            } else {
+                let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            };
+            if name.is_empty() {
+                dst.push('$');
+                continue;
+            }
+
+            let i = match name.parse::<usize>() {
+                Ok(i) => Some(i),
+                Err(_) => self.index_of(name)
+            };
+            dst.push_str(i.and_then(|i| self.get_str(i)).unwrap_or(""));
+            rest = after;
+        }
+        dst.push_str(rest);
+    }
+}
+
+// The names of the groups of `pattern`, by number. Only this crate's own
+// syntax names groups.
+fn group_names(pattern: &str, syntax: Syntax, len: usize) -> Arc<Vec<Option<String>>> {
+    match syntax {
+        Syntax::Default => Arc::new(::expr::group_names(pattern)),
+        Syntax::PosixExtended | Syntax::PosixBasic => Arc::new(vec![None; len])
+    }
+}
+
+// `&captures[i]` is what group `i` matched. Panics if the group does not
+// exist or did not take part in the match.
+impl<'t> Index<usize> for Captures<'t> {
+    type Output = str;
+
+    fn index(&self, i: usize) -> &str {
+        match self.get_str(i) {
+            Some(matched) => matched,
+            None if i < self.len() => panic!("group {} did not take part in the match", i),
+            None => panic!("no group {}, the regex has {} including the whole match", i, self.len())
+        }
+    }
+}
+
+// `&captures[name]` is what the group `(?P<name>` opens matched. Panics if
+// there is no such group or it did not take part in the match.
+impl<'t, 'n> Index<&'n str> for Captures<'t> {
+    type Output = str;
+
+    fn index(&self, name: &str) -> &str {
+        match self.index_of(name) {
+            Some(i) => match self.get_str(i) {
+                Some(matched) => matched,
+                None => panic!("group '{}' did not take part in the match", name)
+            },
+            None => panic!("no group named '{}'", name)
+        }
+    }
+}
+
+// Where each group of a regex matched, like `Captures` without the text, for
+// `Regex::captures_read` to fill in again on each search.
+#[derive(PartialEq,Debug,Clone)]
+pub struct CaptureLocations(Vec<Option<usize>>);
+
+impl CaptureLocations {
+    // `None` if group `i` did not take part in the last match
+    pub fn get(&self, i: usize) -> Option<Match> {
+        match (self.0.get(2 * i), self.0.get(2 * i + 1)) {
+            (Some(&Some(start)), Some(&Some(end))) => Some(Match::new(start, end)),
+            _ => None
+        }
+    }
+
+    // the number of groups, including group 0
+    pub fn len(&self) -> usize {
+        self.0.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// Where the search for the next match starts after `found`: an empty match
+// moves on a char, so that it is not found again. `None` past the end of the
+// text.
+fn next_start(text: &str, found: Match) -> Option<usize> {
+    if found.is_empty() {
+        text[found.end()..].chars().next().map(|c| found.end() + c.len_utf8())
+    } else {
+        Some(found.end())
+    }
+}
+
+// Iterator over the parts of a text between matches.
+pub struct NonMatches<'r, 't> {
+    regex: &'r Regex,
+    cache: Cache,
+    text: &'t str,
+    at: Option<usize>, // where the next search starts, `None` once there are no more matches
+    last_end: Option<usize> // where the next part starts, `None` once it has been yielded
+}
+
+impl<'r, 't> Iterator for NonMatches<'r, 't> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Match> {
+        let start = self.last_end?;
+        let found = self.at.and_then(|at| {
+            self.regex.search(&mut self.cache, &Input::new(self.text).span(at, self.text.len()))
+        });
+
+        match found {
+            Some(found) => {
+                self.at = next_start(self.text, found);
+                self.last_end = Some(found.end());
+                Some(Match::new(start, found.start()))
+            },
+            None => {
+                self.at = None;
+                self.last_end = None;
+                Some(Match::new(start, self.text.len()))
+            }
+        }
+    }
+}
+
+// Iterator over the leftmost-first (or longest) match starting at each position of a text.
+pub struct OverlappingMatches<'r, 't> {
+    nfa: &'r DenseNFA,
+    text: &'t str,
+    position: Option<usize>, // `None` once every position has been tried
+    anchored_start: bool,
+    longest: bool
+}
+
+impl<'r, 't> Iterator for OverlappingMatches<'r, 't> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Match> {
+        while let Some(position) = self.position {
+            self.position = self.text[position..].chars().next()
+                .filter(|_| !self.anchored_start)
+                .map(|c| position + c.len_utf8());
+
+            let rest = self.text.as_bytes()[position..].iter().cloned();
+            let stream = Stream::starting_at(self.nfa, position, true);
+            let found = if self.longest { stream.longest() } else { stream }.first(rest);
+            if found.is_some() {
+                return found;
+            }
+        }
+
+        None
+    }
+}
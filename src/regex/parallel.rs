@@ -0,0 +1,131 @@
+use std::cmp;
+use std::vec;
+
+use ::rayon;
+use ::rayon::prelude::*;
+
+use super::{Regex, Input, Match, next_start};
+
+// texts shorter than this are searched on the calling thread
+const MIN_PARALLEL_LEN: usize = 1 << 16;
+
+impl Regex {
+    /// Like `find_all`, but splits a large `text` into chunks searched on
+    /// as many threads as rayon's pool has. The matches are the same and
+    /// in the same order as `find_all` finds them.
+    pub fn par_find_iter(&self, text: &str) -> vec::IntoIter<Match> {
+        let threads = rayon::current_num_threads();
+        let chunk_len = cmp::max(MIN_PARALLEL_LEN, text.len().div_ceil(threads));
+        find_chunked(self, text, chunk_len).into_iter()
+    }
+}
+
+// What searching one chunk found: the matches starting in it, each with
+// where the search that found it started, and where the search that found
+// no more matches in the chunk started, or None if the text ran out.
+struct Chunk {
+    end: usize,
+    found: Vec<(usize, Match)>,
+    rest: Option<usize>
+}
+
+// Searches `text` in chunks of about `chunk_len` bytes, in parallel on
+// rayon's pool, then stitches the chunks together.
+//
+// A chunk's search sees the text past its end, up to the longest match the
+// pattern has, so a match starting in it and crossing into the next one is
+// found whole. A match found by a chunk only stands once the search before
+// it reaches the chunk: where the previous chunk's last match ends decides
+// where this chunk's first match can start. So chunks are stitched in
+// order, and where the search does not land on a point this chunk's search
+// went through, the overlap is scanned again from there until it does.
+pub fn find_chunked(regex: &Regex, text: &str, chunk_len: usize) -> Vec<Match> {
+    if regex.anchored_start || text.len() <= chunk_len {
+        return regex.find_all(&mut regex.create_cache(), text);
+    }
+
+    let mut bounds = vec![0];
+    while let Some(&last) = bounds.last() {
+        if last == text.len() {
+            break;
+        }
+        bounds.push(boundary(text, cmp::min(last + chunk_len, text.len())));
+    }
+    let max_len = regex.nfa().max_len();
+
+    let chunks: Vec<Chunk> = bounds.par_windows(2)
+        .map(|w| search_chunk(regex, text, w[0], w[1], max_len))
+        .collect();
+
+    let mut matches = Vec::new();
+    let mut cache = regex.create_cache();
+    let mut at = 0;
+    for chunk in chunks {
+        let window = window_end(text, chunk.end, max_len);
+        while at < chunk.end {
+            if let Some(k) = chunk.found.iter().position(|&(from, m)| from <= at && at <= m.start()) {
+                matches.extend(chunk.found[k..].iter().map(|&(_, m)| m));
+                match chunk.rest {
+                    Some(rest) => at = cmp::max(rest, chunk.end),
+                    None => return matches
+                }
+            } else if chunk.rest.is_some_and(|rest| rest <= at) {
+                at = chunk.end;
+            } else {
+                match regex.search(&mut cache, &Input::new(text).span(at, window)) {
+                    Some(found) if found.start() < chunk.end => {
+                        matches.push(found);
+                        match next_start(text, found) {
+                            Some(next) => at = next,
+                            None => return matches
+                        }
+                    }
+                    _ => at = chunk.end
+                }
+            }
+        }
+    }
+    // an empty match at the very end starts in no chunk
+    matches.extend(regex.search(&mut cache, &Input::new(text).span(at, at)));
+    matches
+}
+
+fn search_chunk(regex: &Regex, text: &str, start: usize, end: usize, max_len: Option<usize>) -> Chunk {
+    let window = window_end(text, end, max_len);
+    let mut cache = regex.create_cache();
+    let mut found = Vec::new();
+    let mut at = start;
+    let rest = loop {
+        if at >= end {
+            break Some(at);
+        }
+        match regex.search(&mut cache, &Input::new(text).span(at, window)) {
+            Some(m) if m.start() < end => {
+                found.push((at, m));
+                match next_start(text, m) {
+                    Some(next) => at = next,
+                    None => break None
+                }
+            }
+            _ => break Some(at)
+        }
+    };
+    Chunk { end, found, rest }
+}
+
+// Where the search for matches starting before `end` can stop: no match
+// is longer than `max_len`, if the pattern bounds it.
+fn window_end(text: &str, end: usize, max_len: Option<usize>) -> usize {
+    match max_len {
+        Some(len) => boundary(text, cmp::min(end.saturating_add(len), text.len())),
+        None => text.len()
+    }
+}
+
+// the first char boundary at or after `at`
+fn boundary(text: &str, mut at: usize) -> usize {
+    while !text.is_char_boundary(at) {
+        at += 1;
+    }
+    at
+}
@@ -0,0 +1,59 @@
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de;
+
+use ::expr::Syntax;
+use super::{Regex, RegexBuilder};
+
+// A regex is stored as its pattern and the options it was built with, and
+// built again with them when loaded.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Regex")]
+struct Stored {
+    pattern: String,
+    longest: bool,
+    case_insensitive: bool,
+    line_terminator: u8,
+    syntax: StoredSyntax
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Syntax")]
+enum StoredSyntax {
+    Default,
+    PosixExtended,
+    PosixBasic
+}
+
+impl Serialize for Regex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let stored = Stored {
+            pattern: self.pattern.clone(),
+            longest: self.longest,
+            case_insensitive: self.case_insensitive,
+            line_terminator: self.line_terminator,
+            syntax: match self.syntax {
+                Syntax::Default => StoredSyntax::Default,
+                Syntax::PosixExtended => StoredSyntax::PosixExtended,
+                Syntax::PosixBasic => StoredSyntax::PosixBasic
+            }
+        };
+        stored.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Regex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+        let stored = Stored::deserialize(deserializer)?;
+        RegexBuilder::new(&stored.pattern)
+            .leftmost_longest(stored.longest)
+            .case_insensitive(stored.case_insensitive)
+            .line_terminator(stored.line_terminator)
+            .syntax(match stored.syntax {
+                StoredSyntax::Default => Syntax::Default,
+                StoredSyntax::PosixExtended => Syntax::PosixExtended,
+                StoredSyntax::PosixBasic => Syntax::PosixBasic
+            })
+            .build()
+            .map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,140 @@
+use ::error::Error;
+use ::expr::Expr;
+use ::hir::Hir;
+use ::nfa::NFA;
+use ::pikevm::{self, PikeVM};
+use ::prefilter::{Prefilter, LiteralPrefilter};
+use super::Match;
+use super::builder::{DEFAULT_NEST_LIMIT, strip_start_anchor};
+
+/// Searches for several patterns at once. The patterns are compiled into one
+/// NFA, an alternation with each pattern in a group of its own, so a single
+/// scan finds the leftmost-first match of any of them along with which one
+/// it was. Earlier patterns win among matches starting at the same offset.
+/// A pattern starting with `^` only matches at the start of the text.
+#[derive(PartialEq,Debug,Clone)]
+pub struct RegexSet {
+    patterns: Vec<String>,
+    nfa: NFA,
+    unanchored: Option<NFA>, // the patterns without `^`, if some have it
+    prefilter: Option<LiteralPrefilter>
+}
+
+impl RegexSet {
+    pub fn new<I, S>(patterns: I) -> Result<RegexSet, Error>
+        where I: IntoIterator<Item=S>, S: AsRef<str> {
+
+        let patterns = patterns.into_iter().map(|p| p.as_ref().to_owned()).collect::<Vec<String>>();
+
+        let mut alternatives = Vec::with_capacity(patterns.len());
+        let mut anchored = Vec::with_capacity(patterns.len());
+        for (i, pattern) in patterns.iter().enumerate() {
+            let expr = pattern.parse::<Expr>()?;
+            if expr.nest_depth() > DEFAULT_NEST_LIMIT {
+                return Err(Error::NestLimitExceeded);
+            }
+            let (expr, anchored_start) = strip_start_anchor(pattern, expr);
+            // the pattern's own groups would take the numbers of the others
+            alternatives.push(Hir::Group(i + 1, Box::new(Hir::from_expr(&expr).without_groups())));
+            anchored.push(anchored_start);
+        }
+
+        let nfa = NFA::from_hir(&alternation(alternatives.clone()));
+        let unanchored = if anchored.contains(&true) {
+            let rest = alternatives.into_iter().zip(anchored).filter(|&(_, anchored)| !anchored).map(|(hir, _)| hir);
+            Some(NFA::from_hir(&alternation(rest.collect())))
+        } else {
+            None
+        };
+
+        Ok(RegexSet {
+            patterns,
+            prefilter: LiteralPrefilter::new(unanchored.as_ref().unwrap_or(&nfa)),
+            nfa,
+            unanchored
+        })
+    }
+
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    // the combined NFA, in which group `i + 1` is the whole match of
+    // pattern `i`
+    pub fn nfa(&self) -> &NFA {
+        &self.nfa
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.matches_iter(text).next().is_some()
+    }
+
+    /// Finds the successive non-overlapping matches of any of the patterns
+    /// in `text`, each with the index of the pattern it matched.
+    pub fn matches_iter<'r, 't>(&'r self, text: &'t str) -> SetMatches<'r, 't> {
+        let prefilter = self.prefilter.as_ref().map(|prefilter| prefilter as &dyn Prefilter);
+        SetMatches {
+            vm: PikeVM::new(self.unanchored.as_ref().unwrap_or(&self.nfa)).with_prefilter(prefilter),
+            anchored: self.unanchored.as_ref().map(|_| PikeVM::new(&self.nfa)),
+            cache: pikevm::Cache::new(&self.nfa),
+            text,
+            position: Some(0)
+        }
+    }
+}
+
+// Iterator over the successive non-overlapping matches of a set, as the
+// index of the pattern that matched and where.
+pub struct SetMatches<'r, 't> {
+    vm: PikeVM<'r>, // for the patterns without `^`
+    anchored: Option<PikeVM<'r>>, // for all of them at the start, if some have `^`
+    cache: pikevm::Cache,
+    text: &'t str,
+    position: Option<usize> // `None` once the end of the text has been passed
+}
+
+impl<'r, 't> Iterator for SetMatches<'r, 't> {
+    type Item = (usize, Match);
+
+    fn next(&mut self) -> Option<(usize, Match)> {
+        let position = self.position?;
+        let at_start = match self.anchored {
+            Some(ref vm) if position == 0 => vm.captures_with(&mut self.cache, self.text.as_bytes(), 0, true),
+            _ => None
+        };
+        let found = at_start.or_else(|| self.vm.captures_with(&mut self.cache, self.text.as_bytes(), position, false));
+        let slots = match found {
+            Some(slots) => slots,
+            None => {
+                self.position = None;
+                return None;
+            }
+        };
+
+        let found = Match::new(slots[0].unwrap(), slots[1].unwrap());
+        // an empty match moves on a char, so that it is not found again
+        self.position = if found.is_empty() {
+            self.text[found.end()..].chars().next().map(|c| found.end() + c.len_utf8())
+        } else {
+            Some(found.end())
+        };
+
+        let pattern = (1..slots.len() / 2).find(|&i| slots[2 * i].is_some()).unwrap();
+        Some((pattern - 1, found))
+    }
+}
+
+fn alternation(alternatives: Vec<Hir>) -> Hir {
+    match alternatives.len() {
+        0 => Hir::Class(Vec::new()), // matches nothing
+        _ => Hir::Alternation(alternatives)
+    }
+}
@@ -1,4 +1,11 @@
-use super::Regex;
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use ::encode::DecodeError;
+use ::error::Error;
+use ::expr::{Expr, Syntax};
+use ::span::Span;
+use super::{Regex, RegexBuilder, RegexSet, Cache, Match, CaptureLocations, Anchored, Input, Quit};
 
 #[test]
 fn only_matches_at_start_of_text() {
@@ -43,6 +50,20 @@ fn matches_character_class() {
     assert!(!regex.is_match("z"));
 }
 
+#[test]
+fn matches_escapes() {
+    let r = Regex::from("\\d+").unwrap();
+    assert_eq!(Some(Match::new(2, 4)), r.find("ab42c"));
+    assert_eq!(None, r.find("dd"));
+    let r = Regex::from("\\w+@example\\.com").unwrap();
+    assert_eq!(Some(Match::new(3, 24)), r.find("to jo_smith1@example.com"));
+    assert_eq!(None, r.find("w@examplexcom"));
+    assert_eq!(Some(Match::new(1, 3)), Regex::from("\\t[\\n\\r]").unwrap().find("a\t\r"));
+
+    assert_eq!(Some(Error::InvalidEscape(0, 2)), Regex::from("\\q").err());
+    assert_eq!(Some(Error::InvalidEscape(2, 4)), Regex::from("[a\\Q]").err());
+}
+
 #[test]
 fn backtracks_to_find_match() {
     let regex = Regex::from(".?c").unwrap();
@@ -58,6 +79,48 @@ fn finds_match_position() {
     assert_eq!(Some(3), Regex::from("a.a").unwrap().match_offset("aaab"));
 }
 
+#[test]
+fn anchors_patterns_starting_with_caret() {
+    let r = Regex::from("^a(b*)").unwrap();
+    assert_eq!(Some(Match::new(0, 3)), r.find("abbab"));
+    assert_eq!(None, r.find("xabb"));
+    assert_eq!(vec![Match::new(0, 2)], r.find_all(&mut r.create_cache(), "abab"));
+    assert_eq!(None, r.search(&mut r.create_cache(), &Input::new("abab").span(2, 4)));
+    assert_eq!(Some(Match::new(0, 2)), r.rfind("abab"));
+    assert_eq!(vec![Match::new(0, 2)], r.find_overlapping_iter("abab").collect::<Vec<_>>());
+    assert_eq!(Some("bb"), r.captures("abbx").unwrap().get_str(1));
+
+    let loaded = Regex::from_bytes(&r.to_bytes()).unwrap();
+    assert_eq!(None, loaded.find("xab"));
+
+    // escaped or in a class it is a char
+    assert_eq!(Some(Match::new(1, 3)), Regex::from("\\^a").unwrap().find("x^a"));
+    assert_eq!(Some(Match::new(1, 3)), Regex::from("a[x^]").unwrap().find("xa^"));
+    assert_eq!(Some(Match::new(0, 0)), Regex::from("^").unwrap().find("xb"));
+
+    // anywhere else it would anchor less than the whole pattern
+    assert_eq!(Some(Error::UnsupportedAnchor(1, 2)), Regex::from("a^").err());
+    assert_eq!(Some(Error::UnsupportedAnchor(3, 4)), Regex::from("^a|^b").err());
+    assert_eq!(Some(Error::UnsupportedAnchor(0, 1)), Regex::from("^a|b").err());
+    assert_eq!(Some(Error::UnsupportedAnchor(1, 2)), Regex::from("(^a)").err());
+    assert_eq!(Some(Error::UnsupportedAnchor(2, 3)), Regex::from("a|^b").err());
+    assert_eq!(Some(Error::UnsupportedAnchor(0, 1)), Regex::from("^*a").err());
+    assert_eq!(vec![Error::UnsupportedAnchor(3, 4), Error::UnsupportedAnchor(0, 1)],
+               ::expr::diagnose("^a|^b").iter().map(|d| d.error()).collect::<Vec<_>>());
+}
+
+#[test]
+fn finds_shortest_match() {
+    let r = Regex::from("ab+|abc").unwrap();
+    assert_eq!(Some(2), r.shortest_match("abbbc"));
+    assert_eq!(Some(4), r.match_offset("abbbc"));
+    assert_eq!(None, r.shortest_match("ac"));
+    assert_eq!(Some(0), Regex::from("a*").unwrap().shortest_match("aaa"));
+
+    let r = Regex::from("ab+").unwrap().compile_dfa(1 << 20).unwrap();
+    assert_eq!(Some(2), r.shortest_match("abbb"));
+}
+
 #[test]
 fn matches_zero_or_more() {
     let regex = Regex::from("ab*").unwrap();
@@ -144,3 +207,1100 @@ fn char_class_matches() {
     assert!(regex.is_match("adbd"));
 }
 
+
+#[test]
+fn finds_overlapping_matches() {
+    let spans = |pattern: &str, text: &str| {
+        Regex::from(pattern).unwrap().find_overlapping_iter(text)
+            .map(|m| (m.start(), m.end()))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(vec![(0, 2), (1, 3), (2, 4)], spans("aa", "aaaa"));
+    assert_eq!(vec![(1, 4), (3, 6)], spans("aba", "cababac"));
+    assert_eq!(vec![(0, 3), (1, 3)], spans("a+b", "aab"));
+    assert_eq!(Vec::<(usize, usize)>::new(), spans("ab", "ba"));
+}
+
+#[test]
+fn overlapping_matches_start_on_char_boundaries() {
+    let found = Regex::from("a?").unwrap().find_overlapping_iter("éa").collect::<Vec<_>>();
+    assert_eq!(vec![Match::new(0, 0), Match::new(2, 3), Match::new(3, 3)], found);
+}
+
+#[test]
+fn finds_last_match() {
+    let r = Regex::from("ab").unwrap();
+    assert_eq!(Some(Match::new(5, 7)), r.rfind("abxxxab"));
+    assert_eq!(Some(Match::new(0, 2)), r.rfind("abxxx"));
+    assert_eq!(None, r.rfind("ba"));
+
+    let r = Regex::from("a(bc)+").unwrap();
+    assert_eq!(Some(Match::new(4, 9)), r.rfind("abc-abcbc-"));
+}
+
+#[test]
+fn finds_chars_of_several_bytes_backwards() {
+    let r = Regex::from("[é*]c").unwrap();
+    assert_eq!(Some(Match::new(0, 3)), r.find("éc"));
+    assert_eq!(Some(Match::new(1, 4)), r.rfind("xéc"));
+
+    let r = Regex::from("([.]é)+\n").unwrap();
+    assert_eq!(Some(Match::new(0, 7)), r.find(".é.é\n"));
+    assert_eq!(Some(Match::new(0, 7)), r.rfind(".é.é\n"));
+}
+
+#[test]
+fn finds_last_empty_match() {
+    assert_eq!(Some(Match::new(3, 3)), Regex::from("a*").unwrap().rfind("aab"));
+    assert_eq!(Some(Match::new(0, 2)), Regex::from("a*").unwrap().rfind("aa"));
+}
+
+#[test]
+fn matches_non_ascii() {
+    let r = Regex::from("né+").unwrap();
+    assert_eq!(Some(5), r.match_offset("néé"));
+    assert!(!r.is_match("ne"));
+
+    let r = Regex::from("[aé€]+").unwrap();
+    assert_eq!(Some(6), r.match_offset("éa€b"));
+    assert!(!r.is_match("e"));
+
+    assert!(Regex::from("[ü]").unwrap().is_match("ü"));
+}
+
+#[test]
+fn parses_from_str() {
+    let r = "a+b".parse::<Regex>().unwrap();
+    assert!(r.is_match("aab"));
+
+    assert_eq!(Err(Error::DanglingQuantifier(0, 1)), "+".parse::<Regex>());
+}
+
+#[test]
+fn converts_from_strings() {
+    assert!(Regex::try_from("a|b").unwrap().is_match("b"));
+    assert!(Regex::try_from("a|b".to_owned()).unwrap().is_match("a"));
+    assert_eq!(Err(Error::UnclosedClass(0, 2)), Regex::try_from("[a".to_owned()));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serializes_pattern_and_options() {
+    let r = Regex::from("a+[bc]").unwrap();
+    assert_eq!(r#"{"pattern":"a+[bc]","longest":false,"case_insensitive":false,"line_terminator":10,"syntax":"Default"}"#,
+               ::serde_json::to_string(&r).unwrap());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deserializes_by_compiling() {
+    let json = r#"{"pattern":"a+[bc]","longest":false,"case_insensitive":false,"line_terminator":10,"syntax":"Default"}"#;
+    let r: Regex = ::serde_json::from_str(json).unwrap();
+    assert_eq!(Regex::from("a+[bc]").unwrap(), r);
+    assert!(r.is_match("aac"));
+
+    let json = r#"{"pattern":"a)","longest":false,"case_insensitive":false,"line_terminator":10,"syntax":"Default"}"#;
+    let error = ::serde_json::from_str::<Regex>(json).unwrap_err();
+    assert!(error.to_string().starts_with("unmatched `)` at offset 1"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn round_trips_options_through_serde() {
+    let builders = vec![
+        RegexBuilder::new("ab|a").leftmost_longest(true),
+        RegexBuilder::new("k.").case_insensitive(true),
+        RegexBuilder::new("a.b").line_terminator(b'\0'),
+        RegexBuilder::new("a{2}|b+").syntax(Syntax::PosixExtended)
+    ];
+    for builder in builders {
+        let r = builder.build().unwrap();
+        let loaded: Regex = ::serde_json::from_str(&::serde_json::to_string(&r).unwrap()).unwrap();
+        assert_eq!(r, loaded);
+    }
+
+    let r = RegexBuilder::new("K.").case_insensitive(true).line_terminator(b'\0').build().unwrap();
+    let loaded: Regex = ::serde_json::from_str(&::serde_json::to_string(&r).unwrap()).unwrap();
+    assert!(loaded.is_match("k\n"));
+    assert!(!loaded.is_match("k\0"));
+}
+
+#[test]
+fn round_trips_through_bytes() {
+    let r = Regex::from("a(bc)+|d").unwrap();
+    let loaded = Regex::from_bytes(&r.to_bytes()).unwrap();
+
+    assert_eq!(r, loaded);
+    assert_eq!(Some(5), loaded.match_offset("abcbc"));
+    assert_eq!(Some(Match::new(2, 5)), loaded.rfind("d-abc"));
+}
+
+#[test]
+fn round_trips_groups_through_bytes() {
+    // merged states and the implicit group 0 leave more slots than states
+    for pattern in &["(a)", "()", "((\n))", "(\\*|[a])", "(a)(b)?(c|d)*"] {
+        let r = Regex::from(pattern).unwrap();
+        let loaded = Regex::from_bytes(&r.to_bytes()).unwrap();
+
+        assert_eq!(r, loaded);
+        assert_eq!(r.captures("xabcd"), loaded.captures("xabcd"));
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn round_trips_arbitrary_patterns_through_bytes() {
+    for seed in 0..3000 {
+        let r = RegexBuilder::from_expr(Expr::arbitrary(seed, 12)).build().unwrap();
+        assert_eq!(Ok(r.clone()), Regex::from_bytes(&r.to_bytes()), "{}", r);
+    }
+}
+
+#[test]
+fn rejects_invalid_bytes() {
+    let nfa_bytes = ::nfa::NFA::new().to_bytes();
+    assert_eq!(Err(DecodeError::BadMagic), Regex::from_bytes(&nfa_bytes));
+
+    let bytes = Regex::from("ab").unwrap().to_bytes();
+    assert_eq!(Err(DecodeError::UnexpectedEnd), Regex::from_bytes(&bytes[..bytes.len() - 2]));
+
+    let mut bad_pattern = bytes.clone();
+    bad_pattern[9] = 0xff;
+    assert_eq!(Err(DecodeError::InvalidPattern), Regex::from_bytes(&bad_pattern));
+
+    // an NFA without states, which a stream would search from state 0 of
+    let mut empty = ::encode::Encoder::new(super::MAGIC, super::VERSION);
+    empty.bytes(b"");
+    ::nfa::NFA::new().encode(&mut empty);
+    assert_eq!(Err(DecodeError::InvalidTransition(0)), Regex::from_bytes(&empty.finish()));
+}
+
+#[test]
+fn matches_with_compiled_dfa() {
+    let r = Regex::from("((abc|acc)b)+").unwrap().compile_dfa(1 << 20).unwrap();
+
+    assert!(r.has_dfa());
+    assert_eq!(Some(8), r.match_offset("abcbabcb"));
+    assert_eq!(None, r.match_offset("zzzb"));
+    assert!(r.is_match("accb"));
+}
+
+#[test]
+fn compiled_dfa_respects_size_limit() {
+    assert_eq!(Some(Error::SizeLimitExceeded), Regex::from("(a|b)*a(a|b)(a|b)(a|b)(a|b)(a|b)").unwrap().compile_dfa(1000).err());
+}
+
+#[test]
+fn round_trips_compiled_dfa_through_bytes() {
+    let r = Regex::from("a(bc)+|d").unwrap().compile_dfa(1 << 20).unwrap();
+    let loaded = Regex::from_bytes(&r.to_bytes()).unwrap();
+
+    assert_eq!(r, loaded);
+    assert!(loaded.has_dfa());
+}
+
+#[test]
+fn finds_captures() {
+    let r = Regex::from("([abc]+)@(x|y)").unwrap();
+    let caps = r.captures("--ab@y--").unwrap();
+
+    assert_eq!(3, caps.len());
+    assert!(!caps.is_empty());
+    assert_eq!(Some(Match::new(2, 6)), caps.get(0));
+    assert_eq!(Some("ab"), caps.get_str(1));
+    assert_eq!(Some("y"), caps.get_str(2));
+    assert_eq!(None, caps.get(3));
+
+    assert!(r.captures("ab@z").is_none());
+}
+
+#[test]
+fn reports_spans_of_matches_and_groups() {
+    let r = Regex::from("a(b*)c").unwrap();
+    assert_eq!(Some(Span::new(1, 5)), r.find("xabbc").map(|m| m.span()));
+
+    let caps = r.captures("xabbc").unwrap();
+    assert_eq!(Some(Span::new(2, 4)), caps.span(1));
+    assert_eq!("bb", &"xabbc"[caps.span(1).unwrap().range()]);
+    assert_eq!(None, caps.span(2));
+}
+
+#[test]
+fn finds_captures_from_inner_literal() {
+    let r = Regex::from("([abc]+)@example\\.com").unwrap();
+    let caps = r.captures("to: a@b, cab@example.com").unwrap();
+
+    assert_eq!(Some(Match::new(9, 24)), caps.get(0));
+    assert_eq!(Some("cab"), caps.get_str(1));
+    assert!(r.captures("to: @example.com").is_none());
+
+    let loaded = Regex::from_bytes(&r.to_bytes()).unwrap();
+    assert_eq!(r, loaded);
+    assert_eq!(Some("cab"), loaded.captures("cab@example.com").unwrap().get_str(1));
+}
+
+#[test]
+fn captures_skip_groups_outside_match() {
+    let caps = Regex::from("(a)|(é)").unwrap().captures("xé").unwrap();
+
+    assert_eq!(None, caps.get(1));
+    assert_eq!(Some("é"), caps.get_str(2));
+}
+
+#[test]
+fn skips_to_literal_prefix() {
+    let r = Regex::from("ERROR: ([0123456789]+)").unwrap();
+    let text = "ERROR ".repeat(1000) + "ERROR: 42 ERROR: 7";
+
+    assert_eq!(Some("42"), r.captures(&text).unwrap().get_str(1));
+    assert!(r.captures("ERROR: x").is_none());
+}
+
+#[test]
+fn finds_keywords() {
+    let r = Regex::from("foo|bar|baz|quux").unwrap();
+
+    assert_eq!(Some(Match::new(6, 9)), r.captures("quuz, baz, foo").unwrap().get(0));
+    assert!(r.captures("qux").is_none());
+}
+
+#[test]
+fn compiles_long_pattern() {
+    let pattern = ".".to_owned() + &"ab".repeat(50000);
+    let r = Regex::from(&pattern).unwrap();
+
+    assert!(!r.is_match("xaba"));
+    assert!(r.captures("abab").is_none());
+    assert_eq!(r, Regex::from_bytes(&r.to_bytes()).unwrap());
+}
+
+#[test]
+fn builder_enforces_nest_limit() {
+    let pattern = "(".repeat(300) + "a" + &")".repeat(300);
+
+    assert_eq!(Some(Error::NestLimitExceeded), Regex::from(&pattern).err());
+    assert!(RegexBuilder::new(&pattern).nest_limit(300).build().unwrap().is_match("a"));
+    assert_eq!(Some(Error::NestLimitExceeded), RegexBuilder::new("(a*)?").nest_limit(2).build().err());
+    assert!(RegexBuilder::new("(a*)?").nest_limit(3).build().is_ok());
+}
+
+#[test]
+fn builder_enforces_size_limit() {
+    assert_eq!(Some(Error::SizeLimitExceeded), RegexBuilder::new("abcd").size_limit(16).build().err());
+    assert!(RegexBuilder::new("abcd").size_limit(1 << 10).build().is_ok());
+
+    // intervals are turned away before they are expanded
+    let ere = |pattern: &str| RegexBuilder::new(pattern).syntax(Syntax::PosixExtended).build().err();
+    assert_eq!(Some(Error::SizeLimitExceeded), ere("a{255}{255}{255}"));
+    assert_eq!(Some(Error::SizeLimitExceeded), ere("(a{255}){255}{20}"));
+    assert_eq!(Some(Error::SizeLimitExceeded), RegexBuilder::new("a\\{100\\}").syntax(Syntax::PosixBasic).size_limit(1 << 10).build().err());
+}
+
+#[test]
+fn compiles_long_alternation() {
+    let pattern = vec!["ab"; 5000].join("|") + "|c";
+    let r = Regex::from(&pattern).unwrap();
+
+    assert!(r.is_match("c"));
+    assert!(!r.is_match("b"));
+}
+
+#[test]
+fn matches_without_precomputed_closures() {
+    let r = Regex::from(&"a?".repeat(100)).unwrap();
+
+    assert_eq!(Some(3), r.match_offset("aaab"));
+    assert_eq!(Some(Match::new(0, 3)), r.captures("aaab").unwrap().get(0));
+    assert_eq!(vec![Match::new(0, 3), Match::new(3, 3), Match::new(4, 4)], r.stream_matches("aaab".as_bytes()).map(|m| m.unwrap()).collect::<Vec<_>>());
+}
+
+#[test]
+fn can_be_shared_between_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Regex>();
+
+    let r = ::std::sync::Arc::new(Regex::from("([abc]+)@(x|y)").unwrap());
+    let searches = (0..4).map(|i| {
+        let r = r.clone();
+        ::std::thread::spawn(move || {
+            let mut cache: Cache = r.create_cache();
+            let text = "-".repeat(i) + "ab@y";
+            (0..10).map(|_| r.search_with(&mut cache, &text)).last().unwrap()
+        })
+    }).collect::<Vec<_>>();
+
+    for (i, search) in searches.into_iter().enumerate() {
+        assert_eq!(Some(Match::new(i, i + 4)), search.join().unwrap());
+    }
+}
+
+#[test]
+fn reuses_cache_between_searches() {
+    let r = Regex::from("a(b|c)+").unwrap();
+    let mut cache = r.create_cache();
+
+    assert_eq!(Some(Match::new(1, 4)), r.search_with(&mut cache, "xabc"));
+    assert_eq!(None, r.search_with(&mut cache, "xyz"));
+    assert_eq!(Some("c"), r.captures_with(&mut cache, "acc").unwrap().get_str(1));
+    assert_eq!(Some(Match::new(0, 2)), r.search_with(&mut cache, "ab"));
+}
+
+#[test]
+fn reports_memory_usage() {
+    let r = Regex::from("(a|b)*abb").unwrap();
+    let usage = r.memory_usage();
+
+    assert!(usage > 0);
+    assert!(Regex::from("(a|b)*abbbbbbb").unwrap().memory_usage() > usage);
+    assert!(r.compile_dfa(1 << 20).unwrap().memory_usage() > usage);
+}
+
+#[test]
+fn finds_leftmost_first_match() {
+    let cases = [
+        ("b|ab", "xab", Some((1, 3))), // the earliest start wins over the left branch
+        ("a|ab", "ab", Some((0, 1))), // the left branch wins over the longer match
+        ("ab|a", "ab", Some((0, 2))),
+        ("a*", "aaa", Some((0, 3))), // repetitions are greedy
+        ("a*", "baaa", Some((0, 0))), // an empty match at the start wins over a later one
+        ("(a|ab)(c|bcd)", "abcd", Some((0, 4))),
+        ("(a+|b+)*c", "abbac", Some((0, 5))),
+        ("a?b", "xaab", Some((2, 4))),
+        ("xyz", "xyxy", None)
+    ];
+
+    for &(pattern, text, expected) in cases.iter() {
+        let expected = expected.map(|(start, end)| Match::new(start, end));
+        let r = Regex::from(pattern).unwrap();
+
+        assert_eq!(expected, r.find(text), "{} in {}", pattern, text);
+        assert_eq!(expected, r.stream().first(text.bytes()), "stream: {} in {}", pattern, text);
+
+        let long_text = text.to_owned() + &"-".repeat(1 << 20); // too long to backtrack over
+        assert_eq!(expected, r.find(&long_text), "pike vm: {} in {}", pattern, text);
+    }
+}
+
+#[test]
+fn finds_leftmost_longest_match() {
+    let cases = [
+        ("a|ab", "ab", Some((0, 2))),
+        ("b|ab", "xab", Some((1, 3))),
+        ("(a|ab)(c|bcd)(d*)", "abcd", Some((0, 4))),
+        ("a*?", "aaa", Some((0, 3))),
+        ("foo|foobar", "a foobar", Some((2, 8))), // alternations of literals too
+        ("[xy]+-(a|ab)", "zxy-ab", Some((1, 6))), // and searches from an inner literal
+        ("xyz", "xyxy", None)
+    ];
+
+    for &(pattern, text, expected) in cases.iter() {
+        let expected = expected.map(|(start, end)| Match::new(start, end));
+        let r = RegexBuilder::new(pattern).leftmost_longest(true).build().unwrap();
+
+        assert_eq!(expected, r.find(text), "{} in {}", pattern, text);
+        assert_eq!(expected, r.stream().first(text.bytes()), "stream: {} in {}", pattern, text);
+
+        let long_text = text.to_owned() + &"-".repeat(1 << 20); // too long to backtrack over
+        assert_eq!(expected, r.find(&long_text), "pike vm: {} in {}", pattern, text);
+    }
+}
+
+#[test]
+fn streams_leftmost_longest_matches() {
+    let r = RegexBuilder::new("a|ab").leftmost_longest(true).build().unwrap();
+    let text = "ab-aab";
+
+    let streamed = r.stream_matches(text.as_bytes()).map(|m| m.unwrap()).collect::<Vec<Match>>();
+    assert_eq!(vec![Match::new(0, 2), Match::new(3, 4), Match::new(4, 6)], streamed);
+    assert_eq!(r.find_all(&mut r.create_cache(), text), streamed);
+    assert_eq!(vec![Match::new(0, 2), Match::new(3, 4), Match::new(4, 6)], r.find_overlapping_iter(text).collect::<Vec<Match>>());
+
+    let records = r.stream_split("xaby".as_bytes()).map(|record| record.unwrap()).collect::<Vec<Vec<u8>>>();
+    assert_eq!(vec![b"x".to_vec(), b"y".to_vec()], records);
+
+    let first = Regex::from("a|ab").unwrap();
+    assert_eq!(vec![Match::new(0, 1)], first.stream_matches("ab".as_bytes()).map(|m| m.unwrap()).collect::<Vec<Match>>());
+}
+
+#[test]
+fn matches_longest_at_start() {
+    let r = RegexBuilder::new("a|ab").leftmost_longest(true).build().unwrap();
+
+    assert_eq!(Some(2), r.match_offset("abc"));
+    assert_eq!(Some(2), r.compile_dfa(1 << 20).unwrap().match_offset("abc"));
+}
+
+#[test]
+fn round_trips_leftmost_longest_through_bytes() {
+    let r = RegexBuilder::new("a|ab").leftmost_longest(true).build().unwrap();
+    let loaded = Regex::from_bytes(&r.to_bytes()).unwrap();
+
+    assert_eq!(r, loaded);
+    assert_eq!(Some(Match::new(0, 2)), loaded.find("ab"));
+}
+
+#[test]
+fn round_trips_options_through_bytes() {
+    let r = RegexBuilder::new("a.\\{2\\}").case_insensitive(true).line_terminator(b'\0').syntax(Syntax::PosixBasic)
+        .build().unwrap();
+    assert_eq!(r, Regex::from_bytes(&r.to_bytes()).unwrap());
+    assert_ne!(Regex::from("a").unwrap(), RegexBuilder::new("a").case_insensitive(true).build().unwrap());
+}
+
+#[test]
+fn repeats_items_that_match_empty() {
+    // the match, and where group 1 matched
+    let cases = [
+        ("(a*)*", "", (0, 0), Some((0, 0))), // the empty item still sets the group
+        ("(a*)*", "aab", (0, 2), Some((0, 2))),
+        ("(a*)+", "b", (0, 0), Some((0, 0))),
+        ("(a?)+", "aa", (0, 2), Some((1, 2))),
+        ("(a?)*", "aab", (0, 2), Some((1, 2))),
+        ("(a*|b)*", "aab", (0, 3), Some((2, 3))),
+        ("(a|b*)*c", "abbac", (0, 5), Some((3, 4))),
+        ("(b*|a)*c", "ac", (0, 2), Some((0, 1))),
+        ("((a*)*)*x", "aaax", (0, 4), Some((0, 3)))
+    ];
+
+    for &(pattern, text, (start, end), group) in cases.iter() {
+        let r = Regex::from(pattern).unwrap();
+        let group = group.map(|(start, end)| Match::new(start, end));
+
+        let captures = r.captures(text).unwrap();
+        assert_eq!((Some(Match::new(start, end)), group), (captures.get(0), captures.get(1)), "{} in {}", pattern, text);
+        assert_eq!(Some(end), r.match_offset(text), "{} in {}", pattern, text);
+        assert_eq!(Some(Match::new(start, end)), r.stream().first(text.bytes()), "stream: {} in {}", pattern, text);
+
+        let long_text = text.to_owned() + &"-".repeat(1 << 20); // too long to backtrack over
+        let captures = r.captures(&long_text).unwrap();
+        assert_eq!((Some(Match::new(start, end)), group), (captures.get(0), captures.get(1)), "pike vm: {} in {}", pattern, text);
+    }
+
+    let r = Regex::from("((a*)*|b?)*c").unwrap();
+    assert_eq!(None, r.find(&"ab".repeat(1 << 18))); // gives up in linear time
+}
+
+#[test]
+fn matches_empty_pattern_everywhere() {
+    let r = Regex::from("").unwrap();
+
+    assert_eq!(Some(0), r.match_offset("abc"));
+    assert_eq!(Some(Match::new(0, 0)), r.find("abc"));
+    assert_eq!(vec![Match::new(0, 0), Match::new(1, 1), Match::new(2, 2)],
+               r.stream_matches("ab".as_bytes()).map(|m| m.unwrap()).collect::<Vec<_>>());
+    assert_eq!(3, r.find_overlapping_iter("ab").count());
+}
+
+#[test]
+fn matches_empty_alternatives_and_groups() {
+    let r = Regex::from("x(a|)y").unwrap();
+    assert_eq!(Some(Match::new(1, 4)), r.find("-xay"));
+    assert_eq!(Some(Match::new(1, 3)), r.captures("-xy").unwrap().get(0));
+    assert_eq!(Some(Match::new(2, 2)), r.captures("-xy").unwrap().get(1));
+
+    let r = Regex::from("(|a)+b").unwrap();
+    assert_eq!(Some(3), r.match_offset("aab"));
+    assert_eq!(Some(Match::new(0, 0)), r.captures("b").unwrap().get(1));
+
+    let r = Regex::from("a()b").unwrap();
+    assert_eq!(Some(Match::new(1, 1)), r.captures("ab").unwrap().get(1));
+}
+
+#[test]
+fn matches_case_insensitively() {
+    let r = RegexBuilder::new("[ab]+@example\\.com").case_insensitive(true).build().unwrap();
+
+    assert_eq!(Some(Match::new(1, 17)), r.find("-aBBa@EXAMPLE.com"));
+    assert_eq!(Some(Match::new(0, 16)), r.rfind("ABBA@example.COM"));
+    assert_eq!(None, RegexBuilder::new("abc").build().unwrap().find("ABC"));
+}
+
+#[test]
+fn stops_dot_at_line_terminator() {
+    let r = RegexBuilder::new("a.*").line_terminator(b'\0').build().unwrap();
+    assert_eq!(Some(Match::new(0, 4)), r.find("ab\nc\0ad"));
+    assert_eq!(2, r.count("ab\nc\0ad"));
+    assert_eq!(Some(Match::new(0, 2)), Regex::from("a.*").unwrap().find("ab\nc\0ad"));
+    assert!(RegexBuilder::new("a").line_terminator(b'\0') != RegexBuilder::new("a"));
+}
+
+#[test]
+fn rejects_line_terminator_outside_ascii() {
+    // 0xc3 leads the encoding of `é`, which `.` would never match
+    assert_eq!(Some(Error::InvalidLineTerminator), RegexBuilder::new("a.").line_terminator(0xc3).build().err());
+    assert_eq!(Some(Error::InvalidLineTerminator), RegexBuilder::new("a").line_terminator(0x80).build().err());
+    assert!(RegexBuilder::new("a.").line_terminator(0x7f).build().unwrap().is_match("aé"));
+}
+
+#[test]
+fn builds_from_expression() {
+    let expr = Expr::concat(vec![
+        Expr::group(1, Expr::one_or_more(Expr::class("abc".chars()))),
+        Expr::literal("@"),
+        Expr::group(2, Expr::alternation(vec![Expr::literal("x.y"), Expr::literal("(z)")]))
+    ]);
+    let r = RegexBuilder::from_expr(expr).build().unwrap();
+
+    assert_eq!("([abc]+)@(x\\.y|\\(z\\))", r.pattern);
+    assert_eq!(Some("(z)"), r.captures("-ab@(z)").unwrap().get_str(2));
+    assert_eq!(None, r.find("ab@xzy"));
+    assert_eq!(r, Regex::from(&r.pattern).unwrap());
+}
+
+#[test]
+fn gives_back_its_pattern() {
+    let r = Regex::from("a(b|c)*").unwrap();
+
+    assert_eq!("a(b|c)*", r.as_str());
+    assert_eq!("a(b|c)*", r.to_string());
+}
+
+#[test]
+fn clones_compare_equal() {
+    let r = Regex::from("x[yz]+").unwrap().compile_dfa(1 << 20).unwrap();
+    let copy = r.clone();
+
+    assert_eq!(r, copy);
+    assert_ne!(r, Regex::from("x[yz]*").unwrap());
+    assert_eq!(Some(Match::new(1, 4)), copy.find("-xyz"));
+}
+
+#[test]
+fn clones_share_automata() {
+    let r = Regex::from("x[yz]+").unwrap();
+    let copy = r.clone();
+    assert!(Arc::ptr_eq(&r.program, &copy.program));
+
+    // compiling a DFA for one clone leaves the other as it was
+    let compiled = copy.compile_dfa(1 << 20).unwrap();
+    assert!(compiled.has_dfa() && !r.has_dfa());
+}
+
+#[test]
+fn reads_captures_into_reused_locations() {
+    let r = Regex::from("(a+)(b)?").unwrap();
+    let mut cache = r.create_cache();
+    let mut locations: CaptureLocations = r.capture_locations();
+    assert_eq!(3, locations.len());
+    assert!(!locations.is_empty());
+
+    assert_eq!(Some(Match::new(1, 4)), r.captures_read_with(&mut cache, &mut locations, "-aab"));
+    assert_eq!(Some(Match::new(1, 3)), locations.get(1));
+    assert_eq!(Some(Match::new(3, 4)), locations.get(2));
+
+    // groups from the previous match are cleared
+    assert_eq!(Some(Match::new(0, 1)), r.captures_read(&mut locations, "ac"));
+    assert_eq!(Some(Match::new(0, 1)), locations.get(1));
+    assert_eq!(None, locations.get(2));
+
+    assert_eq!(None, r.captures_read(&mut locations, "xyz"));
+    assert_eq!(None, locations.get(0));
+}
+
+#[test]
+fn expands_template_with_groups() {
+    let r = Regex::from("([123]+)-([123]+)(x)?").unwrap();
+    let captures = r.captures("on 12-3!").unwrap();
+    let mut dst = "swap: ".to_owned();
+
+    captures.expand("$2-$1 ${1}0 [$3] $$5 ${name}$name $ ${2", &mut dst);
+    assert_eq!("swap: 3-12 120 [] $5  $ ${2", dst);
+
+    let r = Regex::from("(?P<first>[acdelov]+) (?P<last>[acdelov]+)").unwrap();
+    let mut dst = String::new();
+    r.captures("ada lovelace").unwrap().expand("$last, ${first}! $first_", &mut dst);
+    assert_eq!("lovelace, ada! ", dst);
+}
+
+#[test]
+fn indexes_captures_by_group() {
+    let r = Regex::from("(a+)(b)?c").unwrap();
+    let captures = r.captures("-aac").unwrap();
+
+    assert_eq!("aac", &captures[0]);
+    assert_eq!("aa", &captures[1]);
+}
+
+#[test]
+#[should_panic(expected = "group 2 did not take part in the match")]
+fn index_panics_on_group_that_did_not_match() {
+    let r = Regex::from("(a+)(b)?c").unwrap();
+    let _ = &r.captures("aac").unwrap()[2];
+}
+
+#[test]
+#[should_panic(expected = "no group 3")]
+fn index_panics_on_missing_group() {
+    let r = Regex::from("(a+)(b)?c").unwrap();
+    let _ = &r.captures("aac").unwrap()[3];
+}
+
+#[test]
+fn indexes_captures_by_name() {
+    let r = Regex::from("(?P<year>[0123456789]+)-(?P<month>[01]?.)(-(?P<day>..))?").unwrap();
+    let captures = r.captures("on 2024-05!").unwrap();
+
+    assert_eq!("2024", &captures["year"]);
+    assert_eq!("05", &captures["month"]);
+    assert_eq!(Some(Match::new(8, 10)), captures.name("month"));
+    assert_eq!(None, captures.name("day"));
+    assert_eq!(None, captures.name("date"));
+}
+
+#[test]
+#[should_panic(expected = "no group named 'date'")]
+fn index_panics_on_name() {
+    let r = Regex::from("(?P<day>a+)").unwrap();
+    let _ = &r.captures("aa").unwrap()["date"];
+}
+
+#[test]
+#[should_panic(expected = "group 'b' did not take part in the match")]
+fn index_panics_on_named_group_that_did_not_match() {
+    let r = Regex::from("(?P<a>a)|(?P<b>b)").unwrap();
+    let _ = &r.captures("a").unwrap()["b"];
+}
+
+#[test]
+fn keeps_group_names_through_bytes() {
+    let r = Regex::from("(?P<x>a)(b)").unwrap();
+    assert_eq!(r.capture_names(), Regex::from_bytes(&r.to_bytes()).unwrap().capture_names());
+
+    let r = RegexBuilder::new("\\(?P<x>\\)").syntax(Syntax::PosixBasic).build().unwrap();
+    assert_eq!(vec![None; 2], Regex::from_bytes(&r.to_bytes()).unwrap().capture_names());
+}
+
+#[test]
+fn set_reports_which_pattern_matched_where() {
+    let set = RegexSet::new(["a+", "b(c)", "ab"]).unwrap();
+    assert_eq!(3, set.len());
+    assert!(!set.is_empty());
+
+    assert_eq!(vec![(0, Match::new(1, 3)), (1, Match::new(3, 5)), (0, Match::new(6, 7))],
+               set.matches_iter("-aabc-ab").collect::<Vec<_>>());
+    assert!(set.is_match("xbc"));
+    assert!(!set.is_match("xyz"));
+}
+
+#[test]
+fn set_moves_past_empty_matches() {
+    let set = RegexSet::new(["x*", "y"]).unwrap();
+
+    assert_eq!(vec![(0, Match::new(0, 0)), (0, Match::new(1, 3)), (0, Match::new(3, 3))],
+               set.matches_iter("yxx").collect::<Vec<_>>());
+}
+
+#[test]
+fn set_matches_patterns_with_caret_only_at_the_start() {
+    let set = RegexSet::new(["^a", "b"]).unwrap();
+    assert_eq!(vec![(0, Match::new(0, 1)), (1, Match::new(1, 2)), (1, Match::new(3, 4))],
+               set.matches_iter("abab").collect::<Vec<_>>());
+    assert_eq!(vec![(1, Match::new(0, 1))], set.matches_iter("baa").collect::<Vec<_>>());
+    assert!(!set.is_match("ca"));
+
+    // earlier patterns still win at the start
+    let set = RegexSet::new(["a", "^ab"]).unwrap();
+    assert_eq!(vec![(0, Match::new(0, 1)), (0, Match::new(2, 3))],
+               set.matches_iter("aba").collect::<Vec<_>>());
+
+    let set = RegexSet::new(["^x*"]).unwrap();
+    assert_eq!(vec![(0, Match::new(0, 0))], set.matches_iter("yx").collect::<Vec<_>>());
+}
+
+#[test]
+fn empty_set_matches_nothing() {
+    let set = RegexSet::new(Vec::<&str>::new()).unwrap();
+
+    assert!(set.is_empty());
+    assert!(!set.is_match("abc"));
+    assert_eq!(Err(Error::UnmatchedGroupClose(1, 2)), RegexSet::new(["a", "a)"]));
+}
+
+#[test]
+fn searches_anchored_or_not() {
+    let r = Regex::from("a(b+)").unwrap();
+
+    assert_eq!(Some(Match::new(1, 3)), r.find_anchored("-ab", Anchored::No));
+    assert_eq!(None, r.find_anchored("-ab", Anchored::Yes));
+    assert_eq!(Some(Match::new(0, 3)), r.find_anchored("abb-ab", Anchored::Yes));
+    assert_eq!(Some("bb"), r.captures_anchored("abb-ab", Anchored::Yes).unwrap().get_str(1));
+    assert!(r.captures_anchored("-abb", Anchored::Yes).is_none());
+
+    // searched for from a literal inside the pattern
+    let r = Regex::from("[xy]+-ab").unwrap();
+    assert_eq!(None, r.find_anchored("zx-ab", Anchored::Yes));
+    assert_eq!(Some(Match::new(0, 5)), r.find_anchored("xy-ab", Anchored::Yes));
+}
+
+#[test]
+fn searches_as_input_says() {
+    let r = Regex::from("a(b+)").unwrap();
+    let mut cache = r.create_cache();
+    let text = "ab-abbb-ab";
+
+    assert_eq!(Some(Match::new(3, 7)), r.search(&mut cache, &Input::new(text).span(1, 10)));
+    assert_eq!(Some(Match::new(3, 6)), r.search(&mut cache, &Input::new(text).span(1, 6)));
+    assert_eq!(None, r.search(&mut cache, &Input::new(text).span(1, 4)));
+    assert_eq!(None, r.search(&mut cache, &Input::new(text).span(2, 10).anchored(Anchored::Yes)));
+    assert_eq!(Some(Match::new(3, 5)), r.search(&mut cache, &Input::new(text).span(1, 10).earliest(true)));
+
+    let captures = r.search_captures(&mut cache, &Input::new(text).span(3, 10).anchored(Anchored::Yes)).unwrap();
+    assert_eq!(Some("bbb"), captures.get_str(1));
+}
+
+#[test]
+fn iterates_between_matches() {
+    let r = Regex::from("[0123456789]+").unwrap();
+    let gaps = r.non_matches_iter("a12bc345").collect::<Vec<_>>();
+    assert_eq!(vec![Match::new(0, 1), Match::new(3, 5), Match::new(8, 8)], gaps);
+
+    let gaps = Regex::from("a*").unwrap().non_matches_iter("b").collect::<Vec<_>>();
+    assert_eq!(vec![Match::new(0, 0), Match::new(0, 1), Match::new(1, 1)], gaps);
+
+    assert_eq!(vec![Match::new(0, 3)], Regex::from("x").unwrap().non_matches_iter("abc").collect::<Vec<_>>());
+    assert_eq!(vec![Match::new(0, 0)], Regex::from("x").unwrap().non_matches_iter("").collect::<Vec<_>>());
+}
+
+#[test]
+fn matches_fall_on_char_boundaries() {
+    let text = "éa€\n𝄞";
+    for pattern in [".", ".*", "..", "[é€]?.", "", "a|.", "(.)+"].iter() {
+        let r = Regex::from(pattern).unwrap();
+        for found in r.find_all(&mut r.create_cache(), text) {
+            assert!(text.is_char_boundary(found.start()) && text.is_char_boundary(found.end()), "{:?} {:?}", pattern, found);
+        }
+        let found = r.rfind(text).unwrap();
+        assert!(text.is_char_boundary(found.start()) && text.is_char_boundary(found.end()), "{:?} {:?}", pattern, found);
+    }
+    let r = Regex::from(".").unwrap();
+    assert_eq!(vec![Match::new(0, 2), Match::new(2, 3)], r.find_all(&mut r.create_cache(), "éa"));
+}
+
+#[test]
+fn finds_within_range() {
+    let r = Regex::from("a(b+)").unwrap();
+    assert_eq!(Some(Match::new(3, 6)), r.find_in("ab-abbb-ab", 1..6));
+    assert_eq!(None, r.find_in("ab-abbb-ab", 1..4));
+    assert_eq!(Some(Match::new(8, 10)), r.find_in("ab-abbb-ab", 7..10));
+
+    // the start of the range is not the start of the text
+    let r = Regex::from("^ab").unwrap();
+    assert_eq!(None, r.find_in("abab", 2..4));
+    assert_eq!(Some(Match::new(0, 2)), r.find_in("abab", 0..4));
+}
+
+#[test]
+fn quits_at_quit_bytes() {
+    let r = Regex::from("a(b+)").unwrap();
+    let mut cache = r.create_cache();
+    let text = "xab\nabbb\nzz";
+
+    let input = Input::new(text).quit(b'\n');
+    assert_eq!(Ok(Some(Match::new(1, 3))), r.try_search(&mut cache, &input));
+    assert_eq!(Err(Quit::new(b'\n', 3)), r.try_search(&mut cache, &input.span(3, 11)));
+    assert_eq!(Ok(Some(Match::new(4, 8))), r.try_search(&mut cache, &input.span(4, 11)));
+    assert_eq!(Err(Quit::new(b'\n', 8)), r.try_search(&mut cache, &input.span(5, 11)));
+    assert_eq!(Ok(None), r.try_search(&mut cache, &input.span(9, 11)));
+    assert_eq!(None, r.search(&mut cache, &input.span(5, 11)));
+
+    // no match takes in a quit byte
+    let r = Regex::from("a.b").unwrap();
+    assert_eq!(Err(Quit::new(b'-', 1)), r.try_search(&mut r.create_cache(), &Input::new("a-b").quit(b'-')));
+    assert_eq!(Some(Match::new(0, 3)), r.find("a-b"));
+}
+
+#[test]
+#[should_panic(expected = "invalid span 2..1")]
+fn input_rejects_reversed_span() {
+    Input::new("abc").span(2, 1);
+}
+
+#[test]
+fn searches_lines_of_reader() {
+    let r = Regex::from("ab*").unwrap();
+    let text = "xab ab\nnone\n\nabb\n";
+
+    let lines = r.search_lines(text.as_bytes()).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(vec![(1, "xab ab".to_owned(), vec![Match::new(1, 3), Match::new(4, 6)]),
+                    (4, "abb".to_owned(), vec![Match::new(0, 3)])],
+               lines);
+}
+
+#[test]
+fn search_lines_reports_invalid_utf8() {
+    let r = Regex::from("a").unwrap();
+    let text: &[u8] = b"a\n\xff\na\n";
+    let mut lines = r.search_lines(text);
+
+    assert_eq!(1, lines.next().unwrap().unwrap().0);
+    assert!(lines.next().unwrap().is_err());
+    assert!(lines.next().is_none());
+}
+
+#[test]
+fn search_lines_steps_over_empty_matches() {
+    let r = Regex::from("x*").unwrap();
+    let lines = r.search_lines("ax".as_bytes()).collect::<Result<Vec<_>, _>>().unwrap();
+
+    assert_eq!(vec![(1, "ax".to_owned(), vec![Match::new(0, 0), Match::new(1, 2), Match::new(2, 2)])], lines);
+}
+
+#[test]
+fn highlights_matches() {
+    let r = Regex::from("ab*").unwrap();
+    let pieces = |text: &str| {
+        r.highlight(text).iter().map(|s| (s.as_str().to_owned(), s.is_match())).collect::<Vec<_>>()
+    };
+
+    assert_eq!(vec![("x".to_owned(), false), ("abb".to_owned(), true), ("a".to_owned(), true), ("-".to_owned(), false)],
+               pieces("xabba-"));
+    assert_eq!(vec![("zz".to_owned(), false)], pieces("zz"));
+    assert!(pieces("").is_empty());
+    assert_eq!(Span::new(1, 4), r.highlight("xabba-")[1].span());
+}
+
+#[test]
+fn matches_negated_character_class() {
+    let regex = Regex::from("[^abé]+").unwrap();
+    assert_eq!(Some(Match::new(1, 11)), regex.find("ax\n😀cè-é"));
+    assert!(!regex.is_match("é"));
+    assert_eq!(Some(Match::new(0, 0)), Regex::from("[^ab]*").unwrap().find("ab"));
+
+    let regex = RegexBuilder::new("[^k]").case_insensitive(true).build().unwrap();
+    assert_eq!(None, regex.find("kK\u{212a}"));
+}
+
+#[test]
+fn compiles_posix_patterns() {
+    let r = RegexBuilder::new("\\([[:digit:]]\\{2\\}\\)-x*").syntax(Syntax::PosixBasic).build().unwrap();
+    assert_eq!(Some("42"), r.captures("n 42-xx").unwrap().get_str(1));
+
+    let r = RegexBuilder::new("(ab|c){2}").syntax(Syntax::PosixExtended).build().unwrap();
+    assert_eq!(Some(Match::new(1, 4)), r.find("-cab-"));
+
+    let r = RegexBuilder::new("^[^#]*=[^=]*").syntax(Syntax::PosixExtended).build().unwrap();
+    assert_eq!(Some(Match::new(0, 5)), r.find("a = b"));
+    assert_eq!(None, r.find("# a = b"));
+    assert_eq!(None, r.find(" #a=b"));
+
+    let ere = |pattern: &str| RegexBuilder::new(pattern).syntax(Syntax::PosixExtended).build();
+    assert_eq!(Err(Error::UnsupportedAnchor(3, 4)), ere("a|b$"));
+}
+
+#[test]
+fn compiles_intervals_up_to_dup_max() {
+    let ere = |pattern: &str| RegexBuilder::new(pattern).syntax(Syntax::PosixExtended).build().unwrap();
+    let r = ere("a{0,255}");
+    assert_eq!(Some(Match::new(0, 0)), r.find("b"));
+    assert_eq!(Some(Match::new(0, 255)), r.find(&"a".repeat(300)));
+    let r = ere("xa{1,255}");
+    assert_eq!(None, r.find("xb"));
+    assert_eq!(Some(Match::new(1, 4)), r.find("-xaa"));
+    assert_eq!(Some(Match::new(0, 256)), r.find(&format!("x{}", "a".repeat(300))));
+
+    // the optional copies give the groups the nested ones would
+    let r = ere("(a|ab)(c|bcd){0,3}(d*)");
+    assert_eq!(Some("abcd"), r.captures("abcd").unwrap().get_str(0));
+    assert_eq!(Some("bcd"), r.captures("abcd").unwrap().get_str(2));
+}
+
+#[test]
+fn compares_what_patterns_match() {
+    let regex = |pattern: &str| Regex::from(pattern).unwrap();
+
+    assert!(regex("(a|b)*").is_equivalent(&regex("(a*b*)*")));
+    assert!(regex("a|ab").is_equivalent(&regex("ab|a")));
+    assert!(!regex("a+").is_equivalent(&regex("a*")));
+
+    assert!(regex("a+").is_subset(&regex("a*")));
+    assert!(!regex("a*").is_subset(&regex("a+")));
+    assert!(regex("abc").is_subset(&regex("a.c")));
+    assert!(!regex("a.c").is_subset(&regex("abc")));
+    assert!(regex("a.c").is_subset(&regex("a.c")));
+}
+
+#[test]
+fn traces_searches() {
+    let regex = Regex::from("b+").unwrap();
+    let trace = regex.trace("abbc");
+
+    assert_eq!(regex.find("abbc"), trace.found());
+    let consumed = trace.steps().iter().filter(|step| !step.taken().is_empty()).map(|step| step.at()).collect::<Vec<_>>();
+    assert_eq!(vec![1, 2], consumed);
+}
+
+#[test]
+fn finds_with_metrics() {
+    let regex = Regex::from("(a|b)*c").unwrap();
+    let (found, metrics) = regex.find_with_metrics("ababx");
+
+    assert_eq!(regex.find("ababx"), found);
+    assert_eq!(5, metrics.bytes_scanned());
+    assert!(metrics.threads_spawned() > 0);
+}
+
+#[test]
+fn searches_from_inner_literals_in_linear_time() {
+    use std::time::{Duration, Instant};
+
+    let regex = Regex::from("[abcdefghijklmnopqrstuvwxyz]+@.*c").unwrap();
+    let text = "a@".repeat(40_000);
+
+    let started = Instant::now();
+    assert_eq!(None, regex.find(&text));
+    assert!(regex.captures(&text).is_none());
+    assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+
+    assert_eq!(Some(Match::new(0, 2 * 40_000 + 1)), regex.find(&(text + "c")));
+}
+
+#[test]
+fn finds_readme_inner_literal_example() {
+    let regex = Regex::from("\\w+@example\\.com").unwrap();
+    assert!(regex.program.inner.is_some());
+    let text = "mail jo_smith1@example.com, not w@example-com or @example.com";
+    assert_eq!(vec![Match::new(5, 26)], regex.find_all(&mut regex.create_cache(), text));
+    assert_eq!(Some("jo_smith1@example.com"), regex.captures(text).unwrap().get_str(0));
+}
+
+#[test]
+fn rejects_backreferences() {
+    assert_eq!(Some(Error::UnsupportedBackreference(3, 5)), Regex::from("(a)\\1").err());
+    assert_eq!(Some(Error::UnsupportedBackreference(5, 7)),
+               RegexBuilder::new("\\(a\\)\\1").syntax(Syntax::PosixBasic).build().err());
+}
+
+#[test]
+fn searches_pathological_patterns_in_linear_time() {
+    let regex = Regex::from("((a|a)+)+b").unwrap();
+    let text = "a".repeat(2000);
+    assert_eq!(None, regex.captures(&text[..30]));
+    assert_eq!(None, regex.captures(&text));
+
+    let (found, metrics) = regex.find_with_metrics(&text);
+    assert_eq!(None, found);
+    assert!(metrics.states_visited() <= regex.nfa().num_states() * (text.len() + 1));
+}
+
+#[test]
+fn counts_matches() {
+    let count = |pattern: &str, text: &str| {
+        let r = Regex::from(pattern).unwrap();
+        assert_eq!(r.find_all(&mut r.create_cache(), text).len(), r.count(text), "{:?} in {:?}", pattern, text);
+        r.count(text)
+    };
+    assert_eq!(3, count("[0123456789]+", "a12bc345d6"));
+    assert_eq!(0, count("x", "abc"));
+    assert_eq!(3, count("a*", "baa"));
+    assert_eq!(3, count("", "éa"));
+    assert_eq!(2, count(".", "éa"));
+    assert_eq!(2, count("ab|a", "aab"));
+    assert_eq!(1, count("^a", "aaa"));
+
+    let r = RegexBuilder::new("a|ab").leftmost_longest(true).build().unwrap();
+    assert_eq!(2, r.count("abab"));
+}
+
+#[test]
+fn describes_groups() {
+    let r = Regex::from("(a(b))|(c)?").unwrap();
+    assert_eq!(4, r.capture_len());
+    assert_eq!(r.capture_len(), r.captures("ab").unwrap().len());
+    assert_eq!(vec![None; 4], r.capture_names());
+    assert_eq!(vec![None, Some("a"), None, Some("c")], Regex::from("(?P<a>a(b))|(?P<c>c)?").unwrap().capture_names());
+    assert!((0..4).all(|i| r.can_participate(i)));
+    assert!(!r.can_participate(4));
+}
+
+#[test]
+fn estimates_complexity() {
+    let complexity = |pattern: &str| Regex::from(pattern).unwrap().complexity();
+    assert!(complexity("(x+x+)+y").has_nested_repetition());
+    assert!(!complexity("x+y").has_nested_repetition());
+    assert!(complexity("[abc]x[abc]x[abc]").states() > complexity("[abc]x").states());
+}
+
+#[test]
+fn matches_within_budget() {
+    use ::pikevm::Budget;
+
+    let regex = Regex::from("(a|b)*c").unwrap();
+    let text = "ab".repeat(1000) + "c";
+    assert_eq!(Ok(true), regex.try_is_match(&text, Budget::new().steps(100000)));
+    assert_eq!(Ok(false), regex.try_is_match("abd", Budget::new().steps(100)));
+    assert!(regex.try_is_match(&text, Budget::new().steps(100)).is_err());
+}
+
+#[test]
+fn extracts_literals() {
+    let literals = |pattern: &str| Regex::from(pattern).unwrap().literals();
+    let strings = |literals: &[&str]| Some(literals.iter().map(|&literal| literal.to_owned()).collect::<Vec<_>>());
+
+    assert_eq!(strings(&["abc"]), literals("abc"));
+    assert_eq!(strings(&["a", "ab", "é"]), literals("a|ab|é|a"));
+    assert_eq!(strings(&["xb", "xc", ""]), literals("x(b|c)|"));
+    assert_eq!(None, literals("ab*"));
+    assert_eq!(None, literals("a.c"));
+    assert_eq!(strings(&["ab", "ac"]), literals("a[cb]"));
+    assert_eq!(None, literals("^abc"));
+    assert!(Regex::from("foo|bar").unwrap().is_literal());
+    assert!(!Regex::from("fo+").unwrap().is_literal());
+    let folded = RegexBuilder::new("foo").case_insensitive(true).build().unwrap();
+    assert_eq!(Some(8), folded.literals().map(|literals| literals.len()));
+
+    let longest = RegexBuilder::new("a|abc|ab").leftmost_longest(true).build().unwrap();
+    assert_eq!(strings(&["abc", "ab", "a"]), longest.literals());
+}
+
+#[test]
+fn skips_ahead_with_custom_prefilter() {
+    use std::sync::Arc;
+    use ::prefilter::Prefilter;
+
+    // only offers where a `b` is, so that matches starting with `a` are
+    // never tried
+    #[derive(Debug)]
+    struct AtB;
+
+    impl Prefilter for AtB {
+        fn find(&self, text: &[u8], from: usize) -> Option<usize> {
+            text[from..].iter().position(|&byte| byte == b'b').map(|offset| from + offset)
+        }
+    }
+
+    let prefilter: Arc<dyn Prefilter> = Arc::new(AtB);
+    let builder = RegexBuilder::new("[ab]c").prefilter(prefilter.clone());
+    let regex = builder.build().unwrap();
+    assert_eq!(Some(Match::new(3, 5)), regex.find("ac bc"));
+    assert_eq!(Some(Match::new(3, 5)), regex.find_with_metrics("ac bc").0);
+    assert_eq!(Some(Match::new(0, 2)), Regex::from("[ab]c").unwrap().find("ac bc"));
+
+    assert!(builder == RegexBuilder::new("[ab]c").prefilter(prefilter));
+    assert!(builder != RegexBuilder::new("[ab]c").prefilter(Arc::new(AtB)));
+    assert!(builder != RegexBuilder::new("[ab]c"));
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn finds_in_chunks_what_find_all_finds() {
+    use ::rng::Rng;
+    use super::parallel::find_chunked;
+
+    let mut rng = Rng::new(11);
+    let patterns = ["ab", "a+b?", "b*", "(ab|a)c?", "é.", "a.*b", "ba{2,5}", "^ab", "x?"];
+    for _ in 0..40 {
+        let text: String = (0..rng.below(120)).map(|_| ['a', 'b', 'c', 'é'][rng.below(4)]).collect();
+        for pattern in patterns.iter() {
+            let r = Regex::from(pattern).unwrap();
+            let expected = r.find_all(&mut r.create_cache(), &text);
+            for &chunk_len in [1, 2, 5, 16].iter() {
+                assert_eq!(expected, find_chunked(&r, &text, chunk_len), "{:?} in {:?}", pattern, text);
+            }
+        }
+    }
+    let r = Regex::from("ab+").unwrap();
+    let text = "xabbb".repeat(20_000);
+    assert_eq!(r.find_all(&mut r.create_cache(), &text), r.par_find_iter(&text).collect::<Vec<_>>());
+}
+
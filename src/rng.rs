@@ -0,0 +1,32 @@
+// A small xorshift64* generator, for the randomness the crate needs without
+// a dependency. The same seed always gives the same numbers.
+pub struct Rng {
+    state: u64
+}
+
+const DEFAULT_SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // xorshift never leaves zero
+        Rng { state: if seed == 0 { DEFAULT_SEED } else { seed } }
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 32
+    }
+
+    // a number from 0 up to but not including `n`
+    pub fn below(&mut self, n: usize) -> usize {
+        self.next() as usize % n
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Rng {
+        Rng::new(DEFAULT_SEED)
+    }
+}
@@ -0,0 +1,168 @@
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::{Deref, DerefMut};
+
+#[cfg(test)] mod spec;
+
+// A list that keeps up to `N` items in place and only allocates once it
+// grows past them, so that the NFAs and thread lists of small patterns take
+// no allocation of their own. Items are `Copy`, so the unused part of the
+// array can hold default values instead of uninitialized memory.
+#[derive(Clone)]
+pub enum SmallVec<T: Copy + Default, const N: usize> {
+    Inline([T; N], usize),
+    Heap(Vec<T>)
+}
+
+impl<T: Copy + Default, const N: usize> SmallVec<T, N> {
+    pub fn new() -> SmallVec<T, N> {
+        SmallVec::Inline([T::default(); N], 0)
+    }
+
+    // a list of `len` copies of `value`
+    pub fn from_elem(value: T, len: usize) -> SmallVec<T, N> {
+        if len <= N {
+            SmallVec::Inline([value; N], len)
+        } else {
+            SmallVec::Heap(vec![value; len])
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> SmallVec<T, N> {
+        if capacity <= N {
+            SmallVec::new()
+        } else {
+            SmallVec::Heap(Vec::with_capacity(capacity))
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        let spilled = match *self {
+            SmallVec::Inline(ref mut items, ref mut len) if *len < N => {
+                items[*len] = item;
+                *len += 1;
+                return;
+            },
+            SmallVec::Inline(ref items, len) => {
+                let mut spilled = Vec::with_capacity(2 * N + 1);
+                spilled.extend_from_slice(&items[..len]);
+                spilled.push(item);
+                spilled
+            },
+            SmallVec::Heap(ref mut items) => {
+                items.push(item);
+                return;
+            }
+        };
+        *self = SmallVec::Heap(spilled);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        match *self {
+            SmallVec::Inline(ref items, ref mut len) => {
+                if *len == 0 {
+                    return None;
+                }
+                *len -= 1;
+                Some(items[*len])
+            },
+            SmallVec::Heap(ref mut items) => items.pop()
+        }
+    }
+
+    pub fn clear(&mut self) {
+        match *self {
+            SmallVec::Inline(_, ref mut len) => *len = 0,
+            SmallVec::Heap(ref mut items) => items.clear()
+        }
+    }
+
+    // whether the items had to be moved to the heap
+    pub fn spilled(&self) -> bool {
+        match *self {
+            SmallVec::Inline(..) => false,
+            SmallVec::Heap(_) => true
+        }
+    }
+}
+
+impl<T: Copy + Default, const N: usize> Deref for SmallVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            SmallVec::Inline(items, len) => &items[..*len],
+            SmallVec::Heap(items) => items
+        }
+    }
+}
+
+impl<T: Copy + Default, const N: usize> DerefMut for SmallVec<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        match *self {
+            SmallVec::Inline(ref mut items, len) => &mut items[..len],
+            SmallVec::Heap(ref mut items) => items
+        }
+    }
+}
+
+impl<T: Copy + Default, const N: usize> Default for SmallVec<T, N> {
+    fn default() -> SmallVec<T, N> {
+        SmallVec::new()
+    }
+}
+
+impl<T: Copy + Default + PartialEq, const N: usize> PartialEq for SmallVec<T, N> {
+    fn eq(&self, other: &SmallVec<T, N>) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Copy + Default + Eq, const N: usize> Eq for SmallVec<T, N> {}
+
+impl<T: Copy + Default + PartialEq, const N: usize> PartialEq<SmallVec<T, N>> for Vec<T> {
+    fn eq(&self, other: &SmallVec<T, N>) -> bool {
+        self[..] == other[..]
+    }
+}
+
+impl<T: Copy + Default + fmt::Debug, const N: usize> fmt::Debug for SmallVec<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Copy + Default, const N: usize> Extend<T> for SmallVec<T, N> {
+    fn extend<I: IntoIterator<Item=T>>(&mut self, items: I) {
+        for item in items {
+            self.push(item);
+        }
+    }
+}
+
+impl<T: Copy + Default, const N: usize> FromIterator<T> for SmallVec<T, N> {
+    fn from_iter<I: IntoIterator<Item=T>>(items: I) -> SmallVec<T, N> {
+        let mut list = SmallVec::new();
+        list.extend(items);
+        list
+    }
+}
+
+impl<T: Copy + Default, const N: usize> From<Vec<T>> for SmallVec<T, N> {
+    fn from(items: Vec<T>) -> SmallVec<T, N> {
+        if items.len() <= N {
+            items.into_iter().collect()
+        } else {
+            SmallVec::Heap(items)
+        }
+    }
+}
+
+impl<'a, T: Copy + Default, const N: usize> IntoIterator for &'a SmallVec<T, N> {
+    type Item = &'a T;
+    type IntoIter = ::std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> ::std::slice::Iter<'a, T> {
+        self.iter()
+    }
+}
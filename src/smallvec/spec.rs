@@ -0,0 +1,26 @@
+use super::SmallVec;
+
+#[test]
+fn keeps_few_items_inline() {
+    let mut list = SmallVec::<usize, 2>::new();
+    list.push(1);
+    list.push(2);
+    assert!(!list.spilled());
+    assert_eq!(&[1, 2], &list[..]);
+
+    list.push(3);
+    assert!(list.spilled());
+    assert_eq!(&[1, 2, 3], &list[..]);
+    assert_eq!(Some(3), list.pop());
+    list.clear();
+    assert!(list.is_empty());
+}
+
+#[test]
+fn converts_from_vec() {
+    let list = SmallVec::<u8, 4>::from(vec![1, 2]);
+    assert!(!list.spilled());
+    assert_eq!(list, vec![1, 2].into_iter().collect());
+    assert!(SmallVec::<u8, 1>::from(vec![1, 2]).spilled());
+    assert_eq!(&[false; 5], &SmallVec::<bool, 4>::from_elem(false, 5)[..]);
+}
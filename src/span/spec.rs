@@ -0,0 +1,18 @@
+use std::ops::Range;
+
+use super::Span;
+
+#[test]
+fn measures_span() {
+    let span = Span::new(2, 5);
+    assert_eq!((2, 5, 3), (span.start(), span.end(), span.len()));
+    assert!(!span.is_empty());
+    assert!(Span::new(4, 4).is_empty());
+}
+
+#[test]
+fn converts_to_and_from_ranges() {
+    assert_eq!(Span::new(1, 3), Span::from(1..3));
+    assert_eq!(1..3, Range::from(Span::new(1, 3)));
+    assert_eq!("bc", &"abcd"[Span::new(1, 3).range()]);
+}
@@ -1,5 +1,4 @@
-use super::{Regex};
-use std::iter;
+use super::{Regex, escape};
 
 #[test]
 fn creates_new() {
@@ -8,7 +7,7 @@ fn creates_new() {
 
 #[test]
 fn creates_empty() {
-    assert!(Regex::from("").is_err());
+    assert!(Regex::from("").is_ok());
 }
 
 #[test]
@@ -37,8 +36,17 @@ fn complex_examples() {
 
 #[test]
 fn test() {
-    let r = Regex::from("(a|(aa))+b").unwrap();
+    let _r = Regex::from("(a|(aa))+b").unwrap();
 
     //assert!(!m.match_offset("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"));
 }
 
+
+#[test]
+fn escaped_pattern_matches_literally() {
+    let text = "(a|b)+.[c]\\";
+    let r = Regex::from(&escape(text)).unwrap();
+
+    assert_eq!(Some(text.len()), r.match_offset(text));
+    assert!(!r.is_match("aab."));
+}
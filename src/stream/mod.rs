@@ -0,0 +1,358 @@
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::mem;
+
+use ::nfa::{Transition, DenseNFA};
+use ::regex::Match;
+
+#[cfg(test)] mod spec;
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+#[derive(PartialEq,Debug,Clone)]
+struct Thread {
+    state: usize,
+    start: usize
+}
+
+// Searches input fed to it chunk by chunk, running all NFA threads in lock
+// step so no byte has to be looked at twice. The only bytes kept around are
+// those seen after the end of a match that may still grow; they are replayed
+// once the match is final, since the next search resumes at its end.
+pub struct Stream<'r> {
+    nfa: &'r DenseNFA,
+    threads: Vec<Thread>, // in order of priority
+    spare: Vec<Thread>, // the previous threads, kept to be reused
+    seen: Vec<bool>, // the states visited while adding threads
+    edges: Vec<Transition>, // the stack used while adding threads
+    start: usize, // absolute offset of the first byte
+    offset: usize, // absolute offset of the next byte
+    search_from: usize, // no match may start before this offset
+    best: Option<Match>,
+    anchored: bool, // matches may only start at `start`
+    longest: bool, // keep the longest match starting first rather than the leftmost-first
+    pending: Vec<u8>, // bytes after `pending_offset`, kept while `best` is set
+    pending_offset: usize
+}
+
+impl<'r> Stream<'r> {
+    pub fn new(nfa: &'r DenseNFA) -> Stream<'r> {
+        Self::starting_at(nfa, 0, false)
+    }
+
+    // a stream whose first byte is at `offset` in the input. If it is
+    // `anchored`, the only match it finds is one starting there.
+    pub fn starting_at(nfa: &'r DenseNFA, offset: usize, anchored: bool) -> Stream<'r> {
+        Stream {
+            nfa,
+            threads: Vec::new(),
+            spare: Vec::new(),
+            seen: vec![false; nfa.num_states()],
+            edges: Vec::new(),
+            start: offset,
+            offset,
+            search_from: offset,
+            best: None,
+            anchored,
+            longest: false,
+            pending: Vec::new(),
+            pending_offset: 0
+        }
+    }
+
+    // looks for leftmost-longest matches instead of leftmost-first ones
+    pub fn longest(mut self) -> Stream<'r> {
+        self.longest = true;
+        self
+    }
+
+    // finds the first match in `rest`, stopping as soon as it is final
+    pub fn first<I: Iterator<Item=u8>>(&mut self, rest: I) -> Option<Match> {
+        for byte in rest {
+            self.step(byte);
+
+            if self.threads.is_empty() && (self.best.is_some() || self.anchored) {
+                return self.best.take();
+            }
+        }
+
+        self.seed(None);
+        self.best.take()
+    }
+
+    /// Consumes the next chunk of input, returning the matches that became final.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<Match> {
+        let mut found = Vec::new();
+
+        for &byte in chunk {
+            self.step(byte);
+
+            // the bytes after a match that became final are searched again
+            // before the rest of the chunk, and so are those after a match
+            // found among them
+            let mut replay = self.take_final(&mut found);
+            let mut at = 0;
+            while at < replay.len() {
+                self.step(replay[at]);
+                at += 1;
+
+                let mut again = self.take_final(&mut found);
+                if !again.is_empty() {
+                    again.extend_from_slice(&replay[at..]);
+                    replay = again;
+                    at = 0;
+                }
+            }
+        }
+
+        found
+    }
+
+    /// Signals the end of input, returning the remaining matches.
+    pub fn finish(&mut self) -> Vec<Match> {
+        let mut found = Vec::new();
+
+        loop {
+            self.seed(None);
+            self.threads.clear(); // no more input for them to consume
+
+            match self.best.take() {
+                Some(m) => {
+                    let replay = self.restart(m);
+                    found.push(m);
+                    found.extend(self.feed(&replay));
+                },
+                None => break
+            }
+        }
+
+        found
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    fn step(&mut self, byte: u8) {
+        self.seed(Some(byte));
+
+        if self.best.is_some() {
+            self.pending.push(byte);
+        }
+
+        let spare = std::mem::take(&mut self.spare);
+        let mut current = mem::replace(&mut self.threads, spare);
+        self.clear_seen();
+        let next_offset = self.offset + 1;
+
+        for thread in &current {
+            // threads starting after the match found so far cannot beat it.
+            // The leftmost-first search drops them along with the threads of
+            // lower priority as soon as it is found, the longest one here
+            if self.best.is_some_and(|best| thread.start > best.start()) {
+                continue;
+            }
+            let nfa = self.nfa;
+            if nfa.takes(thread.state, byte) && self.add_thread(nfa.out(thread.state), thread.start, next_offset) && !self.longest {
+                break; // lower priority threads lose to this match
+            }
+        }
+
+        current.clear();
+        self.spare = current;
+        self.offset = next_offset;
+    }
+
+    // starts a thread at the current offset, before `next` if there is more
+    // input. A char's continuation bytes are skipped: only an empty match
+    // could start there, which would split the char.
+    fn seed(&mut self, next: Option<u8>) {
+        if self.best.is_some() || self.offset < self.search_from {
+            return;
+        }
+        if next.is_some_and(|byte| byte & 0xc0 == 0x80) {
+            return;
+        }
+        if self.anchored && self.offset != self.start {
+            return;
+        }
+
+        if self.threads.is_empty() {
+            self.clear_seen(); // left over from before a restart
+        }
+
+        let start = self.nfa.start();
+        let offset = self.offset;
+        self.add_thread(start, offset, offset);
+    }
+
+    fn clear_seen(&mut self) {
+        for seen in self.seen.iter_mut() {
+            *seen = false;
+        }
+    }
+
+    // follows empty edges from `edge`, queueing a thread for every state that
+    // consumes a byte. Returns true if a match was reached.
+    fn add_thread(&mut self, edge: Transition, start: usize, offset: usize) -> bool {
+        let threads = &mut self.threads;
+        let matched = self.nfa.closure(edge, self.longest, &mut self.seen, &mut self.edges, |state| threads.push(Thread { state, start }));
+        if matched {
+            self.record(Match::new(start, offset));
+        }
+        matched
+    }
+
+    fn record(&mut self, m: Match) {
+        if self.best.is_some_and(|best| m.start() > best.start()) {
+            return; // only the longest search goes on past a match to find later ones
+        }
+        if self.best.is_none() {
+            self.pending.clear();
+            self.pending_offset = m.end();
+        }
+        self.best = Some(m);
+    }
+
+    // adds the match found so far to `found` once no thread can still change
+    // it, returning the bytes after it that must be searched again
+    fn take_final(&mut self, found: &mut Vec<Match>) -> Vec<u8> {
+        if !self.threads.is_empty() {
+            return Vec::new();
+        }
+        match self.best.take() {
+            Some(m) => {
+                found.push(m);
+                self.restart(m)
+            },
+            None => Vec::new()
+        }
+    }
+
+    // resumes searching at the end of `m`, returning the bytes that must be
+    // searched again
+    fn restart(&mut self, m: Match) -> Vec<u8> {
+        let replay = self.pending.split_off(m.end() - self.pending_offset);
+        self.pending.clear();
+        self.threads.clear();
+
+        self.offset = m.end();
+        // an empty match is not reported twice at the same offset; the
+        // search goes on at the next char, as `seed` skips the rest of this one
+        self.search_from = if m.is_empty() { m.end() + 1 } else { m.end() };
+
+        replay
+    }
+}
+
+
+// Iterator over the matches in a reader, which is read in fixed size chunks.
+pub struct StreamMatches<'r, R> {
+    stream: Stream<'r>,
+    reader: R,
+    found: VecDeque<Match>,
+    done: bool
+}
+
+impl<'r, R: Read> StreamMatches<'r, R> {
+    pub fn new(stream: Stream<'r>, reader: R) -> StreamMatches<'r, R> {
+        StreamMatches {
+            stream,
+            reader,
+            found: VecDeque::new(),
+            done: false
+        }
+    }
+}
+
+impl<'r, R: Read> Iterator for StreamMatches<'r, R> {
+    type Item = io::Result<Match>;
+
+    fn next(&mut self) -> Option<io::Result<Match>> {
+        let mut buf = [0; CHUNK_SIZE];
+
+        while self.found.is_empty() && !self.done {
+            match self.reader.read(&mut buf) {
+                Ok(0) => {
+                    self.found.extend(self.stream.finish());
+                    self.done = true;
+                },
+                Ok(n) => self.found.extend(self.stream.feed(&buf[..n])),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        self.found.pop_front().map(Ok)
+    }
+}
+
+// Iterator over the records in a reader that are separated by matches of a
+// pattern. Bytes are kept only until the delimiter after them is found, so a
+// delimiter can straddle the chunks the reader is read in. Empty matches do
+// not delimit anything. As with `str::split`, the text after the last
+// delimiter is a record even if it is empty.
+pub struct StreamSplit<'r, R> {
+    stream: Stream<'r>,
+    reader: R,
+    buffer: Vec<u8>, // the input from `buffer_offset` on that is not in a record yet
+    buffer_offset: usize,
+    records: VecDeque<Vec<u8>>,
+    done: bool
+}
+
+impl<'r, R: Read> StreamSplit<'r, R> {
+    pub fn new(stream: Stream<'r>, reader: R) -> StreamSplit<'r, R> {
+        StreamSplit {
+            stream,
+            reader,
+            buffer: Vec::new(),
+            buffer_offset: 0,
+            records: VecDeque::new(),
+            done: false
+        }
+    }
+
+    fn split_off(&mut self, delimiters: Vec<Match>) {
+        for m in delimiters.into_iter().filter(|m| !m.is_empty()) {
+            let record = self.buffer[..m.start() - self.buffer_offset].to_vec();
+            self.buffer.drain(..m.end() - self.buffer_offset);
+            self.buffer_offset = m.end();
+            self.records.push_back(record);
+        }
+    }
+}
+
+impl<'r, R: Read> Iterator for StreamSplit<'r, R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<u8>>> {
+        let mut buf = [0; CHUNK_SIZE];
+
+        while self.records.is_empty() && !self.done {
+            match self.reader.read(&mut buf) {
+                Ok(0) => {
+                    let delimiters = self.stream.finish();
+                    self.split_off(delimiters);
+                    self.records.push_back(std::mem::take(&mut self.buffer));
+                    self.done = true;
+                },
+                Ok(n) => {
+                    self.buffer.extend_from_slice(&buf[..n]);
+                    let delimiters = self.stream.feed(&buf[..n]);
+                    self.split_off(delimiters);
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        self.records.pop_front().map(Ok)
+    }
+}
@@ -0,0 +1,164 @@
+use std::io::{self, Cursor, Read};
+
+use ::regex::{Regex, Match};
+
+fn stream_all(pattern: &str, chunks: &[&str]) -> Vec<(usize, usize)> {
+    let regex = Regex::from(pattern).unwrap();
+    let mut stream = regex.stream();
+
+    let mut found = Vec::new();
+    for chunk in chunks {
+        found.extend(stream.feed(chunk.as_bytes()));
+    }
+    found.extend(stream.finish());
+
+    found.iter().map(|m| (m.start(), m.end())).collect()
+}
+
+#[test]
+fn finds_matches_at_absolute_offsets() {
+    assert_eq!(vec![(1, 3), (5, 7)], stream_all("ab", &["xabxxab"]));
+    assert_eq!(vec![(1, 3), (5, 7)], stream_all("ab", &["xa", "bxx", "a", "b"]));
+}
+
+#[test]
+fn finds_nothing() {
+    assert_eq!(Vec::<(usize, usize)>::new(), stream_all("ba", &["aaaa", "bbbb"]));
+    assert_eq!(Vec::<(usize, usize)>::new(), stream_all("ab", &[]));
+}
+
+#[test]
+fn extends_match_across_chunks() {
+    assert_eq!(vec![(0, 6)], stream_all("a+", &["aa", "aa", "aa"]));
+    assert_eq!(vec![(1, 6)], stream_all("a(bc)*", &["xab", "c", "bcb"]));
+}
+
+#[test]
+fn is_greedy() {
+    assert_eq!(vec![(0, 3)], stream_all("a.*b", &["a", "bb", "c"]));
+    assert_eq!(vec![(0, 1)], stream_all("a|ab", &["ab"]));
+    assert_eq!(vec![(0, 2)], stream_all("ab|a", &["ab"]));
+}
+
+#[test]
+fn resumes_search_after_failed_extension() {
+    // `(ab)+` has to read past the first match to find out it ends there
+    assert_eq!(vec![(0, 2), (3, 5)], stream_all("(ab)+", &["aba", "ab"]));
+    assert_eq!(vec![(0, 2), (2, 4)], stream_all("ab(cd)?", &["ab", "ab", "c"]));
+}
+
+#[test]
+fn reports_empty_matches() {
+    assert_eq!(vec![(0, 0), (1, 2), (2, 2)], stream_all("a?", &["b", "a"]));
+    assert_eq!(vec![(0, 2), (2, 2)], stream_all("a*", &["aa"]));
+    assert_eq!(vec![(0, 0)], stream_all("a*", &[]));
+}
+
+#[test]
+fn finds_what_find_all_finds_in_chars_outside_ascii() {
+    assert_eq!(vec![(0, 0), (2, 2)], stream_all("a?", &["é"]));
+
+    let text = "aé€b😀éa";
+    for pattern in ["a?", "é*", ".?", "b|", "x*", "^a?", "^x*"].iter() {
+        let regex = Regex::from(pattern).unwrap();
+        let expected = regex.find_all(&mut regex.create_cache(), text).iter()
+            .map(|m| (m.start(), m.end()))
+            .collect::<Vec<_>>();
+        let bytes = text.as_bytes();
+        for at in 0..bytes.len() {
+            let mut stream = regex.stream();
+            let mut found = stream.feed(&bytes[..at]);
+            found.extend(stream.feed(&bytes[at..]));
+            found.extend(stream.finish());
+            assert_eq!(expected, found.iter().map(|m| (m.start(), m.end())).collect::<Vec<_>>(), "{:?}", pattern);
+        }
+    }
+}
+
+#[test]
+fn finds_only_a_match_at_the_start_when_anchored() {
+    assert_eq!(vec![(0, 1)], stream_all("^a", &["aaa"]));
+    assert_eq!(vec![(0, 1)], stream_all("^a", &["a", "a", "a"]));
+    assert_eq!(vec![(0, 0)], stream_all("^", &["abc"]));
+    assert_eq!(Vec::<(usize, usize)>::new(), stream_all("^a", &["ba"]));
+}
+
+#[test]
+fn tracks_offset() {
+    let regex = Regex::from("a").unwrap();
+    let mut stream = regex.stream();
+
+    stream.feed(b"bbb");
+    assert_eq!(3, stream.offset());
+}
+
+#[test]
+fn reads_matches() {
+    let regex = Regex::from("[ab]+").unwrap();
+    let reader = Cursor::new("xxaba--b".to_owned().into_bytes());
+
+    let found = regex.stream_matches(reader).map(|m| m.unwrap()).collect::<Vec<_>>();
+    assert_eq!(vec![Match::new(2, 5), Match::new(7, 8)], found);
+
+    let anchored = Regex::from("^[ab]+").unwrap();
+    let found = anchored.stream_matches(Cursor::new(&b"ab-ab"[..])).map(|m| m.unwrap()).collect::<Vec<_>>();
+    assert_eq!(vec![Match::new(0, 2)], found);
+}
+
+// reads a byte at a time, so that every delimiter straddles reads
+struct ByteAtATime<'a>(&'a [u8]);
+
+impl<'a> Read for ByteAtATime<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.split_first() {
+            Some((&byte, rest)) if !buf.is_empty() => {
+                buf[0] = byte;
+                self.0 = rest;
+                Ok(1)
+            },
+            _ => Ok(0)
+        }
+    }
+}
+
+fn split<R: Read>(pattern: &str, reader: R) -> Vec<String> {
+    let regex = Regex::from(pattern).unwrap();
+    regex.stream_split(reader)
+        .map(|record| String::from_utf8(record.unwrap()).unwrap())
+        .collect()
+}
+
+#[test]
+fn splits_reader_on_delimiters() {
+    assert_eq!(vec!["a", "b c", "d"], split("; *", Cursor::new(&b"a; b c;d"[..])));
+    assert_eq!(vec!["a", "b", ""], split("; *", ByteAtATime(b"a;   b; ")));
+    assert_eq!(vec!["x\ny", "z"], split("\n\n\n*", ByteAtATime(b"x\ny\n\n\nz")));
+}
+
+#[test]
+fn split_ignores_empty_delimiters() {
+    assert_eq!(vec!["a", "b"], split(",*", Cursor::new(&b"a,b"[..])));
+    assert_eq!(vec![""], split(",", Cursor::new(&b""[..])));
+}
+
+#[test]
+fn splits_only_at_an_anchored_delimiter_at_the_start() {
+    assert_eq!(vec!["", "b;c"], split("^;", ByteAtATime(b";b;c")));
+    assert_eq!(vec!["a;b"], split("^;", Cursor::new(&b"a;b"[..])));
+}
+
+#[test]
+fn runs_any_dense_nfa() {
+    use ::expr::Expr;
+    use ::nfa::NFA;
+    use super::Stream;
+
+    let nfa = NFA::from_expr(&"ab(cd)+|x".parse::<Expr>().unwrap());
+    for dense in [nfa.to_dense(), nfa.with_literals().to_dense()].iter() {
+        let mut stream = Stream::new(dense);
+        let mut found = stream.feed(b"-abcdcdx-ab");
+        found.extend(stream.feed(b"cdabc"));
+        found.extend(stream.finish());
+        assert_eq!(vec![Match::new(1, 7), Match::new(7, 8), Match::new(9, 13)], found);
+    }
+}
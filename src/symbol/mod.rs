@@ -0,0 +1,211 @@
+use std::mem;
+
+use ::hir::Repetition;
+use ::regex::Match;
+
+#[cfg(test)] mod spec;
+
+/// A pattern over a sequence of symbols of any type, like tokens from a
+/// lexer or event IDs, rather than over the bytes of a text. Built in code,
+/// since symbols have no syntax.
+#[derive(PartialEq,Debug,Clone)]
+pub enum Pattern<T> {
+    /// Matches without consuming a symbol.
+    Empty,
+    Symbol(T),
+    /// Any one of the symbols.
+    OneOf(Vec<T>),
+    /// Any single symbol.
+    Any,
+    Concat(Vec<Pattern<T>>),
+    /// Any of the alternatives, preferring earlier ones.
+    Alternation(Vec<Pattern<T>>),
+    /// Greedy repetition of the item.
+    Repeat(Repetition, Box<Pattern<T>>)
+}
+
+impl<T> Pattern<T> {
+    pub fn repeat(repetition: Repetition, item: Pattern<T>) -> Pattern<T> {
+        Pattern::Repeat(repetition, Box::new(item))
+    }
+}
+
+#[derive(PartialEq,Debug,Clone)]
+enum Step<T> {
+    Accept(Pattern<T>, usize), // a `Symbol`, `OneOf` or `Any`, and the step after it
+    Split(usize, usize), // the first is preferred
+    Match
+}
+
+/// An NFA over symbols, searched like the Pike VM searches bytes: all threads
+/// run in lock step, so each symbol is looked at once per state at most.
+/// Matches are leftmost-first, as with `Regex`.
+#[derive(PartialEq,Debug,Clone)]
+pub struct SymbolNFA<T> {
+    steps: Vec<Step<T>>,
+    start: usize
+}
+
+impl<T: PartialEq + Clone> SymbolNFA<T> {
+    pub fn new(pattern: &Pattern<T>) -> SymbolNFA<T> {
+        let mut steps = vec![Step::Match];
+        let start = compile(pattern, 0, &mut steps);
+
+        SymbolNFA {
+            steps,
+            start
+        }
+    }
+
+    pub fn is_match(&self, haystack: &[T]) -> bool {
+        self.find_at(haystack, 0).is_some()
+    }
+
+    pub fn find(&self, haystack: &[T]) -> Option<Match> {
+        self.find_at(haystack, 0)
+    }
+
+    /// The leftmost-first match starting at or after `from`.
+    pub fn find_at(&self, haystack: &[T], from: usize) -> Option<Match> {
+        let mut threads: Vec<(usize, usize)> = Vec::new(); // steps with where their match started
+        let mut next = Vec::new();
+        let mut seen = vec![usize::MAX; self.steps.len()]; // the position each step was last added at
+        let mut found = None;
+
+        for at in from..haystack.len() + 1 {
+            if found.is_none() {
+                self.add_thread(&mut threads, &mut seen, self.start, at, at);
+            }
+            if threads.is_empty() && found.is_some() {
+                break;
+            }
+
+            next.clear();
+            for &(id, start) in &threads {
+                match self.steps[id] {
+                    Step::Match => {
+                        found = Some(Match::new(start, at));
+                        break; // lower priority threads lose to this match
+                    },
+                    Step::Accept(ref accepts, out) => {
+                        if at < haystack.len() && accepts_symbol(accepts, &haystack[at]) {
+                            self.add_thread(&mut next, &mut seen, out, start, at + 1);
+                        }
+                    },
+                    Step::Split(..) => ()
+                }
+            }
+            mem::swap(&mut threads, &mut next);
+        }
+
+        found
+    }
+
+    /// The successive non-overlapping matches in `haystack`.
+    pub fn find_iter<'n, 'h>(&'n self, haystack: &'h [T]) -> SymbolMatches<'n, 'h, T> {
+        SymbolMatches {
+            nfa: self,
+            haystack,
+            position: Some(0)
+        }
+    }
+
+    // adds the steps reached from `id` without consuming a symbol, in order
+    // of priority
+    fn add_thread(&self, threads: &mut Vec<(usize, usize)>, seen: &mut [usize], id: usize, start: usize, at: usize) {
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if seen[id] == at {
+                continue;
+            }
+            seen[id] = at;
+
+            match self.steps[id] {
+                Step::Split(first, second) => {
+                    stack.push(second);
+                    stack.push(first);
+                },
+                _ => threads.push((id, start))
+            }
+        }
+    }
+}
+
+// Compiles `pattern` to steps leading to `next`, returning the first of them.
+// Working backwards from the end means each step knows where it goes when it
+// is created, except the split of a loop, which is filled in after its body.
+fn compile<T: Clone>(pattern: &Pattern<T>, next: usize, steps: &mut Vec<Step<T>>) -> usize {
+    match pattern {
+        Pattern::Empty => next,
+        Pattern::Symbol(_) | Pattern::OneOf(_) | Pattern::Any => {
+            steps.push(Step::Accept(pattern.clone(), next));
+            steps.len() - 1
+        },
+        Pattern::Concat(items) => {
+            items.iter().rev().fold(next, |next, item| compile(item, next, steps))
+        },
+        Pattern::Alternation(alternatives) => {
+            if alternatives.is_empty() {
+                steps.push(Step::Accept(Pattern::OneOf(Vec::new()), next)); // matches nothing
+                return steps.len() - 1;
+            }
+            let mut ids = alternatives.iter().map(|item| compile(item, next, steps)).collect::<Vec<usize>>();
+            let mut id = ids.pop().unwrap();
+            while let Some(preferred) = ids.pop() {
+                steps.push(Step::Split(preferred, id));
+                id = steps.len() - 1;
+            }
+            id
+        },
+        Pattern::Repeat(Repetition::ZeroOrOne, item) => {
+            let body = compile(item, next, steps);
+            steps.push(Step::Split(body, next));
+            steps.len() - 1
+        },
+        Pattern::Repeat(repetition, item) => {
+            steps.push(Step::Split(next, next));
+            let split = steps.len() - 1;
+            let body = compile(item, split, steps);
+            steps[split] = Step::Split(body, next);
+
+            match repetition {
+                Repetition::OneOrMore => body,
+                _ => split
+            }
+        }
+    }
+}
+
+fn accepts_symbol<T: PartialEq>(pattern: &Pattern<T>, symbol: &T) -> bool {
+    match pattern {
+        Pattern::Symbol(s) => s == symbol,
+        Pattern::OneOf(symbols) => symbols.contains(symbol),
+        Pattern::Any => true,
+        _ => false
+    }
+}
+
+// Iterator over the successive non-overlapping matches in a sequence of
+// symbols. An empty match moves the search on by one symbol, so that it is
+// not found again.
+pub struct SymbolMatches<'n, 'h, T: 'n + 'h> {
+    nfa: &'n SymbolNFA<T>,
+    haystack: &'h [T],
+    position: Option<usize>
+}
+
+impl<'n, 'h, T: PartialEq + Clone> Iterator for SymbolMatches<'n, 'h, T> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Match> {
+        let position = self.position?;
+        let found = self.nfa.find_at(self.haystack, position);
+
+        self.position = match found {
+            Some(m) if m.is_empty() && m.end() < self.haystack.len() => Some(m.end() + 1),
+            Some(m) if !m.is_empty() => Some(m.end()),
+            _ => None
+        };
+        found
+    }
+}
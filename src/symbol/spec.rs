@@ -0,0 +1,54 @@
+use ::hir::Repetition;
+use ::regex::Match;
+use super::{Pattern, SymbolNFA};
+
+#[derive(PartialEq,Debug,Clone,Copy)]
+enum Event {
+    Login,
+    Fail,
+    Read,
+    Logout
+}
+
+use self::Event::*;
+
+#[test]
+fn matches_over_any_symbol_type() {
+    // a login after at least two failures
+    let nfa = SymbolNFA::new(&Pattern::Concat(vec![
+        Pattern::Symbol(Fail),
+        Pattern::repeat(Repetition::OneOrMore, Pattern::Symbol(Fail)),
+        Pattern::Symbol(Login)
+    ]));
+
+    assert_eq!(Some(Match::new(1, 5)), nfa.find(&[Read, Fail, Fail, Fail, Login]));
+    assert!(!nfa.is_match(&[Fail, Login, Fail, Read, Login]));
+}
+
+#[test]
+fn prefers_earlier_alternatives_and_longer_repeats() {
+    let nfa = SymbolNFA::new(&Pattern::Alternation(vec![
+        Pattern::Symbol(1u32),
+        Pattern::Concat(vec![Pattern::Symbol(1), Pattern::Symbol(2)]),
+        Pattern::repeat(Repetition::ZeroOrMore, Pattern::OneOf(vec![3, 4]))
+    ]));
+
+    assert_eq!(Some(Match::new(0, 1)), nfa.find(&[1, 2]));
+    assert_eq!(Some(Match::new(0, 0)), nfa.find(&[2, 3]));
+    assert_eq!(Some(Match::new(1, 3)), nfa.find_at(&[2, 3, 4, 5], 1));
+}
+
+#[test]
+fn finds_successive_matches() {
+    let nfa = SymbolNFA::new(&Pattern::Concat(vec![
+        Pattern::Symbol(Login),
+        Pattern::repeat(Repetition::ZeroOrMore, Pattern::Any),
+        Pattern::repeat(Repetition::ZeroOrOne, Pattern::Symbol(Logout))
+    ]));
+    let events = [Read, Login, Read, Logout];
+
+    assert_eq!(vec![Match::new(1, 4)], nfa.find_iter(&events).collect::<Vec<_>>());
+
+    let empty = SymbolNFA::new(&Pattern::repeat(Repetition::ZeroOrMore, Pattern::<u8>::Empty));
+    assert_eq!(3, empty.find_iter(&[0, 0]).count());
+}
@@ -0,0 +1,44 @@
+#![cfg(feature = "grep")]
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// runs rmgrep with `args` over `input` on stdin, giving its output and exit code
+fn rmgrep(args: &[&str], input: &str) -> (String, i32) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rmgrep"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    (String::from_utf8(output.stdout).unwrap(), output.status.code().unwrap())
+}
+
+const INPUT: &str = "foo bar\nbaz\nfoofoo\n";
+
+#[test]
+fn prints_matching_lines() {
+    assert_eq!(("foo bar\nfoofoo\n".to_owned(), 0), rmgrep(&["foo"], INPUT));
+    assert_eq!(("".to_owned(), 1), rmgrep(&["qux"], INPUT));
+    assert_eq!(("baz\n".to_owned(), 0), rmgrep(&["-i", "BAZ"], INPUT));
+}
+
+#[test]
+fn inverts_numbers_and_extracts() {
+    assert_eq!(("baz\n".to_owned(), 0), rmgrep(&["-v", "foo"], INPUT));
+    assert_eq!(("1:foo bar\n3:foofoo\n".to_owned(), 0), rmgrep(&["-n", "foo"], INPUT));
+    assert_eq!(("2:baz\n".to_owned(), 0), rmgrep(&["-vn", "foo"], INPUT));
+    assert_eq!(("foo\nfoo\nfoo\n".to_owned(), 0), rmgrep(&["-o", "foo"], INPUT));
+    assert_eq!(("1:ba\n2:ba\n".to_owned(), 0), rmgrep(&["-on", "ba"], INPUT));
+    // empty matches are not printed on their own, but the line still matches
+    assert_eq!(("".to_owned(), 0), rmgrep(&["-o", "x*"], "ab\n"));
+}
+
+#[test]
+fn reports_invalid_patterns() {
+    assert_eq!(("".to_owned(), 2), rmgrep(&["a("], INPUT));
+}
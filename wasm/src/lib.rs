@@ -0,0 +1,65 @@
+//! `Regex` for JavaScript, built to WebAssembly with wasm-bindgen.
+
+extern crate regexmatcher;
+extern crate wasm_bindgen;
+
+use wasm_bindgen::prelude::*;
+
+use regexmatcher::Regex;
+
+#[cfg(test)] mod spec;
+
+/// A regex exported to JavaScript with wasm-bindgen: errors are thrown as
+/// messages, and offsets are in UTF-16 code units as JavaScript strings
+/// count them, rather than in bytes.
+#[wasm_bindgen]
+pub struct WasmRegex {
+    regex: Regex
+}
+
+#[wasm_bindgen]
+impl WasmRegex {
+    pub fn compile(pattern: &str) -> Result<WasmRegex, String> {
+        Regex::from(pattern)
+            .map(|regex| WasmRegex { regex })
+            .map_err(|e| e.to_string())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn pattern(&self) -> String {
+        self.regex.as_str().to_owned()
+    }
+
+    /// Whether there is a match anywhere in `text`, as `RegExp.test` tells,
+    /// rather than only at its start as `Regex::is_match` does.
+    #[wasm_bindgen(js_name = isMatch)]
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.find(text).is_some()
+    }
+
+    /// The non-overlapping matches in `text`, flattened into the start and
+    /// end of each in turn, which crosses into JavaScript as a `Uint32Array`.
+    #[wasm_bindgen(js_name = findAll)]
+    pub fn find_all(&self, text: &str) -> Vec<u32> {
+        let offsets = utf16_offsets(text);
+        self.regex.find_all(&mut self.regex.create_cache(), text).iter()
+            .flat_map(|m| vec![offsets[m.start()], offsets[m.end()]])
+            .collect()
+    }
+}
+
+// The UTF-16 offset of each byte offset in `text`, and of its end. A byte
+// inside a char, where `.` can end a match, maps to the end of the char.
+fn utf16_offsets(text: &str) -> Vec<u32> {
+    let mut offsets = Vec::with_capacity(text.len() + 1);
+    let mut utf16 = 0;
+    for c in text.chars() {
+        offsets.push(utf16);
+        utf16 += c.len_utf16() as u32;
+        for _ in 1..c.len_utf8() {
+            offsets.push(utf16);
+        }
+    }
+    offsets.push(utf16);
+    offsets
+}
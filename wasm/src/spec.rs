@@ -0,0 +1,23 @@
+use super::{WasmRegex, utf16_offsets};
+
+#[test]
+fn compiles_or_reports_the_error() {
+    assert!(WasmRegex::compile("a+b").unwrap().is_match("xaab"));
+    assert!(!WasmRegex::compile("a+b").unwrap().is_match("xaa"));
+    assert_eq!("a+b", WasmRegex::compile("a+b").unwrap().pattern());
+    assert!(WasmRegex::compile("(a").is_err());
+}
+
+#[test]
+fn finds_all_in_utf16_offsets() {
+    let regex = WasmRegex::compile("b+").unwrap();
+    assert_eq!(vec![1, 3, 4, 5], regex.find_all("abbcb"));
+    assert_eq!(vec![1, 3, 6, 7], regex.find_all("ébbc😀b"));
+    assert_eq!(Vec::<u32>::new(), regex.find_all("xyz"));
+}
+
+#[test]
+fn maps_byte_offsets_to_utf16() {
+    assert_eq!(vec![0, 1, 1, 2], utf16_offsets("éa"));
+    assert_eq!(vec![0, 2, 2, 2, 2], utf16_offsets("😀"));
+}